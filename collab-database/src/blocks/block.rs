@@ -5,8 +5,8 @@ use collab_entity::CollabType;
 
 use crate::error::DatabaseError;
 use crate::rows::{
-  Cell, DatabaseRow, Row, RowChangeSender, RowDetail, RowId, RowMeta, RowMetaKey, RowMetaUpdate,
-  RowUpdate, default_database_row_data, meta_id_from_row_id,
+  Cell, DatabaseRow, Row, RowChange, RowChangeSender, RowDetail, RowId, RowMeta, RowMetaKey,
+  RowMetaUpdate, RowUpdate, default_database_row_data, meta_id_from_row_id,
 };
 use crate::views::RowOrder;
 use crate::workspace_database::DatabaseCollabService;
@@ -139,6 +139,12 @@ impl Block {
       self.collab_service.clone(),
     )?;
 
+    if let Some(row_change_tx) = &self.row_change_tx {
+      if let Some(row) = database_row.get_row() {
+        let _ = row_change_tx.send(RowChange::DidCreateRow { row });
+      }
+    }
+
     let database_row = Arc::new(RwLock::from(database_row));
     if let Some(persistence) = self.collab_service.persistence() {
       if let Ok(encoded_collab) = database_row.write().await.encoded_collab() {
@@ -202,6 +208,11 @@ impl Block {
         error!("Can't delete the row from disk: {:?}", err);
       }
     }
+    if let Some(row_change_tx) = &self.row_change_tx {
+      let _ = row_change_tx.send(RowChange::DidDeleteRow {
+        row_id: row_id.clone(),
+      });
+    }
     row
   }
 
@@ -1,26 +1,31 @@
 use std::borrow::{Borrow, BorrowMut};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::io;
 use std::ops::{Deref, DerefMut};
 
 use crate::blocks::{Block, BlockEvent};
 use crate::database_state::DatabaseNotify;
 use crate::error::DatabaseError;
 use crate::fields::{
-  Field, FieldChangeReceiver, FieldMap, FieldUpdate, TypeOptionCellReader, TypeOptionCellWriter,
-  type_option_cell_reader, type_option_cell_writer,
+  Field, FieldChangeReceiver, FieldMap, FieldSettings, FieldUpdate, FieldVisibility,
+  FormulaDependencies, FormulaError, FormulaEvaluator, FormulaResultType, TypeOptionCellReader,
+  TypeOptionCellWriter, check_formula, default_field_visibility,
+  formula_type_option::FormulaTypeOption, type_option_cell_reader, type_option_cell_writer,
 };
 use crate::meta::MetaMap;
 use crate::rows::{
-  CreateRowParams, CreateRowParamsValidator, DatabaseRow, Row, RowCell, RowChangeReceiver,
+  Cell, CreateRowParams, CreateRowParamsValidator, DatabaseRow, Row, RowCell, RowChangeReceiver,
   RowDetail, RowId, RowMeta, RowMetaKey, RowMetaUpdate, RowUpdate, meta_id_from_row_id,
 };
 use crate::util::encoded_collab;
 use crate::views::define::DATABASE_VIEW_ROW_ORDERS;
 use crate::views::{
-  CalculationMap, DatabaseLayout, DatabaseViewUpdate, DatabaseViews, FieldOrder,
-  FieldSettingsByFieldIdMap, FieldSettingsMap, FilterMap, GroupSettingMap, LayoutSetting,
-  OrderArray, OrderObjectPosition, RowOrder, RowOrderArray, SortMap, ViewChangeReceiver,
+  Calculation, CalculationChangeReceiver, CalculationMap, CalculationResult, CalculationState,
+  DatabaseLayout, DatabaseViewUpdate, DatabaseViews, FieldOrder, FieldSettingsByFieldIdMap,
+  FieldSettingsMap, Filter, FilterEvaluator, FilterMap, GROUPS, Group, GroupMap, GroupSetting,
+  GroupSettingMap, LayoutSetting, OrderArray, OrderObjectPosition, RowGroup, RowGrouper, RowOrder,
+  RowOrderArray, RowSorter, Sort, SortMap, SortedRows, ViewChangeReceiver,
 };
 use crate::workspace_database::{
   DatabaseCollabService, DatabaseMeta, NoPersistenceDatabaseCollabService,
@@ -30,6 +35,7 @@ use crate::entity::{
   CreateDatabaseParams, CreateViewParams, CreateViewParamsValidator, DatabaseView,
   DatabaseViewMeta, EncodedCollabInfo, EncodedDatabase, FieldType,
 };
+use crate::template::csv_export::{CSVExportOptions, CSVExporter};
 use crate::template::entity::DatabaseTemplate;
 
 use collab::core::origin::CollabOrigin;
@@ -52,9 +58,11 @@ use rayon::iter::ParallelIterator;
 use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 pub use tokio_stream::wrappers::WatchStream;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, instrument, trace};
+use tracing::{error, info, instrument, trace, warn};
 use uuid::Uuid;
 
 pub struct Database {
@@ -367,6 +375,8 @@ impl Database {
       .update_all_views(&mut txn, |_view_id, update| {
         update.insert_row_order(&row_order, &OrderObjectPosition::default());
       });
+    drop(txn);
+    self.refresh_formula_cells(&row_order.id).await;
     Ok(row_order)
   }
 
@@ -412,6 +422,8 @@ impl Database {
       .body
       .index_of_row(&txn, view_id, &row_order.id)
       .unwrap_or_default();
+    drop(txn);
+    self.refresh_formula_cells(&row_order.id).await;
     Ok((index, row_order))
   }
 
@@ -458,12 +470,40 @@ impl Database {
     rows
   }
 
-  /// Update the row
+  /// Update the row. The formula fields of the row are recomputed after the update if one of the
+  /// cells they read from changed.
   pub async fn update_row<F>(&mut self, row_id: RowId, f: F)
   where
     F: FnOnce(RowUpdate),
   {
-    self.body.block.update_row(row_id, f).await;
+    let dependencies = self.get_formula_dependencies();
+    if !dependencies.has_formulas() {
+      self.body.block.update_row(row_id, f).await;
+      return;
+    }
+
+    // Only the cells the formulas read are compared, so the whole row is read once, and only
+    // when the formulas have to be recomputed
+    let field_ids = dependencies.referenced_field_ids().collect::<Vec<_>>();
+    let old_cells = self.read_cells(&row_id, &field_ids).await;
+    self.body.block.update_row(row_id.clone(), f).await;
+    let new_cells = match self.read_cells(&row_id, &field_ids).await {
+      Some(cells) => cells,
+      None => return,
+    };
+    let is_affected = match &old_cells {
+      Some(old_cells) => dependencies.is_affected_by(
+        field_ids
+          .iter()
+          .zip(old_cells.iter().zip(&new_cells))
+          .filter(|(_, (old_cell, new_cell))| old_cell != new_cell)
+          .map(|(field_id, _)| *field_id),
+      ),
+      None => true,
+    };
+    if is_affected {
+      self.refresh_formula_cells(&row_id).await;
+    }
   }
 
   /// Update the meta of the row
@@ -481,6 +521,26 @@ impl Database {
     self.body.index_of_row(&txn, view_id, row_id)
   }
 
+  /// Return the [Row] with the given row id.
+  /// Return None if the row is not found, unlike [Database::get_row].
+  async fn read_row(&self, row_id: &RowId) -> Option<Row> {
+    let database_row = self.body.block.get_database_row(row_id).await?;
+    database_row.read().await.get_row()
+  }
+
+  /// Return the cells of the row with the given field ids, in the same order.
+  /// Return None if the row is not found.
+  async fn read_cells(&self, row_id: &RowId, field_ids: &[&str]) -> Option<Vec<Option<Cell>>> {
+    let database_row = self.body.block.get_database_row(row_id).await?;
+    let database_row = database_row.read().await;
+    Some(
+      field_ids
+        .iter()
+        .map(|field_id| database_row.get_cell(field_id))
+        .collect(),
+    )
+  }
+
   /// Return the [Row] with the given row id.
   pub async fn get_row(&self, row_id: &RowId) -> Row {
     let row = self.body.block.get_database_row(row_id).await;
@@ -646,6 +706,62 @@ impl Database {
     })
   }
 
+  /// Return the rows of the view that pass all the filters of the view.
+  /// The rows are ordered by the [RowOrder] of the view.
+  pub async fn get_filtered_rows_for_view(&self, view_id: &str) -> Vec<Row> {
+    let row_orders = self.get_row_orders_for_view(view_id);
+    let rows = self
+      .get_rows_from_row_orders(row_orders, 20, None)
+      .await
+      .filter_map(|result| async move { result.ok() })
+      .collect::<Vec<_>>()
+      .await;
+
+    let filters = self.get_all_filters::<Filter>(view_id);
+    if filters.is_empty() {
+      return rows;
+    }
+    let evaluator = FilterEvaluator::new(self.get_fields(None));
+    rows
+      .into_iter()
+      .filter(|row| evaluator.is_visible(&filters, row))
+      .collect()
+  }
+
+  /// Return the [RowOrder]s of the view whose rows pass all the filters of the view.
+  pub async fn get_filtered_row_orders_for_view(&self, view_id: &str) -> Vec<RowOrder> {
+    let row_orders = self.get_row_orders_for_view(view_id);
+    if self.get_all_filters::<Filter>(view_id).is_empty() {
+      return row_orders;
+    }
+
+    let visible_row_ids = self
+      .get_filtered_rows_for_view(view_id)
+      .await
+      .into_iter()
+      .map(|row| row.id)
+      .collect::<HashSet<_>>();
+    row_orders
+      .into_iter()
+      .filter(|row_order| visible_row_ids.contains(&row_order.id))
+      .collect()
+  }
+
+  /// Return the rows of the view that pass all the filters of the view, ordered by the sorts of
+  /// the view. Rows that compare equal keep the order of the view's [RowOrder]s.
+  pub async fn get_sorted_rows_for_view(&self, view_id: &str) -> Vec<Row> {
+    self.get_sorted_row_list_for_view(view_id).await.into_rows()
+  }
+
+  /// Same as [Database::get_sorted_rows_for_view], but returns a [SortedRows] that can be kept up
+  /// to date with the [crate::rows::RowChange]s from [Database::subscribe_row_change].
+  pub async fn get_sorted_row_list_for_view(&self, view_id: &str) -> SortedRows {
+    let rows = self.get_filtered_rows_for_view(view_id).await;
+    let sorts = self.get_all_sorts::<Sort>(view_id);
+    let sorter = RowSorter::new(self.get_fields(None));
+    SortedRows::new(sorts, sorter, rows)
+  }
+
   /// Return a list of [RowCell] for the given view and field.
   pub async fn get_cells_for_field(&self, view_id: &str, field_id: &str) -> Vec<RowCell> {
     let txn = self.collab.transact();
@@ -813,6 +929,137 @@ impl Database {
       });
   }
 
+  /// Return the rows of the view bucketed by the first [GroupSetting] of the view. The rows are
+  /// filtered and sorted before being grouped, and the groups follow the order and visibility
+  /// stored in [GroupSetting::groups].
+  pub async fn get_groups_for_view(&self, view_id: &str) -> Result<Vec<RowGroup>, DatabaseError> {
+    let setting = self.get_first_group_setting(view_id)?;
+    let rows = self.get_sorted_rows_for_view(view_id).await;
+    let field = self
+      .get_field(&setting.field_id)
+      .ok_or_else(|| DatabaseError::NoRequiredData(format!("field {}", setting.field_id)))?;
+    let grouper = RowGrouper::new(&setting, &field)?;
+    Ok(grouper.group_rows(rows, &setting.groups))
+  }
+
+  /// Move the group with `from_group_id` to the position of the group with `to_group_id`.
+  pub async fn move_group(
+    &mut self,
+    view_id: &str,
+    from_group_id: &str,
+    to_group_id: &str,
+  ) -> Result<(), DatabaseError> {
+    let (setting, mut groups) = self
+      .get_group_order_for_view(view_id, &[from_group_id, to_group_id])
+      .await?;
+    let from = groups.iter().position(|group| group.id == from_group_id);
+    let to = groups.iter().position(|group| group.id == to_group_id);
+    if let (Some(from), Some(to)) = (from, to) {
+      let group = groups.remove(from);
+      groups.insert(to, group);
+      self.set_groups_in_view(view_id, &setting.id, groups);
+    }
+    Ok(())
+  }
+
+  /// Show or hide the group with the given id
+  pub async fn update_group_visibility(
+    &mut self,
+    view_id: &str,
+    group_id: &str,
+    visible: bool,
+  ) -> Result<(), DatabaseError> {
+    let (setting, mut groups) = self.get_group_order_for_view(view_id, &[group_id]).await?;
+    if let Some(group) = groups.iter_mut().find(|group| group.id == group_id) {
+      if group.visible != visible {
+        group.visible = visible;
+        self.set_groups_in_view(view_id, &setting.id, groups);
+      }
+    }
+    Ok(())
+  }
+
+  /// Move a row from one group to another by rewriting the cell of the grouping field. The new
+  /// cell is built with the [TypeOptionCellWriter] of the field, so every client that observes
+  /// the row computes the same groups.
+  pub async fn move_row_to_group(
+    &mut self,
+    view_id: &str,
+    row_id: &RowId,
+    from_group_id: &str,
+    to_group_id: &str,
+  ) -> Result<(), DatabaseError> {
+    let setting = self.get_first_group_setting(view_id)?;
+    let field = self
+      .get_field(&setting.field_id)
+      .ok_or_else(|| DatabaseError::NoRequiredData(format!("field {}", setting.field_id)))?;
+    let row = self.get_row(row_id).await;
+    let cell = RowGrouper::new(&setting, &field)?.cell_for_move(
+      row.cells.get(&field.id),
+      from_group_id,
+      to_group_id,
+    )?;
+    self
+      .update_row(row_id.clone(), |row_update| {
+        row_update.update_cells(|cells_update| {
+          cells_update.insert_cell(&field.id, cell);
+        });
+      })
+      .await;
+    Ok(())
+  }
+
+  fn get_first_group_setting(&self, view_id: &str) -> Result<GroupSetting, DatabaseError> {
+    self
+      .get_all_group_setting::<GroupSetting>(view_id)
+      .into_iter()
+      .next()
+      .ok_or_else(|| DatabaseError::NoRequiredData(format!("group setting of view {}", view_id)))
+  }
+
+  /// Returns the first group setting of the view and the order of its groups. The rows of the
+  /// view are only grouped if one of the given groups only exists because a row has its value,
+  /// see [RowGrouper::groups_without_rows].
+  async fn get_group_order_for_view(
+    &self,
+    view_id: &str,
+    group_ids: &[&str],
+  ) -> Result<(GroupSetting, Vec<Group>), DatabaseError> {
+    let setting = self.get_first_group_setting(view_id)?;
+    let field = self
+      .get_field(&setting.field_id)
+      .ok_or_else(|| DatabaseError::NoRequiredData(format!("field {}", setting.field_id)))?;
+    let groups = RowGrouper::new(&setting, &field)?.groups_without_rows(&setting.groups);
+    let contains_all = group_ids
+      .iter()
+      .all(|group_id| groups.iter().any(|group| &group.id == group_id));
+    if contains_all {
+      return Ok((setting, groups));
+    }
+
+    // The grouper isn't Send, so it's only created after the rows are read
+    let rows = self.get_sorted_rows_for_view(view_id).await;
+    let groups = RowGrouper::new(&setting, &field)?
+      .group_rows(rows, &setting.groups)
+      .into_iter()
+      .map(|group| Group {
+        id: group.id,
+        visible: group.visible,
+      })
+      .collect();
+    Ok((setting, groups))
+  }
+
+  /// Replaces the groups of the group setting, the other keys of the setting are kept
+  fn set_groups_in_view(&mut self, view_id: &str, setting_id: &str, groups: Vec<Group>) {
+    self.update_group_setting(view_id, setting_id, |setting| {
+      let groups = groups
+        .into_iter()
+        .map(|group| Any::from(GroupMap::from(group)));
+      setting.insert(GROUPS.into(), Any::Array(groups.collect()));
+    });
+  }
+
   pub fn insert_sort(&mut self, view_id: &str, sort: impl Into<SortMap>) {
     let mut txn = self.collab.transact_mut();
     self
@@ -922,6 +1169,69 @@ impl Database {
       });
   }
 
+  /// Compute the calculations of the view. Only the rows that pass the filters of the view are
+  /// calculated.
+  pub async fn compute_calculations(&self, view_id: &str) -> Vec<CalculationResult> {
+    self.get_calculation_state_for_view(view_id).await.results()
+  }
+
+  /// Return a [CalculationState] for the view. The state can be kept up to date with the
+  /// [crate::rows::RowChange]s from [Database::subscribe_row_change] without reading all the rows
+  /// again.
+  pub async fn get_calculation_state_for_view(&self, view_id: &str) -> CalculationState {
+    let row_orders = self.get_row_orders_for_view(view_id);
+    let rows = self
+      .get_rows_from_row_orders(row_orders, 20, None)
+      .await
+      .filter_map(|result| async move { result.ok() })
+      .collect::<Vec<_>>()
+      .await;
+    CalculationState::new(
+      self.get_all_calculations::<Calculation>(view_id),
+      self.get_fields(None),
+      self.get_all_filters::<Filter>(view_id),
+      rows,
+    )
+  }
+
+  /// Subscribe to the results of the calculations of the view. The results are sent when a
+  /// [crate::rows::RowChange] updates them, see [CalculationState::apply_row_change]. Return None
+  /// if the database doesn't notify its row changes.
+  ///
+  /// The state is kept until the database is dropped, or until a result is sent after every
+  /// receiver was dropped.
+  pub async fn subscribe_calculation_change(
+    &self,
+    view_id: &str,
+  ) -> Option<CalculationChangeReceiver> {
+    // Subscribe before reading the rows, so the changes made while reading them aren't missed.
+    // Applying a change that was already read doesn't change the results.
+    let mut row_change_rx = self.subscribe_row_change()?;
+    let mut state = self.get_calculation_state_for_view(view_id).await;
+    let (calculation_change_tx, calculation_change_rx) = broadcast::channel(100);
+    let view_id = view_id.to_string();
+    tokio::spawn(async move {
+      loop {
+        match row_change_rx.recv().await {
+          Ok(change) => {
+            let results = state.apply_row_change(&change);
+            if !results.is_empty() && calculation_change_tx.send(results).is_err() {
+              break;
+            }
+          },
+          Err(RecvError::Lagged(skipped)) => {
+            warn!(
+              "Skipped {} row changes, the calculations of view {} may be stale",
+              skipped, view_id
+            );
+          },
+          Err(RecvError::Closed) => break,
+        }
+      }
+    });
+    Some(calculation_change_rx)
+  }
+
   pub fn get_all_calculations<T: TryFrom<CalculationMap>>(&self, view_id: &str) -> Vec<T> {
     let txn = self.collab.transact();
     self
@@ -1364,6 +1674,60 @@ impl Database {
     self.body.views.get_view(&txn, view_id)
   }
 
+  /// Write the rows of the view as CSV using the default [CSVExportOptions].
+  pub async fn export_csv<W: io::Write>(
+    &self,
+    view_id: &str,
+    writer: W,
+  ) -> Result<(), DatabaseError> {
+    self
+      .export_csv_with_options(view_id, writer, &CSVExportOptions::default())
+      .await
+  }
+
+  /// Write the rows of the view as CSV. The columns follow the field order of the view and skip
+  /// the hidden fields unless [CSVExportOptions::include_hidden_fields] is set. The rows follow
+  /// the row order of the view, and each cell is formatted by the type option of its field.
+  pub async fn export_csv_with_options<W: io::Write>(
+    &self,
+    view_id: &str,
+    writer: W,
+    options: &CSVExportOptions,
+  ) -> Result<(), DatabaseError> {
+    let view = self
+      .get_view(view_id)
+      .ok_or(DatabaseError::DatabaseViewNotExist)?;
+    let mut fields = self.get_fields_in_view(view_id, None);
+    if !options.include_hidden_fields {
+      let field_settings = view.field_settings.into_inner();
+      fields.retain(|field| {
+        let visibility = field_settings
+          .get(&field.id)
+          .map(|settings| FieldSettings::from_any_map(&field.id, view.layout, settings).visibility)
+          .unwrap_or_else(|| default_field_visibility(view.layout));
+        field.is_primary || visibility != FieldVisibility::AlwaysHidden
+      });
+    }
+
+    let rows: Vec<Row> = self
+      .get_rows_for_view(view_id, 20, None)
+      .await
+      .filter_map(|result| async move { result.ok() })
+      .collect()
+      .await;
+    let mut rows_with_meta = Vec::with_capacity(rows.len());
+    for row in rows {
+      let row_meta = if options.include_row_meta {
+        self.get_row_meta(&row.id).await
+      } else {
+        None
+      };
+      rows_with_meta.push((row, row_meta));
+    }
+
+    CSVExporter::new(options, fields).write(writer, &rows_with_meta)
+  }
+
   pub async fn to_json_value(&self) -> JsonValue {
     let database_data = self.get_database_data().await;
     serde_json::to_value(&database_data).unwrap()
@@ -1439,6 +1803,101 @@ impl Database {
     let mut txn = self.collab.transact_mut();
     self.body.fields.update_field(&mut txn, field_id, f);
   }
+
+  /// Checks the expression of the formula field against the fields of the database without
+  /// saving it. Returns the type of the value the expression evaluates to.
+  pub fn validate_formula(
+    &self,
+    field_id: &str,
+    expression: &str,
+  ) -> Result<FormulaResultType, DatabaseError> {
+    let fields = self.get_all_fields();
+    Ok(check_formula(field_id, expression, &fields)?)
+  }
+
+  /// Saves the expression of the formula field and recomputes the formula cells of all the rows.
+  /// The expression is rejected if it's invalid, in which case the field is left unchanged.
+  pub async fn update_formula(
+    &mut self,
+    field_id: &str,
+    expression: &str,
+  ) -> Result<FormulaResultType, DatabaseError> {
+    let field = self
+      .get_field(field_id)
+      .ok_or_else(|| FormulaError::UnknownField(field_id.to_string()))?;
+    if !FieldType::from(field.field_type).is_formula() {
+      return Err(FormulaError::NotFormulaField(field_id.to_string()).into());
+    }
+
+    let result_type = self.validate_formula(field_id, expression)?;
+    let type_option = FormulaTypeOption::new(expression).with_result_type(result_type);
+    self.update_field(field_id, |update| {
+      update.set_type_option(FieldType::Formula.into(), Some(type_option.into()));
+    });
+    self.refresh_all_formula_cells().await;
+    Ok(result_type)
+  }
+
+  /// Returns the fields read by the formulas of the database. They are cached until a field
+  /// changes, so it's cheap to call on every row update.
+  pub fn get_formula_dependencies(&self) -> Arc<FormulaDependencies> {
+    let txn = self.collab.transact();
+    self.body.fields.get_formula_dependencies(&txn)
+  }
+
+  /// Recomputes the formula cells of the given row. Only the cells whose value changed are
+  /// written.
+  pub async fn refresh_formula_cells(&mut self, row_id: &RowId) {
+    if !self.get_formula_dependencies().has_formulas() {
+      return;
+    }
+    if let Some(row) = self.read_row(row_id).await {
+      let cells = FormulaEvaluator::new(&self.get_all_fields()).compute_cells(&row);
+      self.write_formula_cells(row_id, cells).await;
+    }
+  }
+
+  /// Recomputes the formula cells of every row of the database
+  pub async fn refresh_all_formula_cells(&mut self) {
+    if !self.get_formula_dependencies().has_formulas() {
+      return;
+    }
+    let rows = self.collect_all_rows().await;
+    // The evaluator isn't Send, so it's dropped before the rows are written
+    let updates = {
+      let evaluator = FormulaEvaluator::new(&self.get_all_fields());
+      rows
+        .into_iter()
+        .flatten()
+        .map(|row| {
+          let cells = evaluator.compute_cells(&row);
+          (row.id, cells)
+        })
+        .collect::<Vec<_>>()
+    };
+    for (row_id, cells) in updates {
+      self.write_formula_cells(&row_id, cells).await;
+    }
+  }
+
+  async fn write_formula_cells(&mut self, row_id: &RowId, cells: Vec<(String, Cell)>) {
+    if cells.is_empty() {
+      return;
+    }
+    self
+      .body
+      .block
+      .update_row(row_id.clone(), |row_update| {
+        row_update.update_cells(|cells_update| {
+          cells
+            .into_iter()
+            .fold(cells_update, |update, (field_id, cell)| {
+              update.insert_cell(&field_id, cell)
+            });
+        });
+      })
+      .await;
+  }
 }
 
 impl Deref for Database {
@@ -4,9 +4,12 @@ use crate::error::DatabaseError;
 use crate::fields::checkbox_type_option::CheckboxTypeOption;
 use crate::fields::checklist_type_option::ChecklistTypeOption;
 use crate::fields::date_type_option::{DateTypeOption, TimeTypeOption};
+use crate::fields::formula_type_option::FormulaTypeOption;
+use crate::fields::lookup_type_option::LookupTypeOption;
 use crate::fields::media_type_option::MediaTypeOption;
 use crate::fields::number_type_option::NumberTypeOption;
 use crate::fields::relation_type_option::RelationTypeOption;
+use crate::fields::rollup_type_option::RollupTypeOption;
 use crate::fields::select_type_option::{MultiSelectTypeOption, SingleSelectTypeOption};
 use crate::fields::summary_type_option::SummarizationTypeOption;
 use crate::fields::text_type_option::RichTextTypeOption;
@@ -285,6 +288,9 @@ pub enum FieldType {
   Translate = 12,
   Time = 13,
   Media = 14,
+  Formula = 15,
+  Lookup = 16,
+  Rollup = 17,
 }
 
 impl FieldType {
@@ -357,6 +363,9 @@ impl FieldType {
       FieldType::Translate => "Translate",
       FieldType::Time => "Time",
       FieldType::Media => "Media",
+      FieldType::Formula => "Formula",
+      FieldType::Lookup => "Lookup",
+      FieldType::Rollup => "Rollup",
     };
     s.to_string()
   }
@@ -421,8 +430,24 @@ impl FieldType {
     matches!(self, FieldType::Media)
   }
 
+  pub fn is_formula(&self) -> bool {
+    matches!(self, FieldType::Formula)
+  }
+
+  pub fn is_lookup(&self) -> bool {
+    matches!(self, FieldType::Lookup)
+  }
+
+  pub fn is_rollup(&self) -> bool {
+    matches!(self, FieldType::Rollup)
+  }
+
   pub fn can_be_group(&self) -> bool {
-    self.is_select_option() || self.is_checkbox() || self.is_url()
+    self.is_select_option()
+      || self.is_checkbox()
+      || self.is_url()
+      || self.is_date()
+      || self.is_text()
   }
 
   pub fn is_auto_update(&self) -> bool {
@@ -448,6 +473,9 @@ impl From<i64> for FieldType {
       12 => FieldType::Translate,
       13 => FieldType::Time,
       14 => FieldType::Media,
+      15 => FieldType::Formula,
+      16 => FieldType::Lookup,
+      17 => FieldType::Rollup,
       _ => {
         error!("Unknown field type: {}, fallback to text", index);
         FieldType::RichText
@@ -476,6 +504,9 @@ pub fn default_type_option_data_from_type(field_type: FieldType) -> TypeOptionDa
     FieldType::Relation => RelationTypeOption::default().into(),
     FieldType::Summary => SummarizationTypeOption::default().into(),
     FieldType::Translate => TranslateTypeOption::default().into(),
+    FieldType::Formula => FormulaTypeOption::default().into(),
+    FieldType::Lookup => LookupTypeOption::default().into(),
+    FieldType::Rollup => RollupTypeOption::default().into(),
   }
 }
 
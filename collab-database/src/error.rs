@@ -1,3 +1,4 @@
+use crate::fields::FormulaError;
 use crate::rows::RowId;
 use collab_entity::CollabValidateError;
 
@@ -45,6 +46,12 @@ pub enum DatabaseError {
   #[error("Import data failed: {0}")]
   ImportData(String),
 
+  #[error("Export data failed: {0}")]
+  ExportData(String),
+
+  #[error(transparent)]
+  InvalidFormula(#[from] FormulaError),
+
   #[error("Internal failure: {0}")]
   Internal(#[from] anyhow::Error),
 }
@@ -59,6 +66,7 @@ impl From<CollabValidateError> for DatabaseError {
   fn from(error: CollabValidateError) -> Self {
     match error {
       CollabValidateError::NoRequiredData(data) => DatabaseError::NoRequiredData(data),
+      err @ CollabValidateError::InvalidData(_) => DatabaseError::NoRequiredData(err.to_string()),
     }
   }
 }
@@ -1,9 +1,14 @@
-use collab::preclude::{Map, MapExt, MapRef, ReadTxn, Subscription, TransactionMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use collab::preclude::{
+  DeepObservable, Map, MapExt, MapRef, ReadTxn, Subscription, TransactionMut,
+};
 
 use crate::database::timestamp;
 use crate::fields::{
-  Field, FieldBuilder, FieldChangeSender, FieldUpdate, field_from_map_ref, field_from_value,
-  field_id_from_value, primary_field_id_from_value, subscribe_field_change,
+  Field, FieldBuilder, FieldChangeSender, FieldUpdate, FormulaDependencies, field_from_map_ref,
+  field_from_value, field_id_from_value, primary_field_id_from_value, subscribe_field_change,
 };
 use crate::views::FieldOrder;
 
@@ -12,15 +17,48 @@ pub struct FieldMap {
   container: MapRef,
   #[allow(dead_code)]
   subscription: Option<Subscription>,
+  /// Incremented every time a field is changed, locally or by a remote update
+  revision: Arc<AtomicU64>,
+  #[allow(dead_code)]
+  revision_subscription: Subscription,
+  /// The [FormulaDependencies] of the fields, with the revision they were built from
+  formula_dependencies: Mutex<Option<(u64, Arc<FormulaDependencies>)>>,
 }
 
 impl FieldMap {
   pub fn new(mut container: MapRef, field_change_tx: Option<FieldChangeSender>) -> Self {
     let subscription = field_change_tx.map(|tx| subscribe_field_change(&mut container, tx));
+    let revision = Arc::new(AtomicU64::new(0));
+    let revision_subscription = {
+      let revision = revision.clone();
+      container.observe_deep(move |_, _| {
+        revision.fetch_add(1, Ordering::AcqRel);
+      })
+    };
     Self {
       container,
       subscription,
+      revision,
+      revision_subscription,
+      formula_dependencies: Mutex::new(None),
+    }
+  }
+
+  /// Returns the [FormulaDependencies] of the fields. They are only rebuilt after a field changed.
+  pub fn get_formula_dependencies<T: ReadTxn>(&self, txn: &T) -> Arc<FormulaDependencies> {
+    let revision = self.revision.load(Ordering::Acquire);
+    let mut cache = self
+      .formula_dependencies
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    if let Some((cached_revision, dependencies)) = cache.as_ref() {
+      if *cached_revision == revision {
+        return dependencies.clone();
+      }
     }
+    let dependencies = Arc::new(FormulaDependencies::new(&self.get_all_fields(txn)));
+    *cache = Some((revision, dependencies.clone()));
+    dependencies
   }
 
   /// Insert a field into the map with a transaction
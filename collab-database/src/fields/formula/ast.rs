@@ -0,0 +1,163 @@
+use std::fmt::{Display, Formatter};
+
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+/// The type of the value an [Expr] evaluates to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum FormulaResultType {
+  #[default]
+  Text = 0,
+  Number = 1,
+  Bool = 2,
+  /// A unix timestamp in seconds
+  Date = 3,
+}
+
+impl FormulaResultType {
+  pub fn value(&self) -> i64 {
+    *self as i64
+  }
+}
+
+impl From<i64> for FormulaResultType {
+  fn from(value: i64) -> Self {
+    match value {
+      1 => FormulaResultType::Number,
+      2 => FormulaResultType::Bool,
+      3 => FormulaResultType::Date,
+      _ => FormulaResultType::Text,
+    }
+  }
+}
+
+impl Display for FormulaResultType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      FormulaResultType::Text => "text",
+      FormulaResultType::Number => "number",
+      FormulaResultType::Bool => "boolean",
+      FormulaResultType::Date => "date",
+    };
+    f.write_str(s)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+  Neg,
+  Not,
+}
+
+impl Display for UnaryOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      UnaryOp::Neg => f.write_str("-"),
+      UnaryOp::Not => f.write_str("not"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+  Concat,
+  Eq,
+  NotEq,
+  Lt,
+  LtEq,
+  Gt,
+  GtEq,
+  And,
+  Or,
+}
+
+impl Display for BinaryOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      BinaryOp::Add => "+",
+      BinaryOp::Sub => "-",
+      BinaryOp::Mul => "*",
+      BinaryOp::Div => "/",
+      BinaryOp::Rem => "%",
+      BinaryOp::Concat => "&",
+      BinaryOp::Eq => "==",
+      BinaryOp::NotEq => "!=",
+      BinaryOp::Lt => "<",
+      BinaryOp::LtEq => "<=",
+      BinaryOp::Gt => ">",
+      BinaryOp::GtEq => ">=",
+      BinaryOp::And => "and",
+      BinaryOp::Or => "or",
+    };
+    f.write_str(s)
+  }
+}
+
+/// The syntax tree of a formula expression. It's produced by [crate::fields::parse_formula].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+  Number(f64),
+  Text(String),
+  Bool(bool),
+  /// A reference to another field of the same row, written as `{field_id}`
+  Field(String),
+  Unary {
+    op: UnaryOp,
+    expr: Box<Expr>,
+  },
+  Binary {
+    op: BinaryOp,
+    lhs: Box<Expr>,
+    rhs: Box<Expr>,
+  },
+  /// `if(condition, then, otherwise)`
+  If {
+    condition: Box<Expr>,
+    then: Box<Expr>,
+    otherwise: Box<Expr>,
+  },
+  Call {
+    name: String,
+    args: Vec<Expr>,
+  },
+}
+
+impl Expr {
+  /// Returns the ids of all the fields referenced by the expression
+  pub fn field_refs(&self) -> Vec<&str> {
+    let mut refs = vec![];
+    self.collect_field_refs(&mut refs);
+    refs
+  }
+
+  fn collect_field_refs<'a>(&'a self, refs: &mut Vec<&'a str>) {
+    match self {
+      Expr::Number(_) | Expr::Text(_) | Expr::Bool(_) => {},
+      Expr::Field(field_id) => {
+        if !refs.contains(&field_id.as_str()) {
+          refs.push(field_id);
+        }
+      },
+      Expr::Unary { expr, .. } => expr.collect_field_refs(refs),
+      Expr::Binary { lhs, rhs, .. } => {
+        lhs.collect_field_refs(refs);
+        rhs.collect_field_refs(refs);
+      },
+      Expr::If {
+        condition,
+        then,
+        otherwise,
+      } => {
+        condition.collect_field_refs(refs);
+        then.collect_field_refs(refs);
+        otherwise.collect_field_refs(refs);
+      },
+      Expr::Call { args, .. } => args.iter().for_each(|arg| arg.collect_field_refs(refs)),
+    }
+  }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use crate::entity::FieldType;
+use crate::fields::Field;
+use crate::fields::formula::ast::{BinaryOp, Expr, FormulaResultType, UnaryOp};
+use crate::fields::formula::error::FormulaError;
+use crate::fields::formula::parser::parse_formula;
+use crate::fields::formula_type_option::FormulaTypeOption;
+
+/// Units accepted by `date_add` and `date_diff`
+pub(crate) const DATE_UNITS: [&str; 6] = ["minutes", "hours", "days", "weeks", "months", "years"];
+
+/// The type a formula sees when it refers to a field of the given type.
+///
+/// Checklists are read as their completion percentage (0 to 1), every field type that isn't
+/// listed here is read as the text shown in its cell.
+pub fn formula_type_of_field(field_type: &FieldType) -> FormulaResultType {
+  match field_type {
+    FieldType::Number | FieldType::Time | FieldType::Checklist => FormulaResultType::Number,
+    FieldType::Checkbox => FormulaResultType::Bool,
+    FieldType::DateTime | FieldType::CreatedTime | FieldType::LastEditedTime => {
+      FormulaResultType::Date
+    },
+    _ => FormulaResultType::Text,
+  }
+}
+
+/// A parsed and type checked formula
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledFormula {
+  pub(crate) expr: Expr,
+  pub(crate) result_type: FormulaResultType,
+}
+
+/// Resolves the formulas of a database. Every formula is checked at most once, and the
+/// references between formula fields are followed to find circular references.
+pub(crate) struct FormulaResolver<'a> {
+  fields: HashMap<&'a str, &'a Field>,
+  /// Overrides the expression stored in the type option of a formula field. It's used to check
+  /// an expression before it's saved.
+  overrides: HashMap<String, String>,
+  resolved: HashMap<String, Result<CompiledFormula, FormulaError>>,
+  stack: Vec<String>,
+}
+
+impl<'a> FormulaResolver<'a> {
+  pub(crate) fn new(fields: &'a [Field]) -> Self {
+    Self {
+      fields: fields
+        .iter()
+        .map(|field| (field.id.as_str(), field))
+        .collect(),
+      overrides: HashMap::new(),
+      resolved: HashMap::new(),
+      stack: vec![],
+    }
+  }
+
+  pub(crate) fn with_expression(mut self, field_id: &str, expression: &str) -> Self {
+    self
+      .overrides
+      .insert(field_id.to_string(), expression.to_string());
+    self
+  }
+
+  /// Parses and type checks the formula of the given field
+  pub(crate) fn resolve(&mut self, field_id: &str) -> Result<CompiledFormula, FormulaError> {
+    if let Some(result) = self.resolved.get(field_id) {
+      return result.clone();
+    }
+    if let Some(index) = self.stack.iter().position(|id| id == field_id) {
+      let mut cycle = self.stack[index..].to_vec();
+      cycle.push(field_id.to_string());
+      return Err(FormulaError::CircularReference(cycle));
+    }
+
+    let expression = match self.overrides.get(field_id) {
+      Some(expression) => expression.clone(),
+      None => {
+        let field = self
+          .fields
+          .get(field_id)
+          .ok_or_else(|| FormulaError::UnknownField(field_id.to_string()))?;
+        if FieldType::from(field.field_type) != FieldType::Formula {
+          return Err(FormulaError::NotFormulaField(field_id.to_string()));
+        }
+        field
+          .get_type_option::<FormulaTypeOption>(FieldType::Formula.type_id())
+          .unwrap_or_default()
+          .expression
+      },
+    };
+
+    self.stack.push(field_id.to_string());
+    let result = parse_formula(&expression).and_then(|expr| {
+      let result_type = self.check(&expr)?;
+      Ok(CompiledFormula { expr, result_type })
+    });
+    self.stack.pop();
+    self.resolved.insert(field_id.to_string(), result.clone());
+    result
+  }
+
+  fn field_type(&mut self, field_id: &str) -> Result<FormulaResultType, FormulaError> {
+    if self.overrides.contains_key(field_id) {
+      return self.resolve(field_id).map(|formula| formula.result_type);
+    }
+    let field_type = self
+      .fields
+      .get(field_id)
+      .map(|field| FieldType::from(field.field_type))
+      .ok_or_else(|| FormulaError::UnknownField(field_id.to_string()))?;
+    match field_type {
+      FieldType::Formula => self.resolve(field_id).map(|formula| formula.result_type),
+      field_type => Ok(formula_type_of_field(&field_type)),
+    }
+  }
+
+  fn check(&mut self, expr: &Expr) -> Result<FormulaResultType, FormulaError> {
+    use FormulaResultType::*;
+
+    match expr {
+      Expr::Number(_) => Ok(Number),
+      Expr::Text(_) => Ok(Text),
+      Expr::Bool(_) => Ok(Bool),
+      Expr::Field(field_id) => self.field_type(field_id),
+      Expr::Unary { op, expr } => {
+        let ty = self.check(expr)?;
+        let expected = match op {
+          UnaryOp::Neg => Number,
+          UnaryOp::Not => Bool,
+        };
+        if ty != expected {
+          return Err(FormulaError::type_mismatch(
+            format!("'{}'", op),
+            expected,
+            ty,
+          ));
+        }
+        Ok(ty)
+      },
+      Expr::Binary { op, lhs, rhs } => {
+        let lhs_ty = self.check(lhs)?;
+        let rhs_ty = self.check(rhs)?;
+        let result = match (op, lhs_ty, rhs_ty) {
+          (BinaryOp::Concat, _, _) => Some(Text),
+          (BinaryOp::Add, Number, Number) => Some(Number),
+          (BinaryOp::Add, Text, Text) => Some(Text),
+          (BinaryOp::Add, Date, Number) | (BinaryOp::Add, Number, Date) => Some(Date),
+          (BinaryOp::Sub, Number, Number) => Some(Number),
+          (BinaryOp::Sub, Date, Number) => Some(Date),
+          (BinaryOp::Sub, Date, Date) => Some(Number),
+          (BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem, Number, Number) => Some(Number),
+          (BinaryOp::Eq | BinaryOp::NotEq, lhs, rhs) if lhs == rhs => Some(Bool),
+          (BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq, lhs, rhs)
+            if lhs == rhs && lhs != Bool =>
+          {
+            Some(Bool)
+          },
+          (BinaryOp::And | BinaryOp::Or, Bool, Bool) => Some(Bool),
+          _ => None,
+        };
+        result.ok_or_else(|| {
+          let expected = match op {
+            BinaryOp::Add => "two numbers, two texts or a date and a number of days",
+            BinaryOp::Sub => "two numbers, two dates or a date and a number of days",
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => "two numbers",
+            BinaryOp::Eq | BinaryOp::NotEq => "two values of the same type",
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {
+              "two numbers, texts or dates"
+            },
+            BinaryOp::And | BinaryOp::Or => "two booleans",
+            BinaryOp::Concat => "any two values",
+          };
+          let found = format!("{} and {}", lhs_ty, rhs_ty);
+          FormulaError::type_mismatch(format!("'{}'", op), expected, found)
+        })
+      },
+      Expr::If {
+        condition,
+        then,
+        otherwise,
+      } => {
+        let condition_ty = self.check(condition)?;
+        if condition_ty != Bool {
+          return Err(FormulaError::type_mismatch(
+            "the condition of 'if'",
+            Bool,
+            condition_ty,
+          ));
+        }
+        let then_ty = self.check(then)?;
+        let otherwise_ty = self.check(otherwise)?;
+        if then_ty != otherwise_ty {
+          return Err(FormulaError::type_mismatch(
+            "the branches of 'if'",
+            then_ty,
+            otherwise_ty,
+          ));
+        }
+        Ok(then_ty)
+      },
+      Expr::Call { name, args } => self.check_call(name, args),
+    }
+  }
+
+  fn check_call(&mut self, name: &str, args: &[Expr]) -> Result<FormulaResultType, FormulaError> {
+    use FormulaResultType::*;
+
+    let arg_types = args
+      .iter()
+      .map(|arg| self.check(arg))
+      .collect::<Result<Vec<_>, _>>()?;
+    let (params, variadic, result): (Vec<Option<FormulaResultType>>, bool, FormulaResultType) =
+      match name {
+        "concat" => (vec![None], true, Text),
+        "text" => (vec![None], false, Text),
+        "is_empty" => (vec![None], false, Bool),
+        "number" => (vec![None], false, Number),
+        "len" => (vec![Some(Text)], false, Number),
+        "upper" | "lower" | "trim" => (vec![Some(Text)], false, Text),
+        "contains" => (vec![Some(Text), Some(Text)], false, Bool),
+        "abs" | "floor" | "ceil" => (vec![Some(Number)], false, Number),
+        "round" => match args.len() {
+          1 => (vec![Some(Number)], false, Number),
+          _ => (vec![Some(Number), Some(Number)], false, Number),
+        },
+        "min" | "max" => (vec![Some(Number)], true, Number),
+        "date" => (vec![Some(Number), Some(Number), Some(Number)], false, Date),
+        "year" | "month" | "day" | "weekday" => (vec![Some(Date)], false, Number),
+        "date_add" => (vec![Some(Date), Some(Number), Some(Text)], false, Date),
+        "date_diff" => (vec![Some(Date), Some(Date), Some(Text)], false, Number),
+        _ => return Err(FormulaError::UnknownFunction(name.to_string())),
+      };
+
+    let count_matches = if variadic {
+      args.len() >= params.len()
+    } else {
+      args.len() == params.len()
+    };
+    if !count_matches {
+      let expected = match (name, variadic) {
+        ("round", _) => "1 or 2".to_string(),
+        (_, true) => format!("at least {}", params.len()),
+        (_, false) => params.len().to_string(),
+      };
+      return Err(FormulaError::ArgumentCount {
+        function: name.to_string(),
+        expected,
+        found: args.len(),
+      });
+    }
+
+    for (index, ty) in arg_types.iter().enumerate() {
+      let expected = params
+        .get(index)
+        .or_else(|| params.last())
+        .copied()
+        .flatten();
+      if let Some(expected) = expected {
+        if *ty != expected {
+          return Err(FormulaError::type_mismatch(
+            format!("argument {} of '{}'", index + 1, name),
+            expected,
+            *ty,
+          ));
+        }
+      }
+    }
+
+    if let ("date_add" | "date_diff", Some(Expr::Text(unit))) = (name, args.get(2)) {
+      if !DATE_UNITS.contains(&unit.to_lowercase().as_str()) {
+        return Err(FormulaError::InvalidArgument {
+          function: name.to_string(),
+          message: format!(
+            "unknown unit \"{}\", expected one of {}",
+            unit,
+            DATE_UNITS.join(", ")
+          ),
+        });
+      }
+    }
+    Ok(result)
+  }
+}
+
+/// Checks the expression of a formula field before it's saved, and returns the type of the
+/// value it evaluates to.
+///
+/// The field doesn't need to exist yet. Unknown field references, references that lead back to
+/// the field itself and type errors are all reported as a [FormulaError].
+pub fn check_formula(
+  field_id: &str,
+  expression: &str,
+  fields: &[Field],
+) -> Result<FormulaResultType, FormulaError> {
+  FormulaResolver::new(fields)
+    .with_expression(field_id, expression)
+    .resolve(field_id)
+    .map(|formula| formula.result_type)
+}
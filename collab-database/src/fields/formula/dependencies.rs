@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use crate::entity::FieldType;
+use crate::fields::Field;
+use crate::fields::formula::parser::parse_formula;
+use crate::fields::formula_type_option::FormulaTypeOption;
+
+/// The fields the formulas of a database read from.
+///
+/// It's much cheaper to build than a [crate::fields::FormulaEvaluator], and it's cached by
+/// [crate::fields::FieldMap] until a field changes, so it's used to skip recomputing the formula
+/// cells of a row when none of its inputs changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormulaDependencies {
+  has_formulas: bool,
+  referenced_field_ids: HashSet<String>,
+  /// True if a formula reads a [FieldType::LastEditedTime] field, which changes with every update
+  /// of the row
+  references_last_edited_time: bool,
+}
+
+impl FormulaDependencies {
+  pub fn new(fields: &[Field]) -> Self {
+    let mut referenced_field_ids = HashSet::new();
+    let mut has_formulas = false;
+    for field in fields {
+      if !FieldType::from(field.field_type).is_formula() {
+        continue;
+      }
+      has_formulas = true;
+      let expression = field
+        .get_type_option::<FormulaTypeOption>(FieldType::Formula.type_id())
+        .unwrap_or_default()
+        .expression;
+      // An invalid formula always evaluates to an empty value, so it doesn't depend on any field
+      if let Ok(expr) = parse_formula(&expression) {
+        referenced_field_ids.extend(expr.field_refs().into_iter().map(str::to_string));
+      }
+    }
+
+    let references_last_edited_time = fields.iter().any(|field| {
+      FieldType::from(field.field_type) == FieldType::LastEditedTime
+        && referenced_field_ids.contains(&field.id)
+    });
+    Self {
+      has_formulas,
+      referenced_field_ids,
+      references_last_edited_time,
+    }
+  }
+
+  /// Returns true if the database has at least one formula field
+  pub fn has_formulas(&self) -> bool {
+    self.has_formulas
+  }
+
+  /// Returns the ids of the fields read by the formulas
+  pub fn referenced_field_ids(&self) -> impl Iterator<Item = &str> {
+    self.referenced_field_ids.iter().map(String::as_str)
+  }
+
+  /// Returns true if updating the given cells of a row can change the value of a formula
+  pub fn is_affected_by<'a>(&self, updated_field_ids: impl IntoIterator<Item = &'a str>) -> bool {
+    if !self.has_formulas {
+      return false;
+    }
+    self.references_last_edited_time
+      || updated_field_ids
+        .into_iter()
+        .any(|field_id| self.referenced_field_ids.contains(field_id))
+  }
+}
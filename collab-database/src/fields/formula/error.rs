@@ -0,0 +1,57 @@
+/// Errors found while configuring a formula. Evaluating a valid formula never fails, a value that
+/// can't be computed for a row (e.g. a division by zero) is treated as empty.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum FormulaError {
+  #[error("Syntax error at position {position}: {message}")]
+  Syntax { position: usize, message: String },
+
+  #[error("The formula refers to an unknown field: {0}")]
+  UnknownField(String),
+
+  #[error("The formula has a circular reference: {}", .0.join(" -> "))]
+  CircularReference(Vec<String>),
+
+  #[error("Unknown function: {0}")]
+  UnknownFunction(String),
+
+  #[error("Function {function} expects {expected} argument(s), but got {found}")]
+  ArgumentCount {
+    function: String,
+    expected: String,
+    found: usize,
+  },
+
+  #[error("Type mismatch in {context}: expected {expected}, found {found}")]
+  TypeMismatch {
+    context: String,
+    expected: String,
+    found: String,
+  },
+
+  #[error("Invalid argument for {function}: {message}")]
+  InvalidArgument { function: String, message: String },
+
+  #[error("The field {0} is not a formula field")]
+  NotFormulaField(String),
+}
+
+impl FormulaError {
+  pub(crate) fn syntax(position: usize, message: impl ToString) -> Self {
+    FormulaError::Syntax {
+      position,
+      message: message.to_string(),
+    }
+  }
+
+  pub(crate) fn type_mismatch(
+    context: impl ToString,
+    expected: impl ToString,
+    found: impl ToString,
+  ) -> Self {
+    FormulaError::TypeMismatch {
+      context: context.to_string(),
+      expected: expected.to_string(),
+      found: found.to_string(),
+    }
+  }
+}
@@ -0,0 +1,684 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, Timelike, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+use tracing::warn;
+
+use crate::entity::FieldType;
+use crate::fields::date_type_option::DateCellData;
+use crate::fields::formula::ast::{BinaryOp, Expr, FormulaResultType, UnaryOp};
+use crate::fields::formula::checker::{CompiledFormula, FormulaResolver};
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{Field, TypeOptionCellReader, type_option_cell_reader};
+use crate::rows::{Cell, Row};
+use crate::template::check_list_parse::ChecklistCellData;
+use crate::template::formula_parse::FormulaCellData;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// The value of a formula for a single row
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaValue {
+  /// The value couldn't be computed, e.g. the referenced cell is empty or a number was divided
+  /// by zero
+  Empty,
+  Number(f64),
+  Text(String),
+  Bool(bool),
+  /// A unix timestamp in seconds
+  Date(i64),
+}
+
+impl FormulaValue {
+  pub fn is_empty(&self) -> bool {
+    match self {
+      FormulaValue::Empty => true,
+      FormulaValue::Text(s) => s.is_empty(),
+      _ => false,
+    }
+  }
+
+  /// Returns the numeric value used to sort and calculate the formula. Dates are returned as
+  /// timestamps and booleans as 1 or 0.
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      FormulaValue::Number(n) => Some(*n),
+      FormulaValue::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+      FormulaValue::Date(timestamp) => Some(*timestamp as f64),
+      FormulaValue::Empty | FormulaValue::Text(_) => None,
+    }
+  }
+
+  fn number(value: f64) -> Self {
+    if value.is_finite() {
+      FormulaValue::Number(value)
+    } else {
+      FormulaValue::Empty
+    }
+  }
+}
+
+impl Display for FormulaValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FormulaValue::Empty => Ok(()),
+      FormulaValue::Number(n) => match Decimal::from_f64(*n) {
+        Some(decimal) => write!(f, "{}", decimal.round_dp(10).normalize()),
+        None => write!(f, "{}", n),
+      },
+      FormulaValue::Text(s) => f.write_str(s),
+      FormulaValue::Bool(true) => f.write_str("Yes"),
+      FormulaValue::Bool(false) => f.write_str("No"),
+      FormulaValue::Date(timestamp) => match DateTime::<Utc>::from_timestamp(*timestamp, 0) {
+        Some(date) if date.num_seconds_from_midnight() == 0 => {
+          write!(f, "{}", date.format("%Y-%m-%d"))
+        },
+        Some(date) => write!(f, "{}", date.format("%Y-%m-%d %H:%M")),
+        None => Ok(()),
+      },
+    }
+  }
+}
+
+struct SourceField {
+  field_type: FieldType,
+  reader: Box<dyn TypeOptionCellReader>,
+}
+
+/// [FormulaEvaluator] computes the formula fields of a database.
+///
+/// All the formulas are parsed and checked once when the evaluator is built. A formula that is
+/// invalid, or that depends on an invalid formula, evaluates to [FormulaValue::Empty] for every
+/// row. Dates are computed in UTC.
+pub struct FormulaEvaluator {
+  formulas: HashMap<String, CompiledFormula>,
+  fields: HashMap<String, SourceField>,
+}
+
+impl FormulaEvaluator {
+  pub fn new(fields: &[Field]) -> Self {
+    let mut resolver = FormulaResolver::new(fields);
+    let mut formulas = HashMap::new();
+    for field in fields {
+      if FieldType::from(field.field_type) != FieldType::Formula {
+        continue;
+      }
+      match resolver.resolve(&field.id) {
+        Ok(formula) => {
+          formulas.insert(field.id.clone(), formula);
+        },
+        Err(err) => warn!("formula field {} is invalid: {}", field.id, err),
+      }
+    }
+
+    let fields = fields
+      .iter()
+      .map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let type_option = field
+          .get_any_type_option(field_type.type_id())
+          .unwrap_or_default();
+        (
+          field.id.clone(),
+          SourceField {
+            field_type,
+            reader: type_option_cell_reader(type_option, &field_type),
+          },
+        )
+      })
+      .collect();
+    Self { formulas, fields }
+  }
+
+  /// Returns true if the database has at least one formula field
+  pub fn has_formulas(&self) -> bool {
+    self
+      .fields
+      .values()
+      .any(|field| field.field_type == FieldType::Formula)
+  }
+
+  /// Returns the value of the given formula field for the row
+  pub fn evaluate(&self, field_id: &str, row: &Row) -> FormulaValue {
+    let mut cache = HashMap::new();
+    self.evaluate_field(field_id, row, &mut cache)
+  }
+
+  /// Computes the cells of all the formula fields of the row. Only the cells whose value differs
+  /// from the one cached in the row are returned.
+  pub fn compute_cells(&self, row: &Row) -> Vec<(String, Cell)> {
+    let mut cache = HashMap::new();
+    let mut field_ids = self
+      .fields
+      .iter()
+      .filter(|(_, field)| field.field_type == FieldType::Formula)
+      .map(|(field_id, _)| field_id.clone())
+      .collect::<Vec<_>>();
+    field_ids.sort();
+
+    field_ids
+      .into_iter()
+      .filter_map(|field_id| {
+        let value = self.evaluate_field(&field_id, row, &mut cache);
+        let cell_data = FormulaCellData::from(&value);
+        let old_cell_data = row.cells.get(&field_id).map(FormulaCellData::from);
+        if old_cell_data.as_ref() == Some(&cell_data) {
+          None
+        } else {
+          Some((field_id, Cell::from(cell_data)))
+        }
+      })
+      .collect()
+  }
+
+  fn evaluate_field(
+    &self,
+    field_id: &str,
+    row: &Row,
+    cache: &mut HashMap<String, FormulaValue>,
+  ) -> FormulaValue {
+    if let Some(value) = cache.get(field_id) {
+      return value.clone();
+    }
+    let value = match self.formulas.get(field_id) {
+      // Circular references are rejected when the formulas are resolved, so the recursion always
+      // terminates.
+      Some(formula) => self.eval(&formula.expr, row, cache),
+      None => FormulaValue::Empty,
+    };
+    cache.insert(field_id.to_string(), value.clone());
+    value
+  }
+
+  fn field_value(
+    &self,
+    field_id: &str,
+    row: &Row,
+    cache: &mut HashMap<String, FormulaValue>,
+  ) -> FormulaValue {
+    let field = match self.fields.get(field_id) {
+      Some(field) => field,
+      None => return FormulaValue::Empty,
+    };
+    let cell = row.cells.get(field_id);
+    match field.field_type {
+      FieldType::Formula => self.evaluate_field(field_id, row, cache),
+      FieldType::CreatedTime => FormulaValue::Date(row.created_at),
+      FieldType::LastEditedTime => FormulaValue::Date(row.modified_at),
+      FieldType::Checkbox => FormulaValue::Bool(
+        cell
+          .and_then(|cell| field.reader.numeric_cell(cell))
+          .is_some_and(|value| value > 0.0),
+      ),
+      FieldType::Number | FieldType::Time => cell
+        .and_then(|cell| field.reader.numeric_cell(cell))
+        .map(FormulaValue::number)
+        .unwrap_or(FormulaValue::Empty),
+      FieldType::DateTime => cell
+        .and_then(|cell| DateCellData::from(cell).timestamp)
+        .map(FormulaValue::Date)
+        .unwrap_or(FormulaValue::Empty),
+      FieldType::Checklist => {
+        let cell_data = cell.map(ChecklistCellData::from).unwrap_or_default();
+        if cell_data.options.is_empty() {
+          FormulaValue::Empty
+        } else {
+          FormulaValue::Number(cell_data.percentage_complete())
+        }
+      },
+      FieldType::URL => FormulaValue::Text(
+        cell
+          .map(|cell| URLCellData::from(cell).data)
+          .unwrap_or_default(),
+      ),
+      _ => FormulaValue::Text(
+        cell
+          .map(|cell| field.reader.stringify_cell(cell))
+          .unwrap_or_default(),
+      ),
+    }
+  }
+
+  fn eval(
+    &self,
+    expr: &Expr,
+    row: &Row,
+    cache: &mut HashMap<String, FormulaValue>,
+  ) -> FormulaValue {
+    use FormulaValue::*;
+
+    match expr {
+      Expr::Number(n) => Number(*n),
+      Expr::Text(s) => Text(s.clone()),
+      Expr::Bool(b) => Bool(*b),
+      Expr::Field(field_id) => self.field_value(field_id, row, cache),
+      Expr::Unary { op, expr } => match (op, self.eval(expr, row, cache)) {
+        (UnaryOp::Neg, Number(n)) => Number(-n),
+        (UnaryOp::Not, Bool(b)) => Bool(!b),
+        _ => Empty,
+      },
+      Expr::Binary { op, lhs, rhs } => {
+        let lhs = self.eval(lhs, row, cache);
+        // Only evaluate the right hand side when it's needed
+        match (op, &lhs) {
+          (BinaryOp::And, Bool(false)) => return Bool(false),
+          (BinaryOp::Or, Bool(true)) => return Bool(true),
+          _ => {},
+        }
+        let rhs = self.eval(rhs, row, cache);
+        binary(*op, lhs, rhs)
+      },
+      Expr::If {
+        condition,
+        then,
+        otherwise,
+      } => match self.eval(condition, row, cache) {
+        Bool(true) => self.eval(then, row, cache),
+        _ => self.eval(otherwise, row, cache),
+      },
+      Expr::Call { name, args } => {
+        let args = args
+          .iter()
+          .map(|arg| self.eval(arg, row, cache))
+          .collect::<Vec<_>>();
+        call(name, args)
+      },
+    }
+  }
+}
+
+fn binary(op: BinaryOp, lhs: FormulaValue, rhs: FormulaValue) -> FormulaValue {
+  use FormulaValue::*;
+
+  match (op, lhs, rhs) {
+    (BinaryOp::Concat, lhs, rhs) => Text(format!("{}{}", lhs, rhs)),
+    (BinaryOp::Add, Text(lhs), Text(rhs)) => Text(lhs + &rhs),
+    (BinaryOp::Add, Number(lhs), Number(rhs)) => FormulaValue::number(lhs + rhs),
+    (BinaryOp::Add, Date(date), Number(days)) | (BinaryOp::Add, Number(days), Date(date)) => {
+      add_seconds(date, days * SECONDS_PER_DAY as f64)
+    },
+    (BinaryOp::Sub, Number(lhs), Number(rhs)) => FormulaValue::number(lhs - rhs),
+    (BinaryOp::Sub, Date(date), Number(days)) => add_seconds(date, -days * SECONDS_PER_DAY as f64),
+    (BinaryOp::Sub, Date(lhs), Date(rhs)) => lhs
+      .checked_sub(rhs)
+      .map(|seconds| FormulaValue::number(seconds as f64 / SECONDS_PER_DAY as f64))
+      .unwrap_or(Empty),
+    (BinaryOp::Mul, Number(lhs), Number(rhs)) => FormulaValue::number(lhs * rhs),
+    (BinaryOp::Div, Number(lhs), Number(rhs)) => FormulaValue::number(lhs / rhs),
+    (BinaryOp::Rem, Number(lhs), Number(rhs)) => FormulaValue::number(lhs % rhs),
+    (BinaryOp::And, Bool(lhs), Bool(rhs)) => Bool(lhs && rhs),
+    (BinaryOp::Or, Bool(lhs), Bool(rhs)) => Bool(lhs || rhs),
+    (BinaryOp::Eq, lhs, rhs) => Bool(lhs == rhs),
+    (BinaryOp::NotEq, lhs, rhs) => Bool(lhs != rhs),
+    (op @ (BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq), lhs, rhs) => {
+      let ordering = match (lhs, rhs) {
+        (Number(lhs), Number(rhs)) => lhs.partial_cmp(&rhs),
+        (Text(lhs), Text(rhs)) => Some(lhs.cmp(&rhs)),
+        (Date(lhs), Date(rhs)) => Some(lhs.cmp(&rhs)),
+        // Comparing with an empty value is always false
+        _ => None,
+      };
+      Bool(ordering.is_some_and(|ordering| match op {
+        BinaryOp::Lt => ordering.is_lt(),
+        BinaryOp::LtEq => ordering.is_le(),
+        BinaryOp::Gt => ordering.is_gt(),
+        _ => ordering.is_ge(),
+      }))
+    },
+    _ => Empty,
+  }
+}
+
+fn add_seconds(timestamp: i64, seconds: f64) -> FormulaValue {
+  if !seconds.is_finite() {
+    return FormulaValue::Empty;
+  }
+  timestamp
+    .checked_add(seconds.round() as i64)
+    .map(FormulaValue::Date)
+    .unwrap_or(FormulaValue::Empty)
+}
+
+fn datetime(timestamp: i64) -> Option<DateTime<Utc>> {
+  DateTime::<Utc>::from_timestamp(timestamp, 0)
+}
+
+fn add_months(timestamp: i64, months: i64) -> Option<i64> {
+  let date = datetime(timestamp)?;
+  let date = if months >= 0 {
+    date.checked_add_months(Months::new(u32::try_from(months).ok()?))?
+  } else {
+    date.checked_sub_months(Months::new(u32::try_from(months.unsigned_abs()).ok()?))?
+  };
+  Some(date.timestamp())
+}
+
+/// Returns the number of whole months from `from` to `to`
+fn months_between(to: i64, from: i64) -> Option<i64> {
+  let (to_date, from_date) = (datetime(to)?, datetime(from)?);
+  let months = (to_date.year() as i64 * 12 + to_date.month0() as i64)
+    - (from_date.year() as i64 * 12 + from_date.month0() as i64);
+  let shifted = add_months(from, months)?;
+  if months > 0 && shifted > to {
+    Some(months - 1)
+  } else if months < 0 && shifted < to {
+    Some(months + 1)
+  } else {
+    Some(months)
+  }
+}
+
+fn call(name: &str, args: Vec<FormulaValue>) -> FormulaValue {
+  use FormulaValue::*;
+
+  let mut args = args.into_iter();
+  let mut next = || args.next().unwrap_or(Empty);
+  match name {
+    "concat" => {
+      let mut text = next().to_string();
+      for arg in args {
+        text.push_str(&arg.to_string());
+      }
+      Text(text)
+    },
+    "text" => Text(next().to_string()),
+    "is_empty" => Bool(next().is_empty()),
+    "number" => match next() {
+      Text(s) => s
+        .trim()
+        .parse::<f64>()
+        .map(FormulaValue::number)
+        .unwrap_or(Empty),
+      value => value.as_f64().map(FormulaValue::number).unwrap_or(Empty),
+    },
+    "len" => match next() {
+      Text(s) => Number(s.chars().count() as f64),
+      _ => Empty,
+    },
+    "upper" | "lower" | "trim" => match next() {
+      Text(s) => Text(match name {
+        "upper" => s.to_uppercase(),
+        "lower" => s.to_lowercase(),
+        _ => s.trim().to_string(),
+      }),
+      _ => Empty,
+    },
+    "contains" => match (next(), next()) {
+      (Text(s), Text(pattern)) => Bool(s.to_lowercase().contains(&pattern.to_lowercase())),
+      _ => Empty,
+    },
+    "abs" | "floor" | "ceil" => match next() {
+      Number(n) => Number(match name {
+        "abs" => n.abs(),
+        "floor" => n.floor(),
+        _ => n.ceil(),
+      }),
+      _ => Empty,
+    },
+    "round" => match (next(), next()) {
+      (Number(n), Empty) => Number(n.round()),
+      (Number(n), Number(digits)) => {
+        let factor = 10f64.powi(digits.clamp(-15.0, 15.0) as i32);
+        FormulaValue::number((n * factor).round() / factor)
+      },
+      _ => Empty,
+    },
+    "min" | "max" => {
+      let numbers = std::iter::once(next())
+        .chain(args)
+        .filter_map(|arg| match arg {
+          Number(n) => Some(n),
+          _ => None,
+        });
+      let result = if name == "min" {
+        numbers.reduce(f64::min)
+      } else {
+        numbers.reduce(f64::max)
+      };
+      result.map(Number).unwrap_or(Empty)
+    },
+    "date" => match (next(), next(), next()) {
+      (Number(year), Number(month), Number(day)) => {
+        NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32)
+          .and_then(|date| date.and_hms_opt(0, 0, 0))
+          .map(|date| Date(date.and_utc().timestamp()))
+          .unwrap_or(Empty)
+      },
+      _ => Empty,
+    },
+    "year" | "month" | "day" | "weekday" => match next() {
+      Date(timestamp) => datetime(timestamp)
+        .map(|date| {
+          Number(match name {
+            "year" => date.year() as f64,
+            "month" => date.month() as f64,
+            "day" => date.day() as f64,
+            // Monday is 1 and Sunday is 7
+            _ => date.weekday().number_from_monday() as f64,
+          })
+        })
+        .unwrap_or(Empty),
+      _ => Empty,
+    },
+    "date_add" => match (next(), next(), next()) {
+      (Date(timestamp), Number(amount), Text(unit)) => match unit.to_lowercase().as_str() {
+        "minutes" => add_seconds(timestamp, amount * 60.0),
+        "hours" => add_seconds(timestamp, amount * 3600.0),
+        "days" => add_seconds(timestamp, amount * SECONDS_PER_DAY as f64),
+        "weeks" => add_seconds(timestamp, amount * 7.0 * SECONDS_PER_DAY as f64),
+        "months" => add_months(timestamp, amount.trunc() as i64)
+          .map(Date)
+          .unwrap_or(Empty),
+        "years" => (amount.trunc() as i64)
+          .checked_mul(12)
+          .and_then(|months| add_months(timestamp, months))
+          .map(Date)
+          .unwrap_or(Empty),
+        _ => Empty,
+      },
+      _ => Empty,
+    },
+    "date_diff" => match (next(), next(), next()) {
+      (Date(to), Date(from), Text(unit)) => {
+        let Some(seconds) = to.checked_sub(from).map(|seconds| seconds as f64) else {
+          return Empty;
+        };
+        match unit.to_lowercase().as_str() {
+          "minutes" => Number((seconds / 60.0).trunc()),
+          "hours" => Number((seconds / 3600.0).trunc()),
+          "days" => Number((seconds / SECONDS_PER_DAY as f64).trunc()),
+          "weeks" => Number((seconds / (7 * SECONDS_PER_DAY) as f64).trunc()),
+          "months" => months_between(to, from)
+            .map(|months| Number(months as f64))
+            .unwrap_or(Empty),
+          "years" => months_between(to, from)
+            .map(|months| Number((months / 12) as f64))
+            .unwrap_or(Empty),
+          _ => Empty,
+        }
+      },
+      _ => Empty,
+    },
+    _ => Empty,
+  }
+}
+
+impl From<&FormulaValue> for FormulaResultType {
+  fn from(value: &FormulaValue) -> Self {
+    match value {
+      FormulaValue::Empty | FormulaValue::Text(_) => FormulaResultType::Text,
+      FormulaValue::Number(_) => FormulaResultType::Number,
+      FormulaValue::Bool(_) => FormulaResultType::Bool,
+      FormulaValue::Date(_) => FormulaResultType::Date,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fields::formula_type_option::FormulaTypeOption;
+  use crate::fields::number_type_option::NumberTypeOption;
+  use crate::rows::RowId;
+  use crate::template::entity::CELL_DATA;
+  use crate::template::number_parse::NumberCellData;
+
+  fn formula_field(id: &str, expression: &str) -> Field {
+    Field::new(
+      id.to_string(),
+      id.to_string(),
+      FieldType::Formula.into(),
+      false,
+    )
+    .with_type_option_data(
+      FieldType::Formula.type_id(),
+      FormulaTypeOption::new(expression).into(),
+    )
+  }
+
+  fn fields(formulas: &[(&str, &str)]) -> Vec<Field> {
+    let mut fields = vec![
+      Field::new(
+        "name".to_string(),
+        "Name".to_string(),
+        FieldType::RichText.into(),
+        true,
+      ),
+      Field::new(
+        "price".to_string(),
+        "Price".to_string(),
+        FieldType::Number.into(),
+        false,
+      )
+      .with_type_option_data(
+        FieldType::Number.type_id(),
+        NumberTypeOption::default().into(),
+      ),
+      Field::new(
+        "done".to_string(),
+        "Done".to_string(),
+        FieldType::Checkbox.into(),
+        false,
+      ),
+    ];
+    fields.extend(
+      formulas
+        .iter()
+        .map(|(id, expression)| formula_field(id, expression)),
+    );
+    fields
+  }
+
+  fn row() -> Row {
+    let mut row = Row::new(RowId::from("r1".to_string()), "d1");
+    let mut name = crate::rows::new_cell_builder(FieldType::RichText);
+    name.insert(CELL_DATA.into(), "Apple".into());
+    row.cells.insert("name".to_string(), name);
+    row.cells.insert(
+      "price".to_string(),
+      NumberCellData("12.5".to_string()).into(),
+    );
+    // 2024-02-29 00:00:00 UTC
+    row.created_at = 1709164800;
+    row
+  }
+
+  #[test]
+  fn evaluate_expression_test() {
+    let cases = [
+      ("{price} * 2 + 1", FormulaValue::Number(26.0)),
+      ("round({price} / 3, 2)", FormulaValue::Number(4.17)),
+      (
+        r#"{name} & " costs " & {price}"#,
+        FormulaValue::Text("Apple costs 12.5".to_string()),
+      ),
+      (
+        r#"if({price} > 10, "expensive", "cheap")"#,
+        FormulaValue::Text("expensive".to_string()),
+      ),
+      ("not {done}", FormulaValue::Bool(true)),
+      ("{price} / 0", FormulaValue::Empty),
+      (
+        r#"date_add({created}, 1, "years")"#,
+        FormulaValue::Date(1740700800),
+      ),
+      ("{created} + 1", FormulaValue::Date(1709251200)),
+      (
+        r#"date_diff(date(2024, 5, 1), {created}, "months")"#,
+        FormulaValue::Number(2.0),
+      ),
+      ("weekday({created})", FormulaValue::Number(4.0)),
+      ("upper({name}) == \"APPLE\"", FormulaValue::Bool(true)),
+    ];
+    for (expression, expected) in cases {
+      let mut fields = fields(&[("f", expression)]);
+      fields.push(Field::new(
+        "created".to_string(),
+        "Created".to_string(),
+        FieldType::CreatedTime.into(),
+        false,
+      ));
+      let evaluator = FormulaEvaluator::new(&fields);
+      assert_eq!(evaluator.evaluate("f", &row()), expected, "{}", expression);
+    }
+  }
+
+  #[test]
+  fn date_overflow_evaluates_to_empty_test() {
+    let mut fields = fields(&[]);
+    fields.push(Field::new(
+      "created".to_string(),
+      "Created".to_string(),
+      FieldType::CreatedTime.into(),
+      false,
+    ));
+    fields.push(Field::new(
+      "edited".to_string(),
+      "Edited".to_string(),
+      FieldType::LastEditedTime.into(),
+      false,
+    ));
+    let mut row = row();
+    row.created_at = i64::MIN;
+    row.modified_at = i64::MAX;
+    for expression in [
+      "{edited} - {created}",
+      r#"date_diff({edited}, {created}, "days")"#,
+      r#"date_add({created}, -100000000000000000000, "years")"#,
+    ] {
+      let mut fields = fields.clone();
+      fields.push(formula_field("f", expression));
+      let evaluator = FormulaEvaluator::new(&fields);
+      assert_eq!(
+        evaluator.evaluate("f", &row),
+        FormulaValue::Empty,
+        "{}",
+        expression
+      );
+    }
+  }
+
+  #[test]
+  fn evaluate_nested_formula_test() {
+    let fields = fields(&[
+      ("total", "{price} * 2"),
+      ("label", r#""Total: " & {total}"#),
+    ]);
+    let evaluator = FormulaEvaluator::new(&fields);
+    let cells = evaluator.compute_cells(&row());
+    assert_eq!(cells.len(), 2);
+    let label = cells.iter().find(|(id, _)| id == "label").unwrap();
+    assert_eq!(FormulaCellData::from(&label.1).to_string(), "Total: 25");
+  }
+
+  #[test]
+  fn invalid_formula_evaluates_to_empty_test() {
+    let fields = fields(&[("a", "{b} + 1"), ("b", "{a} + 1"), ("c", "{missing}")]);
+    let evaluator = FormulaEvaluator::new(&fields);
+    for field_id in ["a", "b", "c"] {
+      assert_eq!(evaluator.evaluate(field_id, &row()), FormulaValue::Empty);
+    }
+  }
+}
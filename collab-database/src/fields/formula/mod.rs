@@ -0,0 +1,13 @@
+mod ast;
+mod checker;
+mod dependencies;
+mod error;
+mod evaluator;
+mod parser;
+
+pub use ast::*;
+pub use checker::{check_formula, formula_type_of_field};
+pub use dependencies::*;
+pub use error::*;
+pub use evaluator::*;
+pub use parser::*;
@@ -0,0 +1,466 @@
+use crate::fields::formula::ast::{BinaryOp, Expr, UnaryOp};
+use crate::fields::formula::error::FormulaError;
+
+/// Deeply nested expressions are rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Number(f64),
+  Text(String),
+  Ident(String),
+  Field(String),
+  LParen,
+  RParen,
+  Comma,
+  Op(BinaryOp),
+  Minus,
+  Not,
+  Eof,
+}
+
+impl Token {
+  fn describe(&self) -> String {
+    match self {
+      Token::Number(n) => format!("number {}", n),
+      Token::Text(s) => format!("text \"{}\"", s),
+      Token::Ident(s) => format!("'{}'", s),
+      Token::Field(s) => format!("field {{{}}}", s),
+      Token::LParen => "'('".to_string(),
+      Token::RParen => "')'".to_string(),
+      Token::Comma => "','".to_string(),
+      Token::Op(op) => format!("'{}'", op),
+      Token::Minus => "'-'".to_string(),
+      Token::Not => "'not'".to_string(),
+      Token::Eof => "end of formula".to_string(),
+    }
+  }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, FormulaError> {
+  let mut tokens = vec![];
+  let mut chars = input.char_indices().peekable();
+  while let Some(&(position, c)) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+      continue;
+    }
+
+    let token = match c {
+      '0'..='9' | '.' => {
+        let mut literal = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+          if c.is_ascii_digit() || c == '.' {
+            literal.push(c);
+            chars.next();
+          } else {
+            break;
+          }
+        }
+        let number = literal
+          .parse::<f64>()
+          .map_err(|_| FormulaError::syntax(position, format!("invalid number '{}'", literal)))?;
+        tokens.push((position, Token::Number(number)));
+        continue;
+      },
+      '"' => {
+        chars.next();
+        let mut text = String::new();
+        loop {
+          match chars.next() {
+            None => return Err(FormulaError::syntax(position, "unterminated text literal")),
+            Some((_, '"')) => break,
+            Some((escape_position, '\\')) => match chars.next() {
+              Some((_, '"')) => text.push('"'),
+              Some((_, '\\')) => text.push('\\'),
+              Some((_, 'n')) => text.push('\n'),
+              _ => {
+                return Err(FormulaError::syntax(
+                  escape_position,
+                  "invalid escape sequence",
+                ));
+              },
+            },
+            Some((_, c)) => text.push(c),
+          }
+        }
+        tokens.push((position, Token::Text(text)));
+        continue;
+      },
+      '{' => {
+        chars.next();
+        let mut field_id = String::new();
+        loop {
+          match chars.next() {
+            None => {
+              return Err(FormulaError::syntax(
+                position,
+                "unterminated field reference",
+              ));
+            },
+            Some((_, '}')) => break,
+            Some((_, c)) => field_id.push(c),
+          }
+        }
+        let field_id = field_id.trim().to_string();
+        if field_id.is_empty() {
+          return Err(FormulaError::syntax(position, "empty field reference"));
+        }
+        tokens.push((position, Token::Field(field_id)));
+        continue;
+      },
+      c if c.is_alphabetic() || c == '_' => {
+        let mut ident = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+          if c.is_alphanumeric() || c == '_' {
+            ident.push(c);
+            chars.next();
+          } else {
+            break;
+          }
+        }
+        let token = match ident.to_lowercase().as_str() {
+          "and" => Token::Op(BinaryOp::And),
+          "or" => Token::Op(BinaryOp::Or),
+          "not" => Token::Not,
+          _ => Token::Ident(ident),
+        };
+        tokens.push((position, token));
+        continue;
+      },
+      _ => {
+        chars.next();
+        let next = chars.peek().map(|(_, c)| *c);
+        let mut two_chars = |token: Token| {
+          chars.next();
+          token
+        };
+        match (c, next) {
+          ('(', _) => Token::LParen,
+          (')', _) => Token::RParen,
+          (',', _) => Token::Comma,
+          ('+', _) => Token::Op(BinaryOp::Add),
+          ('-', _) => Token::Minus,
+          ('*', _) => Token::Op(BinaryOp::Mul),
+          ('/', _) => Token::Op(BinaryOp::Div),
+          ('%', _) => Token::Op(BinaryOp::Rem),
+          ('&', Some('&')) => two_chars(Token::Op(BinaryOp::And)),
+          ('&', _) => Token::Op(BinaryOp::Concat),
+          ('|', Some('|')) => two_chars(Token::Op(BinaryOp::Or)),
+          ('=', Some('=')) => two_chars(Token::Op(BinaryOp::Eq)),
+          ('=', _) => Token::Op(BinaryOp::Eq),
+          ('!', Some('=')) => two_chars(Token::Op(BinaryOp::NotEq)),
+          ('!', _) => Token::Not,
+          ('<', Some('=')) => two_chars(Token::Op(BinaryOp::LtEq)),
+          ('<', Some('>')) => two_chars(Token::Op(BinaryOp::NotEq)),
+          ('<', _) => Token::Op(BinaryOp::Lt),
+          ('>', Some('=')) => two_chars(Token::Op(BinaryOp::GtEq)),
+          ('>', _) => Token::Op(BinaryOp::Gt),
+          _ => {
+            return Err(FormulaError::syntax(
+              position,
+              format!("unexpected character '{}'", c),
+            ));
+          },
+        }
+      },
+    };
+    tokens.push((position, token));
+  }
+  tokens.push((input.len(), Token::Eof));
+  Ok(tokens)
+}
+
+/// Parses a formula expression.
+///
+/// The grammar, from the lowest to the highest precedence:
+/// - `a or b`, `a || b`
+/// - `a and b`, `a && b`
+/// - `a == b`, `a != b`, `a < b`, `a <= b`, `a > b`, `a >= b`
+/// - `a & b` concatenates the text of both sides
+/// - `a + b`, `a - b`
+/// - `a * b`, `a / b`, `a % b`
+/// - `-a`, `not a`
+/// - literals (`1.5`, `"text"`, `true`), field references (`{field_id}`), function calls
+///   (`round({price} * 1.2, 2)`) and `if(condition, then, otherwise)`
+pub fn parse_formula(expression: &str) -> Result<Expr, FormulaError> {
+  let tokens = tokenize(expression)?;
+  let mut parser = Parser {
+    tokens,
+    index: 0,
+    depth: 0,
+  };
+  let expr = parser.parse_expr()?;
+  let (position, token) = parser.peek();
+  if *token != Token::Eof {
+    return Err(FormulaError::syntax(
+      position,
+      format!("unexpected {}", token.describe()),
+    ));
+  }
+  Ok(expr)
+}
+
+struct Parser {
+  tokens: Vec<(usize, Token)>,
+  index: usize,
+  depth: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> (usize, &Token) {
+    let (position, token) = &self.tokens[self.index.min(self.tokens.len() - 1)];
+    (*position, token)
+  }
+
+  fn next(&mut self) -> (usize, Token) {
+    let (position, token) = self.peek();
+    let token = token.clone();
+    if token != Token::Eof {
+      self.index += 1;
+    }
+    (position, token)
+  }
+
+  fn expect(&mut self, expected: Token) -> Result<(), FormulaError> {
+    let (position, token) = self.next();
+    if token == expected {
+      Ok(())
+    } else {
+      Err(FormulaError::syntax(
+        position,
+        format!(
+          "expected {}, found {}",
+          expected.describe(),
+          token.describe()
+        ),
+      ))
+    }
+  }
+
+  fn parse_expr(&mut self) -> Result<Expr, FormulaError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      let (position, _) = self.peek();
+      return Err(FormulaError::syntax(
+        position,
+        "expression is nested too deeply",
+      ));
+    }
+    let expr = self.parse_binary(0);
+    self.depth -= 1;
+    expr
+  }
+
+  fn parse_binary(&mut self, level: usize) -> Result<Expr, FormulaError> {
+    const LEVELS: [&[BinaryOp]; 6] = [
+      &[BinaryOp::Or],
+      &[BinaryOp::And],
+      &[
+        BinaryOp::Eq,
+        BinaryOp::NotEq,
+        BinaryOp::Lt,
+        BinaryOp::LtEq,
+        BinaryOp::Gt,
+        BinaryOp::GtEq,
+      ],
+      &[BinaryOp::Concat],
+      &[BinaryOp::Add, BinaryOp::Sub],
+      &[BinaryOp::Mul, BinaryOp::Div, BinaryOp::Rem],
+    ];
+    if level == LEVELS.len() {
+      return self.parse_unary();
+    }
+
+    let mut lhs = self.parse_binary(level + 1)?;
+    loop {
+      let op = match self.peek().1 {
+        Token::Op(op) if LEVELS[level].contains(op) => *op,
+        Token::Minus if LEVELS[level].contains(&BinaryOp::Sub) => BinaryOp::Sub,
+        _ => break,
+      };
+      self.next();
+      let rhs = self.parse_binary(level + 1)?;
+      lhs = Expr::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+      };
+    }
+    Ok(lhs)
+  }
+
+  fn parse_unary(&mut self) -> Result<Expr, FormulaError> {
+    let op = match self.peek().1 {
+      Token::Minus => UnaryOp::Neg,
+      Token::Not => UnaryOp::Not,
+      _ => return self.parse_primary(),
+    };
+    self.next();
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      let (position, _) = self.peek();
+      return Err(FormulaError::syntax(
+        position,
+        "expression is nested too deeply",
+      ));
+    }
+    let expr = self.parse_unary();
+    self.depth -= 1;
+    Ok(Expr::Unary {
+      op,
+      expr: Box::new(expr?),
+    })
+  }
+
+  fn parse_primary(&mut self) -> Result<Expr, FormulaError> {
+    let (position, token) = self.next();
+    match token {
+      Token::Number(n) => Ok(Expr::Number(n)),
+      Token::Text(s) => Ok(Expr::Text(s)),
+      Token::Field(field_id) => Ok(Expr::Field(field_id)),
+      Token::LParen => {
+        let expr = self.parse_expr()?;
+        self.expect(Token::RParen)?;
+        Ok(expr)
+      },
+      Token::Ident(ident) => match ident.to_lowercase().as_str() {
+        "true" => Ok(Expr::Bool(true)),
+        "false" => Ok(Expr::Bool(false)),
+        name => {
+          let name = name.to_string();
+          if *self.peek().1 != Token::LParen {
+            return Err(FormulaError::syntax(
+              position,
+              format!(
+                "unknown identifier '{}', refer to fields with {{field_id}}",
+                ident
+              ),
+            ));
+          }
+          let args = self.parse_args()?;
+          if name == "if" {
+            if args.len() != 3 {
+              return Err(FormulaError::ArgumentCount {
+                function: name,
+                expected: "3".to_string(),
+                found: args.len(),
+              });
+            }
+            let mut args = args.into_iter();
+            let mut next = || Box::new(args.next().unwrap_or(Expr::Bool(false)));
+            return Ok(Expr::If {
+              condition: next(),
+              then: next(),
+              otherwise: next(),
+            });
+          }
+          Ok(Expr::Call { name, args })
+        },
+      },
+      token => Err(FormulaError::syntax(
+        position,
+        format!("unexpected {}", token.describe()),
+      )),
+    }
+  }
+
+  fn parse_args(&mut self) -> Result<Vec<Expr>, FormulaError> {
+    self.expect(Token::LParen)?;
+    let mut args = vec![];
+    if *self.peek().1 == Token::RParen {
+      self.next();
+      return Ok(args);
+    }
+    loop {
+      args.push(self.parse_expr()?);
+      let (position, token) = self.next();
+      match token {
+        Token::Comma => continue,
+        Token::RParen => break,
+        token => {
+          return Err(FormulaError::syntax(
+            position,
+            format!("expected ',' or ')', found {}", token.describe()),
+          ));
+        },
+      }
+    }
+    Ok(args)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_precedence_test() {
+    let expr = parse_formula("1 + 2 * {a} > 3 and not false").unwrap();
+    let expected = Expr::Binary {
+      op: BinaryOp::And,
+      lhs: Box::new(Expr::Binary {
+        op: BinaryOp::Gt,
+        lhs: Box::new(Expr::Binary {
+          op: BinaryOp::Add,
+          lhs: Box::new(Expr::Number(1.0)),
+          rhs: Box::new(Expr::Binary {
+            op: BinaryOp::Mul,
+            lhs: Box::new(Expr::Number(2.0)),
+            rhs: Box::new(Expr::Field("a".to_string())),
+          }),
+        }),
+        rhs: Box::new(Expr::Number(3.0)),
+      }),
+      rhs: Box::new(Expr::Unary {
+        op: UnaryOp::Not,
+        expr: Box::new(Expr::Bool(false)),
+      }),
+    };
+    assert_eq!(expr, expected);
+  }
+
+  #[test]
+  fn parse_if_and_call_test() {
+    let expr = parse_formula(r#"if({done}, "yes \"ok\"", upper({name}))"#).unwrap();
+    assert_eq!(
+      expr,
+      Expr::If {
+        condition: Box::new(Expr::Field("done".to_string())),
+        then: Box::new(Expr::Text("yes \"ok\"".to_string())),
+        otherwise: Box::new(Expr::Call {
+          name: "upper".to_string(),
+          args: vec![Expr::Field("name".to_string())],
+        }),
+      }
+    );
+  }
+
+  #[test]
+  fn parse_error_test() {
+    for (expression, position) in [
+      ("1 +", 3),
+      ("(1 + 2", 6),
+      ("\"abc", 0),
+      ("{abc", 0),
+      ("1 # 2", 2),
+      ("price * 2", 0),
+      ("1 2", 2),
+      ("if(true, 1)", 0),
+    ] {
+      match parse_formula(expression) {
+        Err(FormulaError::Syntax { position: p, .. }) => assert_eq!(p, position, "{}", expression),
+        Err(FormulaError::ArgumentCount { .. }) => {},
+        other => panic!("{}: unexpected result {:?}", expression, other),
+      }
+    }
+  }
+
+  #[test]
+  fn parse_deeply_nested_test() {
+    let expression = format!("{}1{}", "(".repeat(1000), ")".repeat(1000));
+    assert!(parse_formula(&expression).is_err());
+    let expression = format!("{}1", "-".repeat(1000));
+    assert!(parse_formula(&expression).is_err());
+  }
+}
@@ -3,6 +3,7 @@ mod field_id;
 mod field_map;
 mod field_observer;
 mod field_settings;
+mod formula;
 mod type_option;
 
 pub use field::*;
@@ -10,4 +11,5 @@ pub use field_id::*;
 pub use field_map::*;
 pub use field_observer::*;
 pub use field_settings::*;
+pub use formula::*;
 pub use type_option::*;
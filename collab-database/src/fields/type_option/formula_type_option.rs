@@ -0,0 +1,123 @@
+use super::{TypeOptionData, TypeOptionDataBuilder};
+use crate::fields::{FormulaResultType, FormulaValue, TypeOptionCellReader, TypeOptionCellWriter};
+use crate::rows::Cell;
+use crate::template::formula_parse::FormulaCellData;
+use collab::util::AnyMapExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use yrs::Any;
+
+/// The type option of a formula field.
+///
+/// The expression is evaluated by [crate::fields::FormulaEvaluator] and the result is cached in
+/// the cells of the field, which is what [TypeOptionCellReader] reads. Use
+/// [crate::fields::check_formula] to validate an expression before saving it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FormulaTypeOption {
+  #[serde(default)]
+  pub expression: String,
+  /// The type of the value the expression evaluates to. It's set when the expression is checked.
+  #[serde(default)]
+  pub result_type: FormulaResultType,
+}
+
+impl FormulaTypeOption {
+  pub fn new(expression: impl ToString) -> Self {
+    Self {
+      expression: expression.to_string(),
+      result_type: FormulaResultType::default(),
+    }
+  }
+
+  pub fn with_result_type(mut self, result_type: FormulaResultType) -> Self {
+    self.result_type = result_type;
+    self
+  }
+}
+
+impl From<TypeOptionData> for FormulaTypeOption {
+  fn from(data: TypeOptionData) -> Self {
+    let expression: String = data.get_as("expression").unwrap_or_default();
+    let result_type = data
+      .get_as::<i64>("result_type")
+      .map(FormulaResultType::from)
+      .unwrap_or_default();
+    Self {
+      expression,
+      result_type,
+    }
+  }
+}
+
+impl From<FormulaTypeOption> for TypeOptionData {
+  fn from(data: FormulaTypeOption) -> Self {
+    TypeOptionDataBuilder::from([
+      ("expression".into(), data.expression.into()),
+      ("result_type".into(), Any::BigInt(data.result_type.value())),
+    ])
+  }
+}
+
+impl TypeOptionCellReader for FormulaTypeOption {
+  fn json_cell(&self, cell: &Cell) -> Value {
+    let cell_data = FormulaCellData::from(cell);
+    match (cell_data.result_type, cell_data.number) {
+      (FormulaResultType::Number, Some(number)) => json!(number),
+      (FormulaResultType::Bool, Some(number)) => json!(number > 0.0),
+      _ => json!(cell_data.data),
+    }
+  }
+
+  fn numeric_cell(&self, cell: &Cell) -> Option<f64> {
+    FormulaCellData::from(cell).number
+  }
+
+  fn convert_raw_cell_data(&self, cell_data: &str) -> String {
+    cell_data.to_string()
+  }
+}
+
+impl TypeOptionCellWriter for FormulaTypeOption {
+  fn convert_json_to_cell(&self, json_value: Value) -> Cell {
+    let value = match json_value {
+      Value::Number(number) => number
+        .as_f64()
+        .map(FormulaValue::Number)
+        .unwrap_or(FormulaValue::Empty),
+      Value::Bool(b) => FormulaValue::Bool(b),
+      Value::String(s) => FormulaValue::Text(s),
+      _ => FormulaValue::Empty,
+    };
+    Cell::from(FormulaCellData::from(&value))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn formula_type_option_serde_test() {
+    let type_option =
+      FormulaTypeOption::new("{price} * 2").with_result_type(FormulaResultType::Number);
+    let data = TypeOptionData::from(type_option.clone());
+    assert_eq!(FormulaTypeOption::from(data), type_option);
+  }
+
+  #[test]
+  fn formula_cell_reader_test() {
+    let type_option = FormulaTypeOption::new("{price} * 2");
+    let cell = type_option.convert_json_to_cell(json!(25.5));
+    assert_eq!(type_option.stringify_cell(&cell), "25.5");
+    assert_eq!(type_option.numeric_cell(&cell), Some(25.5));
+    assert_eq!(type_option.json_cell(&cell), json!(25.5));
+
+    let cell = type_option.convert_json_to_cell(json!("hello"));
+    assert_eq!(type_option.stringify_cell(&cell), "hello");
+    assert_eq!(type_option.numeric_cell(&cell), None);
+
+    let cell = type_option.convert_json_to_cell(json!(true));
+    assert_eq!(type_option.stringify_cell(&cell), "Yes");
+    assert_eq!(type_option.json_cell(&cell), json!(true));
+  }
+}
@@ -0,0 +1,102 @@
+use super::{TypeOptionData, TypeOptionDataBuilder};
+use crate::fields::relation_type_option::RelatedFieldReader;
+use crate::fields::{Field, TypeOptionCellReader, TypeOptionCellWriter};
+use crate::rows::{Cell, Row};
+use crate::template::lookup_parse::LookupCellData;
+use crate::template::util::ToCellString;
+use collab::util::AnyMapExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// A lookup field shows the values of a field of the rows linked by a relation field.
+///
+/// The values are cached in the cells of the lookup field. They're computed by
+/// [crate::workspace_database::WorkspaceDatabaseManager::refresh_related_fields], which resolves
+/// the related database of the relation field.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LookupTypeOption {
+  /// The id of the relation field of this database
+  pub relation_field_id: String,
+  /// The id of the field of the related database whose values are shown
+  pub target_field_id: String,
+}
+
+impl LookupTypeOption {
+  pub fn new(relation_field_id: impl ToString, target_field_id: impl ToString) -> Self {
+    Self {
+      relation_field_id: relation_field_id.to_string(),
+      target_field_id: target_field_id.to_string(),
+    }
+  }
+
+  /// Builds the cell of the lookup field from the related rows. An empty cell is returned when
+  /// the target field doesn't exist anymore.
+  pub fn compute_cell(&self, target_field: Option<&Field>, related_rows: &[Row]) -> Cell {
+    let values = match target_field {
+      Some(target_field) => {
+        let reader = RelatedFieldReader::new(target_field);
+        related_rows
+          .iter()
+          .map(|row| reader.text(row))
+          .filter(|value| !value.is_empty())
+          .collect()
+      },
+      None => vec![],
+    };
+    Cell::from(LookupCellData { values })
+  }
+}
+
+impl From<TypeOptionData> for LookupTypeOption {
+  fn from(data: TypeOptionData) -> Self {
+    Self {
+      relation_field_id: data.get_as("relation_field_id").unwrap_or_default(),
+      target_field_id: data.get_as("target_field_id").unwrap_or_default(),
+    }
+  }
+}
+
+impl From<LookupTypeOption> for TypeOptionData {
+  fn from(data: LookupTypeOption) -> Self {
+    TypeOptionDataBuilder::from([
+      ("relation_field_id".into(), data.relation_field_id.into()),
+      ("target_field_id".into(), data.target_field_id.into()),
+    ])
+  }
+}
+
+impl TypeOptionCellReader for LookupTypeOption {
+  fn json_cell(&self, cell: &Cell) -> Value {
+    json!(LookupCellData::from(cell).values)
+  }
+
+  fn stringify_cell(&self, cell: &Cell) -> String {
+    LookupCellData::from(cell).to_cell_string()
+  }
+
+  fn numeric_cell(&self, _cell: &Cell) -> Option<f64> {
+    None
+  }
+
+  fn convert_raw_cell_data(&self, cell_data: &str) -> String {
+    cell_data.to_string()
+  }
+}
+
+impl TypeOptionCellWriter for LookupTypeOption {
+  fn convert_json_to_cell(&self, json_value: Value) -> Cell {
+    let values = match json_value {
+      Value::Array(values) => values
+        .into_iter()
+        .filter_map(|value| match value {
+          Value::String(s) => Some(s),
+          Value::Null => None,
+          value => Some(value.to_string()),
+        })
+        .collect(),
+      Value::String(s) if !s.is_empty() => vec![s],
+      _ => vec![],
+    };
+    Cell::from(LookupCellData { values })
+  }
+}
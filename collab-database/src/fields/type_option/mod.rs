@@ -1,9 +1,12 @@
 pub mod checkbox_type_option;
 pub mod checklist_type_option;
 pub mod date_type_option;
+pub mod formula_type_option;
+pub mod lookup_type_option;
 pub mod media_type_option;
 pub mod number_type_option;
 pub mod relation_type_option;
+pub mod rollup_type_option;
 pub mod select_type_option;
 pub mod summary_type_option;
 pub mod text_type_option;
@@ -17,9 +20,12 @@ use std::ops::{Deref, DerefMut};
 use crate::entity::FieldType;
 use crate::fields::checklist_type_option::ChecklistTypeOption;
 use crate::fields::date_type_option::{DateTypeOption, TimeTypeOption};
+use crate::fields::formula_type_option::FormulaTypeOption;
+use crate::fields::lookup_type_option::LookupTypeOption;
 use crate::fields::media_type_option::MediaTypeOption;
 use crate::fields::number_type_option::NumberTypeOption;
 use crate::fields::relation_type_option::RelationTypeOption;
+use crate::fields::rollup_type_option::RollupTypeOption;
 use crate::fields::select_type_option::{MultiSelectTypeOption, SingleSelectTypeOption};
 use crate::fields::summary_type_option::SummarizationTypeOption;
 use crate::fields::timestamp_type_option::TimestampTypeOption;
@@ -121,7 +127,7 @@ pub type TypeOptionUpdate = MapRef;
 
 /// [TypeOptionCellReader] is a trait that provides methods to read cell data based on the field type.
 /// It's used to convert the raw cell data into a human-readable text representation.
-pub trait TypeOptionCellReader {
+pub trait TypeOptionCellReader: Send + Sync {
   /// Returns the cell data as a JSON value.
   ///
   /// The type of the returned value depends on the field type:
@@ -178,6 +184,9 @@ pub fn type_option_cell_writer(
     FieldType::Relation => Box::new(RelationTypeOption::from(type_option_data)),
     FieldType::Summary => Box::new(SummarizationTypeOption::from(type_option_data)),
     FieldType::Translate => Box::new(TranslateTypeOption::from(type_option_data)),
+    FieldType::Formula => Box::new(FormulaTypeOption::from(type_option_data)),
+    FieldType::Lookup => Box::new(LookupTypeOption::from(type_option_data)),
+    FieldType::Rollup => Box::new(RollupTypeOption::from(type_option_data)),
   }
 }
 
@@ -201,5 +210,8 @@ pub fn type_option_cell_reader(
     FieldType::Relation => Box::new(RelationTypeOption::from(type_option_data)),
     FieldType::Summary => Box::new(SummarizationTypeOption::from(type_option_data)),
     FieldType::Translate => Box::new(TranslateTypeOption::from(type_option_data)),
+    FieldType::Formula => Box::new(FormulaTypeOption::from(type_option_data)),
+    FieldType::Lookup => Box::new(LookupTypeOption::from(type_option_data)),
+    FieldType::Rollup => Box::new(RollupTypeOption::from(type_option_data)),
   }
 }
@@ -1,9 +1,14 @@
 use super::{TypeOptionData, TypeOptionDataBuilder};
-use crate::fields::{TypeOptionCellReader, TypeOptionCellWriter};
-use crate::rows::Cell;
+use crate::entity::FieldType;
+use crate::fields::date_type_option::DateCellData;
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{Field, TypeOptionCellReader, TypeOptionCellWriter, type_option_cell_reader};
+use crate::rows::{Cell, Row};
 use crate::template::relation_parse::RelationCellData;
 use crate::template::util::ToCellString;
 use collab::util::AnyMapExt;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::str::FromStr;
@@ -48,3 +53,87 @@ impl TypeOptionCellWriter for RelationTypeOption {
     Cell::from(cell_data)
   }
 }
+
+/// Reads the values of a field of the related database. It's used by the lookup and rollup
+/// fields to read the related rows.
+pub(crate) struct RelatedFieldReader {
+  field_id: String,
+  field_type: FieldType,
+  reader: Box<dyn TypeOptionCellReader>,
+}
+
+impl RelatedFieldReader {
+  pub(crate) fn new(field: &Field) -> Self {
+    let field_type = FieldType::from(field.field_type);
+    let type_option = field
+      .get_any_type_option(field_type.type_id())
+      .unwrap_or_default();
+    Self {
+      field_id: field.id.clone(),
+      field_type,
+      reader: type_option_cell_reader(type_option, &field_type),
+    }
+  }
+
+  pub(crate) fn field_type(&self) -> &FieldType {
+    &self.field_type
+  }
+
+  /// Returns the text shown in the cell of the row
+  pub(crate) fn text(&self, row: &Row) -> String {
+    match self.field_type {
+      FieldType::DateTime | FieldType::CreatedTime | FieldType::LastEditedTime => self
+        .timestamp(row)
+        .map(|timestamp| self.format_timestamp(timestamp))
+        .unwrap_or_default(),
+      FieldType::URL => row
+        .cells
+        .get(&self.field_id)
+        .map(|cell| URLCellData::from(cell).data)
+        .unwrap_or_default(),
+      _ => row
+        .cells
+        .get(&self.field_id)
+        .map(|cell| self.reader.stringify_cell(cell))
+        .unwrap_or_default(),
+    }
+  }
+
+  pub(crate) fn numeric(&self, row: &Row) -> Option<f64> {
+    let cell = row.cells.get(&self.field_id)?;
+    self.reader.numeric_cell(cell)
+  }
+
+  pub(crate) fn timestamp(&self, row: &Row) -> Option<i64> {
+    match self.field_type {
+      FieldType::CreatedTime => Some(row.created_at),
+      FieldType::LastEditedTime => Some(row.modified_at),
+      FieldType::DateTime => row
+        .cells
+        .get(&self.field_id)
+        .and_then(|cell| DateCellData::from(cell).timestamp),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn is_checked(&self, row: &Row) -> bool {
+    self.field_type.is_checkbox() && self.numeric(row).is_some_and(|value| value > 0.0)
+  }
+
+  /// Formats the timestamp the way the date field of the related database does
+  pub(crate) fn format_timestamp(&self, timestamp: i64) -> String {
+    self.reader.convert_raw_cell_data(&timestamp.to_string())
+  }
+
+  /// Formats the number the way the number field of the related database does
+  pub(crate) fn format_number(&self, value: f64) -> String {
+    let decimal = match Decimal::from_f64(value) {
+      Some(decimal) => decimal.round_dp(2).normalize(),
+      None => return String::new(),
+    };
+    match self.field_type {
+      FieldType::Number => self.reader.convert_raw_cell_data(&decimal.to_string()),
+      _ => decimal.to_string(),
+    }
+  }
+}
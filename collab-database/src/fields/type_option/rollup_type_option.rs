@@ -0,0 +1,328 @@
+use super::{TypeOptionData, TypeOptionDataBuilder};
+use crate::entity::FieldType;
+use crate::fields::relation_type_option::RelatedFieldReader;
+use crate::fields::{Field, TypeOptionCellReader, TypeOptionCellWriter};
+use crate::rows::{Cell, Row};
+use crate::template::rollup_parse::RollupCellData;
+use collab::util::AnyMapExt;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use yrs::Any;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum RollupCalculation {
+  /// The number of related rows
+  #[default]
+  Count = 0,
+  Sum = 1,
+  Average = 2,
+  Min = 3,
+  Max = 4,
+  EarliestDate = 5,
+  LatestDate = 6,
+  /// The percentage of related rows whose checkbox is checked
+  PercentChecked = 7,
+}
+
+impl RollupCalculation {
+  pub fn value(&self) -> i64 {
+    *self as i64
+  }
+
+  /// Returns true if the calculation can aggregate a field of the given type
+  pub fn is_supported_by(&self, field_type: &FieldType) -> bool {
+    match self {
+      RollupCalculation::Count => true,
+      RollupCalculation::Sum
+      | RollupCalculation::Average
+      | RollupCalculation::Min
+      | RollupCalculation::Max => matches!(
+        field_type,
+        FieldType::Number | FieldType::Time | FieldType::Formula | FieldType::Rollup
+      ),
+      RollupCalculation::EarliestDate | RollupCalculation::LatestDate => matches!(
+        field_type,
+        FieldType::DateTime | FieldType::CreatedTime | FieldType::LastEditedTime
+      ),
+      RollupCalculation::PercentChecked => field_type.is_checkbox(),
+    }
+  }
+}
+
+impl From<i64> for RollupCalculation {
+  fn from(value: i64) -> Self {
+    match value {
+      1 => RollupCalculation::Sum,
+      2 => RollupCalculation::Average,
+      3 => RollupCalculation::Min,
+      4 => RollupCalculation::Max,
+      5 => RollupCalculation::EarliestDate,
+      6 => RollupCalculation::LatestDate,
+      7 => RollupCalculation::PercentChecked,
+      _ => RollupCalculation::Count,
+    }
+  }
+}
+
+/// A rollup field aggregates a field of the rows linked by a relation field.
+///
+/// Like [crate::fields::lookup_type_option::LookupTypeOption], the result is cached in the cells
+/// of the field and computed by
+/// [crate::workspace_database::WorkspaceDatabaseManager::refresh_related_fields].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RollupTypeOption {
+  /// The id of the relation field of this database
+  pub relation_field_id: String,
+  /// The id of the field of the related database that is aggregated
+  pub target_field_id: String,
+  pub calculation: RollupCalculation,
+}
+
+impl RollupTypeOption {
+  pub fn new(
+    relation_field_id: impl ToString,
+    target_field_id: impl ToString,
+    calculation: RollupCalculation,
+  ) -> Self {
+    Self {
+      relation_field_id: relation_field_id.to_string(),
+      target_field_id: target_field_id.to_string(),
+      calculation,
+    }
+  }
+
+  /// Builds the cell of the rollup field from the related rows. The cell is empty when the
+  /// target field doesn't exist anymore, or when the calculation doesn't support its type.
+  pub fn compute_cell(&self, target_field: Option<&Field>, related_rows: &[Row]) -> Cell {
+    let cell_data = match target_field {
+      Some(target_field) => self.compute(&RelatedFieldReader::new(target_field), related_rows),
+      None => RollupCellData::default(),
+    };
+    Cell::from(cell_data)
+  }
+
+  fn compute(&self, reader: &RelatedFieldReader, related_rows: &[Row]) -> RollupCellData {
+    if !self.calculation.is_supported_by(reader.field_type()) {
+      return RollupCellData::default();
+    }
+
+    let numbers = || related_rows.iter().filter_map(|row| reader.numeric(row));
+    let timestamps = || related_rows.iter().filter_map(|row| reader.timestamp(row));
+    let number = match self.calculation {
+      RollupCalculation::Count => {
+        let count = related_rows.len() as f64;
+        return RollupCellData {
+          data: count.to_string(),
+          number: Some(count),
+        };
+      },
+      RollupCalculation::PercentChecked => {
+        if related_rows.is_empty() {
+          return RollupCellData::default();
+        }
+        let checked = related_rows
+          .iter()
+          .filter(|row| reader.is_checked(row))
+          .count();
+        let percent = checked as f64 / related_rows.len() as f64 * 100.0;
+        let data = Decimal::from_f64(percent)
+          .map(|decimal| format!("{}%", decimal.round_dp(2).normalize()))
+          .unwrap_or_default();
+        return RollupCellData {
+          data,
+          number: Some(percent),
+        };
+      },
+      RollupCalculation::EarliestDate | RollupCalculation::LatestDate => {
+        let timestamp = if self.calculation == RollupCalculation::EarliestDate {
+          timestamps().min()
+        } else {
+          timestamps().max()
+        };
+        return match timestamp {
+          Some(timestamp) => RollupCellData {
+            data: reader.format_timestamp(timestamp),
+            number: Some(timestamp as f64),
+          },
+          None => RollupCellData::default(),
+        };
+      },
+      // An empty sum is still a sum
+      RollupCalculation::Sum => Some(numbers().sum::<f64>()),
+      RollupCalculation::Average => {
+        let (sum, count) = numbers().fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+        (count > 0).then(|| sum / count as f64)
+      },
+      RollupCalculation::Min => numbers().reduce(f64::min),
+      RollupCalculation::Max => numbers().reduce(f64::max),
+    };
+
+    match number {
+      Some(number) => RollupCellData {
+        data: reader.format_number(number),
+        number: Some(number),
+      },
+      None => RollupCellData::default(),
+    }
+  }
+}
+
+impl From<TypeOptionData> for RollupTypeOption {
+  fn from(data: TypeOptionData) -> Self {
+    Self {
+      relation_field_id: data.get_as("relation_field_id").unwrap_or_default(),
+      target_field_id: data.get_as("target_field_id").unwrap_or_default(),
+      calculation: data
+        .get_as::<i64>("calculation")
+        .map(RollupCalculation::from)
+        .unwrap_or_default(),
+    }
+  }
+}
+
+impl From<RollupTypeOption> for TypeOptionData {
+  fn from(data: RollupTypeOption) -> Self {
+    TypeOptionDataBuilder::from([
+      ("relation_field_id".into(), data.relation_field_id.into()),
+      ("target_field_id".into(), data.target_field_id.into()),
+      ("calculation".into(), Any::BigInt(data.calculation.value())),
+    ])
+  }
+}
+
+impl TypeOptionCellReader for RollupTypeOption {
+  fn json_cell(&self, cell: &Cell) -> Value {
+    let cell_data = RollupCellData::from(cell);
+    match (self.calculation, cell_data.number) {
+      (
+        RollupCalculation::EarliestDate
+        | RollupCalculation::LatestDate
+        | RollupCalculation::PercentChecked,
+        _,
+      )
+      | (_, None) => json!(cell_data.data),
+      (_, Some(number)) => json!(number),
+    }
+  }
+
+  fn numeric_cell(&self, cell: &Cell) -> Option<f64> {
+    RollupCellData::from(cell).number
+  }
+
+  fn convert_raw_cell_data(&self, cell_data: &str) -> String {
+    cell_data.to_string()
+  }
+}
+
+impl TypeOptionCellWriter for RollupTypeOption {
+  fn convert_json_to_cell(&self, json_value: Value) -> Cell {
+    let cell_data = match json_value {
+      Value::Number(number) => RollupCellData {
+        data: number.to_string(),
+        number: number.as_f64(),
+      },
+      Value::String(s) => RollupCellData {
+        number: s.parse::<f64>().ok(),
+        data: s,
+      },
+      _ => RollupCellData::default(),
+    };
+    Cell::from(cell_data)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fields::number_type_option::{NumberFormat, NumberTypeOption};
+  use crate::rows::RowId;
+  use crate::template::entity::CELL_DATA;
+  use crate::template::number_parse::NumberCellData;
+
+  fn related_rows(amounts: &[&str], checked: &[bool]) -> Vec<Row> {
+    amounts
+      .iter()
+      .zip(checked)
+      .enumerate()
+      .map(|(index, (amount, checked))| {
+        let mut row = Row::new(RowId::from(format!("r{}", index)), "d2");
+        row.cells.insert(
+          "amount".to_string(),
+          NumberCellData(amount.to_string()).into(),
+        );
+        let mut cell = crate::rows::new_cell_builder(FieldType::Checkbox);
+        cell.insert(CELL_DATA.into(), checked.to_string().into());
+        row.cells.insert("done".to_string(), cell);
+        row
+      })
+      .collect()
+  }
+
+  fn fields() -> (Field, Field) {
+    let number_type_option = NumberTypeOption {
+      format: NumberFormat::USD,
+      ..Default::default()
+    };
+    let amount = Field::new(
+      "amount".to_string(),
+      "Amount".to_string(),
+      FieldType::Number.into(),
+      false,
+    )
+    .with_type_option_data(FieldType::Number, number_type_option.into());
+    let done = Field::new(
+      "done".to_string(),
+      "Done".to_string(),
+      FieldType::Checkbox.into(),
+      false,
+    );
+    (amount, done)
+  }
+
+  #[test]
+  fn rollup_compute_test() {
+    let (amount, done) = fields();
+    let rows = related_rows(&["10", "2.5", ""], &[true, false, true]);
+    let cases = [
+      (RollupCalculation::Count, &amount, Some(3.0)),
+      (RollupCalculation::Sum, &amount, Some(12.5)),
+      (RollupCalculation::Average, &amount, Some(6.25)),
+      (RollupCalculation::Min, &amount, Some(2.5)),
+      (RollupCalculation::Max, &amount, Some(10.0)),
+      (
+        RollupCalculation::PercentChecked,
+        &done,
+        Some(2.0 / 3.0 * 100.0),
+      ),
+      (RollupCalculation::Sum, &done, None),
+      (RollupCalculation::LatestDate, &amount, None),
+    ];
+    for (calculation, field, expected) in cases {
+      let type_option = RollupTypeOption::new("relation", &field.id, calculation);
+      let cell = type_option.compute_cell(Some(field), &rows);
+      assert_eq!(
+        type_option.numeric_cell(&cell),
+        expected,
+        "{:?}",
+        calculation
+      );
+    }
+
+    let type_option = RollupTypeOption::new("relation", "amount", RollupCalculation::Sum);
+    let cell = type_option.compute_cell(Some(&amount), &rows);
+    assert_eq!(type_option.stringify_cell(&cell), "$12.5");
+    let cell = type_option.compute_cell(None, &rows);
+    assert_eq!(type_option.stringify_cell(&cell), "");
+  }
+
+  #[test]
+  fn rollup_type_option_serde_test() {
+    let type_option = RollupTypeOption::new("relation", "amount", RollupCalculation::LatestDate);
+    let data = TypeOptionData::from(type_option.clone());
+    assert_eq!(RollupTypeOption::from(data), type_option);
+  }
+}
@@ -30,6 +30,14 @@ pub enum RowChange {
   DidUpdateRowComment {
     row: Row,
   },
+  /// The row is created by [crate::blocks::Block::create_new_row].
+  DidCreateRow {
+    row: Row,
+  },
+  /// The row is deleted by [crate::blocks::Block::delete_row].
+  DidDeleteRow {
+    row_id: RowId,
+  },
 }
 
 pub(crate) fn subscribe_row_data_change(
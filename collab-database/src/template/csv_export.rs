@@ -0,0 +1,255 @@
+use crate::entity::FieldType;
+use crate::error::DatabaseError;
+use crate::fields::date_type_option::DateCellData;
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{Field, TypeOptionCellReader, type_option_cell_reader};
+use crate::rows::{Row, RowMeta};
+use chrono::DateTime;
+use std::io;
+
+pub const ICON_COLUMN: &str = "Icon";
+pub const COVER_COLUMN: &str = "Cover";
+pub const DOCUMENT_ID_COLUMN: &str = "Document ID";
+
+/// Controls how [crate::database::Database::export_csv_with_options] writes a view.
+///
+/// The default options write a file that [crate::template::csv::CSVTemplate::try_from_reader]
+/// imports back to the same fields.
+#[derive(Debug, Clone)]
+pub struct CSVExportOptions {
+  pub delimiter: u8,
+  pub header: CSVHeader,
+  pub date_format: CSVDateFormat,
+  pub number_format: CSVNumberFormat,
+  /// Export the fields that are hidden in the view
+  pub include_hidden_fields: bool,
+  /// Append the icon, the cover and the document id of each row. They're written after the
+  /// fields, in the [ICON_COLUMN], [COVER_COLUMN] and [DOCUMENT_ID_COLUMN] columns.
+  pub include_row_meta: bool,
+}
+
+impl Default for CSVExportOptions {
+  fn default() -> Self {
+    Self {
+      delimiter: b',',
+      header: CSVHeader::default(),
+      date_format: CSVDateFormat::default(),
+      number_format: CSVNumberFormat::default(),
+      include_hidden_fields: false,
+      include_row_meta: false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CSVHeader {
+  /// The name of the field
+  #[default]
+  FieldName,
+  /// The name of the field followed by its type, e.g. `Price (Number)`
+  FieldNameWithType,
+  FieldId,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CSVDateFormat {
+  /// The date format and time format of the field
+  #[default]
+  Field,
+  /// `2024-04-23`, or `2024-04-23 14:30` when the date includes the time. The dates are written
+  /// in UTC.
+  Iso8601,
+  /// The unix timestamp in seconds
+  Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CSVNumberFormat {
+  /// The number format of the field, e.g. `$1,200.50`
+  #[default]
+  Field,
+  /// The plain number, e.g. `1200.5`
+  Raw,
+}
+
+struct CSVColumn {
+  field: Field,
+  field_type: FieldType,
+  reader: Box<dyn TypeOptionCellReader>,
+}
+
+/// Writes the rows of a view as CSV. The fields and the rows are expected to be in the order of
+/// the view already.
+pub(crate) struct CSVExporter<'a> {
+  options: &'a CSVExportOptions,
+  columns: Vec<CSVColumn>,
+}
+
+impl<'a> CSVExporter<'a> {
+  pub(crate) fn new(options: &'a CSVExportOptions, fields: Vec<Field>) -> Self {
+    let columns = fields
+      .into_iter()
+      .map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let type_option = field
+          .get_any_type_option(field_type.type_id())
+          .unwrap_or_default();
+        CSVColumn {
+          reader: type_option_cell_reader(type_option, &field_type),
+          field,
+          field_type,
+        }
+      })
+      .collect();
+    Self { options, columns }
+  }
+
+  pub(crate) fn write<W: io::Write>(
+    &self,
+    writer: W,
+    rows: &[(Row, Option<RowMeta>)],
+  ) -> Result<(), DatabaseError> {
+    let mut writer = csv::WriterBuilder::new()
+      .delimiter(self.options.delimiter)
+      .from_writer(writer);
+
+    writer
+      .write_record(self.header())
+      .map_err(|err| DatabaseError::ExportData(err.to_string()))?;
+    for (row, row_meta) in rows {
+      writer
+        .write_record(self.record(row, row_meta.as_ref()))
+        .map_err(|err| DatabaseError::ExportData(err.to_string()))?;
+    }
+    writer
+      .flush()
+      .map_err(|err| DatabaseError::ExportData(err.to_string()))?;
+    Ok(())
+  }
+
+  fn header(&self) -> Vec<String> {
+    let mut header = self
+      .columns
+      .iter()
+      .map(|column| match self.options.header {
+        CSVHeader::FieldName => column.field.name.clone(),
+        CSVHeader::FieldNameWithType => {
+          format!(
+            "{} ({})",
+            column.field.name,
+            column.field_type.default_name()
+          )
+        },
+        CSVHeader::FieldId => column.field.id.clone(),
+      })
+      .collect::<Vec<_>>();
+    if self.options.include_row_meta {
+      header.extend([ICON_COLUMN, COVER_COLUMN, DOCUMENT_ID_COLUMN].map(String::from));
+    }
+    header
+  }
+
+  fn record(&self, row: &Row, row_meta: Option<&RowMeta>) -> Vec<String> {
+    let mut record = self
+      .columns
+      .iter()
+      .map(|column| self.format_cell(column, row))
+      .collect::<Vec<_>>();
+    if self.options.include_row_meta {
+      let icon = row_meta.and_then(|meta| meta.icon_url.clone());
+      let cover = row_meta.and_then(|meta| meta.cover.as_ref().map(|cover| cover.data.clone()));
+      let document_id = row_meta
+        .filter(|meta| !meta.is_document_empty)
+        .map(|_| row.document_id());
+      record.extend([icon, cover, document_id].map(Option::unwrap_or_default));
+    }
+    record
+  }
+
+  fn format_cell(&self, column: &CSVColumn, row: &Row) -> String {
+    let cell = row.cells.get(&column.field.id);
+    match column.field_type {
+      // The created and last edited time are read from the row rather than from a cell
+      FieldType::CreatedTime | FieldType::LastEditedTime => {
+        let timestamp = if column.field_type == FieldType::CreatedTime {
+          row.created_at
+        } else {
+          row.modified_at
+        };
+        match self.options.date_format {
+          CSVDateFormat::Field => column.reader.convert_raw_cell_data(&timestamp.to_string()),
+          date_format => format_timestamp(date_format, timestamp, true),
+        }
+      },
+      FieldType::DateTime if self.options.date_format != CSVDateFormat::Field => {
+        let cell_data = cell.map(DateCellData::from).unwrap_or_default();
+        let start = cell_data.timestamp.map(|timestamp| {
+          format_timestamp(self.options.date_format, timestamp, cell_data.include_time)
+        });
+        let end = cell_data
+          .end_timestamp
+          .filter(|_| cell_data.is_range)
+          .map(|timestamp| {
+            format_timestamp(self.options.date_format, timestamp, cell_data.include_time)
+          });
+        match (start, end) {
+          (Some(start), Some(end)) => format!("{} → {}", start, end),
+          (start, _) => start.unwrap_or_default(),
+        }
+      },
+      FieldType::Number | FieldType::Time | FieldType::Rollup
+        if self.options.number_format == CSVNumberFormat::Raw =>
+      {
+        cell
+          .and_then(|cell| column.reader.numeric_cell(cell))
+          .map(|number| number.to_string())
+          .unwrap_or_default()
+      },
+      // The cell string of a url is its json form, which wouldn't be imported back as a url
+      FieldType::URL => cell
+        .map(|cell| URLCellData::from(cell).data)
+        .unwrap_or_default(),
+      _ => cell
+        .map(|cell| column.reader.stringify_cell(cell))
+        .unwrap_or_default(),
+    }
+  }
+}
+
+fn format_timestamp(date_format: CSVDateFormat, timestamp: i64, include_time: bool) -> String {
+  match date_format {
+    CSVDateFormat::Timestamp => timestamp.to_string(),
+    CSVDateFormat::Iso8601 | CSVDateFormat::Field => {
+      let pattern = if include_time {
+        "%Y-%m-%d %H:%M"
+      } else {
+        "%Y-%m-%d"
+      };
+      DateTime::from_timestamp(timestamp, 0)
+        .map(|date_time| date_time.format(pattern).to_string())
+        .unwrap_or_default()
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn format_timestamp_test() {
+    let timestamp = 1713882600;
+    assert_eq!(
+      format_timestamp(CSVDateFormat::Iso8601, timestamp, false),
+      "2024-04-23"
+    );
+    assert_eq!(
+      format_timestamp(CSVDateFormat::Iso8601, timestamp, true),
+      "2024-04-23 14:30"
+    );
+    assert_eq!(
+      format_timestamp(CSVDateFormat::Timestamp, timestamp, true),
+      "1713882600"
+    );
+  }
+}
@@ -0,0 +1,77 @@
+use crate::entity::FieldType;
+use crate::fields::{FormulaResultType, FormulaValue};
+use crate::rows::{Cell, new_cell_builder};
+use crate::template::entity::CELL_DATA;
+use crate::template::util::{ToCellString, TypeOptionCellData};
+use collab::util::AnyMapExt;
+use serde::{Deserialize, Serialize};
+use yrs::Any;
+
+const FORMULA_NUMBER: &str = "number";
+const FORMULA_RESULT_TYPE: &str = "result_type";
+
+/// The cached result of a formula. The text shown in the cell is stored in [CELL_DATA]. Numbers,
+/// dates and booleans also keep their numeric value so they can be sorted and calculated.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormulaCellData {
+  pub data: String,
+  pub number: Option<f64>,
+  pub result_type: FormulaResultType,
+}
+
+impl TypeOptionCellData for FormulaCellData {
+  fn is_cell_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+}
+
+impl From<&FormulaValue> for FormulaCellData {
+  fn from(value: &FormulaValue) -> Self {
+    Self {
+      data: value.to_string(),
+      number: value.as_f64(),
+      result_type: FormulaResultType::from(value),
+    }
+  }
+}
+
+impl From<&Cell> for FormulaCellData {
+  fn from(cell: &Cell) -> Self {
+    Self {
+      data: cell.get_as(CELL_DATA).unwrap_or_default(),
+      number: cell.get_as(FORMULA_NUMBER),
+      result_type: cell
+        .get_as::<i64>(FORMULA_RESULT_TYPE)
+        .map(FormulaResultType::from)
+        .unwrap_or_default(),
+    }
+  }
+}
+
+impl From<FormulaCellData> for Cell {
+  fn from(data: FormulaCellData) -> Self {
+    let mut cell = new_cell_builder(FieldType::Formula);
+    cell.insert(CELL_DATA.into(), data.data.into());
+    // Updating a cell doesn't remove the keys that are missing from the new value, so an empty
+    // number is written explicitly.
+    let number = data.number.map(Any::Number).unwrap_or(Any::Null);
+    cell.insert(FORMULA_NUMBER.into(), number);
+    cell.insert(
+      FORMULA_RESULT_TYPE.into(),
+      Any::BigInt(data.result_type.value()),
+    );
+    cell
+  }
+}
+
+impl ToCellString for FormulaCellData {
+  fn to_cell_string(&self) -> String {
+    self.data.clone()
+  }
+}
+
+impl std::fmt::Display for FormulaCellData {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.data)
+  }
+}
@@ -0,0 +1,54 @@
+use crate::entity::FieldType;
+use crate::rows::{Cell, new_cell_builder};
+use crate::template::entity::CELL_DATA;
+use crate::template::util::{ToCellString, TypeOptionCellData};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use yrs::Any;
+
+/// The cached values of a lookup field, one for each related row that has a value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LookupCellData {
+  pub values: Vec<String>,
+}
+
+impl TypeOptionCellData for LookupCellData {
+  fn is_cell_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+}
+
+impl From<&Cell> for LookupCellData {
+  fn from(cell: &Cell) -> Self {
+    let values = match cell.get(CELL_DATA) {
+      Some(Any::Array(array)) => array
+        .iter()
+        .filter_map(|item| match item {
+          Any::String(value) => Some(value.to_string()),
+          _ => None,
+        })
+        .collect(),
+      _ => vec![],
+    };
+    Self { values }
+  }
+}
+
+impl From<LookupCellData> for Cell {
+  fn from(data: LookupCellData) -> Self {
+    let values = data
+      .values
+      .into_iter()
+      .map(|value| Any::String(Arc::from(value)))
+      .collect::<Vec<_>>();
+    let mut cell = new_cell_builder(FieldType::Lookup);
+    cell.insert(CELL_DATA.into(), Any::Array(Arc::from(values)));
+    cell
+  }
+}
+
+impl ToCellString for LookupCellData {
+  fn to_cell_string(&self) -> String {
+    self.values.join(", ")
+  }
+}
@@ -2,12 +2,16 @@ pub mod builder;
 pub mod check_list_parse;
 pub mod checkbox_parse;
 pub mod csv;
+pub mod csv_export;
 pub mod date_parse;
 pub mod entity;
+pub mod formula_parse;
+pub mod lookup_parse;
 pub mod media_parse;
 pub mod number_parse;
 pub mod option_parse;
 pub mod relation_parse;
+pub mod rollup_parse;
 pub mod summary_parse;
 pub mod time_parse;
 pub mod timestamp_parse;
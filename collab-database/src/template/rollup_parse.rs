@@ -0,0 +1,50 @@
+use crate::entity::FieldType;
+use crate::rows::{Cell, new_cell_builder};
+use crate::template::entity::CELL_DATA;
+use crate::template::util::{ToCellString, TypeOptionCellData};
+use collab::util::AnyMapExt;
+use serde::{Deserialize, Serialize};
+use yrs::Any;
+
+const ROLLUP_NUMBER: &str = "number";
+
+/// The cached result of a rollup field. The text shown in the cell is stored in [CELL_DATA], and
+/// the numeric value is kept so the field can be sorted and calculated.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RollupCellData {
+  pub data: String,
+  pub number: Option<f64>,
+}
+
+impl TypeOptionCellData for RollupCellData {
+  fn is_cell_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+}
+
+impl From<&Cell> for RollupCellData {
+  fn from(cell: &Cell) -> Self {
+    Self {
+      data: cell.get_as(CELL_DATA).unwrap_or_default(),
+      number: cell.get_as(ROLLUP_NUMBER),
+    }
+  }
+}
+
+impl From<RollupCellData> for Cell {
+  fn from(data: RollupCellData) -> Self {
+    let mut cell = new_cell_builder(FieldType::Rollup);
+    cell.insert(CELL_DATA.into(), data.data.into());
+    // Write null instead of leaving the key out, otherwise the number of the previous result
+    // would survive when the cell is updated.
+    let number = data.number.map(Any::Number).unwrap_or(Any::Null);
+    cell.insert(ROLLUP_NUMBER.into(), number);
+    cell
+  }
+}
+
+impl ToCellString for RollupCellData {
+  fn to_cell_string(&self) -> String {
+    self.data.clone()
+  }
+}
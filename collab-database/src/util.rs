@@ -1,7 +1,9 @@
 use crate::error::DatabaseError;
 use collab::entity::EncodedCollab;
-use collab::preclude::Collab;
+use collab::preclude::{Any, Collab};
 use collab_entity::CollabType;
+use std::collections::HashMap;
+
 pub(crate) fn encoded_collab(
   collab: &Collab,
   collab_type: &CollabType,
@@ -10,3 +12,14 @@ pub(crate) fn encoded_collab(
     collab.encode_collab_v1(|collab| collab_type.validate_require_data(collab))?;
   Ok(encoded_collab)
 }
+
+/// Read an integer value from a map. Integers might be stored as [Any::Number] after a round
+/// trip through JSON, so both representations are accepted.
+pub(crate) fn i64_from_map(map: &HashMap<String, Any>, key: &str) -> Option<i64> {
+  match map.get(key)? {
+    Any::BigInt(value) => Some(*value),
+    Any::Number(value) => Some(*value as i64),
+    Any::String(value) => value.parse().ok(),
+    _ => None,
+  }
+}
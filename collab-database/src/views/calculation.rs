@@ -1,6 +1,702 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{anyhow, bail};
 use collab::preclude::Any;
-use std::collections::HashMap;
+use collab::util::AnyMapExt;
+use rust_decimal::Decimal;
+use tokio::sync::broadcast;
+
+use super::filter::{Filter, FilterEvaluator, FilterInner};
+use crate::entity::FieldType;
+use crate::fields::number_type_option::NumberTypeOption;
+use crate::fields::{Field, TypeOptionCellReader, type_option_cell_reader};
+use crate::rows::{Cell, Row, RowChange, RowId};
+use crate::util::i64_from_map;
 
 pub type CalculationArray = Vec<Any>;
 pub type CalculationMap = HashMap<String, Any>;
 pub type CalculationMapBuilder = HashMap<String, Any>;
+
+pub const CALCULATION_ID: &str = "id";
+pub const CALCULATION_FIELD_ID: &str = "field_id";
+pub const CALCULATION_TYPE: &str = "ty";
+pub const CALCULATION_VALUE: &str = "calculation_value";
+
+pub type CalculationChangeSender = broadcast::Sender<Vec<CalculationResult>>;
+pub type CalculationChangeReceiver = broadcast::Receiver<Vec<CalculationResult>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CalculationType {
+  Average = 0,
+  Max = 1,
+  Median = 2,
+  Min = 3,
+  Sum = 4,
+  Count = 5,
+  CountEmpty = 6,
+  CountNonEmpty = 7,
+  PercentChecked = 8,
+}
+
+impl CalculationType {
+  pub fn value(&self) -> i64 {
+    *self as i64
+  }
+
+  /// Returns true if the calculation can be applied to the given field type. Counting works for
+  /// every field type, the numeric calculations need a number or time field, and
+  /// [CalculationType::PercentChecked] needs a checkbox field.
+  pub fn is_supported_by(&self, field_type: &FieldType) -> bool {
+    match self {
+      CalculationType::Count | CalculationType::CountEmpty | CalculationType::CountNonEmpty => true,
+      CalculationType::PercentChecked => field_type.is_checkbox(),
+      CalculationType::Average
+      | CalculationType::Max
+      | CalculationType::Median
+      | CalculationType::Min
+      | CalculationType::Sum => matches!(
+        field_type,
+        FieldType::Number | FieldType::Time | FieldType::Formula | FieldType::Rollup
+      ),
+    }
+  }
+}
+
+impl TryFrom<i64> for CalculationType {
+  type Error = anyhow::Error;
+
+  fn try_from(value: i64) -> Result<Self, Self::Error> {
+    let calculation_type = match value {
+      0 => CalculationType::Average,
+      1 => CalculationType::Max,
+      2 => CalculationType::Median,
+      3 => CalculationType::Min,
+      4 => CalculationType::Sum,
+      5 => CalculationType::Count,
+      6 => CalculationType::CountEmpty,
+      7 => CalculationType::CountNonEmpty,
+      8 => CalculationType::PercentChecked,
+      _ => bail!("Unknown calculation type: {}", value),
+    };
+    Ok(calculation_type)
+  }
+}
+
+/// A typed representation of the [CalculationMap] stored in a view
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calculation {
+  pub id: String,
+  pub field_id: String,
+  pub calculation_type: CalculationType,
+  /// The last value that was stored with the calculation
+  pub value: String,
+}
+
+impl Calculation {
+  pub fn new(id: String, field_id: String, calculation_type: CalculationType) -> Self {
+    Self {
+      id,
+      field_id,
+      calculation_type,
+      value: String::new(),
+    }
+  }
+}
+
+impl TryFrom<CalculationMap> for Calculation {
+  type Error = anyhow::Error;
+
+  fn try_from(calculation_map: CalculationMap) -> Result<Self, Self::Error> {
+    let id: String = calculation_map
+      .get_as(CALCULATION_ID)
+      .ok_or_else(|| anyhow!("calculation id is missing"))?;
+    let field_id: String = calculation_map
+      .get_as(CALCULATION_FIELD_ID)
+      .ok_or_else(|| anyhow!("calculation field_id is missing"))?;
+    let calculation_type = i64_from_map(&calculation_map, CALCULATION_TYPE)
+      .ok_or_else(|| anyhow!("calculation type is missing"))
+      .and_then(CalculationType::try_from)?;
+    let value: String = calculation_map
+      .get_as(CALCULATION_VALUE)
+      .unwrap_or_default();
+    Ok(Self {
+      id,
+      field_id,
+      calculation_type,
+      value,
+    })
+  }
+}
+
+impl From<Calculation> for CalculationMap {
+  fn from(calculation: Calculation) -> Self {
+    CalculationMapBuilder::from([
+      (CALCULATION_ID.into(), calculation.id.into()),
+      (CALCULATION_FIELD_ID.into(), calculation.field_id.into()),
+      (
+        CALCULATION_TYPE.into(),
+        Any::BigInt(calculation.calculation_type.value()),
+      ),
+      (CALCULATION_VALUE.into(), calculation.value.into()),
+    ])
+  }
+}
+
+/// The result of a [Calculation]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalculationResult {
+  pub calculation_id: String,
+  pub field_id: String,
+  pub calculation_type: CalculationType,
+  /// None if the calculation is not supported by the field type or there is no value to
+  /// calculate, for example the average of an empty column.
+  pub value: Option<f64>,
+  /// The value formatted with the [NumberTypeOption] of number fields. Counts are formatted as
+  /// integers and [CalculationType::PercentChecked] as a percentage.
+  pub formatted_value: String,
+}
+
+/// What a cell contributes to the calculations of its field
+#[derive(Debug, Clone, Copy, Default)]
+struct CellContribution {
+  numeric: Option<f64>,
+  is_empty: bool,
+  is_checked: bool,
+}
+
+/// A f64 that can be used as a key of a [BTreeMap]
+#[derive(Debug, Clone, Copy)]
+struct FloatKey(f64);
+
+impl PartialEq for FloatKey {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for FloatKey {}
+
+impl PartialOrd for FloatKey {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for FloatKey {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.0.total_cmp(&other.0)
+  }
+}
+
+/// Sum that keeps the rounding error of each addition (Neumaier's summation), so adding and
+/// removing values many times doesn't drift away from the sum of the remaining values.
+#[derive(Debug, Clone, Copy, Default)]
+struct CompensatedSum {
+  sum: f64,
+  compensation: f64,
+}
+
+impl CompensatedSum {
+  fn add(&mut self, value: f64) {
+    let sum = self.sum + value;
+    if self.sum.abs() >= value.abs() {
+      self.compensation += (self.sum - sum) + value;
+    } else {
+      self.compensation += (value - sum) + self.sum;
+    }
+    self.sum = sum;
+  }
+
+  fn value(&self) -> f64 {
+    self.sum + self.compensation
+  }
+}
+
+/// Running aggregate of the visible cells of a field. Cells are added and removed one at a time,
+/// so a change in a single cell doesn't require reading the other rows.
+#[derive(Debug, Default)]
+struct Aggregate {
+  count: usize,
+  empty_count: usize,
+  checked_count: usize,
+  sum: CompensatedSum,
+  numeric_count: usize,
+  /// Numeric values with their number of occurrences, used by min, max and median
+  numeric_values: BTreeMap<FloatKey, usize>,
+}
+
+impl Aggregate {
+  fn add(&mut self, contribution: &CellContribution) {
+    self.count += 1;
+    if contribution.is_empty {
+      self.empty_count += 1;
+    }
+    if contribution.is_checked {
+      self.checked_count += 1;
+    }
+    if let Some(value) = contribution.numeric {
+      self.sum.add(value);
+      self.numeric_count += 1;
+      *self.numeric_values.entry(FloatKey(value)).or_default() += 1;
+    }
+  }
+
+  fn remove(&mut self, contribution: &CellContribution) {
+    self.count = self.count.saturating_sub(1);
+    if contribution.is_empty {
+      self.empty_count = self.empty_count.saturating_sub(1);
+    }
+    if contribution.is_checked {
+      self.checked_count = self.checked_count.saturating_sub(1);
+    }
+    if let Some(value) = contribution.numeric {
+      self.sum.add(-value);
+      self.numeric_count = self.numeric_count.saturating_sub(1);
+      if self.numeric_count == 0 {
+        self.sum = CompensatedSum::default();
+      }
+      if let Some(occurrences) = self.numeric_values.get_mut(&FloatKey(value)) {
+        *occurrences -= 1;
+        if *occurrences == 0 {
+          self.numeric_values.remove(&FloatKey(value));
+        }
+      }
+    }
+  }
+
+  fn calculate(&self, calculation_type: CalculationType) -> Option<f64> {
+    match calculation_type {
+      CalculationType::Count => Some(self.count as f64),
+      CalculationType::CountEmpty => Some(self.empty_count as f64),
+      CalculationType::CountNonEmpty => Some((self.count - self.empty_count) as f64),
+      CalculationType::PercentChecked => {
+        (self.count > 0).then(|| self.checked_count as f64 / self.count as f64 * 100.0)
+      },
+      CalculationType::Sum => Some(self.sum.value()),
+      CalculationType::Average => {
+        (self.numeric_count > 0).then(|| self.sum.value() / self.numeric_count as f64)
+      },
+      CalculationType::Min => self.numeric_values.keys().next().map(|key| key.0),
+      CalculationType::Max => self.numeric_values.keys().next_back().map(|key| key.0),
+      CalculationType::Median => self.median(),
+    }
+  }
+
+  fn median(&self) -> Option<f64> {
+    if self.numeric_count == 0 {
+      return None;
+    }
+    let nth = |n: usize| {
+      let mut seen = 0;
+      self.numeric_values.iter().find_map(|(key, occurrences)| {
+        seen += occurrences;
+        (seen > n).then_some(key.0)
+      })
+    };
+    let middle = self.numeric_count / 2;
+    if self.numeric_count % 2 == 1 {
+      nth(middle)
+    } else {
+      Some((nth(middle - 1)? + nth(middle)?) / 2.0)
+    }
+  }
+}
+
+struct CalculationField {
+  field_type: FieldType,
+  reader: Box<dyn TypeOptionCellReader>,
+  number_type_option: Option<NumberTypeOption>,
+}
+
+impl CalculationField {
+  fn contribution(&self, cell: Option<&Cell>) -> CellContribution {
+    if matches!(
+      self.field_type,
+      FieldType::CreatedTime | FieldType::LastEditedTime
+    ) {
+      return CellContribution::default();
+    }
+
+    match cell {
+      None => CellContribution {
+        is_empty: true,
+        ..Default::default()
+      },
+      Some(cell) => {
+        let numeric = self.reader.numeric_cell(cell);
+        CellContribution {
+          numeric,
+          is_empty: self.reader.stringify_cell(cell).is_empty(),
+          is_checked: self.field_type.is_checkbox() && numeric.is_some_and(|value| value > 0.0),
+        }
+      },
+    }
+  }
+
+  fn format(&self, calculation_type: CalculationType, value: Option<f64>) -> String {
+    let value = match value {
+      Some(value) => value,
+      None => return String::new(),
+    };
+    let decimal = match Decimal::from_f64_retain(value) {
+      Some(decimal) => decimal.round_dp(2).normalize(),
+      None => return String::new(),
+    };
+
+    match calculation_type {
+      CalculationType::Count | CalculationType::CountEmpty | CalculationType::CountNonEmpty => {
+        decimal.trunc().to_string()
+      },
+      CalculationType::PercentChecked => format!("{}%", decimal),
+      _ => match &self.number_type_option {
+        Some(type_option) => type_option
+          .format_cell_data(decimal.to_string())
+          .map(|number| number.to_string())
+          .unwrap_or_else(|_| decimal.to_string()),
+        None => decimal.to_string(),
+      },
+    }
+  }
+}
+
+/// [CalculationState] keeps the results of the calculations of a view up to date.
+///
+/// It's built from all the rows of the view. Only the rows that pass the view's filters are
+/// calculated, and only the cells of the calculated and filtered fields are kept. When a
+/// [RowChange] is applied, only the aggregate of the changed field is updated, unless the change
+/// hides or shows the row, in which case the row is added to or removed from the aggregates of all
+/// fields.
+pub struct CalculationState {
+  calculations: Vec<Calculation>,
+  fields: HashMap<String, CalculationField>,
+  filters: Vec<Filter>,
+  filter_evaluator: FilterEvaluator,
+  /// The calculated and filtered fields
+  kept_field_ids: HashSet<String>,
+  rows: HashMap<RowId, Row>,
+  visible_rows: HashSet<RowId>,
+  aggregates: HashMap<String, Aggregate>,
+}
+
+impl CalculationState {
+  pub fn new(
+    calculations: Vec<Calculation>,
+    fields: Vec<Field>,
+    filters: Vec<Filter>,
+    rows: Vec<Row>,
+  ) -> Self {
+    let field_ids = calculations
+      .iter()
+      .map(|calculation| calculation.field_id.clone())
+      .collect::<HashSet<_>>();
+    let calculation_fields = fields
+      .iter()
+      .filter(|field| field_ids.contains(&field.id))
+      .map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let type_option = field
+          .get_any_type_option(field_type.type_id())
+          .unwrap_or_default();
+        let number_type_option = match field_type {
+          FieldType::Number => Some(NumberTypeOption::from(type_option.clone())),
+          _ => None,
+        };
+        (
+          field.id.clone(),
+          CalculationField {
+            field_type,
+            reader: type_option_cell_reader(type_option, &field_type),
+            number_type_option,
+          },
+        )
+      })
+      .collect::<HashMap<_, _>>();
+    let mut kept_field_ids = field_ids;
+    insert_filter_field_ids(&filters, &mut kept_field_ids);
+
+    let mut state = Self {
+      calculations,
+      aggregates: calculation_fields
+        .keys()
+        .map(|field_id| (field_id.clone(), Aggregate::default()))
+        .collect(),
+      fields: calculation_fields,
+      filters,
+      filter_evaluator: FilterEvaluator::new(fields),
+      kept_field_ids,
+      rows: HashMap::new(),
+      visible_rows: HashSet::new(),
+    };
+    for row in rows {
+      state.add_row(row);
+    }
+    state
+  }
+
+  pub fn calculations(&self) -> &[Calculation] {
+    &self.calculations
+  }
+
+  /// Returns the result of every calculation of the view
+  pub fn results(&self) -> Vec<CalculationResult> {
+    self
+      .calculations
+      .iter()
+      .map(|calculation| self.result(calculation))
+      .collect()
+  }
+
+  /// Insert a new row, or replace the row with the same id. Returns the result of every
+  /// calculation if the row is or was visible, since it counts in every field, otherwise an empty
+  /// list.
+  pub fn insert_row(&mut self, row: Row) -> Vec<CalculationResult> {
+    let was_visible = self.take_row(&row.id);
+    let is_visible = self.add_row(row);
+    if was_visible || is_visible {
+      self.results()
+    } else {
+      vec![]
+    }
+  }
+
+  /// Remove a row. Returns the result of every calculation if the row was visible, otherwise an
+  /// empty list.
+  pub fn remove_row(&mut self, row_id: &RowId) -> Vec<CalculationResult> {
+    if self.take_row(row_id) {
+      self.results()
+    } else {
+      vec![]
+    }
+  }
+
+  /// Apply a row change. Returns the results of the calculations of the updated field, or of every
+  /// calculation if the change creates, deletes, hides or shows a visible row. Returns an empty
+  /// list if nothing changed.
+  pub fn apply_row_change(&mut self, change: &RowChange) -> Vec<CalculationResult> {
+    match change {
+      RowChange::DidCreateRow { row } => {
+        let cells = row
+          .cells
+          .iter()
+          .filter(|(field_id, _)| self.kept_field_ids.contains(*field_id))
+          .map(|(field_id, cell)| (field_id.clone(), cell.clone()))
+          .collect();
+        self.insert_row(Row {
+          id: row.id.clone(),
+          database_id: row.database_id.clone(),
+          cells,
+          height: row.height,
+          visibility: row.visibility,
+          created_at: row.created_at,
+          modified_at: row.modified_at,
+        })
+      },
+      RowChange::DidDeleteRow { row_id } => self.remove_row(row_id),
+      RowChange::DidUpdateCell {
+        row_id,
+        field_id,
+        value,
+      } => self.update_cell(row_id, field_id, value),
+      _ => vec![],
+    }
+  }
+
+  fn update_cell(&mut self, row_id: &RowId, field_id: &str, cell: &Cell) -> Vec<CalculationResult> {
+    if !self.kept_field_ids.contains(field_id) {
+      return vec![];
+    }
+    let row = match self.rows.get_mut(row_id) {
+      Some(row) => row,
+      None => return vec![],
+    };
+    let old_contribution = self
+      .fields
+      .get(field_id)
+      .map(|field| field.contribution(row.cells.get(field_id)));
+    row.cells.insert(field_id.to_string(), cell.clone());
+
+    let was_visible = self.visible_rows.contains(row_id);
+    let is_visible = self.filter_evaluator.is_visible(&self.filters, row);
+    match (was_visible, is_visible) {
+      (false, false) => vec![],
+      (true, true) => {
+        let (field, aggregate, old_contribution) = match (
+          self.fields.get(field_id),
+          self.aggregates.get_mut(field_id),
+          old_contribution,
+        ) {
+          (Some(field), Some(aggregate), Some(old_contribution)) => {
+            (field, aggregate, old_contribution)
+          },
+          _ => return vec![],
+        };
+        aggregate.remove(&old_contribution);
+        aggregate.add(&field.contribution(Some(cell)));
+        self
+          .calculations
+          .iter()
+          .filter(|calculation| calculation.field_id == field_id)
+          .map(|calculation| self.result(calculation))
+          .collect()
+      },
+      (false, true) => {
+        self.visible_rows.insert(row_id.clone());
+        for (id, field) in self.fields.iter() {
+          if let Some(aggregate) = self.aggregates.get_mut(id) {
+            aggregate.add(&field.contribution(row.cells.get(id)));
+          }
+        }
+        self.results()
+      },
+      (true, false) => {
+        self.visible_rows.remove(row_id);
+        // The updated field is removed with the contribution of its cell before the change
+        for (id, field) in self.fields.iter() {
+          let contribution = match old_contribution {
+            Some(old_contribution) if id == field_id => old_contribution,
+            _ => field.contribution(row.cells.get(id)),
+          };
+          if let Some(aggregate) = self.aggregates.get_mut(id) {
+            aggregate.remove(&contribution);
+          }
+        }
+        self.results()
+      },
+    }
+  }
+
+  /// Returns true if the row is visible
+  fn add_row(&mut self, mut row: Row) -> bool {
+    row
+      .cells
+      .retain(|field_id, _| self.kept_field_ids.contains(field_id));
+    let is_visible = self.filter_evaluator.is_visible(&self.filters, &row);
+    if is_visible {
+      for (field_id, field) in self.fields.iter() {
+        if let Some(aggregate) = self.aggregates.get_mut(field_id) {
+          aggregate.add(&field.contribution(row.cells.get(field_id)));
+        }
+      }
+      self.visible_rows.insert(row.id.clone());
+    }
+    self.rows.insert(row.id.clone(), row);
+    is_visible
+  }
+
+  /// Removes the row. Returns true if the row was visible.
+  fn take_row(&mut self, row_id: &RowId) -> bool {
+    let row = match self.rows.remove(row_id) {
+      Some(row) => row,
+      None => return false,
+    };
+    if !self.visible_rows.remove(row_id) {
+      return false;
+    }
+    for (field_id, field) in self.fields.iter() {
+      if let Some(aggregate) = self.aggregates.get_mut(field_id) {
+        aggregate.remove(&field.contribution(row.cells.get(field_id)));
+      }
+    }
+    true
+  }
+
+  fn result(&self, calculation: &Calculation) -> CalculationResult {
+    let value = self
+      .fields
+      .get(&calculation.field_id)
+      .zip(self.aggregates.get(&calculation.field_id))
+      .filter(|(field, _)| {
+        calculation
+          .calculation_type
+          .is_supported_by(&field.field_type)
+      })
+      .and_then(|(_, aggregate)| aggregate.calculate(calculation.calculation_type));
+    let formatted_value = self
+      .fields
+      .get(&calculation.field_id)
+      .map(|field| field.format(calculation.calculation_type, value))
+      .unwrap_or_default();
+    CalculationResult {
+      calculation_id: calculation.id.clone(),
+      field_id: calculation.field_id.clone(),
+      calculation_type: calculation.calculation_type,
+      value,
+      formatted_value,
+    }
+  }
+}
+
+/// Inserts the ids of the fields that the filters read
+fn insert_filter_field_ids(filters: &[Filter], field_ids: &mut HashSet<String>) {
+  for filter in filters {
+    match &filter.inner {
+      FilterInner::And { children } | FilterInner::Or { children } => {
+        insert_filter_field_ids(children, field_ids)
+      },
+      FilterInner::Data { field_id, .. } => {
+        field_ids.insert(field_id.clone());
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn contribution(value: Option<f64>) -> CellContribution {
+    CellContribution {
+      numeric: value,
+      is_empty: value.is_none(),
+      is_checked: false,
+    }
+  }
+
+  #[test]
+  fn aggregate_add_and_remove_test() {
+    let mut aggregate = Aggregate::default();
+    for value in [Some(3.0), Some(1.0), None, Some(10.0), Some(1.0)] {
+      aggregate.add(&contribution(value));
+    }
+    assert_eq!(aggregate.calculate(CalculationType::Count), Some(5.0));
+    assert_eq!(aggregate.calculate(CalculationType::CountEmpty), Some(1.0));
+    assert_eq!(aggregate.calculate(CalculationType::Sum), Some(15.0));
+    assert_eq!(aggregate.calculate(CalculationType::Median), Some(2.0));
+    assert_eq!(aggregate.calculate(CalculationType::Min), Some(1.0));
+    assert_eq!(aggregate.calculate(CalculationType::Max), Some(10.0));
+
+    aggregate.remove(&contribution(Some(10.0)));
+    aggregate.remove(&contribution(Some(1.0)));
+    assert_eq!(aggregate.calculate(CalculationType::Max), Some(3.0));
+    assert_eq!(aggregate.calculate(CalculationType::Median), Some(2.0));
+    assert_eq!(aggregate.calculate(CalculationType::Average), Some(2.0));
+  }
+
+  #[test]
+  fn aggregate_sum_does_not_drift_test() {
+    let mut aggregate = Aggregate::default();
+    aggregate.add(&contribution(Some(1e16)));
+    aggregate.add(&contribution(Some(1.0)));
+    aggregate.remove(&contribution(Some(1e16)));
+    assert_eq!(aggregate.calculate(CalculationType::Sum), Some(1.0));
+
+    for _ in 0..1000 {
+      aggregate.add(&contribution(Some(0.1)));
+      aggregate.remove(&contribution(Some(0.1)));
+    }
+    assert_eq!(aggregate.calculate(CalculationType::Sum), Some(1.0));
+  }
+
+  #[test]
+  fn calculation_map_round_trip_test() {
+    let calculation = Calculation::new(
+      "c1".to_string(),
+      "f1".to_string(),
+      CalculationType::PercentChecked,
+    );
+    let restored = Calculation::try_from(CalculationMap::from(calculation.clone())).unwrap();
+    assert_eq!(restored, calculation);
+  }
+}
@@ -1,6 +1,962 @@
-use collab::preclude::Any;
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+use collab::preclude::Any;
+use collab::util::AnyMapExt;
+use serde::{Deserialize, Serialize};
+
+use crate::entity::FieldType;
+use crate::fields::date_type_option::{DateCellData, DateTypeOption};
+use crate::fields::media_type_option::MediaCellData;
+use crate::fields::select_type_option::{SELECTION_IDS_SEPARATOR, SelectOptionIds};
+use crate::fields::timestamp_type_option::TimestampTypeOption;
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{Field, TypeOptionCellReader, type_option_cell_reader};
+use crate::rows::{Cell, Row, RowId};
+use crate::template::check_list_parse::ChecklistCellData;
+use crate::template::relation_parse::RelationCellData;
+use crate::util::i64_from_map;
 
 pub type FilterArray = Vec<Any>;
 pub type FilterMap = HashMap<String, Any>;
 pub type FilterMapBuilder = HashMap<String, Any>;
+
+pub const FILTER_ID: &str = "id";
+pub const FILTER_TYPE: &str = "filter_type";
+pub const FILTER_CHILDREN: &str = "children";
+pub const FILTER_FIELD_ID: &str = "field_id";
+pub const FILTER_FIELD_TYPE: &str = "ty";
+pub const FILTER_CONDITION: &str = "condition";
+pub const FILTER_CONTENT: &str = "content";
+
+/// A typed representation of the [FilterMap] stored in a view.
+///
+/// A filter is either a condition on a single field ([FilterInner::Data]) or a group of
+/// filters combined with AND/OR. Groups can be nested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+  pub id: String,
+  pub inner: FilterInner,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterInner {
+  And {
+    children: Vec<Filter>,
+  },
+  Or {
+    children: Vec<Filter>,
+  },
+  Data {
+    field_id: String,
+    field_type: FieldType,
+    condition: FieldFilter,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FilterType {
+  Data = 0,
+  And = 1,
+  Or = 2,
+}
+
+impl From<i64> for FilterType {
+  fn from(value: i64) -> Self {
+    match value {
+      1 => FilterType::And,
+      2 => FilterType::Or,
+      _ => FilterType::Data,
+    }
+  }
+}
+
+impl Filter {
+  pub fn new_and(id: String, children: Vec<Filter>) -> Self {
+    Self {
+      id,
+      inner: FilterInner::And { children },
+    }
+  }
+
+  pub fn new_or(id: String, children: Vec<Filter>) -> Self {
+    Self {
+      id,
+      inner: FilterInner::Or { children },
+    }
+  }
+
+  pub fn new_data(
+    id: String,
+    field_id: String,
+    field_type: FieldType,
+    condition: FieldFilter,
+  ) -> Self {
+    Self {
+      id,
+      inner: FilterInner::Data {
+        field_id,
+        field_type,
+        condition,
+      },
+    }
+  }
+
+  pub fn filter_type(&self) -> FilterType {
+    match self.inner {
+      FilterInner::And { .. } => FilterType::And,
+      FilterInner::Or { .. } => FilterType::Or,
+      FilterInner::Data { .. } => FilterType::Data,
+    }
+  }
+
+  /// Find the filter with the given id, including nested filters
+  pub fn find(&self, filter_id: &str) -> Option<&Filter> {
+    if self.id == filter_id {
+      return Some(self);
+    }
+    match &self.inner {
+      FilterInner::And { children } | FilterInner::Or { children } => {
+        children.iter().find_map(|child| child.find(filter_id))
+      },
+      FilterInner::Data { .. } => None,
+    }
+  }
+}
+
+impl TryFrom<FilterMap> for Filter {
+  type Error = anyhow::Error;
+
+  fn try_from(filter_map: FilterMap) -> Result<Self, Self::Error> {
+    let id: String = filter_map
+      .get_as(FILTER_ID)
+      .ok_or_else(|| anyhow!("filter id is missing"))?;
+    let filter_type = i64_from_map(&filter_map, FILTER_TYPE)
+      .map(FilterType::from)
+      .unwrap_or(FilterType::Data);
+
+    let inner = match filter_type {
+      FilterType::And | FilterType::Or => {
+        let children = match filter_map.get(FILTER_CHILDREN) {
+          Some(Any::Array(children)) => children
+            .iter()
+            .map(|child| match child {
+              Any::Map(map) => Filter::try_from(map.as_ref().clone()),
+              _ => Err(anyhow!("filter child must be a map")),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+          _ => vec![],
+        };
+        if filter_type == FilterType::And {
+          FilterInner::And { children }
+        } else {
+          FilterInner::Or { children }
+        }
+      },
+      FilterType::Data => {
+        let field_id: String = filter_map
+          .get_as(FILTER_FIELD_ID)
+          .ok_or_else(|| anyhow!("filter field_id is missing"))?;
+        let field_type = i64_from_map(&filter_map, FILTER_FIELD_TYPE)
+          .map(FieldType::from)
+          .ok_or_else(|| anyhow!("filter field type is missing"))?;
+        let condition = i64_from_map(&filter_map, FILTER_CONDITION).unwrap_or_default();
+        let content: String = filter_map.get_as(FILTER_CONTENT).unwrap_or_default();
+        FilterInner::Data {
+          field_id,
+          field_type,
+          condition: FieldFilter::from_raw(&field_type, condition, &content)?,
+        }
+      },
+    };
+
+    Ok(Self { id, inner })
+  }
+}
+
+impl From<Filter> for FilterMap {
+  fn from(filter: Filter) -> Self {
+    let filter_type = filter.filter_type();
+    let mut filter_map = FilterMapBuilder::from([
+      (FILTER_ID.into(), filter.id.into()),
+      (FILTER_TYPE.into(), Any::BigInt(filter_type as i64)),
+    ]);
+    match filter.inner {
+      FilterInner::And { children } | FilterInner::Or { children } => {
+        let children = children
+          .into_iter()
+          .map(|child| Any::from(FilterMap::from(child)))
+          .collect::<Vec<_>>();
+        filter_map.insert(FILTER_CHILDREN.into(), Any::Array(Arc::from(children)));
+      },
+      FilterInner::Data {
+        field_id,
+        field_type,
+        condition,
+      } => {
+        filter_map.insert(FILTER_FIELD_ID.into(), field_id.into());
+        filter_map.insert(FILTER_FIELD_TYPE.into(), Any::BigInt(field_type.value()));
+        filter_map.insert(
+          FILTER_CONDITION.into(),
+          Any::BigInt(condition.condition_value()),
+        );
+        filter_map.insert(FILTER_CONTENT.into(), condition.content().into());
+      },
+    }
+    filter_map
+  }
+}
+
+impl From<&Filter> for FilterMap {
+  fn from(filter: &Filter) -> Self {
+    FilterMap::from(filter.clone())
+  }
+}
+
+macro_rules! filter_condition {
+  ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:expr),+ $(,)? }) => {
+    $(#[$meta])*
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[repr(u8)]
+    pub enum $name {
+      $($variant = $value),+
+    }
+
+    impl $name {
+      pub fn value(&self) -> i64 {
+        *self as i64
+      }
+    }
+
+    impl TryFrom<i64> for $name {
+      type Error = anyhow::Error;
+
+      fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+          $($value => Ok($name::$variant),)+
+          _ => bail!("Unknown {} value: {}", stringify!($name), value),
+        }
+      }
+    }
+  };
+}
+
+filter_condition!(TextFilterCondition {
+  TextIs = 0,
+  TextIsNot = 1,
+  TextContains = 2,
+  TextDoesNotContain = 3,
+  TextStartsWith = 4,
+  TextEndsWith = 5,
+  TextIsEmpty = 6,
+  TextIsNotEmpty = 7,
+});
+
+filter_condition!(NumberFilterCondition {
+  Equal = 0,
+  NotEqual = 1,
+  GreaterThan = 2,
+  LessThan = 3,
+  GreaterThanOrEqualTo = 4,
+  LessThanOrEqualTo = 5,
+  NumberIsEmpty = 6,
+  NumberIsNotEmpty = 7,
+});
+
+filter_condition!(DateFilterCondition {
+  DateIs = 0,
+  DateBefore = 1,
+  DateAfter = 2,
+  DateOnOrBefore = 3,
+  DateOnOrAfter = 4,
+  DateWithIn = 5,
+  DateIsEmpty = 6,
+  DateIsNotEmpty = 7,
+});
+
+filter_condition!(
+  /// [SelectOptionFilterCondition::OptionContains] matches a row when any of the given options
+  /// is selected, [SelectOptionFilterCondition::OptionContainsAll] requires all of them.
+  SelectOptionFilterCondition {
+    OptionIs = 0,
+    OptionIsNot = 1,
+    OptionContains = 2,
+    OptionDoesNotContain = 3,
+    OptionIsEmpty = 4,
+    OptionIsNotEmpty = 5,
+    OptionContainsAll = 6,
+  }
+);
+
+filter_condition!(CheckboxFilterCondition {
+  IsChecked = 0,
+  IsUnChecked = 1,
+});
+
+filter_condition!(
+  /// The progress conditions compare the percentage of checked options, from 0 to 100, with the
+  /// percentage stored in the content of the filter.
+  ChecklistFilterCondition {
+    IsComplete = 0,
+    IsIncomplete = 1,
+    ProgressIs = 2,
+    ProgressGreaterThanOrEqualTo = 3,
+    ProgressLessThan = 4,
+  }
+);
+
+filter_condition!(RelationFilterCondition {
+  RelationContains = 0,
+  RelationDoesNotContain = 1,
+  RelationIsEmpty = 2,
+  RelationIsNotEmpty = 3,
+});
+
+filter_condition!(MediaFilterCondition {
+  MediaIsEmpty = 0,
+  MediaIsNotEmpty = 1,
+  MediaNameContains = 2,
+});
+
+/// The condition and content of a [FilterInner::Data] filter. Each variant covers the field
+/// types that share the same filtering semantics.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldFilter {
+  /// RichText, URL, Summary, Translate, Formula and Lookup fields
+  Text(TextFilter),
+  /// Number, Time and Rollup fields
+  Number(NumberFilter),
+  /// DateTime, CreatedTime and LastEditedTime fields
+  Date(DateFilter),
+  SelectOption(SelectOptionFilter),
+  Checkbox(CheckboxFilter),
+  Checklist(ChecklistFilter),
+  Relation(RelationFilter),
+  Media(MediaFilter),
+}
+
+impl FieldFilter {
+  /// Build a typed filter from the raw condition and content stored in the [FilterMap]
+  pub fn from_raw(field_type: &FieldType, condition: i64, content: &str) -> anyhow::Result<Self> {
+    let filter = match field_type {
+      FieldType::RichText
+      | FieldType::URL
+      | FieldType::Summary
+      | FieldType::Translate
+      | FieldType::Formula
+      | FieldType::Lookup => FieldFilter::Text(TextFilter {
+        condition: TextFilterCondition::try_from(condition)?,
+        content: content.to_string(),
+      }),
+      FieldType::Number | FieldType::Time | FieldType::Rollup => {
+        FieldFilter::Number(NumberFilter {
+          condition: NumberFilterCondition::try_from(condition)?,
+          content: content.to_string(),
+        })
+      },
+      FieldType::DateTime | FieldType::CreatedTime | FieldType::LastEditedTime => {
+        let content = if content.is_empty() {
+          DateFilterContent::default()
+        } else {
+          serde_json::from_str::<DateFilterContent>(content)?
+        };
+        FieldFilter::Date(DateFilter {
+          condition: DateFilterCondition::try_from(condition)?,
+          timestamp: content.timestamp,
+          start: content.start,
+          end: content.end,
+        })
+      },
+      FieldType::SingleSelect | FieldType::MultiSelect => {
+        FieldFilter::SelectOption(SelectOptionFilter {
+          condition: SelectOptionFilterCondition::try_from(condition)?,
+          option_ids: SelectOptionIds::from_str(content)?.into_inner(),
+        })
+      },
+      FieldType::Checkbox => FieldFilter::Checkbox(CheckboxFilter {
+        condition: CheckboxFilterCondition::try_from(condition)?,
+      }),
+      FieldType::Checklist => FieldFilter::Checklist(ChecklistFilter {
+        condition: ChecklistFilterCondition::try_from(condition)?,
+        content: content.to_string(),
+      }),
+      FieldType::Relation => FieldFilter::Relation(RelationFilter {
+        condition: RelationFilterCondition::try_from(condition)?,
+        row_ids: content
+          .split(SELECTION_IDS_SEPARATOR)
+          .map(str::trim)
+          .filter(|id| !id.is_empty())
+          .map(|id| RowId::from(id.to_string()))
+          .collect(),
+      }),
+      FieldType::Media => FieldFilter::Media(MediaFilter {
+        condition: MediaFilterCondition::try_from(condition)?,
+        content: content.to_string(),
+      }),
+    };
+    Ok(filter)
+  }
+
+  pub fn condition_value(&self) -> i64 {
+    match self {
+      FieldFilter::Text(filter) => filter.condition.value(),
+      FieldFilter::Number(filter) => filter.condition.value(),
+      FieldFilter::Date(filter) => filter.condition.value(),
+      FieldFilter::SelectOption(filter) => filter.condition.value(),
+      FieldFilter::Checkbox(filter) => filter.condition.value(),
+      FieldFilter::Checklist(filter) => filter.condition.value(),
+      FieldFilter::Relation(filter) => filter.condition.value(),
+      FieldFilter::Media(filter) => filter.condition.value(),
+    }
+  }
+
+  /// Returns the content that is stored in the [FILTER_CONTENT] key
+  pub fn content(&self) -> String {
+    match self {
+      FieldFilter::Text(filter) => filter.content.clone(),
+      FieldFilter::Number(filter) => filter.content.clone(),
+      FieldFilter::Date(filter) => serde_json::to_string(&DateFilterContent {
+        timestamp: filter.timestamp,
+        start: filter.start,
+        end: filter.end,
+      })
+      .unwrap_or_default(),
+      FieldFilter::SelectOption(filter) => filter.option_ids.join(SELECTION_IDS_SEPARATOR),
+      FieldFilter::Checkbox(_) => String::new(),
+      FieldFilter::Checklist(filter) => filter.content.clone(),
+      FieldFilter::Relation(filter) => filter
+        .row_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(SELECTION_IDS_SEPARATOR),
+      FieldFilter::Media(filter) => filter.content.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFilter {
+  pub condition: TextFilterCondition,
+  pub content: String,
+}
+
+impl TextFilter {
+  /// The comparison is case-insensitive. An empty content matches every row, except for the
+  /// empty/not empty conditions which don't use the content.
+  pub fn is_visible(&self, cell_text: &str) -> bool {
+    let cell_text = cell_text.to_lowercase();
+    let content = self.content.to_lowercase();
+    match self.condition {
+      TextFilterCondition::TextIsEmpty => return cell_text.is_empty(),
+      TextFilterCondition::TextIsNotEmpty => return !cell_text.is_empty(),
+      _ if content.is_empty() => return true,
+      _ => {},
+    }
+
+    match self.condition {
+      TextFilterCondition::TextIs => cell_text == content,
+      TextFilterCondition::TextIsNot => cell_text != content,
+      TextFilterCondition::TextContains => cell_text.contains(&content),
+      TextFilterCondition::TextDoesNotContain => !cell_text.contains(&content),
+      TextFilterCondition::TextStartsWith => cell_text.starts_with(&content),
+      TextFilterCondition::TextEndsWith => cell_text.ends_with(&content),
+      TextFilterCondition::TextIsEmpty | TextFilterCondition::TextIsNotEmpty => unreachable!(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFilter {
+  pub condition: NumberFilterCondition,
+  pub content: String,
+}
+
+impl NumberFilter {
+  pub fn is_visible(&self, cell_value: Option<f64>) -> bool {
+    match self.condition {
+      NumberFilterCondition::NumberIsEmpty => return cell_value.is_none(),
+      NumberFilterCondition::NumberIsNotEmpty => return cell_value.is_some(),
+      _ => {},
+    }
+
+    let expected = match self.content.trim().parse::<f64>() {
+      Ok(expected) => expected,
+      Err(_) => return true,
+    };
+    let value = match cell_value {
+      Some(value) => value,
+      None => return self.condition == NumberFilterCondition::NotEqual,
+    };
+    match self.condition {
+      NumberFilterCondition::Equal => value == expected,
+      NumberFilterCondition::NotEqual => value != expected,
+      NumberFilterCondition::GreaterThan => value > expected,
+      NumberFilterCondition::LessThan => value < expected,
+      NumberFilterCondition::GreaterThanOrEqualTo => value >= expected,
+      NumberFilterCondition::LessThanOrEqualTo => value <= expected,
+      NumberFilterCondition::NumberIsEmpty | NumberFilterCondition::NumberIsNotEmpty => {
+        unreachable!()
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DateFilterContent {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  timestamp: Option<i64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  start: Option<i64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  end: Option<i64>,
+}
+
+/// Dates are compared by calendar day in the timezone of the field. [DateFilter::start] and
+/// [DateFilter::end] are only used by [DateFilterCondition::DateWithIn].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateFilter {
+  pub condition: DateFilterCondition,
+  pub timestamp: Option<i64>,
+  pub start: Option<i64>,
+  pub end: Option<i64>,
+}
+
+impl DateFilter {
+  pub fn is_visible(&self, cell_timestamp: Option<i64>, timezone: &Tz) -> bool {
+    match self.condition {
+      DateFilterCondition::DateIsEmpty => return cell_timestamp.is_none(),
+      DateFilterCondition::DateIsNotEmpty => return cell_timestamp.is_some(),
+      _ => {},
+    }
+
+    let to_date = |timestamp: Option<i64>| naive_date_from_timestamp(timestamp?, timezone);
+    if self.condition == DateFilterCondition::DateWithIn {
+      let (start, end) = match (to_date(self.start), to_date(self.end)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return true,
+      };
+      return to_date(cell_timestamp).is_some_and(|date| date >= start && date <= end);
+    }
+
+    let expected = match to_date(self.timestamp) {
+      Some(expected) => expected,
+      None => return true,
+    };
+    let date = match to_date(cell_timestamp) {
+      Some(date) => date,
+      None => return false,
+    };
+    match self.condition {
+      DateFilterCondition::DateIs => date == expected,
+      DateFilterCondition::DateBefore => date < expected,
+      DateFilterCondition::DateAfter => date > expected,
+      DateFilterCondition::DateOnOrBefore => date <= expected,
+      DateFilterCondition::DateOnOrAfter => date >= expected,
+      DateFilterCondition::DateWithIn
+      | DateFilterCondition::DateIsEmpty
+      | DateFilterCondition::DateIsNotEmpty => unreachable!(),
+    }
+  }
+}
+
+pub(crate) fn naive_date_from_timestamp(timestamp: i64, timezone: &Tz) -> Option<NaiveDate> {
+  DateTime::from_timestamp(timestamp, 0).map(|date| date.with_timezone(timezone).date_naive())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectOptionFilter {
+  pub condition: SelectOptionFilterCondition,
+  pub option_ids: Vec<String>,
+}
+
+impl SelectOptionFilter {
+  pub fn is_visible(&self, selected_option_ids: &[String]) -> bool {
+    match self.condition {
+      SelectOptionFilterCondition::OptionIsEmpty => return selected_option_ids.is_empty(),
+      SelectOptionFilterCondition::OptionIsNotEmpty => return !selected_option_ids.is_empty(),
+      _ if self.option_ids.is_empty() => return true,
+      _ => {},
+    }
+
+    let is_selected = |id: &String| selected_option_ids.contains(id);
+    match self.condition {
+      SelectOptionFilterCondition::OptionIs => {
+        // A single selected option matches when it is one of the filter options, otherwise the
+        // selected options must be exactly the filter options.
+        if selected_option_ids.len() == 1 {
+          self.option_ids.iter().any(is_selected)
+        } else {
+          selected_option_ids.len() == self.option_ids.len()
+            && self.option_ids.iter().all(is_selected)
+        }
+      },
+      SelectOptionFilterCondition::OptionIsNot => !self.option_ids.iter().any(is_selected),
+      SelectOptionFilterCondition::OptionContains => self.option_ids.iter().any(is_selected),
+      SelectOptionFilterCondition::OptionDoesNotContain => !self.option_ids.iter().any(is_selected),
+      SelectOptionFilterCondition::OptionContainsAll => self.option_ids.iter().all(is_selected),
+      SelectOptionFilterCondition::OptionIsEmpty
+      | SelectOptionFilterCondition::OptionIsNotEmpty => unreachable!(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckboxFilter {
+  pub condition: CheckboxFilterCondition,
+}
+
+impl CheckboxFilter {
+  pub fn is_visible(&self, is_checked: bool) -> bool {
+    match self.condition {
+      CheckboxFilterCondition::IsChecked => is_checked,
+      CheckboxFilterCondition::IsUnChecked => !is_checked,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecklistFilter {
+  pub condition: ChecklistFilterCondition,
+  /// The percentage used by the progress conditions
+  pub content: String,
+}
+
+impl ChecklistFilter {
+  /// A checklist without options has a progress of 0. An invalid percentage matches every row.
+  pub fn is_visible(&self, cell_data: &ChecklistCellData) -> bool {
+    let checked = cell_data
+      .options
+      .iter()
+      .filter(|option| cell_data.selected_option_ids.contains(&option.id))
+      .count();
+    let total = cell_data.options.len();
+    let is_complete = total > 0 && checked == total;
+    match self.condition {
+      ChecklistFilterCondition::IsComplete => return is_complete,
+      ChecklistFilterCondition::IsIncomplete => return !is_complete,
+      _ => {},
+    }
+
+    let expected = match self.content.trim().trim_end_matches('%').parse::<f64>() {
+      Ok(expected) => expected,
+      Err(_) => return true,
+    };
+    let progress = if total == 0 {
+      0.0
+    } else {
+      checked as f64 * 100.0 / total as f64
+    };
+    match self.condition {
+      ChecklistFilterCondition::ProgressIs => (progress - expected).abs() < 0.5,
+      ChecklistFilterCondition::ProgressGreaterThanOrEqualTo => progress >= expected,
+      ChecklistFilterCondition::ProgressLessThan => progress < expected,
+      ChecklistFilterCondition::IsComplete | ChecklistFilterCondition::IsIncomplete => {
+        unreachable!()
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelationFilter {
+  pub condition: RelationFilterCondition,
+  pub row_ids: Vec<RowId>,
+}
+
+impl RelationFilter {
+  pub fn is_visible(&self, related_row_ids: &[RowId]) -> bool {
+    match self.condition {
+      RelationFilterCondition::RelationIsEmpty => related_row_ids.is_empty(),
+      RelationFilterCondition::RelationIsNotEmpty => !related_row_ids.is_empty(),
+      _ if self.row_ids.is_empty() => true,
+      RelationFilterCondition::RelationContains => {
+        self.row_ids.iter().any(|id| related_row_ids.contains(id))
+      },
+      RelationFilterCondition::RelationDoesNotContain => {
+        !self.row_ids.iter().any(|id| related_row_ids.contains(id))
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFilter {
+  pub condition: MediaFilterCondition,
+  pub content: String,
+}
+
+impl MediaFilter {
+  pub fn is_visible(&self, cell_data: &MediaCellData) -> bool {
+    match self.condition {
+      MediaFilterCondition::MediaIsEmpty => cell_data.files.is_empty(),
+      MediaFilterCondition::MediaIsNotEmpty => !cell_data.files.is_empty(),
+      MediaFilterCondition::MediaNameContains => {
+        let content = self.content.to_lowercase();
+        content.is_empty()
+          || cell_data
+            .files
+            .iter()
+            .any(|file| file.name.to_lowercase().contains(&content))
+      },
+    }
+  }
+}
+
+struct FilterField {
+  field_type: FieldType,
+  timezone: Tz,
+  reader: Box<dyn TypeOptionCellReader>,
+}
+
+/// [FilterEvaluator] applies [Filter]s to [Row]s. It caches one [TypeOptionCellReader] per field,
+/// so build it once and reuse it for all the rows of a view.
+pub struct FilterEvaluator {
+  fields: HashMap<String, FilterField>,
+}
+
+impl FilterEvaluator {
+  pub fn new(fields: Vec<Field>) -> Self {
+    let fields = fields
+      .into_iter()
+      .map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let type_option = field
+          .get_any_type_option(field_type.type_id())
+          .unwrap_or_default();
+        let timezone = match field_type {
+          FieldType::DateTime => {
+            Some(DateTypeOption::from(type_option.clone()).timezone_id).filter(|id| !id.is_empty())
+          },
+          FieldType::CreatedTime | FieldType::LastEditedTime => {
+            TimestampTypeOption::from(type_option.clone()).timezone
+          },
+          _ => None,
+        }
+        .and_then(|timezone_id| timezone_id.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC);
+        let reader = type_option_cell_reader(type_option, &field_type);
+        (
+          field.id,
+          FilterField {
+            field_type,
+            timezone,
+            reader,
+          },
+        )
+      })
+      .collect();
+    Self { fields }
+  }
+
+  /// Returns true if the row passes all the given filters. The top level filters are combined
+  /// with AND.
+  pub fn is_visible(&self, filters: &[Filter], row: &Row) -> bool {
+    filters.iter().all(|filter| self.evaluate(filter, row))
+  }
+
+  pub fn evaluate(&self, filter: &Filter, row: &Row) -> bool {
+    match &filter.inner {
+      FilterInner::And { children } => children.iter().all(|child| self.evaluate(child, row)),
+      FilterInner::Or { children } => {
+        children.is_empty() || children.iter().any(|child| self.evaluate(child, row))
+      },
+      FilterInner::Data {
+        field_id,
+        field_type,
+        condition,
+      } => {
+        // Filters that refer to a deleted field, or a field whose type has changed since the
+        // filter was created, don't hide any rows.
+        let field = match self.fields.get(field_id) {
+          Some(field) if &field.field_type == field_type => field,
+          _ => return true,
+        };
+        let cell = row.cells.get(field_id);
+        self.evaluate_field(field, condition, cell, row)
+      },
+    }
+  }
+
+  fn evaluate_field(
+    &self,
+    field: &FilterField,
+    condition: &FieldFilter,
+    cell: Option<&Cell>,
+    row: &Row,
+  ) -> bool {
+    match condition {
+      FieldFilter::Text(filter) => {
+        let text = match (cell, field.field_type) {
+          (None, _) => String::new(),
+          (Some(cell), FieldType::URL) => URLCellData::from(cell).data,
+          (Some(cell), _) => field.reader.stringify_cell(cell),
+        };
+        filter.is_visible(&text)
+      },
+      FieldFilter::Number(filter) => {
+        filter.is_visible(cell.and_then(|cell| field.reader.numeric_cell(cell)))
+      },
+      FieldFilter::Date(filter) => {
+        let timestamp = match field.field_type {
+          FieldType::CreatedTime => Some(row.created_at),
+          FieldType::LastEditedTime => Some(row.modified_at),
+          _ => cell.and_then(|cell| DateCellData::from(cell).timestamp),
+        };
+        filter.is_visible(timestamp, &field.timezone)
+      },
+      FieldFilter::SelectOption(filter) => {
+        let ids = cell.map(SelectOptionIds::from).unwrap_or_default();
+        filter.is_visible(&ids)
+      },
+      FieldFilter::Checkbox(filter) => {
+        let is_checked = cell
+          .and_then(|cell| field.reader.numeric_cell(cell))
+          .is_some_and(|value| value > 0.0);
+        filter.is_visible(is_checked)
+      },
+      FieldFilter::Checklist(filter) => {
+        let cell_data = cell.map(ChecklistCellData::from).unwrap_or_default();
+        filter.is_visible(&cell_data)
+      },
+      FieldFilter::Relation(filter) => {
+        let cell_data = cell.map(RelationCellData::from).unwrap_or_default();
+        filter.is_visible(&cell_data.row_ids)
+      },
+      FieldFilter::Media(filter) => {
+        let cell_data = cell.map(MediaCellData::from).unwrap_or_default();
+        filter.is_visible(&cell_data)
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fields::select_type_option::SelectOption;
+
+  #[test]
+  fn filter_map_round_trip_test() {
+    let filter = Filter::new_or(
+      "root".to_string(),
+      vec![
+        Filter::new_data(
+          "f1".to_string(),
+          "text".to_string(),
+          FieldType::RichText,
+          FieldFilter::Text(TextFilter {
+            condition: TextFilterCondition::TextContains,
+            content: "hello".to_string(),
+          }),
+        ),
+        Filter::new_and(
+          "group".to_string(),
+          vec![Filter::new_data(
+            "f2".to_string(),
+            "date".to_string(),
+            FieldType::DateTime,
+            FieldFilter::Date(DateFilter {
+              condition: DateFilterCondition::DateWithIn,
+              timestamp: None,
+              start: Some(1),
+              end: Some(100),
+            }),
+          )],
+        ),
+      ],
+    );
+
+    let filter_map = FilterMap::from(filter.clone());
+    let restored = Filter::try_from(filter_map).unwrap();
+    assert_eq!(restored, filter);
+    assert!(restored.find("f2").is_some());
+  }
+
+  #[test]
+  fn select_option_filter_test() {
+    let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let contains_any = SelectOptionFilter {
+      condition: SelectOptionFilterCondition::OptionContains,
+      option_ids: ids(&["a", "b"]),
+    };
+    assert!(contains_any.is_visible(&ids(&["b", "c"])));
+    assert!(!contains_any.is_visible(&ids(&["c"])));
+
+    let contains_all = SelectOptionFilter {
+      condition: SelectOptionFilterCondition::OptionContainsAll,
+      option_ids: ids(&["a", "b"]),
+    };
+    assert!(contains_all.is_visible(&ids(&["a", "b", "c"])));
+    assert!(!contains_all.is_visible(&ids(&["a"])));
+  }
+
+  #[test]
+  fn number_filter_test() {
+    let filter = NumberFilter {
+      condition: NumberFilterCondition::GreaterThan,
+      content: "10".to_string(),
+    };
+    assert!(filter.is_visible(Some(11.0)));
+    assert!(!filter.is_visible(Some(10.0)));
+    assert!(!filter.is_visible(None));
+
+    let filter = NumberFilter {
+      condition: NumberFilterCondition::NumberIsEmpty,
+      content: "".to_string(),
+    };
+    assert!(filter.is_visible(None));
+  }
+
+  #[test]
+  fn checklist_progress_filter_test() {
+    let options = ["a", "b", "c", "d"]
+      .iter()
+      .map(|name| SelectOption::new(name))
+      .collect::<Vec<_>>();
+    let cell_data = |checked: usize| ChecklistCellData {
+      selected_option_ids: options[..checked]
+        .iter()
+        .map(|option| option.id.clone())
+        .collect(),
+      options: options.clone(),
+    };
+    let filter = |condition: ChecklistFilterCondition, content: &str| ChecklistFilter {
+      condition,
+      content: content.to_string(),
+    };
+
+    let at_least_half = filter(ChecklistFilterCondition::ProgressGreaterThanOrEqualTo, "50");
+    assert!(at_least_half.is_visible(&cell_data(2)));
+    assert!(at_least_half.is_visible(&cell_data(4)));
+    assert!(!at_least_half.is_visible(&cell_data(1)));
+
+    let below_half = filter(ChecklistFilterCondition::ProgressLessThan, "50%");
+    assert!(below_half.is_visible(&cell_data(1)));
+    assert!(below_half.is_visible(&ChecklistCellData::default()));
+    assert!(!below_half.is_visible(&cell_data(2)));
+
+    let is_75 = filter(ChecklistFilterCondition::ProgressIs, "75");
+    assert!(is_75.is_visible(&cell_data(3)));
+    assert!(!is_75.is_visible(&cell_data(2)));
+
+    let complete = filter(ChecklistFilterCondition::IsComplete, "");
+    assert!(complete.is_visible(&cell_data(4)));
+    assert!(!complete.is_visible(&ChecklistCellData::default()));
+
+    // The content round trips through the filter map
+    let field_filter = FieldFilter::Checklist(at_least_half);
+    let restored = FieldFilter::from_raw(
+      &FieldType::Checklist,
+      field_filter.condition_value(),
+      &field_filter.content(),
+    )
+    .unwrap();
+    assert_eq!(restored, field_filter);
+  }
+}
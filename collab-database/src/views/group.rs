@@ -1,10 +1,25 @@
 use std::{collections::HashMap, sync::Arc};
 
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone};
+use chrono_tz::Tz;
 use collab::preclude::{Any, ArrayRef};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use yrs::encoding::serde::{from_any, to_any};
 
+use super::filter::naive_date_from_timestamp;
 use crate::database::gen_database_group_id;
+use crate::entity::FieldType;
+use crate::error::DatabaseError;
+use crate::fields::date_type_option::{DateCellData, DateTypeOption};
+use crate::fields::select_type_option::{SelectOption, SelectOptionIds, SelectTypeOption};
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{
+  Field, TypeOptionCellReader, TypeOptionCellWriter, type_option_cell_reader,
+  type_option_cell_writer,
+};
+use crate::rows::{Cell, Row};
 
 /// [GroupSettingArray] contains list of [GroupSettingMap]
 pub type GroupSettingArray = Vec<Any>;
@@ -47,7 +62,7 @@ impl GroupSetting {
 const GROUP_ID: &str = "id";
 const FIELD_ID: &str = "field_id";
 const FIELD_TYPE: &str = "ty";
-const GROUPS: &str = "groups";
+pub(crate) const GROUPS: &str = "groups";
 const CONTENT: &str = "content";
 
 impl TryFrom<GroupSettingMap> for GroupSetting {
@@ -102,3 +117,588 @@ impl Group {
     Self { id, visible: true }
   }
 }
+
+pub const CHECKBOX_CHECKED_GROUP_ID: &str = "Yes";
+pub const CHECKBOX_UNCHECKED_GROUP_ID: &str = "No";
+/// The id of the group of the rows without a value. The other groups of a text or URL field are
+/// named after a non-empty value, so the empty id can't be the id of another group.
+pub const NO_VALUE_GROUP_ID: &str = "";
+
+/// The period used to bucket the rows of a date field. It's stored as JSON in
+/// [GroupSetting::content], for example `{"condition":2}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum DateGroupCondition {
+  Day = 0,
+  Week = 1,
+  #[default]
+  Month = 2,
+  Year = 3,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DateGroupContent {
+  #[serde(default)]
+  pub condition: DateGroupCondition,
+}
+
+impl DateGroupContent {
+  pub fn from_json_str(content: &str) -> Self {
+    serde_json::from_str(content).unwrap_or_default()
+  }
+
+  pub fn to_json_string(&self) -> String {
+    serde_json::to_string(self).unwrap_or_default()
+  }
+}
+
+/// A group of rows computed by [RowGrouper]
+#[derive(Debug, Clone)]
+pub struct RowGroup {
+  pub id: String,
+  pub name: String,
+  pub visible: bool,
+  /// True if it's the group of the rows that have no value in the grouping field
+  pub is_no_value: bool,
+  pub rows: Vec<Row>,
+}
+
+/// [RowGrouper] buckets rows by the value of the field of a [GroupSetting].
+///
+/// Supported field types are single/multi select, checkbox, date, URL and text. The id of a group
+/// is the value the group represents: the option id for selects, [CHECKBOX_CHECKED_GROUP_ID] or
+/// [CHECKBOX_UNCHECKED_GROUP_ID] for checkboxes, the first day of the period for dates
+/// (`2024-03-01` when grouping by month) and the cell text for URL and text fields. Rows without
+/// a value are put in the "no value" group, whose id is the field id.
+pub struct RowGrouper {
+  field_id: String,
+  field_type: FieldType,
+  options: Vec<SelectOption>,
+  date_condition: DateGroupCondition,
+  timezone: Tz,
+  reader: Box<dyn TypeOptionCellReader>,
+  writer: Box<dyn TypeOptionCellWriter>,
+}
+
+impl RowGrouper {
+  pub fn new(setting: &GroupSetting, field: &Field) -> Result<Self, DatabaseError> {
+    let field_type = FieldType::from(field.field_type);
+    if !field_type.can_be_group() {
+      return Err(DatabaseError::Internal(anyhow::anyhow!(
+        "Field {} with type {} can not be grouped",
+        field.id,
+        field_type
+      )));
+    }
+
+    let type_option = field
+      .get_any_type_option(field_type.type_id())
+      .unwrap_or_default();
+    let options = match field_type {
+      FieldType::SingleSelect | FieldType::MultiSelect => {
+        SelectTypeOption::from(type_option.clone()).options
+      },
+      _ => vec![],
+    };
+    let timezone = match field_type {
+      FieldType::DateTime => DateTypeOption::from(type_option.clone())
+        .timezone_id
+        .parse::<Tz>()
+        .ok(),
+      _ => None,
+    }
+    .unwrap_or(Tz::UTC);
+
+    Ok(Self {
+      field_id: field.id.clone(),
+      field_type,
+      options,
+      date_condition: DateGroupContent::from_json_str(&setting.content).condition,
+      timezone,
+      reader: type_option_cell_reader(type_option.clone(), &field_type),
+      writer: type_option_cell_writer(type_option, &field_type),
+    })
+  }
+
+  pub fn field_id(&self) -> &str {
+    &self.field_id
+  }
+
+  /// Returns the id of the "no value" group, see [NO_VALUE_GROUP_ID]. Checkbox fields don't have
+  /// a "no value" group because an empty cell is unchecked.
+  pub fn no_value_group_id(&self) -> Option<&str> {
+    match self.field_type {
+      FieldType::Checkbox => None,
+      _ => Some(NO_VALUE_GROUP_ID),
+    }
+  }
+
+  /// Returns the ids of the groups the row belongs to. A multi select row belongs to one group
+  /// per selected option.
+  pub fn group_ids_for_row(&self, row: &Row) -> Vec<String> {
+    let cell = row.cells.get(&self.field_id);
+    let group_ids = match self.field_type {
+      FieldType::SingleSelect | FieldType::MultiSelect => cell
+        .map(SelectOptionIds::from)
+        .unwrap_or_default()
+        .into_inner()
+        .into_iter()
+        .filter(|id| self.options.iter().any(|option| &option.id == id))
+        .collect(),
+      FieldType::Checkbox => {
+        let is_checked = cell
+          .and_then(|cell| self.reader.numeric_cell(cell))
+          .is_some_and(|value| value > 0.0);
+        if is_checked {
+          vec![CHECKBOX_CHECKED_GROUP_ID.to_string()]
+        } else {
+          vec![CHECKBOX_UNCHECKED_GROUP_ID.to_string()]
+        }
+      },
+      FieldType::DateTime => cell
+        .and_then(|cell| DateCellData::from(cell).timestamp)
+        .and_then(|timestamp| naive_date_from_timestamp(timestamp, &self.timezone))
+        .map(|date| vec![self.date_group_id(date)])
+        .unwrap_or_default(),
+      FieldType::URL => cell
+        .map(|cell| URLCellData::from(cell).data)
+        .filter(|url| !url.is_empty())
+        .into_iter()
+        .collect(),
+      _ => cell
+        .map(|cell| self.reader.stringify_cell(cell))
+        .filter(|text| !text.is_empty())
+        .into_iter()
+        .collect(),
+    };
+
+    match (group_ids.is_empty(), self.no_value_group_id()) {
+      (true, Some(no_value_group_id)) => vec![no_value_group_id.to_string()],
+      _ => group_ids,
+    }
+  }
+
+  /// Bucket the rows into groups. The rows keep their relative order inside a group.
+  ///
+  /// Groups are ordered by the given `groups`, which usually come from [GroupSetting::groups].
+  /// Groups that are not in the list are appended after them: the "no value" group first, then
+  /// select options in the order of the type option, checkbox groups checked first, and the
+  /// other groups by ascending id.
+  pub fn group_rows(&self, rows: Vec<Row>, groups: &[Group]) -> Vec<RowGroup> {
+    let mut rows_by_group: HashMap<String, Vec<Row>> = HashMap::new();
+    for row in rows {
+      for group_id in self.group_ids_for_row(&row) {
+        rows_by_group.entry(group_id).or_default().push(row.clone());
+      }
+    }
+
+    let fixed_group_ids = self.fixed_group_ids();
+    let mut dynamic_group_ids = rows_by_group
+      .keys()
+      .filter(|id| !fixed_group_ids.contains(id))
+      .cloned()
+      .collect::<Vec<_>>();
+    dynamic_group_ids.sort();
+
+    self
+      .ordered_group_ids(fixed_group_ids, dynamic_group_ids, groups)
+      .into_iter()
+      .map(|group_id| RowGroup {
+        name: self.group_name(&group_id),
+        visible: group_visibility(groups, &group_id),
+        is_no_value: self.no_value_group_id() == Some(group_id.as_str()),
+        rows: rows_by_group.remove(&group_id).unwrap_or_default(),
+        id: group_id,
+      })
+      .collect()
+  }
+
+  /// Returns the order and visibility of the groups that exist without rows, see
+  /// [RowGrouper::group_rows]. The date, URL and text groups only exist when a row has their
+  /// value, so they are only listed if they are in the given `groups`.
+  pub fn groups_without_rows(&self, groups: &[Group]) -> Vec<Group> {
+    let stored_group_ids = groups
+      .iter()
+      .map(|group| group.id.clone())
+      .collect::<Vec<_>>();
+    self
+      .ordered_group_ids(self.fixed_group_ids(), stored_group_ids, groups)
+      .into_iter()
+      .map(|group_id| Group {
+        visible: group_visibility(groups, &group_id),
+        id: group_id,
+      })
+      .collect()
+  }
+
+  /// Returns the ids of the groups that exist even when they don't contain any row
+  fn fixed_group_ids(&self) -> Vec<String> {
+    let mut fixed_group_ids = match self.field_type {
+      FieldType::SingleSelect | FieldType::MultiSelect => self
+        .options
+        .iter()
+        .map(|option| option.id.clone())
+        .collect::<Vec<_>>(),
+      FieldType::Checkbox => vec![
+        CHECKBOX_CHECKED_GROUP_ID.to_string(),
+        CHECKBOX_UNCHECKED_GROUP_ID.to_string(),
+      ],
+      _ => vec![],
+    };
+    if let Some(no_value_group_id) = self.no_value_group_id() {
+      fixed_group_ids.insert(0, no_value_group_id.to_string());
+    }
+    fixed_group_ids
+  }
+
+  /// Orders the group ids by the given `groups`, then appends the fixed groups and the dynamic
+  /// groups that are not in the list.
+  fn ordered_group_ids(
+    &self,
+    fixed_group_ids: Vec<String>,
+    dynamic_group_ids: Vec<String>,
+    groups: &[Group],
+  ) -> Vec<String> {
+    let mut all_group_ids = fixed_group_ids;
+    for group_id in dynamic_group_ids {
+      if !all_group_ids.contains(&group_id) {
+        all_group_ids.push(group_id);
+      }
+    }
+    let mut ordered_group_ids = groups
+      .iter()
+      .filter(|group| all_group_ids.contains(&group.id))
+      .map(|group| group.id.clone())
+      .collect::<Vec<_>>();
+    for group_id in all_group_ids {
+      if !ordered_group_ids.contains(&group_id) {
+        ordered_group_ids.push(group_id);
+      }
+    }
+    ordered_group_ids
+  }
+
+  /// Returns the cell that moves a row from `from_group_id` to `to_group_id`. The cell is built
+  /// with the [TypeOptionCellWriter] of the field. Moving a date keeps its time of day.
+  pub fn cell_for_move(
+    &self,
+    cell: Option<&Cell>,
+    from_group_id: &str,
+    to_group_id: &str,
+  ) -> Result<Cell, DatabaseError> {
+    let is_no_value = self.no_value_group_id() == Some(to_group_id);
+    let json_value = match self.field_type {
+      FieldType::SingleSelect | FieldType::MultiSelect => {
+        if !is_no_value && !self.options.iter().any(|option| option.id == to_group_id) {
+          return Err(DatabaseError::RecordNotFound);
+        }
+        let mut option_ids = match self.field_type {
+          FieldType::MultiSelect => cell
+            .map(SelectOptionIds::from)
+            .unwrap_or_default()
+            .into_inner()
+            .into_iter()
+            .filter(|id| id != from_group_id && id != to_group_id)
+            .collect(),
+          _ => vec![],
+        };
+        if !is_no_value {
+          option_ids.push(to_group_id.to_string());
+        }
+        Value::Array(
+          option_ids
+            .into_iter()
+            .map(|id| json!({ "id": id }))
+            .collect(),
+        )
+      },
+      FieldType::Checkbox => match to_group_id {
+        CHECKBOX_CHECKED_GROUP_ID => Value::Bool(true),
+        CHECKBOX_UNCHECKED_GROUP_ID => Value::Bool(false),
+        _ => return Err(DatabaseError::RecordNotFound),
+      },
+      FieldType::DateTime => {
+        if is_no_value {
+          Value::Null
+        } else {
+          let mut cell_data = cell.map(DateCellData::from).unwrap_or_default();
+          let time = cell_data
+            .timestamp
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|date_time| date_time.with_timezone(&self.timezone).time())
+            .unwrap_or_default();
+          let timestamp = NaiveDate::parse_from_str(to_group_id, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| {
+              self
+                .timezone
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+            })
+            .map(|date_time| date_time.timestamp())
+            .ok_or(DatabaseError::RecordNotFound)?;
+          // The end of a range moves with its start
+          if let (Some(old_timestamp), Some(end_timestamp)) =
+            (cell_data.timestamp, cell_data.end_timestamp)
+          {
+            let end_timestamp = timestamp
+              .checked_sub(old_timestamp)
+              .and_then(|shift| end_timestamp.checked_add(shift))
+              .ok_or_else(|| {
+                DatabaseError::Internal(anyhow::anyhow!(
+                  "the end of the date range can't be moved to {}",
+                  to_group_id
+                ))
+              })?;
+            cell_data.end_timestamp = Some(end_timestamp);
+          }
+          cell_data.timestamp = Some(timestamp);
+          serde_json::to_value(cell_data)?
+        }
+      },
+      _ => {
+        if is_no_value {
+          Value::String(String::new())
+        } else {
+          Value::String(to_group_id.to_string())
+        }
+      },
+    };
+    Ok(self.writer.convert_json_to_cell(json_value))
+  }
+
+  fn date_group_id(&self, date: NaiveDate) -> String {
+    let start = match self.date_condition {
+      DateGroupCondition::Day => Some(date),
+      DateGroupCondition::Week => {
+        date.checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+      },
+      DateGroupCondition::Month => date.with_day(1),
+      DateGroupCondition::Year => date.with_day(1).and_then(|date| date.with_month(1)),
+    };
+    start.unwrap_or(date).format("%Y-%m-%d").to_string()
+  }
+
+  fn group_name(&self, group_id: &str) -> String {
+    if self.no_value_group_id() == Some(group_id) {
+      return format!("No {}", self.field_type);
+    }
+
+    match self.field_type {
+      FieldType::SingleSelect | FieldType::MultiSelect => self
+        .options
+        .iter()
+        .find(|option| option.id == group_id)
+        .map(|option| option.name.clone())
+        .unwrap_or_default(),
+      FieldType::DateTime => match NaiveDate::parse_from_str(group_id, "%Y-%m-%d") {
+        Ok(date) => match self.date_condition {
+          DateGroupCondition::Day => date.format("%b %-d, %Y").to_string(),
+          DateGroupCondition::Week => format!("Week of {}", date.format("%b %-d, %Y")),
+          DateGroupCondition::Month => date.format("%b %Y").to_string(),
+          DateGroupCondition::Year => date.format("%Y").to_string(),
+        },
+        Err(_) => group_id.to_string(),
+      },
+      _ => group_id.to_string(),
+    }
+  }
+}
+
+fn group_visibility(groups: &[Group], group_id: &str) -> bool {
+  groups
+    .iter()
+    .find(|group| group.id == group_id)
+    .map(|group| group.visible)
+    .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fields::select_type_option::SingleSelectTypeOption;
+  use crate::rows::new_cell_builder;
+  use crate::template::entity::CELL_DATA;
+
+  fn date_field() -> Field {
+    Field::new(
+      "date".to_string(),
+      "Date".to_string(),
+      FieldType::DateTime.into(),
+      false,
+    )
+    .with_type_option_data(FieldType::DateTime, DateTypeOption::default_utc().into())
+  }
+
+  fn row_with_cell(id: &str, field_id: &str, cell: Cell) -> Row {
+    let mut row = Row::new(id.to_string(), "database");
+    row.cells.insert(field_id.to_string(), cell);
+    row
+  }
+
+  #[test]
+  fn group_rows_by_week_test() {
+    let setting = GroupSetting::new(
+      "date".to_string(),
+      FieldType::DateTime.into(),
+      DateGroupContent {
+        condition: DateGroupCondition::Week,
+      }
+      .to_json_string(),
+    );
+    let grouper = RowGrouper::new(&setting, &date_field()).unwrap();
+
+    // 2024-03-06 is a Wednesday
+    let cell = Cell::from(&DateCellData::from_timestamp(1709726400));
+    let group_ids = grouper.group_ids_for_row(&row_with_cell("r1", "date", cell));
+    assert_eq!(group_ids, vec!["2024-03-04"]);
+
+    let cell = grouper
+      .cell_for_move(None, NO_VALUE_GROUP_ID, "2024-03-04")
+      .unwrap();
+    assert_eq!(DateCellData::from(&cell).timestamp, Some(1709510400));
+  }
+
+  #[test]
+  fn move_date_keeps_time_of_day_test() {
+    let setting = GroupSetting::new(
+      "date".to_string(),
+      FieldType::DateTime.into(),
+      DateGroupContent {
+        condition: DateGroupCondition::Day,
+      }
+      .to_json_string(),
+    );
+    let grouper = RowGrouper::new(&setting, &date_field()).unwrap();
+
+    // 2024-03-06 12:00:00 to 2024-03-07 13:00:00 UTC
+    let mut cell_data = DateCellData::from_timestamp_include_time(1709726400);
+    cell_data.end_timestamp = Some(1709816400);
+    cell_data.is_range = true;
+    let cell = grouper
+      .cell_for_move(Some(&Cell::from(&cell_data)), "2024-03-06", "2024-03-10")
+      .unwrap();
+    let moved = DateCellData::from(&cell);
+    assert_eq!(moved.timestamp, Some(1709726400 + 4 * 86400));
+    assert_eq!(moved.end_timestamp, Some(1709816400 + 4 * 86400));
+    assert!(moved.include_time);
+
+    // The end of the range can't be moved out of the range of a timestamp
+    cell_data.end_timestamp = Some(i64::MAX);
+    let result = grouper.cell_for_move(Some(&Cell::from(&cell_data)), "2024-03-06", "2024-03-10");
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn text_equal_to_field_id_is_not_no_value_test() {
+    let field = Field::new(
+      "name".to_string(),
+      "Name".to_string(),
+      FieldType::RichText.into(),
+      true,
+    );
+    let setting = GroupSetting::new(
+      "name".to_string(),
+      FieldType::RichText.into(),
+      "".to_string(),
+    );
+    let grouper = RowGrouper::new(&setting, &field).unwrap();
+
+    let mut cell = new_cell_builder(FieldType::RichText);
+    cell.insert(CELL_DATA.into(), "name".into());
+    let rows = vec![
+      row_with_cell("r1", "name", cell),
+      Row::new("r2".to_string(), "database"),
+    ];
+    let groups = grouper.group_rows(rows, &[]);
+    assert_eq!(groups.len(), 2);
+    assert!(groups[0].is_no_value);
+    assert_eq!(groups[0].rows[0].id.as_str(), "r2");
+    assert_eq!(groups[1].id, "name");
+    assert!(!groups[1].is_no_value);
+    assert_eq!(groups[1].rows[0].id.as_str(), "r1");
+  }
+
+  #[test]
+  fn groups_without_rows_test() {
+    let setting = GroupSetting::new(
+      "date".to_string(),
+      FieldType::DateTime.into(),
+      DateGroupContent::default().to_json_string(),
+    );
+    let grouper = RowGrouper::new(&setting, &date_field()).unwrap();
+    let stored_groups = vec![Group {
+      id: "2024-03-01".to_string(),
+      visible: false,
+    }];
+    let groups = grouper.groups_without_rows(&stored_groups);
+    let group_ids = groups
+      .iter()
+      .map(|group| group.id.as_str())
+      .collect::<Vec<_>>();
+    // The stored groups come first, then the "no value" group
+    assert_eq!(group_ids, vec!["2024-03-01", NO_VALUE_GROUP_ID]);
+    assert!(!groups[0].visible);
+    assert!(groups[1].visible);
+  }
+
+  #[test]
+  fn group_rows_by_select_option_test() {
+    let options = vec![
+      SelectOption {
+        id: "a".to_string(),
+        name: "A".to_string(),
+        color: Default::default(),
+      },
+      SelectOption {
+        id: "b".to_string(),
+        name: "B".to_string(),
+        color: Default::default(),
+      },
+    ];
+    let field = Field::new(
+      "status".to_string(),
+      "Status".to_string(),
+      FieldType::SingleSelect.into(),
+      false,
+    )
+    .with_type_option_data(
+      FieldType::SingleSelect,
+      SingleSelectTypeOption(SelectTypeOption {
+        options,
+        disable_color: false,
+      })
+      .into(),
+    );
+    let setting = GroupSetting::new(
+      "status".to_string(),
+      FieldType::SingleSelect.into(),
+      "".to_string(),
+    );
+    let grouper = RowGrouper::new(&setting, &field).unwrap();
+
+    let mut cell = new_cell_builder(FieldType::SingleSelect);
+    cell.insert(CELL_DATA.into(), "b".into());
+    let rows = vec![
+      row_with_cell("r1", "status", cell),
+      Row::new("r2".to_string(), "database"),
+    ];
+    let stored_groups = vec![
+      Group::new("b".to_string()),
+      Group {
+        id: "a".to_string(),
+        visible: false,
+      },
+    ];
+    let groups = grouper.group_rows(rows, &stored_groups);
+    let group_ids = groups
+      .iter()
+      .map(|group| group.id.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(group_ids, vec!["b", "a", NO_VALUE_GROUP_ID]);
+    assert!(!groups[1].visible);
+    assert_eq!(groups[0].rows.len(), 1);
+    assert!(groups[2].is_no_value);
+    assert_eq!(groups[2].rows.len(), 1);
+  }
+}
@@ -1,6 +1,559 @@
-use collab::preclude::Any;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
+use anyhow::anyhow;
+use collab::preclude::Any;
+use collab::util::AnyMapExt;
+use rust_decimal::Decimal;
+
+use crate::database::timestamp;
+use crate::entity::FieldType;
+use crate::fields::date_type_option::DateCellData;
+use crate::fields::media_type_option::MediaCellData;
+use crate::fields::number_type_option::NumberTypeOption;
+use crate::fields::select_type_option::{SelectOptionIds, SelectTypeOption};
+use crate::fields::url_type_option::URLCellData;
+use crate::fields::{Field, TypeOptionCellReader, type_option_cell_reader};
+use crate::rows::{Cell, LAST_MODIFIED, Row, RowChange, RowId};
+use crate::template::check_list_parse::ChecklistCellData;
+use crate::template::number_parse::NumberCellData;
+use crate::template::relation_parse::RelationCellData;
+use crate::util::i64_from_map;
+
 pub type SortArray = Vec<Any>;
 pub type SortMap = HashMap<String, Any>;
 pub type SortMapBuilder = HashMap<String, Any>;
+
+pub const SORT_ID: &str = "id";
+pub const SORT_FIELD_ID: &str = "field_id";
+pub const SORT_FIELD_TYPE: &str = "ty";
+pub const SORT_CONDITION: &str = "condition";
+pub const SORT_EMPTY_POSITION: &str = "empty_position";
+
+/// A typed representation of the [SortMap] stored in a view.
+///
+/// The sorts of a view are applied in the order they are stored: the first sort is the primary
+/// key, the following sorts break the ties of the previous ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort {
+  pub id: String,
+  pub field_id: String,
+  pub field_type: FieldType,
+  pub condition: SortCondition,
+  pub empty_position: EmptyPosition,
+}
+
+impl Sort {
+  pub fn new(
+    id: String,
+    field_id: String,
+    field_type: FieldType,
+    condition: SortCondition,
+  ) -> Self {
+    Self {
+      id,
+      field_id,
+      field_type,
+      condition,
+      empty_position: EmptyPosition::default(),
+    }
+  }
+
+  pub fn with_empty_position(mut self, empty_position: EmptyPosition) -> Self {
+    self.empty_position = empty_position;
+    self
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum SortCondition {
+  #[default]
+  Ascending = 0,
+  Descending = 1,
+}
+
+impl SortCondition {
+  pub fn value(&self) -> i64 {
+    *self as i64
+  }
+}
+
+impl From<i64> for SortCondition {
+  fn from(value: i64) -> Self {
+    match value {
+      1 => SortCondition::Descending,
+      _ => SortCondition::Ascending,
+    }
+  }
+}
+
+/// Where the rows with an empty cell are placed. Empty cells are not affected by the
+/// [SortCondition] of the sort.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum EmptyPosition {
+  #[default]
+  Last = 0,
+  First = 1,
+}
+
+impl EmptyPosition {
+  pub fn value(&self) -> i64 {
+    *self as i64
+  }
+}
+
+impl From<i64> for EmptyPosition {
+  fn from(value: i64) -> Self {
+    match value {
+      1 => EmptyPosition::First,
+      _ => EmptyPosition::Last,
+    }
+  }
+}
+
+impl TryFrom<SortMap> for Sort {
+  type Error = anyhow::Error;
+
+  fn try_from(sort_map: SortMap) -> Result<Self, Self::Error> {
+    let id: String = sort_map
+      .get_as(SORT_ID)
+      .ok_or_else(|| anyhow!("sort id is missing"))?;
+    let field_id: String = sort_map
+      .get_as(SORT_FIELD_ID)
+      .ok_or_else(|| anyhow!("sort field_id is missing"))?;
+    let field_type = i64_from_map(&sort_map, SORT_FIELD_TYPE)
+      .map(FieldType::from)
+      .ok_or_else(|| anyhow!("sort field type is missing"))?;
+    let condition = i64_from_map(&sort_map, SORT_CONDITION)
+      .map(SortCondition::from)
+      .unwrap_or_default();
+    let empty_position = i64_from_map(&sort_map, SORT_EMPTY_POSITION)
+      .map(EmptyPosition::from)
+      .unwrap_or_default();
+    Ok(Self {
+      id,
+      field_id,
+      field_type,
+      condition,
+      empty_position,
+    })
+  }
+}
+
+impl From<Sort> for SortMap {
+  fn from(sort: Sort) -> Self {
+    SortMapBuilder::from([
+      (SORT_ID.into(), sort.id.into()),
+      (SORT_FIELD_ID.into(), sort.field_id.into()),
+      (SORT_FIELD_TYPE.into(), Any::BigInt(sort.field_type.value())),
+      (SORT_CONDITION.into(), Any::BigInt(sort.condition.value())),
+      (
+        SORT_EMPTY_POSITION.into(),
+        Any::BigInt(sort.empty_position.value()),
+      ),
+    ])
+  }
+}
+
+impl From<&Sort> for SortMap {
+  fn from(sort: &Sort) -> Self {
+    SortMap::from(sort.clone())
+  }
+}
+
+/// The value of a cell used for comparison. Values of different kinds are considered equal.
+#[derive(Debug, Clone, PartialEq)]
+enum SortValue {
+  Text(String),
+  Decimal(Decimal),
+  Float(f64),
+  Timestamp(i64),
+  /// Positions of the selected options in the [SelectTypeOption]
+  Options(Vec<usize>),
+  Bool(bool),
+}
+
+impl SortValue {
+  fn compare(&self, other: &Self) -> Ordering {
+    match (self, other) {
+      (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+      (SortValue::Decimal(a), SortValue::Decimal(b)) => a.cmp(b),
+      (SortValue::Float(a), SortValue::Float(b)) => a.total_cmp(b),
+      (SortValue::Timestamp(a), SortValue::Timestamp(b)) => a.cmp(b),
+      (SortValue::Options(a), SortValue::Options(b)) => a.cmp(b),
+      (SortValue::Bool(a), SortValue::Bool(b)) => a.cmp(b),
+      _ => Ordering::Equal,
+    }
+  }
+}
+
+struct SortField {
+  field_type: FieldType,
+  reader: Box<dyn TypeOptionCellReader>,
+  number_type_option: NumberTypeOption,
+  option_ids: Vec<String>,
+}
+
+impl SortField {
+  /// Returns None if the cell is empty
+  fn sort_value(&self, cell: Option<&Cell>, row: &Row) -> Option<SortValue> {
+    match self.field_type {
+      FieldType::CreatedTime => return Some(SortValue::Timestamp(row.created_at)),
+      FieldType::LastEditedTime => return Some(SortValue::Timestamp(row.modified_at)),
+      // An unchecked checkbox is a value, not an empty cell
+      FieldType::Checkbox => {
+        let is_checked = cell
+          .and_then(|cell| self.reader.numeric_cell(cell))
+          .is_some_and(|value| value > 0.0);
+        return Some(SortValue::Bool(is_checked));
+      },
+      _ => {},
+    }
+
+    let cell = cell?;
+    match self.field_type {
+      FieldType::RichText | FieldType::Summary | FieldType::Translate | FieldType::Lookup => {
+        let text = self.reader.stringify_cell(cell);
+        (!text.is_empty()).then(|| SortValue::Text(text.to_lowercase()))
+      },
+      FieldType::URL => {
+        let url = URLCellData::from(cell).data;
+        (!url.is_empty()).then(|| SortValue::Text(url.to_lowercase()))
+      },
+      FieldType::Number => {
+        let cell_data = NumberCellData::from(cell);
+        let number = self
+          .number_type_option
+          .format_cell_data(&cell_data.0)
+          .ok()?;
+        number.decimal().map(SortValue::Decimal)
+      },
+      FieldType::Time => self.reader.numeric_cell(cell).map(SortValue::Float),
+      // Formulas that evaluate to a number, a date or a boolean are sorted by their numeric value,
+      // and so are rollups
+      FieldType::Formula | FieldType::Rollup => match self.reader.numeric_cell(cell) {
+        Some(value) => Some(SortValue::Float(value)),
+        None => {
+          let text = self.reader.stringify_cell(cell);
+          (!text.is_empty()).then(|| SortValue::Text(text.to_lowercase()))
+        },
+      },
+      FieldType::DateTime => DateCellData::from(cell).timestamp.map(SortValue::Timestamp),
+      FieldType::SingleSelect | FieldType::MultiSelect => {
+        let positions = SelectOptionIds::from(cell)
+          .iter()
+          .filter_map(|id| self.option_ids.iter().position(|option_id| option_id == id))
+          .collect::<Vec<_>>();
+        (!positions.is_empty()).then_some(SortValue::Options(positions))
+      },
+      FieldType::Checklist => {
+        let cell_data = ChecklistCellData::from(cell);
+        (!cell_data.options.is_empty()).then(|| SortValue::Float(cell_data.percentage_complete()))
+      },
+      FieldType::Relation => {
+        let row_ids = RelationCellData::from(cell).row_ids;
+        (!row_ids.is_empty()).then_some(SortValue::Float(row_ids.len() as f64))
+      },
+      FieldType::Media => {
+        let files = MediaCellData::from(cell).files;
+        (!files.is_empty()).then_some(SortValue::Float(files.len() as f64))
+      },
+      FieldType::CreatedTime | FieldType::LastEditedTime | FieldType::Checkbox => unreachable!(),
+    }
+  }
+}
+
+/// [RowSorter] compares [Row]s using the [Sort]s of a view. It caches the type option of each
+/// field, so build it once and reuse it for all the rows of a view.
+pub struct RowSorter {
+  fields: HashMap<String, SortField>,
+}
+
+impl RowSorter {
+  pub fn new(fields: Vec<Field>) -> Self {
+    let fields = fields
+      .into_iter()
+      .map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let type_option = field
+          .get_any_type_option(field_type.type_id())
+          .unwrap_or_default();
+        let number_type_option = match field_type {
+          FieldType::Number => NumberTypeOption::from(type_option.clone()),
+          _ => NumberTypeOption::default(),
+        };
+        let option_ids = match field_type {
+          FieldType::SingleSelect | FieldType::MultiSelect => {
+            SelectTypeOption::from(type_option.clone())
+              .options
+              .into_iter()
+              .map(|option| option.id)
+              .collect()
+          },
+          _ => vec![],
+        };
+        let reader = type_option_cell_reader(type_option, &field_type);
+        (
+          field.id,
+          SortField {
+            field_type,
+            reader,
+            number_type_option,
+            option_ids,
+          },
+        )
+      })
+      .collect();
+    Self { fields }
+  }
+
+  /// Compare two rows with the given sorts. Sorts that refer to a deleted field are ignored.
+  pub fn compare(&self, sorts: &[Sort], left: &Row, right: &Row) -> Ordering {
+    for sort in sorts {
+      let field = match self.fields.get(&sort.field_id) {
+        Some(field) => field,
+        None => continue,
+      };
+      let left_value = field.sort_value(left.cells.get(&sort.field_id), left);
+      let right_value = field.sort_value(right.cells.get(&sort.field_id), right);
+      let order = match (left_value, right_value) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => match sort.empty_position {
+          EmptyPosition::First => Ordering::Less,
+          EmptyPosition::Last => Ordering::Greater,
+        },
+        (Some(_), None) => match sort.empty_position {
+          EmptyPosition::First => Ordering::Greater,
+          EmptyPosition::Last => Ordering::Less,
+        },
+        (Some(left_value), Some(right_value)) => {
+          let order = left_value.compare(&right_value);
+          match sort.condition {
+            SortCondition::Ascending => order,
+            SortCondition::Descending => order.reverse(),
+          }
+        },
+      };
+      if order != Ordering::Equal {
+        return order;
+      }
+    }
+    Ordering::Equal
+  }
+
+  /// Sort the rows in place. The sort is stable, so rows that compare equal keep their order.
+  pub fn sort_rows(&self, sorts: &[Sort], rows: &mut [Row]) {
+    if sorts.is_empty() {
+      return;
+    }
+    rows.sort_by(|left, right| self.compare(sorts, left, right));
+  }
+
+  fn contains_field(&self, sorts: &[Sort], field_id: &str) -> bool {
+    sorts.iter().any(|sort| sort.field_id == field_id) && self.fields.contains_key(field_id)
+  }
+
+  /// Whether one of the sorts depends on the last modified time of the rows
+  fn contains_last_edited_time(&self, sorts: &[Sort]) -> bool {
+    sorts.iter().any(|sort| {
+      self
+        .fields
+        .get(&sort.field_id)
+        .is_some_and(|field| field.field_type == FieldType::LastEditedTime)
+    })
+  }
+}
+
+/// Describes how a row moved after [SortedRows] was updated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowPositionChange {
+  pub row_id: RowId,
+  pub old_index: usize,
+  pub new_index: usize,
+}
+
+/// A list of rows kept in sorted order.
+///
+/// [SortedRows] can be updated with the [RowChange]s emitted by
+/// [crate::database::Database::subscribe_row_change] instead of sorting all the rows again. Rows
+/// that compare equal are ordered by their position in the view when the list was created.
+pub struct SortedRows {
+  sorts: Vec<Sort>,
+  sorter: RowSorter,
+  view_positions: HashMap<RowId, usize>,
+  /// The position given to the next inserted row. It only increases, so the inserted rows never
+  /// share a position with another row.
+  next_position: usize,
+  rows: Vec<Row>,
+}
+
+impl SortedRows {
+  /// Create a sorted list from rows that are ordered by the view's row orders
+  pub fn new(sorts: Vec<Sort>, sorter: RowSorter, rows: Vec<Row>) -> Self {
+    let view_positions = rows
+      .iter()
+      .enumerate()
+      .map(|(index, row)| (row.id.clone(), index))
+      .collect();
+    let mut sorted_rows = Self {
+      sorts,
+      sorter,
+      view_positions,
+      next_position: rows.len(),
+      rows,
+    };
+    sorted_rows.resort();
+    sorted_rows
+  }
+
+  pub fn sorts(&self) -> &[Sort] {
+    &self.sorts
+  }
+
+  pub fn rows(&self) -> &[Row] {
+    &self.rows
+  }
+
+  pub fn into_rows(self) -> Vec<Row> {
+    self.rows
+  }
+
+  pub fn index_of_row(&self, row_id: &RowId) -> Option<usize> {
+    self.rows.iter().position(|row| &row.id == row_id)
+  }
+
+  /// Replace the sorts and sort all the rows again
+  pub fn set_sorts(&mut self, sorts: Vec<Sort>) {
+    self.sorts = sorts;
+    self.resort();
+  }
+
+  /// Insert a new row at its sorted position and return the index of the row
+  pub fn insert_row(&mut self, row: Row) -> usize {
+    if !self.view_positions.contains_key(&row.id) {
+      self
+        .view_positions
+        .insert(row.id.clone(), self.next_position);
+      self.next_position += 1;
+    }
+    let index = self
+      .rows
+      .partition_point(|other| self.compare(other, &row) == Ordering::Less);
+    self.rows.insert(index, row);
+    index
+  }
+
+  pub fn remove_row(&mut self, row_id: &RowId) -> Option<Row> {
+    let index = self.index_of_row(row_id)?;
+    self.view_positions.remove(row_id);
+    Some(self.rows.remove(index))
+  }
+
+  /// Apply a row change. Returns the position change of the row if the row moved.
+  pub fn apply_row_change(&mut self, change: &RowChange) -> Option<RowPositionChange> {
+    match change {
+      RowChange::DidUpdateCell {
+        row_id,
+        field_id,
+        value,
+      } => {
+        let index = self.index_of_row(row_id)?;
+        let row = &mut self.rows[index];
+        row.cells.insert(field_id.clone(), value.clone());
+        row.modified_at = value.get_as::<i64>(LAST_MODIFIED).unwrap_or_else(timestamp);
+        if !self.sorter.contains_field(&self.sorts, field_id)
+          && !self.sorter.contains_last_edited_time(&self.sorts)
+        {
+          return None;
+        }
+        self.reposition(index)
+      },
+      RowChange::DidUpdateHeight { row_id, value } => {
+        let index = self.index_of_row(row_id)?;
+        self.rows[index].height = *value;
+        None
+      },
+      RowChange::DidUpdateVisibility { row_id, value } => {
+        let index = self.index_of_row(row_id)?;
+        self.rows[index].visibility = *value;
+        None
+      },
+      // The rows are added and removed with insert_row and remove_row
+      RowChange::DidUpdateRowComment { .. }
+      | RowChange::DidCreateRow { .. }
+      | RowChange::DidDeleteRow { .. } => None,
+    }
+  }
+
+  fn reposition(&mut self, index: usize) -> Option<RowPositionChange> {
+    let row = self.rows.remove(index);
+    let new_index = self
+      .rows
+      .partition_point(|other| self.compare(other, &row) == Ordering::Less);
+    let row_id = row.id.clone();
+    self.rows.insert(new_index, row);
+    if new_index == index {
+      return None;
+    }
+    Some(RowPositionChange {
+      row_id,
+      old_index: index,
+      new_index,
+    })
+  }
+
+  fn resort(&mut self) {
+    let mut rows = std::mem::take(&mut self.rows);
+    rows.sort_by(|left, right| self.compare(left, right));
+    self.rows = rows;
+  }
+
+  fn compare(&self, left: &Row, right: &Row) -> Ordering {
+    self.sorter.compare(&self.sorts, left, right).then_with(|| {
+      self
+        .view_position(&left.id)
+        .cmp(&self.view_position(&right.id))
+    })
+  }
+
+  fn view_position(&self, row_id: &RowId) -> usize {
+    self
+      .view_positions
+      .get(row_id)
+      .copied()
+      .unwrap_or(usize::MAX)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sort_map_round_trip_test() {
+    let sort = Sort::new(
+      "s1".to_string(),
+      "f1".to_string(),
+      FieldType::Number,
+      SortCondition::Descending,
+    )
+    .with_empty_position(EmptyPosition::First);
+    let restored = Sort::try_from(SortMap::from(&sort)).unwrap();
+    assert_eq!(restored, sort);
+  }
+
+  #[test]
+  fn sort_map_without_empty_position_test() {
+    let sort_map = SortMapBuilder::from([
+      (SORT_ID.into(), "s1".into()),
+      (SORT_FIELD_ID.into(), "f1".into()),
+      (SORT_FIELD_TYPE.into(), Any::BigInt(0)),
+      (SORT_CONDITION.into(), Any::BigInt(1)),
+    ]);
+    let sort = Sort::try_from(sort_map).unwrap();
+    assert_eq!(sort.condition, SortCondition::Descending);
+    assert_eq!(sort.empty_position, EmptyPosition::Last);
+  }
+}
@@ -1,7 +1,9 @@
 use crate::database::{Database, DatabaseContext, DatabaseData, try_fixing_database};
 
 use crate::error::DatabaseError;
+use crate::rows::{Cell, Row, RowChange, RowId};
 use crate::workspace_database::body::{DatabaseMeta, WorkspaceDatabase};
+use crate::workspace_database::relation::{LinkingRowIndex, RelatedField, relation_fields};
 use async_trait::async_trait;
 use collab::core::collab::DataSource;
 use collab::preclude::Collab;
@@ -20,8 +22,10 @@ use dashmap::DashMap;
 use rayon::prelude::*;
 use std::borrow::{Borrow, BorrowMut};
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tracing::{error, info};
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
 
 pub type EncodeCollabByOid = HashMap<String, EncodedCollab>;
 pub type DataSourceByOid = HashMap<String, DataSource>;
@@ -192,7 +196,7 @@ pub struct WorkspaceDatabaseManager {
   /// In memory database handlers.
   /// The key is the database id. The handler will be added when the database is opened or created.
   /// and the handler will be removed when the database is deleted or closed.
-  databases: DashMap<String, Arc<RwLock<Database>>>,
+  databases: Arc<OpenDatabases>,
 }
 
 impl WorkspaceDatabaseManager {
@@ -207,7 +211,7 @@ impl WorkspaceDatabaseManager {
       object_id: object_id.to_string(),
       body,
       collab_service,
-      databases: DashMap::new(),
+      databases: Arc::new(OpenDatabases::default()),
     })
   }
 
@@ -222,7 +226,7 @@ impl WorkspaceDatabaseManager {
       object_id: object_id.to_string(),
       body,
       collab_service,
-      databases: DashMap::new(),
+      databases: Arc::new(OpenDatabases::default()),
     })
   }
 
@@ -248,23 +252,18 @@ impl WorkspaceDatabaseManager {
     }
 
     // Check if the database is already initialized and cached
-    if let Some(database) = self.databases.get(database_id).as_deref().cloned() {
+    if let Some(database) = self.databases.get(database_id) {
       return Ok(database);
     }
 
     // Helper function to insert the database into the cache
-    let insert_database = |db: Database| -> Arc<RwLock<Database>> {
-      let database = Arc::new(RwLock::new(db));
-      self
-        .databases
-        .insert(database_id.to_string(), database.clone());
-      database
-    };
+    let insert_database =
+      |db: Database| cache_database(&self.databases, &self.collab_service, database_id, db);
 
     // Try to open the database
     let context = DatabaseContext::new(self.collab_service.clone());
     match Database::open(database_id, context).await {
-      Ok(database) => Ok(insert_database(database)),
+      Ok(database) => Ok(insert_database(database).await),
       // If the database is missing required data, try to fix it and open it again
       Err(err) => {
         if err.is_no_required_data() {
@@ -282,7 +281,7 @@ impl WorkspaceDatabaseManager {
             )
             .await
             {
-              return Ok(insert_database(database));
+              return Ok(insert_database(database).await);
             }
           }
           Err(err)
@@ -328,10 +327,15 @@ impl WorkspaceDatabaseManager {
       .add_database(&params.database_id, linked_views.into_iter().collect());
     let database_id = params.database_id.clone();
     let database = Database::create_with_view(params, context).await.unwrap();
-    let mutex_database = RwLock::from(database);
-    let database = Arc::new(mutex_database);
-    self.databases.insert(database_id, database.clone());
-    Ok(database)
+    Ok(
+      cache_database(
+        &self.databases,
+        &self.collab_service,
+        &database_id,
+        database,
+      )
+      .await,
+    )
   }
 
   /// Create linked view that shares the same data with the inline view's database
@@ -368,7 +372,7 @@ impl WorkspaceDatabaseManager {
   }
 
   pub fn close_database(&self, database_id: &str) {
-    let _ = self.databases.remove(database_id);
+    self.databases.remove(database_id);
   }
 
   pub fn track_database(&mut self, database_id: &str, database_view_ids: Vec<String>) {
@@ -425,6 +429,63 @@ impl WorkspaceDatabaseManager {
     }
   }
 
+  /// Recompute the lookup and rollup cells of every row of the database.
+  ///
+  /// The cells are kept up to date while the databases are open, see
+  /// [Self::get_or_init_database]. Call it when the database is opened, to pick up the changes made
+  /// to the related databases while it was closed.
+  ///
+  /// The related databases are opened with [Self::get_or_init_database]. The caller must not hold
+  /// the lock of any database of the workspace, because a relation can point to any of them,
+  /// including the database itself.
+  pub async fn refresh_related_fields(&self, database_id: &str) -> Result<(), DatabaseError> {
+    let database = self.get_or_init_database(database_id).await?;
+    refresh_related_cells(
+      &database,
+      |database_id| async move { self.get_or_init_database(&database_id).await },
+      |_| true,
+      None,
+      |_, _| true,
+    )
+    .await
+  }
+
+  /// Recompute the lookup and rollup cells of the given rows.
+  pub async fn refresh_related_fields_for_rows(
+    &self,
+    database_id: &str,
+    row_ids: &[RowId],
+  ) -> Result<(), DatabaseError> {
+    let database = self.get_or_init_database(database_id).await?;
+    refresh_related_cells(
+      &database,
+      |database_id| async move { self.get_or_init_database(&database_id).await },
+      |_| true,
+      Some(row_ids),
+      |_, _| true,
+    )
+    .await
+  }
+
+  /// Recompute the lookup and rollup cells that read the given rows of the database.
+  ///
+  /// Only the databases that are open are refreshed. The cells of the other databases are
+  /// refreshed by [Self::refresh_related_fields] when they are opened.
+  pub async fn refresh_rows_linked_to(
+    &self,
+    database_id: &str,
+    row_ids: &[RowId],
+  ) -> Result<(), DatabaseError> {
+    refresh_linking_rows(
+      &self.databases,
+      |database_id| async move { self.get_or_init_database(&database_id).await },
+      database_id,
+      row_ids,
+      |_| true,
+    )
+    .await
+  }
+
   pub fn flush_workspace_database(&self) -> Result<(), DatabaseError> {
     let encoded_collab = self.body.encode_collab_v1()?;
     self
@@ -490,3 +551,394 @@ impl BorrowMut<Collab> for WorkspaceDatabaseManager {
     self.body.borrow_mut()
   }
 }
+
+/// The open databases of the workspace, with the index of the rows that link to their rows.
+#[derive(Default)]
+struct OpenDatabases {
+  databases: DashMap<String, Arc<RwLock<Database>>>,
+  linking_rows: Mutex<LinkingRowIndex>,
+}
+
+impl OpenDatabases {
+  fn get(&self, database_id: &str) -> Option<Arc<RwLock<Database>>> {
+    self.databases.get(database_id).as_deref().cloned()
+  }
+
+  fn remove(&self, database_id: &str) {
+    self.databases.remove(database_id);
+    self.linking_rows().remove_database(database_id);
+  }
+
+  fn linking_rows(&self) -> MutexGuard<'_, LinkingRowIndex> {
+    self
+      .linking_rows
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+  }
+}
+
+/// Adds the database to the open databases, indexes the relation cells of its rows, and keeps the
+/// lookup and rollup cells that depend on its rows up to date until it's closed, see
+/// [spawn_related_cells_refresh].
+async fn cache_database(
+  databases: &Arc<OpenDatabases>,
+  collab_service: &Arc<dyn DatabaseCollabService>,
+  database_id: &str,
+  database: Database,
+) -> Arc<RwLock<Database>> {
+  let fields = database.get_all_fields();
+  let rows = if relation_fields(&fields).is_empty() {
+    vec![]
+  } else {
+    database
+      .collect_all_rows()
+      .await
+      .into_iter()
+      .flatten()
+      .collect::<Vec<_>>()
+  };
+  databases
+    .linking_rows()
+    .index_database(database_id, &fields, &rows);
+
+  spawn_related_cells_refresh(
+    database_id.to_string(),
+    &database,
+    Arc::downgrade(databases),
+    collab_service.clone(),
+  );
+  let database = Arc::new(RwLock::new(database));
+  databases
+    .databases
+    .insert(database_id.to_string(), database.clone());
+  database
+}
+
+/// Returns the open database, or opens it. Unlike [WorkspaceDatabaseManager::get_or_init_database]
+/// it doesn't try to fix a database that can't be opened.
+async fn get_or_open_database(
+  databases: &Arc<OpenDatabases>,
+  collab_service: &Arc<dyn DatabaseCollabService>,
+  database_id: &str,
+) -> Result<Arc<RwLock<Database>>, DatabaseError> {
+  if let Some(database) = databases.get(database_id) {
+    return Ok(database);
+  }
+  let database = Database::open(database_id, DatabaseContext::new(collab_service.clone())).await?;
+  Ok(cache_database(databases, collab_service, database_id, database).await)
+}
+
+/// Refreshes the lookup and rollup cells when the rows of the database change, locally or by a
+/// remote update:
+/// - when a relation cell changes, the index of the linking rows is updated and the lookup and
+///   rollup cells of its row are recomputed
+/// - when a cell read by lookup or rollup fields changes, the cells of the rows of the open
+///   databases that link to its row are recomputed
+/// - when a row is created or deleted, its relation cells are added to or removed from the index
+///
+/// The task stops when the database or the [WorkspaceDatabaseManager] is dropped.
+fn spawn_related_cells_refresh(
+  database_id: String,
+  database: &Database,
+  databases: Weak<OpenDatabases>,
+  collab_service: Arc<dyn DatabaseCollabService>,
+) {
+  let mut row_change_rx = match database.subscribe_row_change() {
+    Some(row_change_rx) => row_change_rx,
+    None => return,
+  };
+  tokio::spawn(async move {
+    loop {
+      let change = match row_change_rx.recv().await {
+        Ok(change) => change,
+        Err(RecvError::Lagged(skipped)) => {
+          warn!(
+            "Skipped {} row changes of database {}, its lookup and rollup cells may be stale",
+            skipped, database_id
+          );
+          continue;
+        },
+        Err(RecvError::Closed) => break,
+      };
+      let databases = match databases.upgrade() {
+        Some(databases) => databases,
+        None => break,
+      };
+      let (row_id, field_id, cell) = match change {
+        RowChange::DidUpdateCell {
+          row_id,
+          field_id,
+          value,
+        } => (row_id, field_id, value),
+        RowChange::DidCreateRow { row } => {
+          index_created_row(&databases, &database_id, &row).await;
+          continue;
+        },
+        RowChange::DidDeleteRow { row_id } => {
+          databases.linking_rows().remove_row(&database_id, &row_id);
+          continue;
+        },
+        _ => continue,
+      };
+      if let Err(err) = refresh_cells_reading(
+        &databases,
+        &collab_service,
+        &database_id,
+        &row_id,
+        &field_id,
+        &cell,
+      )
+      .await
+      {
+        error!(
+          "Failed to refresh the lookup and rollup cells that read {}: {}",
+          database_id, err
+        );
+      }
+    }
+  });
+}
+
+async fn index_created_row(databases: &OpenDatabases, database_id: &str, row: &Row) {
+  let Some(database) = databases.get(database_id) else {
+    return;
+  };
+  let relation_fields = relation_fields(&database.read().await.get_all_fields());
+  let mut linking_rows = databases.linking_rows();
+  for (field_id, related_database_id) in relation_fields {
+    if let Some(cell) = row.cells.get(&field_id) {
+      linking_rows.set_relation_cell(database_id, &row.id, &field_id, &related_database_id, cell);
+    }
+  }
+}
+
+/// Recompute the lookup and rollup cells that depend on the cell of the given row and field
+async fn refresh_cells_reading(
+  databases: &Arc<OpenDatabases>,
+  collab_service: &Arc<dyn DatabaseCollabService>,
+  database_id: &str,
+  row_id: &RowId,
+  field_id: &str,
+  cell: &Cell,
+) -> Result<(), DatabaseError> {
+  let open = |database_id: String| async move {
+    get_or_open_database(databases, collab_service, &database_id).await
+  };
+
+  // The cell is the relation cell of a lookup or rollup field of the row. The entry of the map is
+  // released before awaiting, the refresh may open other databases.
+  if let Some(database) = databases.get(database_id) {
+    let related_database_id = database
+      .read()
+      .await
+      .get_field(field_id)
+      .and_then(|field| relation_fields(&[field]).into_values().next());
+    if let Some(related_database_id) = related_database_id {
+      databases.linking_rows().set_relation_cell(
+        database_id,
+        row_id,
+        field_id,
+        &related_database_id,
+        cell,
+      );
+      refresh_related_cells(
+        &database,
+        open,
+        |related_field| related_field.relation_field_id() == field_id,
+        Some(std::slice::from_ref(row_id)),
+        |_, _| true,
+      )
+      .await?;
+    }
+  }
+
+  // The cell is read by the lookup and rollup fields of the rows that link to its row
+  refresh_linking_rows(
+    databases,
+    open,
+    database_id,
+    std::slice::from_ref(row_id),
+    |related_field| related_field.target_field_id() == field_id,
+  )
+  .await
+}
+
+/// Recompute the lookup and rollup cells of the open rows that link to the given rows of the
+/// database, for the related fields accepted by `select_field`. The linking rows are found with
+/// the [LinkingRowIndex].
+async fn refresh_linking_rows<O, Fut, F>(
+  databases: &OpenDatabases,
+  open: O,
+  database_id: &str,
+  row_ids: &[RowId],
+  select_field: F,
+) -> Result<(), DatabaseError>
+where
+  O: Fn(String) -> Fut,
+  Fut: Future<Output = Result<Arc<RwLock<Database>>, DatabaseError>>,
+  F: Fn(&RelatedField) -> bool,
+{
+  let linking_rows = databases.linking_rows().linking_rows(database_id, row_ids);
+  for (linking_database_id, linking_row_ids) in linking_rows {
+    let Some(linking_database) = databases.get(&linking_database_id) else {
+      continue;
+    };
+    let linking_row_ids = linking_row_ids.into_iter().collect::<Vec<_>>();
+    refresh_related_cells(
+      &linking_database,
+      &open,
+      |related_field| {
+        related_field.related_database_id == database_id && select_field(related_field)
+      },
+      Some(&linking_row_ids),
+      |related_field, row| {
+        related_field
+          .linked_row_ids(row)
+          .iter()
+          .any(|row_id| row_ids.contains(row_id))
+      },
+    )
+    .await?;
+  }
+  Ok(())
+}
+
+/// Recompute the lookup and rollup cells of the database, for the related fields accepted by
+/// `select_field` and the rows accepted by `select_row`. The rows are read from `row_ids`, or from
+/// the whole database if it's None. The related databases are opened with `open`.
+///
+/// The locks of the databases are taken one after another and never nested.
+async fn refresh_related_cells<O, Fut, F, R>(
+  database: &Arc<RwLock<Database>>,
+  open: O,
+  select_field: F,
+  row_ids: Option<&[RowId]>,
+  select_row: R,
+) -> Result<(), DatabaseError>
+where
+  O: Fn(String) -> Fut,
+  Fut: Future<Output = Result<Arc<RwLock<Database>>, DatabaseError>>,
+  F: Fn(&RelatedField) -> bool,
+  R: Fn(&RelatedField, &Row) -> bool,
+{
+  let (related_fields, rows) = {
+    let read_guard = database.read().await;
+    let related_fields = RelatedField::from_fields(&read_guard.get_all_fields())
+      .into_iter()
+      .filter(|related_field| select_field(related_field))
+      .collect::<Vec<_>>();
+    if related_fields.is_empty() {
+      return Ok(());
+    }
+    let rows = match row_ids {
+      None => read_guard
+        .collect_all_rows()
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>(),
+      Some(row_ids) => {
+        let mut rows = vec![];
+        for row_id in row_ids {
+          if let Some(database_row) = read_guard.get_or_init_database_row(row_id).await {
+            rows.extend(database_row.read().await.get_row());
+          }
+        }
+        rows
+      },
+    };
+    (related_fields, rows)
+  };
+
+  let pending = rows
+    .iter()
+    .filter_map(|row| {
+      let fields = related_fields
+        .iter()
+        .filter(|related_field| select_row(related_field, row))
+        .collect::<Vec<_>>();
+      (!fields.is_empty()).then_some((row, fields))
+    })
+    .collect::<Vec<_>>();
+  if pending.is_empty() {
+    return Ok(());
+  }
+
+  // Load the target fields and the linked rows, grouped by related database
+  let mut wanted: HashMap<&str, (HashSet<&str>, HashSet<RowId>)> = HashMap::new();
+  for (row, fields) in &pending {
+    for related_field in fields {
+      let (field_ids, row_ids) = wanted
+        .entry(related_field.related_database_id.as_str())
+        .or_default();
+      field_ids.insert(related_field.target_field_id());
+      row_ids.extend(related_field.linked_row_ids(row));
+    }
+  }
+  let mut target_fields = HashMap::new();
+  let mut related_rows = HashMap::new();
+  for (related_database_id, (field_ids, row_ids)) in wanted {
+    let related_database = match open(related_database_id.to_string()).await {
+      Ok(related_database) => related_database,
+      Err(err) => {
+        error!(
+          "Failed to open related database {}: {}",
+          related_database_id, err
+        );
+        continue;
+      },
+    };
+    let read_guard = related_database.read().await;
+    for field_id in field_ids {
+      if let Some(field) = read_guard.get_field(field_id) {
+        target_fields.insert((related_database_id, field_id), field);
+      }
+    }
+    for row_id in row_ids {
+      if let Some(database_row) = read_guard.get_or_init_database_row(&row_id).await {
+        if let Some(row) = database_row.read().await.get_row() {
+          related_rows.insert((related_database_id, row_id), row);
+        }
+      }
+    }
+  }
+
+  let updates = pending
+    .into_iter()
+    .filter_map(|(row, fields)| {
+      let cells = fields
+        .into_iter()
+        .filter_map(|related_field| {
+          let database_id = related_field.related_database_id.as_str();
+          let linked_rows = related_field
+            .linked_row_ids(row)
+            .into_iter()
+            .filter_map(|row_id| related_rows.get(&(database_id, row_id)).cloned())
+            .collect::<Vec<_>>();
+          let target_field = target_fields.get(&(database_id, related_field.target_field_id()));
+          let cell = related_field.compute_cell(target_field, &linked_rows);
+          let cached = row.cells.get(&related_field.field_id);
+          (!related_field.is_up_to_date(cached, &cell))
+            .then(|| (related_field.field_id.clone(), cell))
+        })
+        .collect::<Vec<_>>();
+      (!cells.is_empty()).then(|| (row.id.clone(), cells))
+    })
+    .collect::<Vec<_>>();
+
+  let mut write_guard = database.write().await;
+  for (row_id, cells) in updates {
+    write_guard
+      .update_row(row_id, |row_update| {
+        row_update.update_cells(|cells_update| {
+          cells
+            .into_iter()
+            .fold(cells_update, |update, (field_id, cell)| {
+              update.insert_cell(&field_id, cell)
+            });
+        });
+      })
+      .await;
+  }
+  Ok(())
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::entity::FieldType;
+use crate::fields::Field;
+use crate::fields::relation_type_option::RelationTypeOption;
+use crate::rows::{Cell, Row, RowId};
+use crate::template::relation_parse::RelationCellData;
+
+/// A database id and the id of one of its rows
+type DatabaseRowId = (String, RowId);
+
+/// Index of the rows that link to a row through their relation cells. The lookup and rollup cells
+/// that read a row are found with it, without reading every row of the databases.
+///
+/// Only the rows of the open databases are indexed, see [LinkingRowIndex::index_database].
+#[derive(Debug, Default)]
+pub(crate) struct LinkingRowIndex {
+  /// The relation cells of each row: relation field id -> (related database id, linked row ids)
+  relation_cells: HashMap<DatabaseRowId, HashMap<String, (String, Vec<RowId>)>>,
+  /// The rows that link to each row, with the number of their relation cells that link to it
+  linking_rows: HashMap<DatabaseRowId, HashMap<DatabaseRowId, usize>>,
+}
+
+impl LinkingRowIndex {
+  /// Replaces the rows of the database in the index with the given rows
+  pub(crate) fn index_database(&mut self, database_id: &str, fields: &[Field], rows: &[Row]) {
+    self.remove_database(database_id);
+    let relation_fields = relation_fields(fields);
+    if relation_fields.is_empty() {
+      return;
+    }
+    for row in rows {
+      for (field_id, related_database_id) in &relation_fields {
+        if let Some(cell) = row.cells.get(field_id) {
+          self.set_relation_cell(database_id, &row.id, field_id, related_database_id, cell);
+        }
+      }
+    }
+  }
+
+  /// Replaces the rows linked by the relation cell of the row
+  pub(crate) fn set_relation_cell(
+    &mut self,
+    database_id: &str,
+    row_id: &RowId,
+    field_id: &str,
+    related_database_id: &str,
+    cell: &Cell,
+  ) {
+    let linking_row = (database_id.to_string(), row_id.clone());
+    let previous = self
+      .relation_cells
+      .get_mut(&linking_row)
+      .and_then(|cells| cells.remove(field_id));
+    if let Some((previous_database_id, previous_row_ids)) = previous {
+      self.unlink(&linking_row, &previous_database_id, &previous_row_ids);
+    }
+
+    let linked_row_ids = RelationCellData::from(cell).row_ids;
+    for linked_row_id in &linked_row_ids {
+      *self
+        .linking_rows
+        .entry((related_database_id.to_string(), linked_row_id.clone()))
+        .or_default()
+        .entry(linking_row.clone())
+        .or_default() += 1;
+    }
+    self.relation_cells.entry(linking_row).or_default().insert(
+      field_id.to_string(),
+      (related_database_id.to_string(), linked_row_ids),
+    );
+  }
+
+  pub(crate) fn remove_row(&mut self, database_id: &str, row_id: &RowId) {
+    let linking_row = (database_id.to_string(), row_id.clone());
+    if let Some(cells) = self.relation_cells.remove(&linking_row) {
+      for (related_database_id, linked_row_ids) in cells.values() {
+        self.unlink(&linking_row, related_database_id, linked_row_ids);
+      }
+    }
+  }
+
+  pub(crate) fn remove_database(&mut self, database_id: &str) {
+    let row_ids = self
+      .relation_cells
+      .keys()
+      .filter(|(id, _)| id == database_id)
+      .map(|(_, row_id)| row_id.clone())
+      .collect::<Vec<_>>();
+    for row_id in row_ids {
+      self.remove_row(database_id, &row_id);
+    }
+  }
+
+  /// Returns the rows that link to the given rows of the database, grouped by database id
+  pub(crate) fn linking_rows(
+    &self,
+    database_id: &str,
+    row_ids: &[RowId],
+  ) -> HashMap<String, HashSet<RowId>> {
+    let mut linking_rows: HashMap<String, HashSet<RowId>> = HashMap::new();
+    for row_id in row_ids {
+      let linked_row = (database_id.to_string(), row_id.clone());
+      for (linking_database_id, linking_row_id) in self
+        .linking_rows
+        .get(&linked_row)
+        .into_iter()
+        .flat_map(|rows| rows.keys())
+      {
+        linking_rows
+          .entry(linking_database_id.clone())
+          .or_default()
+          .insert(linking_row_id.clone());
+      }
+    }
+    linking_rows
+  }
+
+  fn unlink(
+    &mut self,
+    linking_row: &DatabaseRowId,
+    related_database_id: &str,
+    linked_row_ids: &[RowId],
+  ) {
+    for linked_row_id in linked_row_ids {
+      let linked_row = (related_database_id.to_string(), linked_row_id.clone());
+      let Some(rows) = self.linking_rows.get_mut(&linked_row) else {
+        continue;
+      };
+      if let Some(count) = rows.get_mut(linking_row) {
+        *count -= 1;
+        if *count == 0 {
+          rows.remove(linking_row);
+        }
+      }
+      if rows.is_empty() {
+        self.linking_rows.remove(&linked_row);
+      }
+    }
+  }
+}
+
+/// Returns the related database id of each relation field of the given fields
+pub(crate) fn relation_fields(fields: &[Field]) -> HashMap<String, String> {
+  fields
+    .iter()
+    .filter(|field| FieldType::from(field.field_type) == FieldType::Relation)
+    .filter_map(|field| {
+      let type_option =
+        field.get_type_option::<RelationTypeOption>(FieldType::Relation.type_id())?;
+      Some((field.id.clone(), type_option.database_id))
+    })
+    .collect()
+}
@@ -1,7 +1,11 @@
 mod db_relation;
+mod linking_row_index;
+mod related_field;
 mod row_relation;
 mod row_relation_map;
 
 pub use db_relation::*;
+pub(crate) use linking_row_index::*;
+pub(crate) use related_field::*;
 pub use row_relation::*;
 pub use row_relation_map::*;
@@ -0,0 +1,104 @@
+use crate::entity::FieldType;
+use crate::fields::Field;
+use crate::fields::lookup_type_option::LookupTypeOption;
+use crate::fields::relation_type_option::RelationTypeOption;
+use crate::fields::rollup_type_option::RollupTypeOption;
+use crate::rows::{Cell, Row, RowId};
+use crate::template::lookup_parse::LookupCellData;
+use crate::template::relation_parse::RelationCellData;
+use crate::template::rollup_parse::RollupCellData;
+
+/// A lookup or rollup field of a database, resolved against the relation field it reads through.
+#[derive(Debug, Clone)]
+pub(crate) struct RelatedField {
+  pub(crate) field_id: String,
+  /// The database that the relation field links to
+  pub(crate) related_database_id: String,
+  kind: RelatedFieldKind,
+}
+
+#[derive(Debug, Clone)]
+enum RelatedFieldKind {
+  Lookup(LookupTypeOption),
+  Rollup(RollupTypeOption),
+}
+
+impl RelatedField {
+  /// Returns the lookup and rollup fields of the given fields. Fields whose relation field is
+  /// missing or isn't a relation are skipped.
+  pub(crate) fn from_fields(fields: &[Field]) -> Vec<RelatedField> {
+    fields
+      .iter()
+      .filter_map(|field| {
+        let field_type = FieldType::from(field.field_type);
+        let kind = match field_type {
+          FieldType::Lookup => RelatedFieldKind::Lookup(
+            field.get_type_option::<LookupTypeOption>(field_type.type_id())?,
+          ),
+          FieldType::Rollup => RelatedFieldKind::Rollup(
+            field.get_type_option::<RollupTypeOption>(field_type.type_id())?,
+          ),
+          _ => return None,
+        };
+        let relation_field_id = match &kind {
+          RelatedFieldKind::Lookup(type_option) => &type_option.relation_field_id,
+          RelatedFieldKind::Rollup(type_option) => &type_option.relation_field_id,
+        };
+        let relation_field = fields
+          .iter()
+          .find(|field| &field.id == relation_field_id)
+          .filter(|field| FieldType::from(field.field_type) == FieldType::Relation)?;
+        let related_database_id = relation_field
+          .get_type_option::<RelationTypeOption>(FieldType::Relation.type_id())?
+          .database_id;
+        Some(RelatedField {
+          field_id: field.id.clone(),
+          related_database_id,
+          kind,
+        })
+      })
+      .collect()
+  }
+
+  pub(crate) fn relation_field_id(&self) -> &str {
+    match &self.kind {
+      RelatedFieldKind::Lookup(type_option) => &type_option.relation_field_id,
+      RelatedFieldKind::Rollup(type_option) => &type_option.relation_field_id,
+    }
+  }
+
+  pub(crate) fn target_field_id(&self) -> &str {
+    match &self.kind {
+      RelatedFieldKind::Lookup(type_option) => &type_option.target_field_id,
+      RelatedFieldKind::Rollup(type_option) => &type_option.target_field_id,
+    }
+  }
+
+  /// Returns the ids of the rows linked by the relation cell of the row
+  pub(crate) fn linked_row_ids(&self, row: &Row) -> Vec<RowId> {
+    row
+      .cells
+      .get(self.relation_field_id())
+      .map(|cell| RelationCellData::from(cell).row_ids)
+      .unwrap_or_default()
+  }
+
+  pub(crate) fn compute_cell(&self, target_field: Option<&Field>, related_rows: &[Row]) -> Cell {
+    match &self.kind {
+      RelatedFieldKind::Lookup(type_option) => type_option.compute_cell(target_field, related_rows),
+      RelatedFieldKind::Rollup(type_option) => type_option.compute_cell(target_field, related_rows),
+    }
+  }
+
+  /// Returns true if the cached cell holds the same value as the computed one, in which case
+  /// the row doesn't need to be updated.
+  pub(crate) fn is_up_to_date(&self, cached: Option<&Cell>, computed: &Cell) -> bool {
+    let Some(cached) = cached else {
+      return false;
+    };
+    match self.kind {
+      RelatedFieldKind::Lookup(_) => LookupCellData::from(cached) == LookupCellData::from(computed),
+      RelatedFieldKind::Rollup(_) => RollupCellData::from(cached) == RollupCellData::from(computed),
+    }
+  }
+}
@@ -0,0 +1,156 @@
+use collab_database::entity::FieldType;
+use collab_database::fields::number_type_option::{NumberFormat, NumberTypeOption};
+use collab_database::rows::{Cells, CreateRowParams, RowChange, RowId};
+use collab_database::views::{
+  Calculation, CalculationResult, CalculationType, FieldFilter, Filter, NumberFilter,
+  NumberFilterCondition,
+};
+
+use crate::database_test::helper::{
+  DatabaseTest, DatabaseTestBuilder, data_cell, wait_for_specific_event,
+};
+
+#[tokio::test]
+async fn compute_calculations_test() {
+  let database_test = create_database_with_calculations(NumberFormat::Num).await;
+  let results = database_test.compute_calculations("v1").await;
+  assert_eq!(formatted_value(&results, "sum"), "60");
+  assert_eq!(formatted_value(&results, "average"), "20");
+  assert_eq!(formatted_value(&results, "median"), "20");
+  assert_eq!(formatted_value(&results, "count_empty"), "1");
+  assert_eq!(formatted_value(&results, "percent_checked"), "50%");
+}
+
+#[tokio::test]
+async fn compute_calculations_with_number_format_test() {
+  let database_test = create_database_with_calculations(NumberFormat::USD).await;
+  let results = database_test.compute_calculations("v1").await;
+  let sum = results
+    .iter()
+    .find(|result| result.calculation_id == "sum")
+    .unwrap();
+  assert_eq!(sum.value, Some(60.0));
+  assert!(sum.formatted_value.starts_with('$'));
+}
+
+#[tokio::test]
+async fn compute_calculations_with_filter_test() {
+  let mut database_test = create_database_with_calculations(NumberFormat::Num).await;
+  insert_amount_filter(&mut database_test);
+
+  let results = database_test.compute_calculations("v1").await;
+  assert_eq!(formatted_value(&results, "sum"), "50");
+  assert_eq!(formatted_value(&results, "count_empty"), "0");
+  assert_eq!(formatted_value(&results, "percent_checked"), "0%");
+}
+
+#[tokio::test]
+async fn update_calculations_with_row_change_test() {
+  let mut database_test = create_database_with_calculations(NumberFormat::Num).await;
+  insert_amount_filter(&mut database_test);
+  let mut state = database_test.get_calculation_state_for_view("v1").await;
+
+  // r1 passes the filter after the change, so it's added to every calculation
+  let results = state.apply_row_change(&RowChange::DidUpdateCell {
+    row_id: RowId::from("r1".to_string()),
+    field_id: "amount".to_string(),
+    value: data_cell(FieldType::Number, "40"),
+  });
+  assert_eq!(results.len(), 5);
+  assert_eq!(formatted_value(&results, "sum"), "90");
+  assert_eq!(formatted_value(&results, "percent_checked"), "33.33%");
+
+  // Only the calculations of the changed field are updated
+  let results = state.apply_row_change(&RowChange::DidUpdateCell {
+    row_id: RowId::from("r2".to_string()),
+    field_id: "amount".to_string(),
+    value: data_cell(FieldType::Number, "25"),
+  });
+  assert_eq!(results.len(), 4);
+  assert_eq!(formatted_value(&results, "sum"), "95");
+  assert_eq!(formatted_value(&results, "median"), "30");
+}
+
+#[tokio::test]
+async fn subscribe_calculation_change_test() {
+  let mut database_test = create_database_with_calculations(NumberFormat::Num).await;
+  let database_id = database_test.get_database_id();
+  let calculation_rx = database_test
+    .subscribe_calculation_change("v1")
+    .await
+    .unwrap();
+
+  let row_id = RowId::from("r5".to_string());
+  let cells = Cells::from([("amount".into(), data_cell(FieldType::Number, "40"))]);
+  database_test
+    .create_row(CreateRowParams::new(row_id.clone(), database_id).with_cells(cells))
+    .await
+    .unwrap();
+  wait_for_specific_event(calculation_rx.resubscribe(), |results| {
+    formatted_value(results, "sum") == "100" && formatted_value(results, "count_empty") == "1"
+  })
+  .await
+  .unwrap();
+
+  database_test.remove_row(&row_id).await;
+  wait_for_specific_event(calculation_rx, |results| {
+    formatted_value(results, "sum") == "60"
+  })
+  .await
+  .unwrap();
+}
+
+fn formatted_value(results: &[CalculationResult], calculation_id: &str) -> String {
+  results
+    .iter()
+    .find(|result| result.calculation_id == calculation_id)
+    .map(|result| result.formatted_value.clone())
+    .unwrap()
+}
+
+fn insert_amount_filter(database_test: &mut DatabaseTest) {
+  database_test.insert_filter(
+    "v1",
+    Filter::new_data(
+      "filter".to_string(),
+      "amount".to_string(),
+      FieldType::Number,
+      FieldFilter::Number(NumberFilter {
+        condition: NumberFilterCondition::GreaterThanOrEqualTo,
+        content: "15".to_string(),
+      }),
+    ),
+  );
+}
+
+async fn create_database_with_calculations(format: NumberFormat) -> DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let number_type_option = NumberTypeOption {
+    format,
+    ..Default::default()
+  };
+  let mut database_test = DatabaseTestBuilder::new(1, &database_id)
+    .with_typed_field("amount", "Amount", FieldType::Number)
+    .with_type_option("amount", number_type_option)
+    .with_typed_field("done", "Done", FieldType::Checkbox)
+    .with_typed_row("r1", &[("amount", "10"), ("done", "true")])
+    .with_typed_row("r2", &[("amount", "20"), ("done", "false")])
+    .with_typed_row("r3", &[("amount", ""), ("done", "true")])
+    .with_typed_row("r4", &[("amount", "30")])
+    .build()
+    .await;
+  let calculations = [
+    ("sum", "amount", CalculationType::Sum),
+    ("average", "amount", CalculationType::Average),
+    ("median", "amount", CalculationType::Median),
+    ("count_empty", "amount", CalculationType::CountEmpty),
+    ("percent_checked", "done", CalculationType::PercentChecked),
+  ];
+  for (id, field_id, calculation_type) in calculations {
+    database_test.update_calculation(
+      "v1",
+      Calculation::new(id.to_string(), field_id.to_string(), calculation_type),
+    );
+  }
+  database_test
+}
@@ -2,6 +2,7 @@ use crate::database_test::helper::create_database_with_default_data;
 use assert_json_diff::assert_json_eq;
 use collab::core::origin::CollabOrigin;
 use collab::preclude::Collab;
+use collab_entity::CollabType;
 
 #[tokio::test]
 async fn encode_database_collab_test() {
@@ -32,3 +33,19 @@ async fn encode_database_collab_test() {
     assert_json_eq!(json, expected_json);
   }
 }
+
+#[tokio::test]
+async fn created_database_and_rows_match_schema_test() {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let database_test = create_database_with_default_data(1, &database_id).await;
+  CollabType::Database
+    .validate_schema(&database_test.collab)
+    .unwrap();
+
+  for row_id in &database_test.pre_define_row_ids {
+    let database_row = database_test.get_database_row(row_id).await.unwrap();
+    CollabType::DatabaseRow
+      .validate_schema(&database_row.read().await.collab)
+      .unwrap();
+  }
+}
@@ -1,4 +1,12 @@
-use crate::database_test::helper::{DatabaseTest, create_database_with_default_data};
+use collab_database::entity::FieldType;
+use collab_database::views::{
+  FieldFilter, Filter, NumberFilter, NumberFilterCondition, SelectOptionFilter,
+  SelectOptionFilterCondition, TextFilter, TextFilterCondition,
+};
+
+use crate::database_test::helper::{
+  DatabaseTest, DatabaseTestBuilder, create_database_with_default_data,
+};
 use crate::helper::{FILTER_CONTENT, TestFieldType, TestFilter};
 
 #[tokio::test]
@@ -99,3 +107,114 @@ async fn create_database_with_two_filters() -> DatabaseTest {
 
   database_test
 }
+
+#[tokio::test]
+async fn filter_rows_with_text_filter_test() {
+  let mut database_test = create_database_with_typed_rows().await;
+  database_test.insert_filter(
+    "v1",
+    Filter::new_data(
+      "filter_1".to_string(),
+      "name".to_string(),
+      FieldType::RichText,
+      FieldFilter::Text(TextFilter {
+        condition: TextFilterCondition::TextContains,
+        content: "APP".to_string(),
+      }),
+    ),
+  );
+
+  let rows = database_test.get_filtered_rows_for_view("v1").await;
+  let row_ids = rows
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r1", "r3"]);
+
+  let row_orders = database_test.get_filtered_row_orders_for_view("v1").await;
+  assert_eq!(row_orders.len(), 2);
+}
+
+#[tokio::test]
+async fn filter_rows_with_nested_filters_test() {
+  let mut database_test = create_database_with_typed_rows().await;
+  // (amount >= 10 AND tags contains "b") OR name is empty
+  database_test.insert_filter(
+    "v1",
+    Filter::new_or(
+      "root".to_string(),
+      vec![
+        Filter::new_and(
+          "and".to_string(),
+          vec![
+            Filter::new_data(
+              "amount_filter".to_string(),
+              "amount".to_string(),
+              FieldType::Number,
+              FieldFilter::Number(NumberFilter {
+                condition: NumberFilterCondition::GreaterThanOrEqualTo,
+                content: "10".to_string(),
+              }),
+            ),
+            Filter::new_data(
+              "tags_filter".to_string(),
+              "tags".to_string(),
+              FieldType::MultiSelect,
+              FieldFilter::SelectOption(SelectOptionFilter {
+                condition: SelectOptionFilterCondition::OptionContains,
+                option_ids: vec!["b".to_string()],
+              }),
+            ),
+          ],
+        ),
+        Filter::new_data(
+          "name_filter".to_string(),
+          "name".to_string(),
+          FieldType::RichText,
+          FieldFilter::Text(TextFilter {
+            condition: TextFilterCondition::TextIsEmpty,
+            content: "".to_string(),
+          }),
+        ),
+      ],
+    ),
+  );
+
+  let filter = database_test.get_filter::<Filter>("v1", "root").unwrap();
+  assert!(filter.find("tags_filter").is_some());
+
+  let rows = database_test.get_filtered_rows_for_view("v1").await;
+  let row_ids = rows
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r3", "r4"]);
+}
+
+#[tokio::test]
+async fn filter_rows_without_filters_test() {
+  let database_test = create_database_with_typed_rows().await;
+  let rows = database_test.get_filtered_rows_for_view("v1").await;
+  assert_eq!(rows.len(), 4);
+}
+
+async fn create_database_with_typed_rows() -> DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let mut builder = DatabaseTestBuilder::new(1, &database_id)
+    .with_typed_field("name", "Name", FieldType::RichText)
+    .with_typed_field("amount", "Amount", FieldType::Number)
+    .with_typed_field("tags", "Tags", FieldType::MultiSelect);
+  let rows = [
+    ("r1", "apple", "5", "a"),
+    ("r2", "banana", "20", "a"),
+    ("r3", "pineapple", "12", "a,b"),
+    ("r4", "", "1", ""),
+  ];
+  for (row_id, name, amount, tags) in rows {
+    builder = builder.with_typed_row(
+      row_id,
+      &[("name", name), ("amount", amount), ("tags", tags)],
+    );
+  }
+  builder.build().await
+}
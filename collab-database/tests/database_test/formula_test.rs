@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use collab_database::entity::FieldType;
+use collab_database::error::DatabaseError;
+use collab_database::fields::formula_type_option::FormulaTypeOption;
+use collab_database::fields::{Field, FormulaError, FormulaResultType};
+use collab_database::rows::RowId;
+use collab_database::views::{Calculation, CalculationType, Sort, SortCondition};
+
+use crate::database_test::helper::{DatabaseTest, DatabaseTestBuilder, data_cell};
+
+#[tokio::test]
+async fn formula_cells_are_computed_test() {
+  let mut database_test = create_database_with_formula().await;
+  let result_type = database_test
+    .update_formula("total", "{price} * {quantity}")
+    .await
+    .unwrap();
+  assert_eq!(result_type, FormulaResultType::Number);
+
+  let reader = database_test.get_cell_reader("total").unwrap();
+  let row = database_test.get_row(&RowId::from("r1".to_string())).await;
+  let cell = row.cells.get("total").unwrap();
+  assert_eq!(reader.stringify_cell(cell), "30");
+  assert_eq!(reader.numeric_cell(cell), Some(30.0));
+
+  let type_option = database_test
+    .get_field("total")
+    .unwrap()
+    .get_type_option::<FormulaTypeOption>(FieldType::Formula.type_id())
+    .unwrap();
+  assert_eq!(type_option.expression, "{price} * {quantity}");
+  assert_eq!(type_option.result_type, FormulaResultType::Number);
+}
+
+#[tokio::test]
+async fn formula_cells_update_with_row_test() {
+  let mut database_test = create_database_with_formula().await;
+  database_test
+    .update_formula("total", r#"{name} & ": " & {price} * {quantity}"#)
+    .await
+    .unwrap();
+
+  let row_id = RowId::from("r2".to_string());
+  database_test
+    .update_row(row_id.clone(), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("quantity", data_cell(FieldType::Number, "10"));
+      });
+    })
+    .await;
+
+  let reader = database_test.get_cell_reader("total").unwrap();
+  let row = database_test.get_row(&row_id).await;
+  assert_eq!(
+    reader.stringify_cell(row.cells.get("total").unwrap()),
+    "Pear: 50"
+  );
+}
+
+#[tokio::test]
+async fn formula_dependencies_follow_field_changes_test() {
+  let mut database_test = create_database_with_formula().await;
+  let dependencies = database_test.get_formula_dependencies();
+  assert!(dependencies.has_formulas());
+  assert!(!dependencies.is_affected_by(["price"]));
+  assert!(Arc::ptr_eq(
+    &dependencies,
+    &database_test.get_formula_dependencies()
+  ));
+
+  database_test
+    .update_formula("total", "{price} * {quantity}")
+    .await
+    .unwrap();
+  let dependencies = database_test.get_formula_dependencies();
+  assert!(dependencies.is_affected_by(["name", "price"]));
+  assert!(!dependencies.is_affected_by(["name"]));
+
+  // Updating a cell that no formula reads keeps the formula cells as they are
+  let row_id = RowId::from("r1".to_string());
+  database_test
+    .update_row(row_id.clone(), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("name", data_cell(FieldType::RichText, "Green apple"));
+      });
+    })
+    .await;
+  let reader = database_test.get_cell_reader("total").unwrap();
+  let row = database_test.get_row(&row_id).await;
+  assert_eq!(reader.stringify_cell(row.cells.get("total").unwrap()), "30");
+}
+
+#[tokio::test]
+async fn invalid_formula_is_rejected_test() {
+  let mut database_test = create_database_with_formula().await;
+  database_test
+    .update_formula("total", "{price} * {quantity}")
+    .await
+    .unwrap();
+
+  let error = database_test
+    .update_formula("total", "{price} * {missing}")
+    .await
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    DatabaseError::InvalidFormula(FormulaError::UnknownField(ref field_id)) if field_id == "missing"
+  ));
+
+  let error = database_test
+    .update_formula("total", r#"{price} + "abc""#)
+    .await
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    DatabaseError::InvalidFormula(FormulaError::TypeMismatch { .. })
+  ));
+
+  let error = database_test
+    .update_formula("total", "{price} *")
+    .await
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    DatabaseError::InvalidFormula(FormulaError::Syntax { .. })
+  ));
+
+  // The previous expression is kept
+  let type_option = database_test
+    .get_field("total")
+    .unwrap()
+    .get_type_option::<FormulaTypeOption>(FieldType::Formula.type_id())
+    .unwrap();
+  assert_eq!(type_option.expression, "{price} * {quantity}");
+}
+
+#[tokio::test]
+async fn circular_formula_is_rejected_test() {
+  let mut database_test = create_database_with_formula().await;
+  database_test
+    .update_formula("total", "{price} * {quantity}")
+    .await
+    .unwrap();
+  database_test.insert_field(formula_field("double"));
+  database_test
+    .update_formula("double", "{total} * 2")
+    .await
+    .unwrap();
+
+  let error = database_test
+    .update_formula("total", "{double} + 1")
+    .await
+    .unwrap_err();
+  match error {
+    DatabaseError::InvalidFormula(FormulaError::CircularReference(cycle)) => {
+      assert_eq!(cycle, vec!["total", "double", "total"]);
+    },
+    error => panic!("unexpected error: {:?}", error),
+  }
+
+  let error = database_test
+    .update_formula("total", "{total} + 1")
+    .await
+    .unwrap_err();
+  assert!(matches!(
+    error,
+    DatabaseError::InvalidFormula(FormulaError::CircularReference(_))
+  ));
+}
+
+#[tokio::test]
+async fn sort_and_calculate_formula_test() {
+  let mut database_test = create_database_with_formula().await;
+  database_test
+    .update_formula("total", "{price} * {quantity}")
+    .await
+    .unwrap();
+
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "total".to_string(),
+      FieldType::Formula,
+      SortCondition::Descending,
+    ),
+  );
+  let row_ids = database_test
+    .get_sorted_rows_for_view("v1")
+    .await
+    .into_iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r1", "r3", "r2"]);
+
+  database_test.update_calculation(
+    "v1",
+    Calculation::new("sum".to_string(), "total".to_string(), CalculationType::Sum),
+  );
+  let results = database_test.compute_calculations("v1").await;
+  assert_eq!(results[0].value, Some(50.0));
+}
+
+fn formula_field(field_id: &str) -> Field {
+  Field::new(
+    field_id.to_string(),
+    field_id.to_string(),
+    FieldType::Formula.into(),
+    false,
+  )
+  .with_type_option_data(FieldType::Formula, FormulaTypeOption::default().into())
+}
+
+async fn create_database_with_formula() -> DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let mut builder = DatabaseTestBuilder::new(1, &database_id)
+    .with_typed_field("name", "Name", FieldType::RichText)
+    .with_typed_field("price", "Price", FieldType::Number)
+    .with_typed_field("quantity", "Quantity", FieldType::Number)
+    .with_field(formula_field("total"));
+  let rows = [
+    ("r1", "Apple", "10", "3"),
+    ("r2", "Pear", "5", ""),
+    ("r3", "Plum", "4", "5"),
+  ];
+  for (row_id, name, price, quantity) in rows {
+    builder = builder.with_typed_row(
+      row_id,
+      &[("name", name), ("price", price), ("quantity", quantity)],
+    );
+  }
+  builder.build().await
+}
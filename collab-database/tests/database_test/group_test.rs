@@ -1,9 +1,15 @@
 use collab::preclude::Any;
 use collab::util::{AnyExt, AnyMapExt};
-use collab_database::entity::CreateViewParams;
-use collab_database::views::{DatabaseLayout, GroupMap};
+use collab_database::entity::{CreateViewParams, FieldType};
+use collab_database::fields::select_type_option::{
+  SelectOption, SelectOptionIds, SelectTypeOption, SingleSelectTypeOption,
+};
+use collab_database::rows::RowId;
+use collab_database::views::{DatabaseLayout, GroupMap, GroupSetting, NO_VALUE_GROUP_ID};
 
-use crate::database_test::helper::{DatabaseTest, create_database_with_default_data};
+use crate::database_test::helper::{
+  DatabaseTest, DatabaseTestBuilder, create_database_with_default_data,
+};
 use crate::helper::{CONTENT, GROUPS, TestGroup, TestGroupSetting};
 
 #[tokio::test]
@@ -232,3 +238,116 @@ async fn create_database_with_two_groups() -> DatabaseTest {
   database_test.create_linked_view(params).unwrap();
   database_test
 }
+
+#[tokio::test]
+async fn group_rows_by_single_select_test() {
+  let database_test = create_board_with_status_field().await;
+  let groups = database_test.get_groups_for_view("v1").await.unwrap();
+  let group_ids = groups
+    .iter()
+    .map(|group| group.id.as_str())
+    .collect::<Vec<_>>();
+  assert_eq!(group_ids, vec![NO_VALUE_GROUP_ID, "todo", "doing", "done"]);
+
+  let row_ids = |index: usize| {
+    groups[index]
+      .rows
+      .iter()
+      .map(|row| row.id.to_string())
+      .collect::<Vec<_>>()
+  };
+  assert!(groups[0].is_no_value);
+  assert_eq!(row_ids(0), vec!["r3"]);
+  assert_eq!(row_ids(1), vec!["r1"]);
+  assert!(row_ids(2).is_empty());
+  assert_eq!(row_ids(3), vec!["r2"]);
+}
+
+#[tokio::test]
+async fn move_row_to_group_test() {
+  let mut database_test = create_board_with_status_field().await;
+  let row_id = RowId::from("r1".to_string());
+  database_test
+    .move_row_to_group("v1", &row_id, "todo", "doing")
+    .await
+    .unwrap();
+
+  let row = database_test.get_row(&row_id).await;
+  let option_ids = SelectOptionIds::from(row.cells.get("status").unwrap());
+  assert_eq!(option_ids.into_inner(), vec!["doing".to_string()]);
+
+  let groups = database_test.get_groups_for_view("v1").await.unwrap();
+  assert_eq!(groups[2].id, "doing");
+  assert_eq!(groups[2].rows.len(), 1);
+  assert!(groups[1].rows.is_empty());
+
+  // Moving to the "no value" group clears the cell
+  database_test
+    .move_row_to_group("v1", &row_id, "doing", NO_VALUE_GROUP_ID)
+    .await
+    .unwrap();
+  let groups = database_test.get_groups_for_view("v1").await.unwrap();
+  assert_eq!(groups[0].rows.len(), 2);
+}
+
+#[tokio::test]
+async fn move_and_hide_group_test() {
+  let mut database_test = create_board_with_status_field().await;
+  database_test
+    .move_group("v1", "done", "todo")
+    .await
+    .unwrap();
+  database_test
+    .update_group_visibility("v1", NO_VALUE_GROUP_ID, false)
+    .await
+    .unwrap();
+
+  let groups = database_test.get_groups_for_view("v1").await.unwrap();
+  let group_ids = groups
+    .iter()
+    .map(|group| group.id.as_str())
+    .collect::<Vec<_>>();
+  assert_eq!(group_ids, vec![NO_VALUE_GROUP_ID, "done", "todo", "doing"]);
+  assert!(!groups[0].visible);
+
+  let setting = database_test
+    .get_all_group_setting::<GroupSetting>("v1")
+    .pop()
+    .unwrap();
+  assert_eq!(setting.groups.len(), 4);
+}
+
+async fn create_board_with_status_field() -> DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let options = ["todo", "doing", "done"]
+    .into_iter()
+    .map(|id| SelectOption {
+      id: id.to_string(),
+      name: id.to_string(),
+      color: Default::default(),
+    })
+    .collect();
+  let status_type_option = SingleSelectTypeOption(SelectTypeOption {
+    options,
+    disable_color: false,
+  });
+
+  let mut database_test = DatabaseTestBuilder::new(1, &database_id)
+    .with_layout(DatabaseLayout::Board)
+    .with_typed_field("status", "Status", FieldType::SingleSelect)
+    .with_type_option("status", status_type_option)
+    .with_typed_row("r1", &[("status", "todo")])
+    .with_typed_row("r2", &[("status", "done")])
+    .with_typed_row("r3", &[("status", "")])
+    .build()
+    .await;
+  database_test.insert_group_setting(
+    "v1",
+    GroupSetting::new(
+      "status".to_string(),
+      FieldType::SingleSelect.into(),
+      "".to_string(),
+    ),
+  );
+  database_test
+}
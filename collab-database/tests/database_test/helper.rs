@@ -1,8 +1,12 @@
 use collab::core::collab::DataSource;
-use collab::preclude::{CollabBuilder, uuid_v4};
+use collab::preclude::{Any, CollabBuilder, uuid_v4};
 use collab_database::database::{Database, DatabaseContext};
-use collab_database::fields::Field;
-use collab_database::rows::{Cells, CreateRowParams, DatabaseRow, Row, RowId};
+use collab_database::entity::{FieldType, default_type_option_data_from_type};
+use collab_database::fields::{Field, TypeOptionData};
+use collab_database::rows::{
+  Cell, Cells, CreateRowParams, DatabaseRow, Row, RowId, new_cell_builder,
+};
+use collab_database::template::entity::CELL_DATA;
 use collab_database::views::{
   DatabaseLayout, FieldSettingsByFieldIdMap, FieldSettingsMap, LayoutSetting, LayoutSettings,
   OrderObjectPosition,
@@ -55,6 +59,13 @@ impl DerefMut for DatabaseTest {
   }
 }
 
+/// A cell of the given type whose data is the given string.
+pub fn data_cell(field_type: FieldType, data: &str) -> Cell {
+  let mut cell = new_cell_builder(field_type);
+  cell.insert(CELL_DATA.into(), Any::from(data));
+  cell
+}
+
 /// Create a database with a single view.
 pub fn create_database(uid: i64, database_id: &str) -> DatabaseTest {
   let workspace_id = Uuid::new_v4().to_string();
@@ -209,6 +220,61 @@ impl DatabaseTestBuilder {
     self
   }
 
+  /// Adds a field of the given type with its default type option. The first field is the primary
+  /// field.
+  pub fn with_typed_field(self, field_id: &str, name: &str, field_type: FieldType) -> Self {
+    let is_primary = self.fields.is_empty();
+    let field = Field::new(
+      field_id.to_string(),
+      name.to_string(),
+      field_type.into(),
+      is_primary,
+    )
+    .with_type_option_data(
+      field_type.type_id(),
+      default_type_option_data_from_type(field_type),
+    );
+    self.with_field(field)
+  }
+
+  /// Replaces the type option of a field added with [DatabaseTestBuilder::with_typed_field].
+  pub fn with_type_option(
+    mut self,
+    field_id: &str,
+    type_option: impl Into<TypeOptionData>,
+  ) -> Self {
+    let field = self
+      .fields
+      .iter_mut()
+      .find(|field| field.id == field_id)
+      .unwrap();
+    let field_type = FieldType::from(field.field_type);
+    field
+      .type_options
+      .insert(field_type.type_id(), type_option.into());
+    self
+  }
+
+  /// Adds a row with a [data_cell] for each of the given (field id, data), typed after its field.
+  pub fn with_typed_row(self, row_id: &str, cells: &[(&str, &str)]) -> Self {
+    let cells = cells
+      .iter()
+      .map(|(field_id, data)| {
+        let field = self
+          .fields
+          .iter()
+          .find(|field| field.id == *field_id)
+          .unwrap();
+        (
+          field_id.to_string(),
+          data_cell(FieldType::from(field.field_type), data),
+        )
+      })
+      .collect::<Cells>();
+    let row = CreateRowParams::new(row_id.to_string(), self.database_id.clone()).with_cells(cells);
+    self.with_row(row)
+  }
+
   pub fn with_layout(mut self, layout: DatabaseLayout) -> Self {
     self.layout = layout;
     self
@@ -1,4 +1,5 @@
 mod block_test;
+mod calculation_test;
 mod cell_test;
 mod cell_type_option_test;
 mod encode_collab_test;
@@ -6,6 +7,7 @@ mod field_observe_test;
 mod field_setting_test;
 mod field_test;
 mod filter_test;
+mod formula_test;
 mod group_test;
 pub mod helper;
 mod layout_test;
@@ -1,7 +1,16 @@
-use crate::database_test::helper::{DatabaseTest, create_database_with_default_data};
+use crate::database_test::helper::{
+  DatabaseTest, DatabaseTestBuilder, create_database_with_default_data, data_cell,
+};
 use crate::helper::{SortCondition, TestSort};
-use collab_database::entity::CreateViewParams;
-use collab_database::views::DatabaseLayout;
+use collab::preclude::Any;
+use collab_database::entity::{CreateViewParams, FieldType};
+use collab_database::fields::select_type_option::{
+  SelectOption, SelectTypeOption, SingleSelectTypeOption,
+};
+use collab_database::rows::{LAST_MODIFIED, Row, RowChange, RowId};
+use collab_database::views::{
+  DatabaseLayout, EmptyPosition, RowPositionChange, Sort, SortCondition as ViewSortCondition,
+};
 
 #[tokio::test]
 async fn create_database_view_with_sort_test() {
@@ -114,3 +123,212 @@ async fn create_database_with_two_sorts() -> DatabaseTest {
   database_test.create_linked_view(params).unwrap();
   database_test
 }
+
+#[tokio::test]
+async fn sort_rows_with_multiple_sorts_test() {
+  let mut database_test = create_database_with_priority_and_amount().await;
+  // Sort by priority option position, then by amount in descending order
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "priority".to_string(),
+      FieldType::SingleSelect,
+      ViewSortCondition::Ascending,
+    ),
+  );
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s2".to_string(),
+      "amount".to_string(),
+      FieldType::Number,
+      ViewSortCondition::Descending,
+    ),
+  );
+
+  let rows = database_test.get_sorted_rows_for_view("v1").await;
+  let row_ids = rows
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r4", "r2", "r1", "r3"]);
+}
+
+#[tokio::test]
+async fn sort_rows_with_empty_first_test() {
+  let mut database_test = create_database_with_priority_and_amount().await;
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "priority".to_string(),
+      FieldType::SingleSelect,
+      ViewSortCondition::Descending,
+    )
+    .with_empty_position(EmptyPosition::First),
+  );
+
+  let rows = database_test.get_sorted_rows_for_view("v1").await;
+  let row_ids = rows
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r3", "r1", "r2", "r4"]);
+}
+
+#[tokio::test]
+async fn update_sorted_rows_with_row_change_test() {
+  let mut database_test = create_database_with_priority_and_amount().await;
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "priority".to_string(),
+      FieldType::SingleSelect,
+      ViewSortCondition::Ascending,
+    ),
+  );
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s2".to_string(),
+      "amount".to_string(),
+      FieldType::Number,
+      ViewSortCondition::Descending,
+    ),
+  );
+
+  let mut sorted_rows = database_test.get_sorted_row_list_for_view("v1").await;
+  let change = sorted_rows.apply_row_change(&RowChange::DidUpdateCell {
+    row_id: RowId::from("r1".to_string()),
+    field_id: "priority".to_string(),
+    value: data_cell(FieldType::SingleSelect, "high"),
+  });
+  assert_eq!(
+    change,
+    Some(RowPositionChange {
+      row_id: RowId::from("r1".to_string()),
+      old_index: 2,
+      new_index: 1,
+    })
+  );
+  let row_ids = sorted_rows
+    .rows()
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r4", "r1", "r2", "r3"]);
+
+  // Changing a field that is not sorted doesn't move the row
+  let change = sorted_rows.apply_row_change(&RowChange::DidUpdateCell {
+    row_id: RowId::from("r3".to_string()),
+    field_id: "name".to_string(),
+    value: data_cell(FieldType::RichText, "hello"),
+  });
+  assert!(change.is_none());
+}
+
+#[tokio::test]
+async fn insert_row_after_remove_row_keeps_view_order_test() {
+  let mut database_test = create_database_with_priority_and_amount().await;
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "priority".to_string(),
+      FieldType::SingleSelect,
+      ViewSortCondition::Ascending,
+    ),
+  );
+
+  let mut sorted_rows = database_test.get_sorted_row_list_for_view("v1").await;
+  sorted_rows.remove_row(&RowId::from("r1".to_string()));
+  // The new row ties with r2 and r4, so it goes after them
+  let mut row = Row::new("r5".to_string(), &database_test.get_database_id());
+  row.cells.insert(
+    "priority".to_string(),
+    data_cell(FieldType::SingleSelect, "high"),
+  );
+  assert_eq!(sorted_rows.insert_row(row), 2);
+  let row_ids = sorted_rows
+    .rows()
+    .iter()
+    .map(|row| row.id.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(row_ids, vec!["r2", "r4", "r5", "r3"]);
+}
+
+#[tokio::test]
+async fn update_cell_moves_row_sorted_by_last_edited_time_test() {
+  let mut database_test = create_database_with_priority_and_amount().await;
+  database_test.insert_sort(
+    "v1",
+    Sort::new(
+      "s1".to_string(),
+      "edited".to_string(),
+      FieldType::LastEditedTime,
+      ViewSortCondition::Descending,
+    ),
+  );
+
+  let mut sorted_rows = database_test.get_sorted_row_list_for_view("v1").await;
+  let last_index = sorted_rows.rows().len() - 1;
+  let row_id = sorted_rows.rows()[last_index].id.clone();
+  let modified_at = sorted_rows
+    .rows()
+    .iter()
+    .map(|row| row.modified_at)
+    .max()
+    .unwrap()
+    + 100;
+  let mut cell = data_cell(FieldType::RichText, "hello");
+  cell.insert(LAST_MODIFIED.into(), Any::BigInt(modified_at));
+  let change = sorted_rows.apply_row_change(&RowChange::DidUpdateCell {
+    row_id: row_id.clone(),
+    field_id: "name".to_string(),
+    value: cell,
+  });
+  assert_eq!(
+    change,
+    Some(RowPositionChange {
+      row_id: row_id.clone(),
+      old_index: last_index,
+      new_index: 0,
+    })
+  );
+  assert_eq!(sorted_rows.rows()[0].modified_at, modified_at);
+}
+
+async fn create_database_with_priority_and_amount() -> DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let options = ["high", "low"]
+    .into_iter()
+    .map(|id| SelectOption {
+      id: id.to_string(),
+      name: id.to_string(),
+      color: Default::default(),
+    })
+    .collect();
+  let priority_type_option = SingleSelectTypeOption(SelectTypeOption {
+    options,
+    disable_color: false,
+  });
+
+  let mut builder = DatabaseTestBuilder::new(1, &database_id)
+    .with_typed_field("name", "Name", FieldType::RichText)
+    .with_typed_field("priority", "Priority", FieldType::SingleSelect)
+    .with_type_option("priority", priority_type_option)
+    .with_typed_field("amount", "Amount", FieldType::Number)
+    .with_typed_field("edited", "Edited", FieldType::LastEditedTime);
+  let rows = [
+    ("r1", "low", "5"),
+    ("r2", "high", "1"),
+    ("r3", "", "9"),
+    ("r4", "high", "7"),
+  ];
+  for (row_id, priority, amount) in rows {
+    builder = builder.with_typed_row(row_id, &[("priority", priority), ("amount", amount)]);
+  }
+  builder.build().await
+}
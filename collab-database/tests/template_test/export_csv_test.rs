@@ -0,0 +1,141 @@
+use collab_database::database::Database;
+use collab_database::entity::FieldType;
+use collab_database::fields::number_type_option::{NumberFormat, NumberTypeOption};
+use collab_database::fields::{FieldSettingsBuilder, FieldVisibility};
+use collab_database::template::csv::CSVTemplate;
+use collab_database::template::csv_export::{
+  CSVExportOptions, CSVHeader, CSVNumberFormat, DOCUMENT_ID_COLUMN,
+};
+use collab_database::views::FieldSettingsByFieldIdMap;
+
+use crate::database_test::helper::DatabaseTestBuilder;
+
+#[tokio::test]
+async fn export_csv_round_trip_test() {
+  let csv_data = include_str!("../asset/selected-services-march-2024-quarter-csv.csv");
+  for auto_field_type in [false, true] {
+    let database = import_csv(csv_data, auto_field_type).await;
+    let view_id = database.get_first_database_view_id().unwrap();
+    let mut exported = vec![];
+    database.export_csv(&view_id, &mut exported).await.unwrap();
+
+    let exported = String::from_utf8(exported).unwrap();
+    let reimported = import_csv(&exported, auto_field_type).await;
+    let reimported_view_id = reimported.get_first_database_view_id().unwrap();
+
+    let fields = database.get_fields_in_view(&view_id, None);
+    let reimported_fields = reimported.get_fields_in_view(&reimported_view_id, None);
+    assert_eq!(fields.len(), 14);
+    assert_eq!(fields.len(), reimported_fields.len());
+    for (field, reimported_field) in fields.iter().zip(reimported_fields.iter()) {
+      assert_eq!(field.name, reimported_field.name);
+      assert_eq!(field.field_type, reimported_field.field_type);
+    }
+
+    let mut reexported = vec![];
+    reimported
+      .export_csv(&reimported_view_id, &mut reexported)
+      .await
+      .unwrap();
+    assert_eq!(exported, String::from_utf8(reexported).unwrap());
+  }
+}
+
+#[tokio::test]
+async fn export_csv_follows_view_test() {
+  let mut database_test = create_database().await;
+  database_test.set_field_settings(
+    "v1",
+    FieldSettingsByFieldIdMap::from(
+      [(
+        "secret".to_string(),
+        FieldSettingsBuilder::new("secret")
+          .visibility(FieldVisibility::AlwaysHidden)
+          .build()
+          .into(),
+      )]
+      .into_iter()
+      .collect::<std::collections::HashMap<_, _>>(),
+    ),
+  );
+  database_test.move_row("r3", "r1").await;
+
+  let mut output = vec![];
+  database_test.export_csv("v1", &mut output).await.unwrap();
+  assert_eq!(
+    String::from_utf8(output).unwrap(),
+    "Name,Price\nPear,\"$1,200.5\"\nApple,$10\nPlum,$4.25\n"
+  );
+
+  let options = CSVExportOptions {
+    delimiter: b';',
+    header: CSVHeader::FieldNameWithType,
+    number_format: CSVNumberFormat::Raw,
+    include_hidden_fields: true,
+    ..Default::default()
+  };
+  let mut output = vec![];
+  database_test
+    .export_csv_with_options("v1", &mut output, &options)
+    .await
+    .unwrap();
+  assert_eq!(
+    String::from_utf8(output).unwrap(),
+    "Name (Text);Price (Number);Secret (Text)\nPear;1200.5;c\nApple;10;a\nPlum;4.25;b\n"
+  );
+}
+
+#[tokio::test]
+async fn export_csv_with_row_meta_test() {
+  let database_test = create_database().await;
+  let options = CSVExportOptions {
+    include_row_meta: true,
+    ..Default::default()
+  };
+  let mut output = vec![];
+  database_test
+    .export_csv_with_options("v1", &mut output, &options)
+    .await
+    .unwrap();
+
+  let output = String::from_utf8(output).unwrap();
+  let mut reader = csv::Reader::from_reader(output.as_bytes());
+  let header = reader.headers().unwrap().clone();
+  assert_eq!(header.len(), 6);
+  assert_eq!(&header[5], DOCUMENT_ID_COLUMN);
+  assert_eq!(reader.records().count(), 3);
+}
+
+async fn import_csv(csv_data: &str, auto_field_type: bool) -> Database {
+  let csv_template = CSVTemplate::try_from_reader(csv_data.as_bytes(), auto_field_type, None)
+    .unwrap()
+    .try_into_database_template(None)
+    .await
+    .unwrap();
+  Database::create_with_template(csv_template).await.unwrap()
+}
+
+async fn create_database() -> crate::database_test::helper::DatabaseTest {
+  let database_id = uuid::Uuid::new_v4().to_string();
+  let number_type_option = NumberTypeOption {
+    format: NumberFormat::USD,
+    ..Default::default()
+  };
+  let mut builder = DatabaseTestBuilder::new(1, &database_id)
+    .with_typed_field("name", "Name", FieldType::RichText)
+    .with_typed_field("price", "Price", FieldType::Number)
+    .with_type_option("price", number_type_option)
+    .with_typed_field("secret", "Secret", FieldType::RichText);
+  let rows = [
+    ("r1", "Apple", "10", "a"),
+    ("r2", "Plum", "4.25", "b"),
+    ("r3", "Pear", "1200.5", "c"),
+  ];
+  for (row_id, name, price, secret) in rows {
+    builder = builder.with_typed_row(
+      row_id,
+      &[("name", name), ("price", price), ("secret", secret)],
+    );
+  }
+  builder.build().await
+}
@@ -1,2 +1,3 @@
 mod create_template_test;
+mod export_csv_test;
 mod import_csv_test;
@@ -2,10 +2,13 @@ use crate::user_test::helper::{
   make_default_grid, random_uid, user_database_test_with_db, user_database_test_with_default_data,
   workspace_database_test,
 };
+use collab::preclude::Collab;
 use collab_database::database::gen_database_view_id;
 use collab_database::entity::{CreateDatabaseParams, CreateViewParams, FileUploadType};
 use collab_database::rows::{CoverType, CreateRowParams, Row, RowCover};
+use collab_entity::CollabType;
 use futures::StreamExt;
+use std::borrow::Borrow;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -427,3 +430,12 @@ async fn reopen_database_test() {
   }
   let _ = database.read().await.to_json_value().await;
 }
+
+#[tokio::test]
+async fn created_workspace_database_matches_schema_test() {
+  let test = workspace_database_test(random_uid()).await;
+  let collab: &Collab = (*test).borrow();
+  CollabType::WorkspaceDatabase
+    .validate_schema(collab)
+    .unwrap();
+}
@@ -167,11 +167,11 @@ impl DatabaseCollabService for TestUserDatabaseServiceImpl {
     let data_source = encoded_collab
       .map(|(encoded_collab, _)| DataSource::from(encoded_collab))
       .unwrap_or_else(|| {
-        KVDBCollabPersistenceImpl {
-          db: Arc::downgrade(&self.db),
-          uid: self.uid,
-          workspace_id: self.workspace_id.clone(),
-        }
+        KVDBCollabPersistenceImpl::new(
+          Arc::downgrade(&self.db),
+          self.uid,
+          self.workspace_id.clone(),
+        )
         .into_data_source()
       });
 
@@ -204,11 +204,11 @@ impl DatabaseCollabService for TestUserDatabaseServiceImpl {
       let collab = CollabBuilder::new(
         1,
         &object_id,
-        KVDBCollabPersistenceImpl {
-          db: Arc::downgrade(&self.db),
-          uid: self.uid,
-          workspace_id: self.workspace_id.clone(),
-        }
+        KVDBCollabPersistenceImpl::new(
+          Arc::downgrade(&self.db),
+          self.uid,
+          self.workspace_id.clone(),
+        )
         .into_data_source(),
       )
       .with_device_id("1")
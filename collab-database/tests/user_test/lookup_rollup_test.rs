@@ -0,0 +1,295 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use collab::lock::RwLock;
+use collab_database::database::Database;
+use collab_database::entity::{CreateDatabaseParams, CreateViewParams, FieldType};
+use collab_database::fields::Field;
+use collab_database::fields::checkbox_type_option::CheckboxTypeOption;
+use collab_database::fields::lookup_type_option::LookupTypeOption;
+use collab_database::fields::number_type_option::NumberTypeOption;
+use collab_database::fields::relation_type_option::RelationTypeOption;
+use collab_database::fields::rollup_type_option::{RollupCalculation, RollupTypeOption};
+use collab_database::fields::text_type_option::RichTextTypeOption;
+use collab_database::rows::{Cell, Cells, CreateRowParams, RowId};
+use collab_database::template::relation_parse::RelationCellData;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+use crate::database_test::helper::data_cell;
+use crate::user_test::helper::{WorkspaceDatabaseTest, test_timeout, workspace_database_test};
+
+#[tokio::test]
+async fn lookup_and_rollup_read_related_rows_test() {
+  let (test, projects_id, _) = create_linked_databases().await;
+  test.refresh_related_fields(&projects_id).await.unwrap();
+
+  let projects = test.get_or_init_database(&projects_id).await.unwrap();
+  let projects = projects.read().await;
+  let row = projects.get_row(&RowId::from("p1".to_string())).await;
+  let cell_text = |field_id: &str| {
+    let reader = projects.get_cell_reader(field_id).unwrap();
+    reader.stringify_cell(row.cells.get(field_id).unwrap())
+  };
+  assert_eq!(cell_text("task_names"), "Design, Build");
+  assert_eq!(cell_text("total_hours"), "7.5");
+  assert_eq!(cell_text("done_percent"), "50%");
+
+  let row = projects.get_row(&RowId::from("p2".to_string())).await;
+  let reader = projects.get_cell_reader("total_hours").unwrap();
+  assert_eq!(
+    reader.numeric_cell(row.cells.get("total_hours").unwrap()),
+    Some(0.0)
+  );
+}
+
+#[tokio::test]
+async fn rollup_updates_when_linked_row_changes_test() {
+  let (test, projects_id, tasks_id) = create_linked_databases().await;
+  test.refresh_related_fields(&projects_id).await.unwrap();
+
+  let tasks = test.get_or_init_database(&tasks_id).await.unwrap();
+  let task_id = RowId::from("t2".to_string());
+  tasks
+    .write()
+    .await
+    .update_row(task_id.clone(), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update
+          .insert_cell("name", data_cell(FieldType::RichText, "Ship"))
+          .insert_cell("hours", data_cell(FieldType::Number, "10"))
+          .insert_cell("done", data_cell(FieldType::Checkbox, "true"));
+      });
+    })
+    .await;
+  test
+    .refresh_rows_linked_to(&tasks_id, &[task_id])
+    .await
+    .unwrap();
+
+  let projects = test.get_or_init_database(&projects_id).await.unwrap();
+  let projects = projects.read().await;
+  let row = projects.get_row(&RowId::from("p1".to_string())).await;
+  let cell_text = |field_id: &str| {
+    let reader = projects.get_cell_reader(field_id).unwrap();
+    reader.stringify_cell(row.cells.get(field_id).unwrap())
+  };
+  assert_eq!(cell_text("task_names"), "Design, Ship");
+  assert_eq!(cell_text("total_hours"), "15");
+  assert_eq!(cell_text("done_percent"), "100%");
+}
+
+#[tokio::test]
+async fn lookup_updates_when_relation_changes_test() {
+  let (test, projects_id, _) = create_linked_databases().await;
+  test.refresh_related_fields(&projects_id).await.unwrap();
+
+  let row_id = RowId::from("p2".to_string());
+  let projects = test.get_or_init_database(&projects_id).await.unwrap();
+  projects
+    .write()
+    .await
+    .update_row(row_id.clone(), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("tasks", relation_cell(&["t3"]));
+      });
+    })
+    .await;
+  test
+    .refresh_related_fields_for_rows(&projects_id, &[row_id.clone()])
+    .await
+    .unwrap();
+
+  let projects = projects.read().await;
+  let row = projects.get_row(&row_id).await;
+  let reader = projects.get_cell_reader("task_names").unwrap();
+  assert_eq!(
+    reader.stringify_cell(row.cells.get("task_names").unwrap()),
+    "Test"
+  );
+}
+
+#[tokio::test]
+async fn related_cells_refresh_when_rows_change_test() {
+  let (test, projects_id, tasks_id) = create_linked_databases().await;
+  test.refresh_related_fields(&projects_id).await.unwrap();
+
+  // A cell read by a rollup changes
+  let tasks = test.get_or_init_database(&tasks_id).await.unwrap();
+  tasks
+    .write()
+    .await
+    .update_row(RowId::from("t1".to_string()), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("hours", data_cell(FieldType::Number, "1"));
+      });
+    })
+    .await;
+  let projects = test.get_or_init_database(&projects_id).await.unwrap();
+  wait_for_cell(&projects, "p1", "total_hours", "3.5").await;
+
+  // A relation cell changes
+  projects
+    .write()
+    .await
+    .update_row(RowId::from("p2".to_string()), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("tasks", relation_cell(&["t1", "t3"]));
+      });
+    })
+    .await;
+  wait_for_cell(&projects, "p2", "task_names", "Design, Test").await;
+  wait_for_cell(&projects, "p2", "total_hours", "2").await;
+}
+
+#[tokio::test]
+async fn related_cells_refresh_for_created_rows_test() {
+  let (test, projects_id, tasks_id) = create_linked_databases().await;
+  let projects = test.get_or_init_database(&projects_id).await.unwrap();
+  projects
+    .write()
+    .await
+    .create_row(
+      CreateRowParams::new("p3".to_string(), projects_id.clone())
+        .with_cells(Cells::from([("tasks".into(), relation_cell(&["t3"]))])),
+    )
+    .await
+    .unwrap();
+
+  // The created row links to t3, so it's refreshed when t3 changes
+  let tasks = test.get_or_init_database(&tasks_id).await.unwrap();
+  tasks
+    .write()
+    .await
+    .update_row(RowId::from("t3".to_string()), |row_update| {
+      row_update.update_cells(|cells_update| {
+        cells_update.insert_cell("hours", data_cell(FieldType::Number, "4"));
+      });
+    })
+    .await;
+  wait_for_cell(&projects, "p3", "total_hours", "4").await;
+}
+
+/// Waits until the lookup or rollup cells are refreshed in the background
+async fn wait_for_cell(database: &Arc<RwLock<Database>>, row_id: &str, field_id: &str, text: &str) {
+  test_timeout(async {
+    loop {
+      {
+        let database = database.read().await;
+        let row = database.get_row(&RowId::from(row_id.to_string())).await;
+        let reader = database.get_cell_reader(field_id).unwrap();
+        if row
+          .cells
+          .get(field_id)
+          .is_some_and(|cell| reader.stringify_cell(cell) == text)
+        {
+          return;
+        }
+      }
+      sleep(Duration::from_millis(20)).await;
+    }
+  })
+  .await
+}
+
+fn relation_cell(row_ids: &[&str]) -> Cell {
+  Cell::from(RelationCellData {
+    row_ids: row_ids
+      .iter()
+      .map(|row_id| RowId::from(row_id.to_string()))
+      .collect(),
+  })
+}
+
+fn field(field_id: &str, field_type: FieldType, is_primary: bool) -> Field {
+  Field::new(
+    field_id.to_string(),
+    field_id.to_string(),
+    field_type.into(),
+    is_primary,
+  )
+}
+
+/// Creates a tasks database and a projects database whose rows link to the tasks
+async fn create_linked_databases() -> (WorkspaceDatabaseTest, String, String) {
+  let mut test = workspace_database_test(1).await;
+  let tasks_id = Uuid::new_v4().to_string();
+  let projects_id = Uuid::new_v4().to_string();
+
+  let tasks = [
+    ("t1", "Design", "5", "true"),
+    ("t2", "Build", "2.5", "false"),
+    ("t3", "Test", "1", "false"),
+  ];
+  test
+    .create_database(CreateDatabaseParams {
+      database_id: tasks_id.clone(),
+      fields: vec![
+        field("name", FieldType::RichText, true)
+          .with_type_option_data(FieldType::RichText, RichTextTypeOption.into()),
+        field("hours", FieldType::Number, false)
+          .with_type_option_data(FieldType::Number, NumberTypeOption::default().into()),
+        field("done", FieldType::Checkbox, false)
+          .with_type_option_data(FieldType::Checkbox, CheckboxTypeOption.into()),
+      ],
+      rows: tasks
+        .into_iter()
+        .map(|(row_id, name, hours, done)| {
+          CreateRowParams::new(row_id.to_string(), tasks_id.clone()).with_cells(Cells::from([
+            ("name".into(), data_cell(FieldType::RichText, name)),
+            ("hours".into(), data_cell(FieldType::Number, hours)),
+            ("done".into(), data_cell(FieldType::Checkbox, done)),
+          ]))
+        })
+        .collect(),
+      views: vec![CreateViewParams {
+        database_id: tasks_id.clone(),
+        view_id: "tasks_view".to_string(),
+        ..Default::default()
+      }],
+    })
+    .await
+    .unwrap();
+
+  let relation_type_option = RelationTypeOption {
+    database_id: tasks_id.clone(),
+  };
+  let projects = [("p1", vec!["t1", "t2"]), ("p2", vec![])];
+  test
+    .create_database(CreateDatabaseParams {
+      database_id: projects_id.clone(),
+      fields: vec![
+        field("name", FieldType::RichText, true),
+        field("tasks", FieldType::Relation, false)
+          .with_type_option_data(FieldType::Relation, relation_type_option.into()),
+        field("task_names", FieldType::Lookup, false).with_type_option_data(
+          FieldType::Lookup,
+          LookupTypeOption::new("tasks", "name").into(),
+        ),
+        field("total_hours", FieldType::Rollup, false).with_type_option_data(
+          FieldType::Rollup,
+          RollupTypeOption::new("tasks", "hours", RollupCalculation::Sum).into(),
+        ),
+        field("done_percent", FieldType::Rollup, false).with_type_option_data(
+          FieldType::Rollup,
+          RollupTypeOption::new("tasks", "done", RollupCalculation::PercentChecked).into(),
+        ),
+      ],
+      rows: projects
+        .into_iter()
+        .map(|(row_id, task_ids)| {
+          CreateRowParams::new(row_id.to_string(), projects_id.clone())
+            .with_cells(Cells::from([("tasks".into(), relation_cell(&task_ids))]))
+        })
+        .collect(),
+      views: vec![CreateViewParams {
+        database_id: projects_id.clone(),
+        view_id: "projects_view".to_string(),
+        ..Default::default()
+      }],
+    })
+    .await
+    .unwrap();
+
+  (test, projects_id, tasks_id)
+}
@@ -1,6 +1,7 @@
 mod cell_test;
 mod database_test;
 pub mod helper;
+mod lookup_rollup_test;
 // mod relation_test;
 // mod snapshot_test;
 // mod async_test;
@@ -19,6 +19,7 @@ use crate::blocks::{
 };
 use crate::document_awareness::DocumentAwarenessState;
 use crate::error::DocumentError;
+use crate::exporter::md_exporter::MDExporter;
 use crate::importer::define::BlockType;
 use crate::utils::{
   get_delta_from_block_data, get_delta_from_external_text_id, push_deltas_to_str,
@@ -120,6 +121,12 @@ impl Document {
     self.body.get_document_data(&txn)
   }
 
+  /// Converts the document to Markdown. See [MDExporter] for how each block is written.
+  pub fn to_markdown(&self) -> Result<String, DocumentError> {
+    let document_data = self.get_document_data()?;
+    Ok(MDExporter::new().export(&document_data))
+  }
+
   /// Get page id
   pub fn get_page_id(&self) -> Option<String> {
     let txn = self.collab.transact();
@@ -51,7 +51,9 @@ pub enum DocumentError {
 impl From<CollabValidateError> for DocumentError {
   fn from(error: CollabValidateError) -> Self {
     match error {
-      CollabValidateError::NoRequiredData(_) => DocumentError::NoRequiredData,
+      CollabValidateError::NoRequiredData(_) | CollabValidateError::InvalidData(_) => {
+        DocumentError::NoRequiredData
+      },
     }
   }
 }
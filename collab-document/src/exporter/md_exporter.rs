@@ -0,0 +1,478 @@
+use crate::blocks::{Block, DocumentData};
+use crate::importer::define::*;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+const MENTION_ATTR: &str = "mention";
+const MENTION_TYPE_FIELD: &str = "type";
+const MENTION_PAGE_ID_FIELD: &str = "page_id";
+const MENTION_DATE_FIELD: &str = "date";
+
+/// Converts a [DocumentData] to Markdown.
+///
+/// It's the inverse of [crate::importer::md_importer::MDImporter]: importing the output with the
+/// default parse options gives back an equivalent document.
+#[derive(Default)]
+pub struct MDExporter;
+
+impl MDExporter {
+  pub fn new() -> Self {
+    Self
+  }
+
+  pub fn export(&self, document_data: &DocumentData) -> String {
+    let Some(page) = document_data.blocks.get(&document_data.page_id) else {
+      return String::new();
+    };
+
+    let writer = MDWriter { document_data };
+    let mut markdown = writer.block_lines(page, 1).join("\n");
+    if !markdown.is_empty() {
+      markdown.push('\n');
+    }
+    markdown
+  }
+}
+
+struct MDWriter<'a> {
+  document_data: &'a DocumentData,
+}
+
+impl<'a> MDWriter<'a> {
+  fn children(&self, block: &Block) -> Vec<&'a Block> {
+    self
+      .document_data
+      .meta
+      .children_map
+      .get(&block.children)
+      .map(|children| {
+        children
+          .iter()
+          .filter_map(|child_id| self.document_data.blocks.get(child_id))
+          .collect()
+      })
+      .unwrap_or_default()
+  }
+
+  fn delta(&self, block: &Block) -> Vec<Value> {
+    block
+      .external_id
+      .as_ref()
+      .and_then(|text_id| self.document_data.meta.text_map.as_ref()?.get(text_id))
+      .and_then(|delta| serde_json::from_str(delta).ok())
+      .unwrap_or_default()
+  }
+
+  fn plain_text(&self, block: &Block) -> String {
+    self
+      .delta(block)
+      .iter()
+      .filter_map(|op| op.get("insert").and_then(Value::as_str))
+      .collect()
+  }
+
+  /// Returns the text of the block rendered as inline Markdown, one entry per line.
+  fn text_lines(&self, block: &Block) -> Vec<String> {
+    let text = self.delta(block).iter().map(render_op).collect::<String>();
+    if text.is_empty() {
+      return vec![];
+    }
+    text.split('\n').map(escape_line_start).collect()
+  }
+
+  /// Renders sibling blocks. Consecutive list items of the same type stay in the same list, any
+  /// other blocks are separated by an empty line.
+  fn blocks_lines(&self, blocks: &[&Block]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut previous_ty: Option<&str> = None;
+    let mut number = 1;
+    for block in blocks {
+      let ty = block.ty.as_str();
+      if ty == BlockType::NumberedList.as_str() {
+        number = if previous_ty == Some(ty) {
+          number + 1
+        } else {
+          block
+            .data
+            .get(START_NUMBER_FIELD)
+            .and_then(Value::as_u64)
+            .unwrap_or(1)
+        };
+      }
+
+      let block_lines = self.block_lines(block, number);
+      if block_lines.is_empty() {
+        continue;
+      }
+      if let Some(previous_ty) = previous_ty {
+        if !(is_list(ty) && previous_ty == ty) {
+          lines.push(String::new());
+        }
+      }
+      lines.extend(block_lines);
+      previous_ty = Some(ty);
+    }
+    lines
+  }
+
+  fn block_lines(&self, block: &Block, number: u64) -> Vec<String> {
+    let children = self.children(block);
+    let mut lines = match BlockType::from_block_ty(&block.ty) {
+      BlockType::Page => return self.blocks_lines(&children),
+      BlockType::Table => return self.table_lines(&children),
+      BlockType::BulletedList => return self.list_item_lines(block, "- ", None, &children),
+      BlockType::NumberedList => {
+        return self.list_item_lines(block, &format!("{}. ", number), None, &children);
+      },
+      BlockType::TodoList => {
+        let checked = block
+          .data
+          .get(CHECKED_FIELD)
+          .and_then(Value::as_bool)
+          .unwrap_or(false);
+        return self.list_item_lines(block, "- ", Some(checked), &children);
+      },
+      BlockType::Quote => {
+        let mut lines = self.text_lines(block);
+        self.append_children(&mut lines, &children);
+        return lines
+          .into_iter()
+          .map(|line| {
+            if line.is_empty() {
+              ">".to_string()
+            } else {
+              format!("> {}", line)
+            }
+          })
+          .collect();
+      },
+      BlockType::Heading => {
+        let level = block
+          .data
+          .get(LEVEL_FIELD)
+          .and_then(Value::as_u64)
+          .unwrap_or(1)
+          .clamp(1, 6) as usize;
+        vec![format!(
+          "{} {}",
+          "#".repeat(level),
+          self.text_lines(block).join(" ")
+        )]
+      },
+      BlockType::Code => {
+        let language = block
+          .data
+          .get(LANGUAGE_FIELD)
+          .and_then(Value::as_str)
+          .unwrap_or_default();
+        let code = self.plain_text(block);
+        // The fence has to be longer than any run of backticks in the code
+        let fence = "`".repeat(longest_run(&code, '`').max(2) + 1);
+        let mut lines = vec![format!("{}{}", fence, language)];
+        if !code.is_empty() {
+          lines.extend(code.split('\n').map(String::from));
+        }
+        lines.push(fence);
+        lines
+      },
+      BlockType::MathEquation => {
+        let formula = block
+          .data
+          .get(FORMULA_FIELD)
+          .and_then(Value::as_str)
+          .unwrap_or_default();
+        let mut lines = vec!["$$".to_string()];
+        if !formula.is_empty() {
+          lines.extend(formula.split('\n').map(String::from));
+        }
+        lines.push("$$".to_string());
+        lines
+      },
+      BlockType::Image => match block.data.get(URL_FIELD).and_then(Value::as_str) {
+        Some(url) if !url.is_empty() => vec![format!("![]({})", link_destination(url))],
+        _ => vec![],
+      },
+      BlockType::LinkPreview => match block.data.get(URL_FIELD).and_then(Value::as_str) {
+        Some(url) if !url.is_empty() => {
+          vec![format!("[{}]: {}", escape_text(url), link_destination(url))]
+        },
+        _ => vec![],
+      },
+      BlockType::Divider => vec!["---".to_string()],
+      BlockType::Paragraph | BlockType::TableCell | BlockType::Text | BlockType::Custom(_) => {
+        self.text_lines(block)
+      },
+    };
+    self.append_children(&mut lines, &children);
+    lines
+  }
+
+  fn append_children(&self, lines: &mut Vec<String>, children: &[&Block]) {
+    let child_lines = self.blocks_lines(children);
+    if child_lines.is_empty() {
+      return;
+    }
+    if !lines.is_empty() {
+      lines.push(String::new());
+    }
+    lines.extend(child_lines);
+  }
+
+  /// Renders a list item. The lines after the first one are indented by the width of the marker,
+  /// so that the nested blocks belong to the item.
+  fn list_item_lines(
+    &self,
+    block: &Block,
+    marker: &str,
+    checked: Option<bool>,
+    children: &[&Block],
+  ) -> Vec<String> {
+    let mut body = self.text_lines(block);
+    if let Some(checked) = checked {
+      let task = if checked { "[x]" } else { "[ ]" };
+      match body.first_mut() {
+        Some(first) => *first = format!("{} {}", task, first),
+        None => body.push(task.to_string()),
+      }
+    }
+
+    let child_lines = self.blocks_lines(children);
+    if !child_lines.is_empty() {
+      // A nested list can follow the text directly, but any other block needs an empty line so
+      // that it's not read as a continuation of the text.
+      let starts_with_list = children
+        .first()
+        .map(|child| is_list(&child.ty))
+        .unwrap_or(false);
+      if !body.is_empty() && !starts_with_list {
+        body.push(String::new());
+      }
+      body.extend(child_lines);
+    }
+
+    if body.is_empty() {
+      return vec![marker.trim_end().to_string()];
+    }
+    let indent = " ".repeat(marker.len());
+    body
+      .into_iter()
+      .enumerate()
+      .map(|(index, line)| {
+        if index == 0 {
+          format!("{}{}", marker, line)
+        } else if line.is_empty() {
+          line
+        } else {
+          format!("{}{}", indent, line)
+        }
+      })
+      .collect()
+  }
+
+  /// Renders the cells of a table as a GFM table. The first row is the header, and the alignment
+  /// of its cells is used for the whole column.
+  fn table_lines(&self, cells: &[&Block]) -> Vec<String> {
+    let position = |cell: &Block, field: &str| {
+      cell
+        .data
+        .get(field)
+        .and_then(Value::as_u64)
+        .map(|position| position as usize)
+    };
+    let mut grid: BTreeMap<(usize, usize), &Block> = BTreeMap::new();
+    for cell in cells {
+      if let (Some(row), Some(col)) = (
+        position(cell, ROW_POSITION_FIELD),
+        position(cell, COL_POSITION_FIELD),
+      ) {
+        grid.insert((row, col), cell);
+      }
+    }
+    let rows_len = grid.keys().map(|(row, _)| row + 1).max().unwrap_or(0);
+    let cols_len = grid.keys().map(|(_, col)| col + 1).max().unwrap_or(0);
+    if rows_len == 0 || cols_len == 0 {
+      return vec![];
+    }
+
+    let mut lines = Vec::with_capacity(rows_len + 1);
+    for row in 0..rows_len {
+      let texts = (0..cols_len)
+        .map(|col| {
+          grid
+            .get(&(row, col))
+            .map(|cell| self.table_cell_text(cell))
+            .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+      lines.push(format!("| {} |", texts.join(" | ")));
+
+      if row == 0 {
+        let delimiters = (0..cols_len)
+          .map(|col| {
+            let align = grid
+              .get(&(0, col))
+              .and_then(|cell| cell.data.get(ALIGN_FIELD))
+              .and_then(Value::as_str);
+            match align {
+              Some(ALIGN_CENTER) => ":---:",
+              Some(ALIGN_RIGHT) => "---:",
+              _ => "---",
+            }
+          })
+          .collect::<Vec<_>>();
+        lines.push(format!("| {} |", delimiters.join(" | ")));
+      }
+    }
+    lines
+  }
+
+  fn table_cell_text(&self, cell: &Block) -> String {
+    self
+      .children(cell)
+      .into_iter()
+      .flat_map(|child| self.text_lines(child))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+}
+
+fn is_list(ty: &str) -> bool {
+  ty == BlockType::BulletedList.as_str()
+    || ty == BlockType::NumberedList.as_str()
+    || ty == BlockType::TodoList.as_str()
+}
+
+/// Renders a delta operation as inline Markdown.
+fn render_op(op: &Value) -> String {
+  let insert = op.get("insert").and_then(Value::as_str).unwrap_or_default();
+  let Some(attributes) = op.get("attributes").and_then(Value::as_object) else {
+    return escape_text(insert);
+  };
+
+  if let Some(formula) = attributes.get(FORMULA_ATTR).and_then(Value::as_str) {
+    return format!("${}$", formula);
+  }
+  if let Some(mention) = attributes.get(MENTION_ATTR).and_then(Value::as_object) {
+    return render_mention(mention, insert);
+  }
+
+  let is_set = |attr: &str| {
+    attributes
+      .get(attr)
+      .and_then(Value::as_bool)
+      .unwrap_or(false)
+  };
+  let mut text = if is_set(CODE_ATTR) {
+    code_span(insert)
+  } else {
+    escape_text(insert)
+  };
+  if is_set(STRIKETHROUGH_ATTR) {
+    text = wrap(text, "~~");
+  }
+  if is_set(ITALIC_ATTR) {
+    text = wrap(text, "*");
+  }
+  if is_set(BOLD_ATTR) {
+    text = wrap(text, "**");
+  }
+  if let Some(href) = attributes.get(HREF_ATTR).and_then(Value::as_str) {
+    text = format!("[{}]({})", text, link_destination(href));
+  }
+  text
+}
+
+fn render_mention(mention: &Map<String, Value>, insert: &str) -> String {
+  let field = |name: &str| mention.get(name).and_then(Value::as_str);
+  match field(MENTION_TYPE_FIELD) {
+    Some("page") | Some("childPage") => match field(MENTION_PAGE_ID_FIELD) {
+      Some(page_id) => format!("[@{}]({})", escape_text(page_id), link_destination(page_id)),
+      None => escape_text(insert),
+    },
+    Some("date") => match field(MENTION_DATE_FIELD) {
+      Some(date) => escape_text(&format!("@{}", date)),
+      None => escape_text(insert),
+    },
+    _ => escape_text(insert),
+  }
+}
+
+/// Wraps the text with the emphasis marker. The leading and trailing whitespaces are kept outside
+/// of the markers, otherwise the emphasis is not recognized.
+fn wrap(text: String, marker: &str) -> String {
+  let start = text.len() - text.trim_start().len();
+  let end = text.trim_end().len();
+  if start >= end {
+    return text;
+  }
+  format!(
+    "{}{}{}{}{}",
+    &text[..start],
+    marker,
+    &text[start..end],
+    marker,
+    &text[end..]
+  )
+}
+
+fn code_span(code: &str) -> String {
+  let fence = "`".repeat(longest_run(code, '`') + 1);
+  if code.starts_with('`') || code.ends_with('`') {
+    format!("{} {} {}", fence, code, fence)
+  } else {
+    format!("{}{}{}", fence, code, fence)
+  }
+}
+
+fn link_destination(url: &str) -> String {
+  if url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+    format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+  } else {
+    url.to_string()
+  }
+}
+
+fn longest_run(text: &str, target: char) -> usize {
+  let mut longest = 0;
+  let mut current = 0;
+  for c in text.chars() {
+    if c == target {
+      current += 1;
+      longest = longest.max(current);
+    } else {
+      current = 0;
+    }
+  }
+  longest
+}
+
+/// Escapes the characters that would otherwise start an inline construct.
+fn escape_text(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    if matches!(
+      c,
+      '\\' | '`' | '*' | '_' | '[' | ']' | '~' | '$' | '<' | '>' | '|' | '&'
+    ) {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+/// Escapes the characters at the start of a line that would otherwise start a block, e.g. a
+/// heading or a list item. The leading whitespace of the line is kept.
+fn escape_line_start(line: &str) -> String {
+  let trimmed = line.trim_start();
+  let indent = &line[..line.len() - trimmed.len()];
+  if trimmed.starts_with(['#', '-', '+', '=']) {
+    return format!("{}\\{}", indent, trimmed);
+  }
+
+  let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+  if digits > 0 && trimmed[digits..].starts_with(['.', ')']) {
+    return format!("{}{}\\{}", indent, &trimmed[..digits], &trimmed[digits..]);
+  }
+  line.to_string()
+}
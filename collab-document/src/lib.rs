@@ -3,5 +3,6 @@ pub mod document;
 pub mod document_awareness;
 pub mod document_data;
 pub mod error;
+pub mod exporter;
 pub mod importer;
 mod utils;
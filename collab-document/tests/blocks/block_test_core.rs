@@ -35,11 +35,7 @@ impl BlockTestCore {
       CollabType::Document,
       Arc::downgrade(&db),
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&db),
-      uid: 1,
-      workspace_id,
-    };
+    let data_source = KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), 1, workspace_id);
     let mut collab = CollabBuilder::new(1, doc_id, data_source.into())
       .with_plugin(disk_plugin)
       .with_device_id("1")
@@ -3,6 +3,7 @@ use collab_document::{
   blocks::{Block, BlockAction, BlockActionPayload, BlockActionType},
   document::DocumentIndexContent,
 };
+use collab_entity::CollabType;
 use nanoid::nanoid;
 
 #[test]
@@ -104,3 +105,11 @@ fn document_index_data_from_document() {
   assert_eq!(index_content.page_id, page_id);
   assert_eq!(index_content.text, "Hello world!");
 }
+
+#[test]
+fn created_document_matches_schema_test() {
+  let test = DocumentTest::new(1, "1");
+  CollabType::Document
+    .validate_schema(&test.document)
+    .unwrap();
+}
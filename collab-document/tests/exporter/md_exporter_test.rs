@@ -0,0 +1,189 @@
+use collab_document::blocks::DocumentData;
+use collab_document::document::{Document, gen_document_id};
+use collab_document::exporter::md_exporter::MDExporter;
+use serde_json::{Value, json};
+
+use crate::importer::util::{get_block_by_type, markdown_to_document_data};
+
+#[test]
+fn export_round_trip_test() {
+  let markdown = r#"# Heading 1
+
+### Heading 3
+
+This is **bold**, *italic*, ~~delete~~, `code`, $E=mc^2$ and [a link](https://appflowy.io).
+
+> A quote with *style*
+
+- bullet 1
+  - nested bullet
+    1. nested number
+- bullet 2
+
+3. three
+4. four
+
+- [ ] todo
+- [x] done
+
+```rust
+fn main() {
+    println!("```");
+}
+```
+
+$$
+x^2 + y^2
+$$
+
+![](https://example.com/image.png)
+
+---
+
+[https://appflowy.io]: https://appflowy.io
+
+| Name | Age | City |
+| :---: | ---: | --- |
+| Lucas | 30 | *Paris* |
+| Nathan |  | Tokyo |
+"#;
+  let document_data = markdown_to_document_data(markdown);
+  let exported = MDExporter::new().export(&document_data);
+  let reimported = markdown_to_document_data(&exported);
+  assert_eq!(document_tree(&document_data), document_tree(&reimported));
+
+  // exporting the reimported document gives the same markdown
+  assert_eq!(exported, MDExporter::new().export(&reimported));
+}
+
+#[test]
+fn export_inline_attributes_test() {
+  let document_data = markdown_to_document_data(
+    "This is **bold**, *italic*, ~~delete~~, `code` and [a link](https://appflowy.io).",
+  );
+  assert_eq!(
+    MDExporter::new().export(&document_data),
+    "This is **bold**, *italic*, ~~delete~~, `code` and [a link](https://appflowy.io).\n"
+  );
+}
+
+#[test]
+fn export_escapes_markdown_characters_test() {
+  let mut document_data = markdown_to_document_data("placeholder");
+  let paragraph = get_block_by_type(&document_data, "paragraph");
+  set_delta(
+    &mut document_data,
+    &paragraph.id,
+    json!([{ "insert": "# not a *heading* [x]" }]),
+  );
+
+  let exported = MDExporter::new().export(&document_data);
+  assert_eq!(exported, "\\# not a \\*heading\\* \\[x\\]\n");
+
+  let reimported = markdown_to_document_data(&exported);
+  assert_eq!(document_tree(&document_data), document_tree(&reimported));
+}
+
+#[test]
+fn export_keeps_indentation_test() {
+  let mut document_data = markdown_to_document_data("placeholder");
+  let paragraph = get_block_by_type(&document_data, "paragraph");
+  set_delta(
+    &mut document_data,
+    &paragraph.id,
+    json!([{ "insert": "first\n  second\n    - third" }]),
+  );
+
+  let exported = MDExporter::new().export(&document_data);
+  assert_eq!(exported, "first\n  second\n    \\- third\n");
+}
+
+#[test]
+fn export_nested_list_test() {
+  let markdown = "1. first\n   - nested\n2. second\n\n- [x] done\n- [ ] todo\n";
+  let document_data = markdown_to_document_data(markdown);
+  assert_eq!(MDExporter::new().export(&document_data), markdown);
+}
+
+#[test]
+fn export_table_test() {
+  let markdown = "| a | b |\n| :---: | ---: |\n| 1 | x \\| y |\n";
+  let document_data = markdown_to_document_data(markdown);
+  assert_eq!(MDExporter::new().export(&document_data), markdown);
+}
+
+#[test]
+fn export_mention_test() {
+  let mut document_data = markdown_to_document_data("placeholder");
+  let paragraph = get_block_by_type(&document_data, "paragraph");
+  set_delta(
+    &mut document_data,
+    &paragraph.id,
+    json!([
+      { "insert": "See " },
+      {
+        "insert": "$",
+        "attributes": { "mention": { "type": "page", "page_id": "page_1" } }
+      },
+      { "insert": " on " },
+      {
+        "insert": "$",
+        "attributes": { "mention": { "type": "date", "date": "2024-04-23" } }
+      }
+    ]),
+  );
+
+  assert_eq!(
+    MDExporter::new().export(&document_data),
+    "See [@page\\_1](page_1) on @2024-04-23\n"
+  );
+}
+
+#[test]
+fn document_to_markdown_test() {
+  let markdown = "## Title\n\n- item\n\n```\ncode\n```\n";
+  let document_data = markdown_to_document_data(markdown);
+  let document = Document::create(&gen_document_id(), document_data).unwrap();
+  assert_eq!(document.to_markdown().unwrap(), markdown);
+}
+
+fn set_delta(document_data: &mut DocumentData, text_id: &str, delta: Value) {
+  document_data
+    .meta
+    .text_map
+    .as_mut()
+    .unwrap()
+    .insert(text_id.to_string(), delta.to_string());
+}
+
+/// Returns the document as a tree of block types, data and deltas, without the block ids.
+fn document_tree(document_data: &DocumentData) -> Value {
+  block_tree(document_data, &document_data.page_id)
+}
+
+fn block_tree(document_data: &DocumentData, block_id: &str) -> Value {
+  let block = document_data.blocks.get(block_id).unwrap();
+  let delta = block
+    .external_id
+    .as_ref()
+    .and_then(|text_id| document_data.meta.text_map.as_ref()?.get(text_id))
+    .map(|delta| serde_json::from_str::<Value>(delta).unwrap())
+    .unwrap_or(Value::Null);
+  let children = document_data
+    .meta
+    .children_map
+    .get(&block.children)
+    .map(|children| {
+      children
+        .iter()
+        .map(|child_id| block_tree(document_data, child_id))
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  json!({
+    "type": block.ty,
+    "data": block.data,
+    "delta": delta,
+    "children": children,
+  })
+}
@@ -0,0 +1 @@
+mod md_exporter_test;
@@ -1,3 +1,3 @@
 mod md_importer_customer_test;
 mod md_importer_test;
-mod util;
+pub(crate) mod util;
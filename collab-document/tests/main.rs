@@ -8,5 +8,8 @@ mod util;
 #[cfg(not(target_arch = "wasm32"))]
 mod conversions;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod exporter;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod importer;
@@ -43,11 +43,8 @@ impl DocumentTest {
       CollabType::Document,
       Arc::downgrade(&db),
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&db),
-      uid,
-      workspace_id: workspace_id.clone(),
-    };
+    let data_source =
+      KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), uid, workspace_id.clone());
     let collab = CollabBuilder::new(uid, doc_id, data_source.into())
       .with_device_id("1")
       .with_plugin(disk_plugin)
@@ -135,11 +132,8 @@ pub fn open_document_with_db(
     CollabType::Document,
     Arc::downgrade(&db),
   );
-  let data_source = KVDBCollabPersistenceImpl {
-    db: Arc::downgrade(&db),
-    uid,
-    workspace_id: workspace_id.to_string(),
-  };
+  let data_source =
+    KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), uid, workspace_id.to_string());
   let mut collab = CollabBuilder::new(uid, doc_id, data_source.into())
     .with_device_id("1")
     .with_plugin(disk_plugin)
@@ -3,11 +3,16 @@ use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
 use crate::define::{
-  DATABASE, DATABASE_ID, DATABASE_INLINE_VIEW, DATABASE_METAS, DATABASE_ROW_DATA, DATABASE_ROW_ID,
-  DOCUMENT_ROOT, FOLDER, FOLDER_META, FOLDER_WORKSPACE_ID, USER_AWARENESS, WORKSPACE_DATABASES,
+  DATABASE, DATABASE_FIELDS, DATABASE_ID, DATABASE_INLINE_VIEW, DATABASE_METAS, DATABASE_ROW_CELLS,
+  DATABASE_ROW_DATA, DATABASE_ROW_ID, DATABASE_VIEWS, DOCUMENT_BLOCKS, DOCUMENT_CHILDREN_MAP,
+  DOCUMENT_META, DOCUMENT_ROOT, DOCUMENT_TEXT_MAP, FOLDER, FOLDER_META, FOLDER_RELATION,
+  FOLDER_VIEWS, FOLDER_WORKSPACE_ID, USER_AWARENESS, USER_AWARENESS_APPEARANCE_SETTINGS,
+  USER_AWARENESS_REMINDERS, WORKSPACE_DATABASE_ID, WORKSPACE_DATABASES,
 };
 use crate::proto;
-use collab::preclude::{ArrayRef, Collab, MapExt, MapRef};
+use collab::core::schema::{Schema, SchemaViolation};
+use collab::preclude::{Any, ArrayRef, Collab, MapExt, MapRef};
+use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 /// The type of the collab object. It will be used to determine what kind of services should be
@@ -34,6 +39,9 @@ pub enum CollabType {
 pub enum CollabValidateError {
   #[error("No required data: {0}")]
   NoRequiredData(String),
+
+  #[error("Invalid data: {}", format_violations(.0))]
+  InvalidData(Vec<SchemaViolation>),
 }
 
 impl CollabType {
@@ -131,6 +139,105 @@ impl CollabType {
       CollabType::Unknown => Ok(()),
     }
   }
+
+  /// Returns the expected shape of the data of this type, or [None] for [CollabType::Unknown].
+  ///
+  /// Unlike [CollabType::validate_require_data], the schema checks the kind of every known value,
+  /// so it's stricter and slower.
+  pub fn schema(&self) -> Option<Schema> {
+    match self {
+      CollabType::Document => Some(
+        Schema::map().required(
+          DOCUMENT_ROOT,
+          Schema::map()
+            .required(
+              DOCUMENT_BLOCKS,
+              Schema::map().values(
+                Schema::map()
+                  .required("id", Schema::string())
+                  .required("ty", Schema::string()),
+              ),
+            )
+            .required(
+              DOCUMENT_META,
+              Schema::map()
+                .required(
+                  DOCUMENT_CHILDREN_MAP,
+                  Schema::map().values(Schema::array().items(Schema::string())),
+                )
+                .required(DOCUMENT_TEXT_MAP, Schema::map().values(Schema::text())),
+            ),
+        ),
+      ),
+      CollabType::Database => Some(
+        Schema::map().required(
+          DATABASE,
+          Schema::map()
+            .required(DATABASE_ID, Schema::string())
+            .required(
+              DATABASE_METAS,
+              Schema::map().required(DATABASE_INLINE_VIEW, Schema::string()),
+            )
+            .optional(DATABASE_FIELDS, Schema::map().values(Schema::map()))
+            .optional(DATABASE_VIEWS, Schema::map().values(Schema::map())),
+        ),
+      ),
+      CollabType::WorkspaceDatabase => Some(Schema::map().required(
+        WORKSPACE_DATABASES,
+        Schema::array().items(Schema::map().required(WORKSPACE_DATABASE_ID, Schema::string())),
+      )),
+      CollabType::Folder => Some(
+        Schema::map().required(
+          FOLDER,
+          Schema::map()
+            .required(
+              FOLDER_META,
+              Schema::map().required(
+                FOLDER_WORKSPACE_ID,
+                Schema::string().check(
+                  "must not be empty",
+                  |value| !matches!(value, Any::String(id) if id.is_empty()),
+                ),
+              ),
+            )
+            .optional(FOLDER_VIEWS, Schema::map().values(Schema::map()))
+            .optional(FOLDER_RELATION, Schema::map().values(Schema::array())),
+        ),
+      ),
+      CollabType::DatabaseRow => Some(
+        Schema::map().required(
+          DATABASE_ROW_DATA,
+          Schema::map()
+            .required(DATABASE_ROW_ID, Schema::string())
+            .optional(DATABASE_ROW_CELLS, Schema::map().values(Schema::map())),
+        ),
+      ),
+      CollabType::UserAwareness => Some(
+        Schema::map().required(
+          USER_AWARENESS,
+          Schema::map()
+            .optional(USER_AWARENESS_APPEARANCE_SETTINGS, Schema::map())
+            .optional(USER_AWARENESS_REMINDERS, Schema::array()),
+        ),
+      ),
+      CollabType::Unknown => None,
+    }
+  }
+
+  /// Validates the collab against the [CollabType::schema] of this type, and reports every
+  /// violation.
+  pub fn validate_schema(&self, collab: &Collab) -> Result<(), CollabValidateError> {
+    let Some(schema) = self.schema() else {
+      return Ok(());
+    };
+    let violations = schema.validate(collab);
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(CollabValidateError::InvalidData(violations))
+    }
+  }
+
   pub fn from_proto(proto: &proto::collab::CollabType) -> Self {
     match proto {
       proto::collab::CollabType::Unknown => CollabType::Unknown,
@@ -178,6 +285,14 @@ pub fn validate_data_for_folder(collab: &Collab, workspace_id: &str) -> Result<(
   Ok(())
 }
 
+fn format_violations(violations: &[SchemaViolation]) -> String {
+  violations
+    .iter()
+    .map(|violation| violation.to_string())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
 #[inline]
 fn no_required_data_error(collab_type: &CollabType, reason: &str) -> CollabValidateError {
   CollabValidateError::NoRequiredData(format!("{}:{}", collab_type, reason))
@@ -240,7 +355,7 @@ macro_rules! impl_from_collab_type_for_integer {
 impl_from_integer_for_collab_type!(i32, u8);
 impl_from_collab_type_for_integer!(i32, u8);
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CollabObject {
   pub object_id: String,
   pub uid: i64,
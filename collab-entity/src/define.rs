@@ -1,19 +1,31 @@
 // Document
 pub const DOCUMENT_ROOT: &str = "document";
+pub const DOCUMENT_BLOCKS: &str = "blocks";
+pub const DOCUMENT_META: &str = "meta";
+pub const DOCUMENT_CHILDREN_MAP: &str = "children_map";
+pub const DOCUMENT_TEXT_MAP: &str = "text_map";
 
 // Folder
 pub const FOLDER: &str = "folder";
 pub const FOLDER_META: &str = "meta";
 pub const FOLDER_WORKSPACE_ID: &str = "current_workspace";
+pub const FOLDER_VIEWS: &str = "views";
+pub const FOLDER_RELATION: &str = "relation";
 
 // Database
 pub const WORKSPACE_DATABASES: &str = "databases";
 pub const DATABASE: &str = "database";
 pub const DATABASE_ID: &str = "id";
 pub const DATABASE_METAS: &str = "metas";
+pub const DATABASE_FIELDS: &str = "fields";
+pub const DATABASE_VIEWS: &str = "views";
 pub const DATABASE_INLINE_VIEW: &str = "iid";
 pub const DATABASE_ROW_DATA: &str = "data";
 pub const DATABASE_ROW_ID: &str = "id";
+pub const DATABASE_ROW_CELLS: &str = "cells";
+pub const WORKSPACE_DATABASE_ID: &str = "database_id";
 
 // User Awareness
 pub const USER_AWARENESS: &str = "user_awareness";
+pub const USER_AWARENESS_REMINDERS: &str = "reminders";
+pub const USER_AWARENESS_APPEARANCE_SETTINGS: &str = "appearance_settings";
@@ -16,6 +16,7 @@ impl From<CollabValidateError> for FolderError {
   fn from(error: CollabValidateError) -> Self {
     match error {
       CollabValidateError::NoRequiredData(data) => FolderError::NoRequiredData(data),
+      err @ CollabValidateError::InvalidData(_) => FolderError::NoRequiredData(err.to_string()),
     }
   }
 }
@@ -97,11 +97,8 @@ pub fn open_folder_with_db(
     CollabType::Folder,
     Arc::downgrade(&db),
   ));
-  let data_source = KVDBCollabPersistenceImpl {
-    db: Arc::downgrade(&db),
-    uid: uid.as_i64(),
-    workspace_id: workspace_id.to_string(),
-  };
+  let data_source =
+    KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), uid.as_i64(), workspace_id.to_string());
   let cleaner: Cleaner = Cleaner::new(db_path);
   let mut collab = CollabBuilder::new(1, object_id, data_source.into())
     .with_device_id("1")
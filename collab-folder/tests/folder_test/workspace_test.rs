@@ -1,5 +1,7 @@
+use crate::util::create_folder;
 use collab::core::origin::CollabOrigin;
 use collab::preclude::Collab;
+use collab_entity::CollabType;
 use collab_folder::{Folder, FolderData, UserId, Workspace, check_folder_is_valid};
 
 #[test]
@@ -26,3 +28,11 @@ fn validate_folder_data() {
   let result = Folder::open(1, collab, None);
   assert!(result.is_err());
 }
+
+#[test]
+fn created_folder_matches_schema_test() {
+  let folder_test = create_folder(1.into(), "w1");
+  CollabType::Folder
+    .validate_schema(&folder_test.folder.collab)
+    .unwrap();
+}
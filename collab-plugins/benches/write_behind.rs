@@ -0,0 +1,77 @@
+//! Compares the durability modes of the RocksdbDiskPlugin by inserting many small values into a
+//! collab. Run it with `cargo bench -p collab-plugins --bench write_behind`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use collab::preclude::CollabBuilder;
+use collab_entity::CollabType;
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::kv::KVTransactionDB;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin;
+use collab_plugins::local_storage::rocksdb::util::KVDBCollabPersistenceImpl;
+use collab_plugins::local_storage::{CollabPersistenceConfig, DurabilityMode};
+use tempfile::TempDir;
+
+const UID: i64 = 1;
+const WORKSPACE_ID: &str = "w1";
+const OBJECT_ID: &str = "1";
+const NUMBER_OF_UPDATES: usize = 1000;
+
+fn main() {
+  let modes = [
+    ("per update", DurabilityMode::PerUpdate),
+    ("write behind", DurabilityMode::write_behind()),
+    (
+      "write behind (500 updates)",
+      DurabilityMode::WriteBehind {
+        max_updates: 500,
+        max_delay: Duration::from_secs(1),
+      },
+    ),
+  ];
+  for (name, durability) in modes {
+    let elapsed = insert_values(durability);
+    println!(
+      "{name:<28} {NUMBER_OF_UPDATES} updates in {elapsed:?} ({:?}/update)",
+      elapsed / NUMBER_OF_UPDATES as u32
+    );
+  }
+}
+
+/// Returns the time it takes to insert the values and commit all of them to the disk.
+fn insert_values(durability: DurabilityMode) -> Duration {
+  let tempdir = TempDir::new().unwrap();
+  let db = Arc::new(CollabKVDB::open(tempdir.path()).unwrap());
+  let config = CollabPersistenceConfig::new().durability(durability);
+  let plugin = RocksdbDiskPlugin::new_with_config(
+    UID,
+    WORKSPACE_ID.to_string(),
+    OBJECT_ID.to_string(),
+    CollabType::Unknown,
+    Arc::downgrade(&db),
+    config,
+  );
+  let data_source =
+    KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), UID, WORKSPACE_ID.to_string());
+  let mut collab = CollabBuilder::new(UID, OBJECT_ID, data_source.into())
+    .with_device_id("1")
+    .with_plugin(plugin.clone())
+    .build()
+    .unwrap();
+  collab.initialize();
+
+  let start = Instant::now();
+  for i in 0..NUMBER_OF_UPDATES {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  plugin.flush().unwrap();
+  let elapsed = start.elapsed();
+
+  let number_of_updates = db
+    .read_txn()
+    .number_of_updates(UID, WORKSPACE_ID, OBJECT_ID);
+  assert_eq!(number_of_updates, NUMBER_OF_UPDATES);
+  elapsed
+}
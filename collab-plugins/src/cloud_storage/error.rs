@@ -3,6 +3,9 @@ pub enum SyncError {
   #[error("failed to deserialize message: {0}")]
   DecodingError(#[from] yrs::encoding::read::Error),
 
+  #[error("failed to apply update: {0}")]
+  UpdateError(#[from] yrs::error::UpdateError),
+
   #[error(transparent)]
   SerdeError(#[from] serde_json::Error),
 
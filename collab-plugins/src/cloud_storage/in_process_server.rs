@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use collab::core::collab::DataSource;
+use collab_entity::CollabObject;
+use collab_entity::proto::collab::{
+  ClientOrigin, CollabOrigin as ProtoCollabOrigin, InitSync, ServerInit, ServerOrigin, UpdateSync,
+  collab_origin,
+};
+use tokio::sync::mpsc::unbounded_channel;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, ReadTxn, StateVector, Transact, Update};
+
+use crate::cloud_storage::error::SyncError;
+use crate::cloud_storage::msg::MsgId;
+use crate::cloud_storage::remote_collab::{
+  RemoteCollabSnapshot, RemoteCollabState, RemoteCollabStorage, RemoteUpdateReceiver,
+  RemoteUpdateSender,
+};
+
+/// A reference implementation of the server side of the sync protocol that the [RemoteCollab]
+/// speaks. The collabs are kept in memory, which allows testing the protocol end to end without
+/// a network.
+///
+/// [RemoteCollab]: crate::cloud_storage::RemoteCollab
+#[derive(Default)]
+pub struct InProcessCollabServer {
+  collabs: Mutex<HashMap<String, ServerCollab>>,
+}
+
+#[derive(Default)]
+struct ServerCollab {
+  doc: Doc,
+  subscribers: Vec<RemoteUpdateSender>,
+}
+
+impl InProcessCollabServer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Answers the [InitSync] of a client with the updates the client is missing, according to the
+  /// state vector it sent, and the state vector of the server.
+  pub fn handle_init_sync(&self, init_sync: InitSync) -> Result<ServerInit, SyncError> {
+    let state_vector = StateVector::decode_v1(&init_sync.payload)?;
+    let mut collabs = self.collabs.lock().unwrap();
+    let collab = collabs.entry(init_sync.object_id.clone()).or_default();
+    let txn = collab.doc.transact();
+    Ok(ServerInit {
+      origin: Some(server_origin()),
+      object_id: init_sync.object_id,
+      msg_id: init_sync.msg_id,
+      payload: txn.encode_state_as_update_v1(&state_vector),
+      state_vector: txn.state_vector().encode_v1(),
+    })
+  }
+
+  /// Applies the updates of a client, and broadcasts them to the subscribers of the collab.
+  pub fn handle_update_sync(&self, update_sync: UpdateSync) -> Result<(), SyncError> {
+    let update = Update::decode_v1(&update_sync.payload)?;
+    let mut collabs = self.collabs.lock().unwrap();
+    let collab = collabs.entry(update_sync.object_id).or_default();
+    collab.doc.transact_mut().apply_update(update)?;
+    collab
+      .subscribers
+      .retain(|subscriber| subscriber.send(update_sync.payload.clone()).is_ok());
+    Ok(())
+  }
+
+  /// Returns the doc state of the collab, or [None] if no client synced it yet.
+  pub fn doc_state(&self, object_id: &str) -> Option<Vec<u8>> {
+    let collabs = self.collabs.lock().unwrap();
+    let collab = collabs.get(object_id)?;
+    let txn = collab.doc.transact();
+    Some(txn.encode_state_as_update_v1(&StateVector::default()))
+  }
+
+  fn handle_client_update(
+    &self,
+    object: &CollabObject,
+    msg_id: MsgId,
+    payload: Vec<u8>,
+  ) -> Result<(), anyhow::Error> {
+    self.handle_update_sync(UpdateSync {
+      origin: Some(ProtoCollabOrigin {
+        origin: Some(collab_origin::Origin::Client(ClientOrigin {
+          uid: object.uid,
+          device_id: object.device_id.clone(),
+        })),
+      }),
+      object_id: object.object_id.clone(),
+      msg_id,
+      payload,
+    })?;
+    Ok(())
+  }
+}
+
+fn server_origin() -> ProtoCollabOrigin {
+  ProtoCollabOrigin {
+    origin: Some(collab_origin::Origin::Server(ServerOrigin {})),
+  }
+}
+
+#[async_trait]
+impl RemoteCollabStorage for InProcessCollabServer {
+  fn is_enable(&self) -> bool {
+    true
+  }
+
+  async fn get_doc_state(&self, object: &CollabObject) -> Result<DataSource, anyhow::Error> {
+    Ok(DataSource::DocStateV1(
+      self.doc_state(&object.object_id).unwrap_or_default(),
+    ))
+  }
+
+  async fn get_snapshots(&self, _object_id: &str, _limit: usize) -> Vec<RemoteCollabSnapshot> {
+    vec![]
+  }
+
+  async fn get_collab_state(
+    &self,
+    _object_id: &str,
+  ) -> Result<Option<RemoteCollabState>, anyhow::Error> {
+    Ok(None)
+  }
+
+  async fn create_snapshot(
+    &self,
+    _object: &CollabObject,
+    _snapshot: Vec<u8>,
+  ) -> Result<i64, anyhow::Error> {
+    Err(anyhow!("The in-process server doesn't support snapshots"))
+  }
+
+  async fn init_sync(
+    &self,
+    _object: &CollabObject,
+    init_sync: InitSync,
+  ) -> Result<Option<ServerInit>, anyhow::Error> {
+    Ok(Some(self.handle_init_sync(init_sync)?))
+  }
+
+  async fn send_update(
+    &self,
+    object: &CollabObject,
+    id: MsgId,
+    update: Vec<u8>,
+  ) -> Result<(), anyhow::Error> {
+    self.handle_client_update(object, id, update)
+  }
+
+  async fn send_init_sync(
+    &self,
+    object: &CollabObject,
+    id: MsgId,
+    init_update: Vec<u8>,
+  ) -> Result<(), anyhow::Error> {
+    self.handle_client_update(object, id, init_update)
+  }
+
+  fn subscribe_remote_updates(&self, object: &CollabObject) -> Option<RemoteUpdateReceiver> {
+    let (tx, rx) = unbounded_channel();
+    let mut collabs = self.collabs.lock().unwrap();
+    collabs
+      .entry(object.object_id.clone())
+      .or_default()
+      .subscribers
+      .push(tx);
+    Some(rx)
+  }
+}
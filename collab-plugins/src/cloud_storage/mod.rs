@@ -1,7 +1,12 @@
+pub use error::SyncError;
+pub use in_process_server::InProcessCollabServer;
+pub use outbox::{CollabKVDBOutbox, SinkOutbox};
 pub use remote_collab::{
-  RemoteCollabSnapshot, RemoteCollabState, RemoteCollabStorage, RemoteUpdateReceiver,
+  RemoteCollab, RemoteCollabSnapshot, RemoteCollabState, RemoteCollabStorage, RemoteUpdateReceiver,
   RemoteUpdateSender,
 };
+pub use retry::{DeadLetterHandler, ExponentialBackoff, FixedDelay, RetryPolicy};
+pub use sink::{SinkConfig, SinkMetrics, SinkStrategy};
 pub use yrs::Update as YrsUpdate;
 pub use yrs::merge_updates_v1;
 pub use yrs::updates::decoder::Decode;
@@ -10,6 +15,9 @@ pub mod postgres;
 
 mod channel;
 mod error;
+mod in_process_server;
 mod msg;
+mod outbox;
 mod remote_collab;
+mod retry;
 mod sink;
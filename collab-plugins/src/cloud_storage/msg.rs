@@ -4,6 +4,7 @@ use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
 
 use tokio::sync::oneshot;
+use tokio::time::Instant;
 
 pub type MsgId = u64;
 
@@ -39,6 +40,15 @@ where
   pub(crate) fn push_msg(&mut self, msg_id: MsgId, msg: Msg) {
     self.queue.push(PendingMessage::new(msg, msg_id));
   }
+
+  /// Makes the messages that are waiting for their next attempt ready to be sent.
+  pub(crate) fn clear_retry_at(&mut self) {
+    let mut msgs = std::mem::take(&mut self.queue).into_vec();
+    for msg in msgs.iter_mut() {
+      msg.retry_at = None;
+    }
+    self.queue = BinaryHeap::from(msgs);
+  }
 }
 
 impl<Msg> Deref for PendingMsgQueue<Msg>
@@ -67,6 +77,10 @@ pub(crate) struct PendingMessage<Msg> {
   msg_id: MsgId,
   state: MessageState,
   tx: Option<oneshot::Sender<MsgId>>,
+  /// The number of attempts that were not acknowledged by the remote.
+  attempts: u32,
+  /// The message is not sent again before this instant.
+  retry_at: Option<Instant>,
 }
 
 impl<Msg> PendingMessage<Msg>
@@ -79,6 +93,8 @@ where
       msg_id,
       state: MessageState::Pending,
       tx: None,
+      attempts: 0,
+      retry_at: None,
     }
   }
 
@@ -104,6 +120,31 @@ where
   pub fn msg_id(&self) -> MsgId {
     self.msg_id
   }
+
+  pub fn attempts(&self) -> u32 {
+    self.attempts
+  }
+
+  /// Increases the number of failed attempts and returns it.
+  pub fn increase_attempts(&mut self) -> u32 {
+    self.attempts += 1;
+    self.attempts
+  }
+
+  pub fn set_retry_at(&mut self, retry_at: Instant) {
+    self.retry_at = Some(retry_at);
+  }
+
+  /// Returns true if the message is waiting for its next attempt.
+  pub fn is_waiting_for_retry(&self) -> bool {
+    self
+      .retry_at
+      .is_some_and(|retry_at| retry_at > Instant::now())
+  }
+
+  pub fn into_msg(self) -> Msg {
+    self.msg
+  }
 }
 
 impl<Msg> PendingMessage<Msg>
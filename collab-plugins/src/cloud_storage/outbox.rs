@@ -0,0 +1,133 @@
+use std::marker::PhantomData;
+use std::sync::{Arc, Weak};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::CollabKVDB;
+use crate::cloud_storage::msg::MsgId;
+use crate::local_storage::kv::encryption::{EncryptionKeyProvider, with_encryption};
+use crate::local_storage::kv::outbox::OutboxAction;
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
+
+/// Persists the messages queued in the [CollabSink] until the remote acknowledges them, so the
+/// messages that were not sent before the application exits are sent when the sink starts again.
+///
+/// [CollabSink]: crate::cloud_storage::sink::CollabSink
+pub trait SinkOutbox<Msg>: Send + Sync + 'static {
+  fn push_msg(&self, msg_id: MsgId, msg: &Msg) -> Result<(), PersistenceError>;
+
+  /// Replaces the message with the given id by the merged message, and removes the messages that
+  /// were merged into it.
+  fn merge_msgs(
+    &self,
+    msg_id: MsgId,
+    merged_msg: &Msg,
+    merged_msg_ids: &[MsgId],
+  ) -> Result<(), PersistenceError>;
+
+  fn remove_msg(&self, msg_id: MsgId) -> Result<(), PersistenceError>;
+
+  fn remove_all_msgs(&self) -> Result<(), PersistenceError>;
+
+  /// Returns the messages that were not acknowledged, ordered by their id.
+  fn load_msgs(&self) -> Result<Vec<(MsgId, Msg)>, PersistenceError>;
+}
+
+/// A [SinkOutbox] that stores the messages of a collab object in the [CollabKVDB]. The messages
+/// are encrypted with the keys of the workspace if an [EncryptionKeyProvider] is given.
+pub struct CollabKVDBOutbox<Msg> {
+  uid: i64,
+  workspace_id: String,
+  object_id: String,
+  collab_db: Weak<CollabKVDB>,
+  encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  phantom: PhantomData<fn() -> Msg>,
+}
+
+impl<Msg> CollabKVDBOutbox<Msg> {
+  pub fn new(
+    uid: i64,
+    workspace_id: String,
+    object_id: String,
+    collab_db: Weak<CollabKVDB>,
+  ) -> Self {
+    Self {
+      uid,
+      workspace_id,
+      object_id,
+      collab_db,
+      encryption: None,
+      phantom: PhantomData,
+    }
+  }
+
+  pub fn with_encryption(mut self, encryption: Option<Arc<dyn EncryptionKeyProvider>>) -> Self {
+    self.encryption = encryption;
+    self
+  }
+
+  fn collab_db(&self) -> Result<Arc<CollabKVDB>, PersistenceError> {
+    self
+      .collab_db
+      .upgrade()
+      .ok_or_else(|| PersistenceError::Internal(anyhow!("collab_db is dropped")))
+  }
+}
+
+impl<Msg> SinkOutbox<Msg> for CollabKVDBOutbox<Msg>
+where
+  Msg: Serialize + DeserializeOwned + 'static,
+{
+  fn push_msg(&self, msg_id: MsgId, msg: &Msg) -> Result<(), PersistenceError> {
+    let data = bincode::serialize(msg)?;
+    self.collab_db()?.with_write_txn(|w_db_txn| {
+      with_encryption!(w_db_txn, &self.workspace_id, &self.encryption, store => {
+        store.insert_outbox_msg(self.uid, &self.object_id, msg_id, &data)
+      })
+    })
+  }
+
+  fn merge_msgs(
+    &self,
+    msg_id: MsgId,
+    merged_msg: &Msg,
+    merged_msg_ids: &[MsgId],
+  ) -> Result<(), PersistenceError> {
+    let data = bincode::serialize(merged_msg)?;
+    self.collab_db()?.with_write_txn(|w_db_txn| {
+      with_encryption!(w_db_txn, &self.workspace_id, &self.encryption, store => {
+        store.insert_outbox_msg(self.uid, &self.object_id, msg_id, &data)?
+      });
+      for merged_msg_id in merged_msg_ids {
+        w_db_txn.remove_outbox_msg(self.uid, &self.object_id, *merged_msg_id)?;
+      }
+      Ok(())
+    })
+  }
+
+  fn remove_msg(&self, msg_id: MsgId) -> Result<(), PersistenceError> {
+    self
+      .collab_db()?
+      .with_write_txn(|w_db_txn| w_db_txn.remove_outbox_msg(self.uid, &self.object_id, msg_id))
+  }
+
+  fn remove_all_msgs(&self) -> Result<(), PersistenceError> {
+    self
+      .collab_db()?
+      .with_write_txn(|w_db_txn| w_db_txn.clear_outbox(self.uid, &self.object_id))
+  }
+
+  fn load_msgs(&self) -> Result<Vec<(MsgId, Msg)>, PersistenceError> {
+    let collab_db = self.collab_db()?;
+    let read_txn = collab_db.read_txn();
+    let msgs = with_encryption!(&read_txn, &self.workspace_id, &self.encryption, store => {
+      store.get_outbox_msgs(self.uid, &self.object_id)?
+    });
+    msgs
+      .into_iter()
+      .map(|(msg_id, data)| Ok((msg_id, bincode::deserialize(&data)?)))
+      .collect()
+  }
+}
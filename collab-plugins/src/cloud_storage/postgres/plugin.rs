@@ -18,6 +18,7 @@ use collab_entity::CollabObject;
 use crate::CollabKVDB;
 use crate::cloud_storage::remote_collab::{RemoteCollab, RemoteCollabStorage};
 use crate::cloud_storage::sink::{SinkConfig, SinkStrategy};
+use crate::local_storage::kv::encryption::EncryptionKeyProvider;
 
 pub struct SupabaseDBPlugin {
   uid: i64,
@@ -38,6 +39,7 @@ impl SupabaseDBPlugin {
     sync_per_secs: u64,
     remote_collab_storage: Arc<dyn RemoteCollabStorage>,
     local_collab_storage: Weak<CollabKVDB>,
+    encryption: Option<Arc<dyn EncryptionKeyProvider>>,
   ) -> Self {
     let pending_updates = Arc::new(RwLock::from(Vec::new()));
     let is_first_sync_done = Arc::new(AtomicBool::new(false));
@@ -47,11 +49,13 @@ impl SupabaseDBPlugin {
       .with_strategy(SinkStrategy::FixInterval(Duration::from_secs(
         sync_per_secs,
       )));
-    let remote_collab = Arc::new(RemoteCollab::new(
+    let remote_collab = Arc::new(RemoteCollab::new_with_outbox(
       object.clone(),
       remote_collab_storage.clone(),
       config,
       local_collab.clone(),
+      local_collab_storage.clone(),
+      encryption,
     ));
 
     // Subscribe the sync state from the remote collab
@@ -141,6 +145,7 @@ impl Action for InitSyncAction {
     let weak_remote_collab = self.remote_collab.clone();
     let weak_pending_updates = self.pending_updates.clone();
     let weak_is_first_sync_done = self.is_first_sync_done.clone();
+    let local_collab = self.local_collab.clone();
 
     Box::pin(async move {
       if let (Some(remote_collab), Some(pending_updates), Some(is_first_sync_done)) = (
@@ -148,6 +153,7 @@ impl Action for InitSyncAction {
         weak_pending_updates.upgrade(),
         weak_is_first_sync_done.upgrade(),
       ) {
+        remote_collab.sync(local_collab).await?;
         for update in &*pending_updates.read().await {
           remote_collab.push_update(update)?;
         }
@@ -12,8 +12,11 @@ use collab::core::origin::CollabOrigin;
 use collab::lock::RwLock;
 use collab::preclude::Collab;
 use collab_entity::CollabObject;
+use collab_entity::proto::collab::{
+  ClientOrigin, CollabOrigin as ProtoCollabOrigin, InitSync, ServerInit, collab_origin,
+};
 use rand::random;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::spawn;
 use tokio::sync::mpsc::unbounded_channel;
 use tokio::sync::watch;
@@ -21,13 +24,19 @@ use tokio_stream::StreamExt;
 use tokio_stream::wrappers::WatchStream;
 use tracing::trace;
 use yrs::updates::decoder::Decode;
-use yrs::{ReadTxn, Transact, Update, merge_updates_v1};
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Transact, Update, merge_updates_v1};
 
+use crate::CollabKVDB;
 use crate::cloud_storage::channel::TokioUnboundedSink;
 use crate::cloud_storage::msg::{CollabSinkMessage, MsgId};
+use crate::cloud_storage::outbox::{CollabKVDBOutbox, SinkOutbox};
+use crate::cloud_storage::retry::DeadLetterHandler;
 use crate::cloud_storage::sink::{
-  CollabSink, CollabSinkRunner, MsgIdCounter, SinkConfig, SinkState,
+  CollabSink, CollabSinkRunner, MsgIdCounter, SinkConfig, SinkMetrics, SinkState,
 };
+use crate::connect_state::CollabConnectReachability;
+use crate::local_storage::kv::encryption::EncryptionKeyProvider;
 
 /// The [RemoteCollab] is used to sync the local collab to the remote.
 pub struct RemoteCollab {
@@ -57,6 +66,39 @@ impl RemoteCollab {
     storage: Arc<dyn RemoteCollabStorage>,
     config: SinkConfig,
     local_collab: Weak<RwLock<Collab>>,
+  ) -> Self {
+    Self::new_with_sink_outbox(object, storage, config, local_collab, None)
+  }
+
+  /// Same as [RemoteCollab::new], but the messages that are not acknowledged by the remote are
+  /// kept in the given [CollabKVDB], encrypted with the keys of the workspace if an
+  /// [EncryptionKeyProvider] is given. They are sent again the next time a [RemoteCollab] is
+  /// created for the same object.
+  pub fn new_with_outbox(
+    object: CollabObject,
+    storage: Arc<dyn RemoteCollabStorage>,
+    config: SinkConfig,
+    local_collab: Weak<RwLock<Collab>>,
+    collab_db: Weak<CollabKVDB>,
+    encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  ) -> Self {
+    let outbox = CollabKVDBOutbox::new(
+      object.uid,
+      object.workspace_id.clone(),
+      object.object_id.clone(),
+      collab_db,
+    )
+    .with_encryption(encryption);
+    let outbox: Arc<dyn SinkOutbox<Message>> = Arc::new(outbox);
+    Self::new_with_sink_outbox(object, storage, config, local_collab, Some(outbox))
+  }
+
+  fn new_with_sink_outbox(
+    object: CollabObject,
+    storage: Arc<dyn RemoteCollabStorage>,
+    config: SinkConfig,
+    local_collab: Weak<RwLock<Collab>>,
+    outbox: Option<Arc<dyn SinkOutbox<Message>>>,
   ) -> Self {
     let is_init_sync_finish = Arc::new(AtomicBool::new(false));
     let sync_state = Arc::new(watch::channel(SyncState::InitSyncBegin).0);
@@ -70,14 +112,21 @@ impl RemoteCollab {
     let weak_storage = Arc::downgrade(&storage);
     let (notifier, notifier_rx) = watch::channel(false);
     let (sync_state_tx, sink_state_rx) = watch::channel(SinkState::Init);
-    let collab_sink = Arc::new(CollabSink::new(
+    let dead_letter_handler = config.dead_letter_handler.clone();
+    let mut collab_sink = CollabSink::new(
       object.uid,
       TokioUnboundedSink(sink),
       notifier,
       sync_state_tx,
       RngMsgIdCounter::new(),
       config,
-    ));
+      outbox,
+    );
+    if let Some(handler) = dead_letter_handler {
+      collab_sink =
+        collab_sink.with_dead_letter_handler(Arc::new(UpdateDeadLetterHandler(handler)));
+    }
+    let collab_sink = Arc::new(collab_sink);
 
     // spawns an asynchronous task to continuously listen to the updates stream
     // and process them as they come in.
@@ -204,17 +253,124 @@ impl RemoteCollab {
     self.sync_state.subscribe()
   }
 
-  /// Return the update of the remote collab.
-  /// If the remote collab contains any updates, it will return None.
-  /// Otherwise, it will merge the updates into one and return the merged update.
-  #[allow(dead_code)]
+  /// Pauses sending the updates while the remote is disconnected, and sends them as soon as it's
+  /// connected again.
+  pub fn subscribe_reachability(&self, reachability: &CollabConnectReachability) {
+    self.sink.subscribe_reachability(reachability);
+  }
+
+  /// Returns the [SinkMetrics] of the object.
+  pub async fn sink_metrics(&self) -> SinkMetrics {
+    self
+      .sink
+      .metrics()
+      .await
+      .remove(&self.object.object_id)
+      .unwrap_or_default()
+  }
+
+  /// Sync the local collab with the remote collab, and return the update that was received from
+  /// the remote.
+  ///
+  /// The local collab sends its state vector with an [InitSync]. The remote answers with a
+  /// [ServerInit] that contains the updates the local collab is missing and the state vector of
+  /// the remote, which the local collab uses to send back the updates the remote is missing. If
+  /// the [RemoteCollabStorage] doesn't support it, the whole remote doc state is downloaded
+  /// instead.
   pub async fn sync(&self, local_collab: Weak<RwLock<Collab>>) -> Result<Vec<u8>, Error> {
-    let mut remote_update = vec![];
-    // It would be better if creating a edge function that calculate the diff between the local and remote.
-    // The local only need to send its state vector to the remote. In this way, the local does not need to
-    // get all the updates from remote.
-    // TODO(nathan): create a edge function to calculate the diff between the local and remote.
     tracing::trace!("Try init sync:{}", self.object);
+    let local_collab = local_collab
+      .upgrade()
+      .ok_or(anyhow!("local collab is dropped"))?;
+    let local_state_vector = local_collab.read().await.transact().state_vector();
+    let init_sync = InitSync {
+      origin: Some(ProtoCollabOrigin {
+        origin: Some(collab_origin::Origin::Client(ClientOrigin {
+          uid: self.object.uid,
+          device_id: self.object.device_id.clone(),
+        })),
+      }),
+      object_id: self.object.object_id.clone(),
+      collab_type: self.object.collab_type.to_proto() as i32,
+      workspace_id: self.object.workspace_id.clone(),
+      msg_id: self.sink.next_msg_id(),
+      payload: local_state_vector.encode_v1(),
+    };
+
+    match self.storage.init_sync(&self.object, init_sync).await? {
+      Some(server_init) => self.sync_with_server_init(&local_collab, server_init).await,
+      None => self.sync_with_doc_state(&local_collab).await,
+    }
+  }
+
+  async fn sync_with_server_init(
+    &self,
+    local_collab: &Arc<RwLock<Collab>>,
+    server_init: ServerInit,
+  ) -> Result<Vec<u8>, Error> {
+    let _ = self.sync_state.send(SyncState::InitSyncBegin);
+    let remote_state_vector = StateVector::decode_v1(&server_init.state_vector)?;
+    let mut local_lock = local_collab.write().await;
+    tracing::trace!(
+      "{}: apply remote update with diff len:{}",
+      self.object,
+      server_init.payload.len()
+    );
+    if !server_init.payload.is_empty() {
+      self
+        .collab
+        .write()
+        .await
+        .transact_mut()
+        .apply_update(Update::decode_v1(&server_init.payload)?)?;
+      // Don't use the origin of the local collab, the update comes from the remote.
+      local_lock
+        .get_mut_awareness()
+        .doc_mut()
+        .transact_mut()
+        .apply_update(Update::decode_v1(&server_init.payload)?)?;
+    }
+    if let Err(e) = self.sync_state.send(SyncState::InitSyncEnd) {
+      tracing::error!("🔴Failed to send sync state: {:?}", e);
+    }
+
+    // Send back the updates that the remote is missing.
+    let encode_update = local_lock
+      .transact()
+      .encode_state_as_update_v1(&remote_state_vector);
+    drop(local_lock);
+    let update = Update::decode_v1(&encode_update)?;
+    // Nothing to send back when the remote already has all the local changes.
+    if update.state_vector().is_empty() && update.delete_set().is_empty() {
+      return Ok(server_init.payload);
+    }
+    tracing::trace!(
+      "{}: sync updates to remote:{}",
+      self.object,
+      encode_update.len()
+    );
+    self
+      .collab
+      .write()
+      .await
+      .transact_mut()
+      .apply_update(update)?;
+    self
+      .sink
+      .queue_msg_async(|msg_id| Message {
+        object: self.object.clone(),
+        payloads: vec![encode_update],
+        meta: MessageMeta::Init { msg_id },
+      })
+      .await;
+    Ok(server_init.payload)
+  }
+
+  async fn sync_with_doc_state(
+    &self,
+    local_collab: &Arc<RwLock<Collab>>,
+  ) -> Result<Vec<u8>, Error> {
+    let mut remote_update = vec![];
     let collab_doc_state = self.storage.get_doc_state(&self.object).await?;
     {
       let mut remote_collab = self.collab.write().await;
@@ -243,12 +399,11 @@ impl RemoteCollab {
           remote_update = doc_state;
         },
       }
+      drop(txn);
+      drop(remote_collab);
 
       let _ = self.sync_state.send(SyncState::InitSyncBegin);
       // Encode the remote collab state as update for local collab.
-      let local_collab = local_collab
-        .upgrade()
-        .ok_or(anyhow!("local collab is dropped"))?;
       let mut local_lock = local_collab.write().await;
       let encode_update = self
         .collab
@@ -257,25 +412,22 @@ impl RemoteCollab {
         .transact()
         .encode_state_as_update_v1(&local_lock.transact().state_vector());
       if let Ok(update) = Update::decode_v1(&encode_update) {
-        {
-          // Don't use the with_transact_mut here, because it carries the origin information. So
-          // the update will consider as a local update. But here is apply the remote update.
-          // TODO: nathan define a sync protocol for cloud storage.
-          tracing::trace!(
-            "{}: apply remote update with diff len:{}",
-            self.object,
-            encode_update.len()
-          );
-          local_lock
-            .get_mut_awareness()
-            .doc_mut()
-            .transact_mut()
-            .apply_update(update)?;
-          drop(local_lock);
-
-          if let Err(e) = self.sync_state.send(SyncState::InitSyncEnd) {
-            tracing::error!("🔴Failed to send sync state: {:?}", e);
-          }
+        // Don't use the with_transact_mut here, because it carries the origin information. So
+        // the update will consider as a local update. But here is apply the remote update.
+        tracing::trace!(
+          "{}: apply remote update with diff len:{}",
+          self.object,
+          encode_update.len()
+        );
+        local_lock
+          .get_mut_awareness()
+          .doc_mut()
+          .transact_mut()
+          .apply_update(update)?;
+        drop(local_lock);
+
+        if let Err(e) = self.sync_state.send(SyncState::InitSyncEnd) {
+          tracing::error!("🔴Failed to send sync state: {:?}", e);
         }
       }
     }
@@ -284,8 +436,6 @@ impl RemoteCollab {
     let mut remote_lock = self.collab.write().await;
     let remote_state_vector = remote_lock.transact().state_vector();
     let encode_update = local_collab
-      .upgrade()
-      .ok_or(anyhow!("local collab is dropped"))?
       .read()
       .await
       .transact()
@@ -302,11 +452,14 @@ impl RemoteCollab {
       remote_lock.transact_mut().apply_update(decode_update)?;
       drop(remote_lock);
 
-      self.sink.queue_msg(|msg_id| Message {
-        object: self.object.clone(),
-        payloads: vec![encode_update],
-        meta: MessageMeta::Init { msg_id },
-      });
+      self
+        .sink
+        .queue_msg_async(|msg_id| Message {
+          object: self.object.clone(),
+          payloads: vec![encode_update],
+          meta: MessageMeta::Init { msg_id },
+        })
+        .await;
     }
     Ok(remote_update)
   }
@@ -329,6 +482,8 @@ impl RemoteCollab {
     Ok(())
   }
 
+  /// Removes the updates that are waiting to be sent. The updates that are kept in the outbox
+  /// stay there until the remote acknowledges them.
   #[allow(dead_code)]
   pub fn clear(&self) {
     self.sink.remove_all_pending_msgs();
@@ -383,6 +538,19 @@ pub trait RemoteCollabStorage: Send + Sync + 'static {
     snapshot: Vec<u8>,
   ) -> Result<i64, anyhow::Error>;
 
+  /// Start the init sync with the state vector of the local collab. The remote returns the
+  /// updates that the local collab is missing, and its own state vector.
+  ///
+  /// Returns [None] if the storage doesn't support it. The [RemoteCollab] downloads the whole
+  /// doc state with [RemoteCollabStorage::get_doc_state] instead.
+  async fn init_sync(
+    &self,
+    _object: &CollabObject,
+    _init_sync: InitSync,
+  ) -> Result<Option<ServerInit>, anyhow::Error> {
+    Ok(None)
+  }
+
   /// Send the update to the remote storage.
   async fn send_update(
     &self,
@@ -432,6 +600,14 @@ where
     (**self).create_snapshot(object, update).await
   }
 
+  async fn init_sync(
+    &self,
+    object: &CollabObject,
+    init_sync: InitSync,
+  ) -> Result<Option<ServerInit>, Error> {
+    (**self).init_sync(object, init_sync).await
+  }
+
   async fn send_update(
     &self,
     object: &CollabObject,
@@ -455,7 +631,7 @@ where
   }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MessageMeta {
   Init { msg_id: MsgId },
   Update { msg_id: MsgId },
@@ -475,7 +651,7 @@ impl MessageMeta {
 }
 
 /// A message that is sent to the remote.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct Message {
   object: CollabObject,
   meta: MessageMeta,
@@ -584,6 +760,19 @@ impl Display for Message {
   }
 }
 
+/// Hands the update of the [Message] that was given up over to the
+/// [SinkConfig::dead_letter_handler].
+struct UpdateDeadLetterHandler(Arc<dyn DeadLetterHandler<Vec<u8>>>);
+
+impl DeadLetterHandler<Message> for UpdateDeadLetterHandler {
+  fn handle_dead_letter(&self, msg_id: MsgId, msg: Message, attempts: u32) {
+    match msg.split() {
+      Ok((_, _, update)) => self.0.handle_dead_letter(msg_id, update, attempts),
+      Err(e) => tracing::error!("🔴Failed to split message: {:?}", e),
+    }
+  }
+}
+
 #[derive(Debug, thiserror::Error)]
 enum CollabError {
   #[error("Internal error")]
@@ -602,7 +791,7 @@ impl RngMsgIdCounter {
       .as_millis() as u64;
 
     let random: u64 = (random::<u16>() as u64) & RANDOM_MASK;
-    let value = timestamp << 16 | random;
+    let value = (timestamp << 16) | random;
     Self(AtomicU64::new(value))
   }
 }
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::cloud_storage::msg::MsgId;
+
+/// Decides when the [CollabSink] sends a message again after the remote didn't acknowledge it
+/// within [SinkConfig::timeout].
+///
+/// [CollabSink]: crate::cloud_storage::sink::CollabSink
+/// [SinkConfig::timeout]: crate::cloud_storage::SinkConfig::timeout
+pub trait RetryPolicy: Send + Sync + 'static {
+  /// Returns the delay to wait before the next attempt, or [None] to give up. `attempt` is the
+  /// number of attempts that already failed, starting from 1.
+  fn next_delay(&self, attempt: u32) -> Option<Duration>;
+}
+
+/// Waits the same delay between the attempts.
+#[derive(Debug, Clone, Default)]
+pub struct FixedDelay {
+  delay: Duration,
+  max_attempts: Option<u32>,
+}
+
+impl FixedDelay {
+  pub fn new(delay: Duration) -> Self {
+    Self {
+      delay,
+      max_attempts: None,
+    }
+  }
+
+  /// The number of attempts after which the message is given up. [None] means no limit.
+  pub fn with_max_attempts(mut self, max_attempts: Option<u32>) -> Self {
+    self.max_attempts = max_attempts;
+    self
+  }
+}
+
+impl RetryPolicy for FixedDelay {
+  fn next_delay(&self, attempt: u32) -> Option<Duration> {
+    if self
+      .max_attempts
+      .is_some_and(|max_attempts| attempt >= max_attempts)
+    {
+      return None;
+    }
+    Some(self.delay)
+  }
+}
+
+/// Doubles the delay after every attempt, up to `max_delay`. The jitter spreads the attempts of
+/// the clients that lost the connection at the same time.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+  initial_delay: Duration,
+  max_delay: Duration,
+  multiplier: f64,
+  jitter: f64,
+  max_attempts: Option<u32>,
+}
+
+impl Default for ExponentialBackoff {
+  fn default() -> Self {
+    Self {
+      initial_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(60),
+      multiplier: 2.0,
+      jitter: 0.2,
+      max_attempts: None,
+    }
+  }
+}
+
+impl ExponentialBackoff {
+  pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+    Self {
+      initial_delay,
+      max_delay,
+      ..Default::default()
+    }
+  }
+
+  pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+    self.multiplier = multiplier.max(1.0);
+    self
+  }
+
+  /// The fraction of the delay that is randomly added or removed, between 0.0 and 1.0.
+  pub fn with_jitter(mut self, jitter: f64) -> Self {
+    self.jitter = jitter.clamp(0.0, 1.0);
+    self
+  }
+
+  /// The number of attempts after which the message is given up. [None] means no limit.
+  pub fn with_max_attempts(mut self, max_attempts: Option<u32>) -> Self {
+    self.max_attempts = max_attempts;
+    self
+  }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+  fn next_delay(&self, attempt: u32) -> Option<Duration> {
+    if self
+      .max_attempts
+      .is_some_and(|max_attempts| attempt >= max_attempts)
+    {
+      return None;
+    }
+
+    let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+    let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+    let delay = delay.min(self.max_delay.as_secs_f64());
+    let jitter = if self.jitter > 0.0 {
+      rand::thread_rng().gen_range(-self.jitter..=self.jitter)
+    } else {
+      0.0
+    };
+    Some(Duration::from_secs_f64((delay * (1.0 + jitter)).max(0.0)))
+  }
+}
+
+/// Receives the messages the [CollabSink] gave up on, according to its [RetryPolicy]. The
+/// messages are removed from the pending queue and from the outbox before they are handed over.
+///
+/// [CollabSink]: crate::cloud_storage::sink::CollabSink
+pub trait DeadLetterHandler<Msg>: Send + Sync + 'static {
+  fn handle_dead_letter(&self, msg_id: MsgId, msg: Msg, attempts: u32);
+}
+
+impl<Msg, F> DeadLetterHandler<Msg> for F
+where
+  F: Fn(MsgId, Msg, u32) + Send + Sync + 'static,
+{
+  fn handle_dead_letter(&self, msg_id: MsgId, msg: Msg, attempts: u32) {
+    self(msg_id, msg, attempts)
+  }
+}
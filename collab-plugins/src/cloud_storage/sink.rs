@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+use std::collections::binary_heap::PeekMut;
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
@@ -8,10 +10,13 @@ use futures_util::SinkExt;
 use tokio::spawn;
 use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::{Instant, Interval};
-use tracing::{debug, trace};
+use tracing::{debug, error, trace};
 
 use crate::cloud_storage::error::SyncError;
-use crate::cloud_storage::msg::{CollabSinkMessage, MessageState, PendingMsgQueue};
+use crate::cloud_storage::msg::{CollabSinkMessage, MessageState, PendingMessage, PendingMsgQueue};
+use crate::cloud_storage::outbox::SinkOutbox;
+use crate::cloud_storage::retry::{DeadLetterHandler, FixedDelay, RetryPolicy};
+use crate::connect_state::{CollabConnectReachability, CollabConnectState};
 
 pub const DEFAULT_SYNC_TIMEOUT: u64 = 2;
 #[derive(Clone, Debug)]
@@ -41,6 +46,16 @@ pub struct CollabSink<Sink, Msg> {
   /// remote. It will merge the messages if possible.
   pending_msg_queue: Arc<Mutex<PendingMsgQueue<Msg>>>, //FIXME: this should be a channel
   msg_id_counter: Arc<dyn MsgIdCounter>,
+  /// Persists the pending messages until they are acknowledged by the remote. See [SinkOutbox].
+  outbox: Option<Arc<dyn SinkOutbox<Msg>>>,
+  /// Receives the messages that are given up according to the [SinkConfig::retry_policy].
+  dead_letter_handler: Option<Arc<dyn DeadLetterHandler<Msg>>>,
+  /// The sink doesn't send any message while the remote is not reachable.
+  is_connected: AtomicBool,
+  /// Set when the remote becomes reachable again, so the pending messages are sent without
+  /// waiting for the next [SinkStrategy::FixInterval] tick.
+  flush_on_next: AtomicBool,
+  metrics: std::sync::Mutex<HashMap<String, SinkMetrics>>,
 
   /// The [watch::Sender] is used to notify the [CollabSinkRunner] to process the pending messages.
   /// Sending `false` will stop the [CollabSinkRunner].
@@ -69,6 +84,14 @@ where
   Sink: SinkExt<Msg, Error = E> + Send + Sync + Unpin + 'static,
   Msg: CollabSinkMessage,
 {
+  /// Creates a sink that sends the queued messages with the given [Sink].
+  ///
+  /// If an [SinkOutbox] is given, the queued messages are persisted in it until the remote
+  /// acknowledges them. The messages that were left in the outbox by a previous sink are queued
+  /// again, in order, and the mergeable ones are merged.
+  ///
+  /// The ids generated by the [MsgIdCounter] must be greater than the ids of the messages left in
+  /// the outbox, otherwise the new messages might overwrite them.
   pub fn new<C>(
     uid: i64,
     sink: Sink,
@@ -76,6 +99,7 @@ where
     sync_state_tx: watch::Sender<SinkState>,
     msg_id_counter: C,
     config: SinkConfig,
+    outbox: Option<Arc<dyn SinkOutbox<Msg>>>,
   ) -> Self
   where
     C: MsgIdCounter,
@@ -83,7 +107,10 @@ where
     let notifier = Arc::new(notifier);
     let state_notifier = Arc::new(sync_state_tx);
     let sender = Arc::new(Mutex::from(sink));
-    let pending_msg_queue = PendingMsgQueue::new();
+    let mut pending_msg_queue = PendingMsgQueue::new();
+    if let Some(outbox) = &outbox {
+      replay_outbox_msgs(outbox.as_ref(), &mut pending_msg_queue);
+    }
     let pending_msg_queue = Arc::new(Mutex::from(pending_msg_queue));
     let msg_id_counter = Arc::new(msg_id_counter);
     //
@@ -100,6 +127,11 @@ where
       sender,
       pending_msg_queue,
       msg_id_counter,
+      outbox,
+      dead_letter_handler: None,
+      is_connected: AtomicBool::new(true),
+      flush_on_next: AtomicBool::new(false),
+      metrics: Default::default(),
       notifier,
       state_notifier,
       config,
@@ -114,17 +146,93 @@ where
   /// [PartialOrd] trait. Check out the [CollabMessage] for more details.
   ///
   pub fn queue_msg(&self, f: impl FnOnce(MsgId) -> Msg) {
-    {
-      let mut pending_msgs = self.pending_msg_queue.blocking_lock();
-      let msg_id = self.msg_id_counter.next();
-      let msg = f(msg_id);
-      pending_msgs.push_msg(msg_id, msg);
-      drop(pending_msgs);
+    let msg_id = self.msg_id_counter.next();
+    let msg = f(msg_id);
+    // Persist the message before queuing it, so it can't be acknowledged before it's persisted.
+    if let Some(outbox) = &self.outbox {
+      persist_msg(outbox.as_ref(), msg_id, &msg);
     }
+    self.pending_msg_queue.blocking_lock().push_msg(msg_id, msg);
+    self.notify();
+  }
 
+  /// Same as [CollabSink::queue_msg], but it doesn't block the current thread, so it can be
+  /// called within an asynchronous context.
+  pub async fn queue_msg_async(&self, f: impl FnOnce(MsgId) -> Msg) {
+    let msg_id = self.msg_id_counter.next();
+    let msg = f(msg_id);
+    if let Some(outbox) = self.outbox.clone() {
+      let cloned_msg = msg.clone();
+      let _ =
+        tokio::task::spawn_blocking(move || persist_msg(outbox.as_ref(), msg_id, &cloned_msg))
+          .await;
+    }
+    self.pending_msg_queue.lock().await.push_msg(msg_id, msg);
     self.notify();
   }
 
+  /// Sets the [DeadLetterHandler] that receives the messages given up according to the
+  /// [SinkConfig::retry_policy]. Without it, the messages are dropped.
+  pub fn with_dead_letter_handler(mut self, handler: Arc<dyn DeadLetterHandler<Msg>>) -> Self {
+    self.dead_letter_handler = Some(handler);
+    self
+  }
+
+  /// Pauses the sink while the remote is disconnected. Once it's connected again, the pending
+  /// messages are sent immediately, including the ones that were waiting for their next retry.
+  pub fn set_connect_state(&self, state: CollabConnectState) {
+    let is_connected = state == CollabConnectState::Connected;
+    let was_connected = self.is_connected.swap(is_connected, Ordering::SeqCst);
+    if is_connected && !was_connected {
+      debug!(
+        "[Client {}]: remote is reachable, flush pending messages",
+        self.uid
+      );
+      self.flush_on_next.store(true, Ordering::SeqCst);
+      self.notify();
+    }
+  }
+
+  /// Follows the state of the given [CollabConnectReachability]. See
+  /// [CollabSink::set_connect_state].
+  pub fn subscribe_reachability(self: &Arc<Self>, reachability: &CollabConnectReachability) {
+    self.set_connect_state(reachability.state());
+    let mut state_rx = reachability.subscribe();
+    let weak_sink = Arc::downgrade(self);
+    spawn(async move {
+      while let Ok(state) = state_rx.recv().await {
+        match weak_sink.upgrade() {
+          Some(sink) => sink.set_connect_state(state),
+          None => break,
+        }
+      }
+    });
+  }
+
+  pub fn is_connected(&self) -> bool {
+    self.is_connected.load(Ordering::SeqCst)
+  }
+
+  /// Returns the [SinkMetrics] of each object that has pending messages, or that had messages
+  /// retried or given up.
+  pub async fn metrics(&self) -> HashMap<String, SinkMetrics> {
+    let mut metrics = self.metrics.lock().unwrap().clone();
+    let pending_msgs = self.pending_msg_queue.lock().await;
+    for pending_msg in pending_msgs.iter() {
+      if !pending_msg.state().is_done() {
+        let object_id = pending_msg.get_msg().object_id().to_string();
+        metrics.entry(object_id).or_default().queue_depth += 1;
+      }
+    }
+    metrics
+  }
+
+  pub fn next_msg_id(&self) -> MsgId {
+    self.msg_id_counter.next()
+  }
+
+  /// Removes the pending messages from the queue. The messages stay in the [SinkOutbox] until the
+  /// remote acknowledges them, so they are sent again the next time a sink is created for them.
   pub fn remove_all_pending_msgs(&self) {
     self.pending_msg_queue.blocking_lock().clear();
   }
@@ -146,6 +254,11 @@ where
       );
       if pending_msg.msg_id() == msg_id {
         debug!("{} message:{} was sent", object_id, msg_id);
+        if let Some(outbox) = &self.outbox {
+          if let Err(err) = outbox.remove_msg(msg_id) {
+            error!("remove message:{} from the outbox failed: {}", msg_id, err);
+          }
+        }
         pending_msg.set_state(MessageState::Done);
         self.notify();
       }
@@ -153,6 +266,17 @@ where
   }
 
   async fn process_next_msg(&self) -> Result<(), SyncError> {
+    if !self.is_connected() {
+      return Ok(());
+    }
+
+    if self.flush_on_next.swap(false, Ordering::SeqCst) {
+      // The messages that were waiting for their next attempt are sent right away.
+      self.pending_msg_queue.lock().await.clear_retry_at();
+      self.try_send_msg_immediately().await;
+      return Ok(());
+    }
+
     // Check if the next message can be deferred. If not, try to send the message immediately. The
     // default value is true.
     let deferrable = self
@@ -210,9 +334,16 @@ where
         return None;
       }
 
+      // The sink is notified again once the message is ready to be retried.
+      if sending_msg.is_waiting_for_retry() {
+        pending_msg_queue.push(sending_msg);
+        return None;
+      }
+
       // If the message can merge other messages, try to merge the next message until the
       // message is not mergeable.
       if sending_msg.is_mergeable() {
+        let mut merged_msg_ids = vec![];
         while let Some(pending_msg) = pending_msg_queue.pop() {
           debug!("Try merge collab message: {}", pending_msg.get_msg());
 
@@ -220,6 +351,17 @@ where
             pending_msg_queue.push(pending_msg);
             break;
           }
+          merged_msg_ids.push(pending_msg.msg_id());
+        }
+
+        if let Some(outbox) = &self.outbox {
+          if !merged_msg_ids.is_empty() {
+            if let Err(err) =
+              outbox.merge_msgs(sending_msg.msg_id(), sending_msg.get_msg(), &merged_msg_ids)
+            {
+              error!("merge messages in the outbox failed: {}", err);
+            }
+          }
         }
       }
 
@@ -259,8 +401,12 @@ where
       },
       Err(_) => {
         let mut lock = self.pending_msg_queue.lock().await;
-        if let Some(mut pending_msg) = lock.peek_mut() {
-          pending_msg.set_state(MessageState::Timeout);
+        let dead_letter = lock
+          .peek_mut()
+          .and_then(|pending_msg| self.did_timeout(pending_msg));
+        drop(lock);
+        if let Some(pending_msg) = dead_letter {
+          self.give_up_msg(pending_msg);
         }
         self.notify();
       },
@@ -268,6 +414,71 @@ where
     None
   }
 
+  /// Schedules the next attempt of the message that timed out, according to the
+  /// [SinkConfig::retry_policy]. Returns the message if it's given up.
+  fn did_timeout(
+    &self,
+    mut pending_msg: PeekMut<'_, PendingMessage<Msg>>,
+  ) -> Option<PendingMessage<Msg>> {
+    pending_msg.set_state(MessageState::Timeout);
+    // The attempts made while the remote is not reachable don't count.
+    if !self.is_connected() {
+      return None;
+    }
+
+    let attempts = pending_msg.increase_attempts();
+    self.update_metrics(pending_msg.get_msg().object_id(), |metrics| {
+      metrics.retry_count += 1
+    });
+    match self.config.retry_policy.next_delay(attempts) {
+      Some(delay) => {
+        trace!(
+          "retry {} in {:?}, attempts: {}",
+          pending_msg.get_msg(),
+          delay,
+          attempts
+        );
+        if !delay.is_zero() {
+          pending_msg.set_retry_at(Instant::now() + delay);
+          self.notify_after(delay);
+        }
+        None
+      },
+      None => Some(PeekMut::pop(pending_msg)),
+    }
+  }
+
+  fn give_up_msg(&self, pending_msg: PendingMessage<Msg>) {
+    let msg_id = pending_msg.msg_id();
+    let attempts = pending_msg.attempts();
+    let msg = pending_msg.into_msg();
+    error!("give up {} after {} attempts", msg, attempts);
+    self.update_metrics(msg.object_id(), |metrics| metrics.dead_letter_count += 1);
+    if let Some(outbox) = &self.outbox {
+      if let Err(err) = outbox.remove_msg(msg_id) {
+        error!("remove message:{} from the outbox failed: {}", msg_id, err);
+      }
+    }
+    if let Some(handler) = &self.dead_letter_handler {
+      handler.handle_dead_letter(msg_id, msg, attempts);
+    }
+  }
+
+  fn update_metrics(&self, object_id: &str, f: impl FnOnce(&mut SinkMetrics)) {
+    let mut metrics = self.metrics.lock().unwrap();
+    f(metrics.entry(object_id.to_string()).or_default());
+  }
+
+  fn notify_after(&self, delay: Duration) {
+    let weak_notifier = Arc::downgrade(&self.notifier);
+    spawn(async move {
+      tokio::time::sleep(delay).await;
+      if let Some(notifier) = weak_notifier.upgrade() {
+        let _ = notifier.send(false);
+      }
+    });
+  }
+
   /// Notify the sink to process the next message.
   pub(crate) fn notify(&self) {
     let _ = self.notifier.send(false);
@@ -280,6 +491,54 @@ where
   }
 }
 
+fn persist_msg<Msg: 'static>(outbox: &dyn SinkOutbox<Msg>, msg_id: MsgId, msg: &Msg) {
+  if let Err(err) = outbox.push_msg(msg_id, msg) {
+    error!("persist message:{} in the outbox failed: {}", msg_id, err);
+  }
+}
+
+/// Queues the messages left in the outbox. The consecutive update messages are merged while they
+/// are mergeable, and the outbox is updated accordingly.
+fn replay_outbox_msgs<Msg>(
+  outbox: &dyn SinkOutbox<Msg>,
+  pending_msg_queue: &mut PendingMsgQueue<Msg>,
+) where
+  Msg: CollabSinkMessage,
+{
+  let msgs = match outbox.load_msgs() {
+    Ok(msgs) => msgs,
+    Err(err) => {
+      error!("load messages from the outbox failed: {}", err);
+      return;
+    },
+  };
+
+  let mut replay_msgs: Vec<(MsgId, Msg, Vec<MsgId>)> = vec![];
+  for (msg_id, msg) in msgs {
+    if let Some((_, last_msg, merged_msg_ids)) = replay_msgs.last_mut() {
+      if !last_msg.is_init_msg()
+        && !msg.is_init_msg()
+        && last_msg.mergeable()
+        && last_msg.merge(&msg)
+      {
+        merged_msg_ids.push(msg_id);
+        continue;
+      }
+    }
+    replay_msgs.push((msg_id, msg, vec![]));
+  }
+
+  for (msg_id, msg, merged_msg_ids) in replay_msgs {
+    if !merged_msg_ids.is_empty() {
+      if let Err(err) = outbox.merge_msgs(msg_id, &msg, &merged_msg_ids) {
+        error!("merge messages in the outbox failed: {}", err);
+      }
+    }
+    trace!("replay message from the outbox: {}", msg);
+    pending_msg_queue.push_msg(msg_id, msg);
+  }
+}
+
 pub struct CollabSinkRunner<Msg>(PhantomData<Msg>);
 
 impl<Msg> CollabSinkRunner<Msg> {
@@ -323,6 +582,11 @@ pub struct SinkConfig {
   pub max_merge_size: usize,
   /// `strategy` is the strategy to send the messages.
   pub strategy: SinkStrategy,
+  /// `retry_policy` decides when a message that timed out is sent again, and when it's given up.
+  pub retry_policy: Arc<dyn RetryPolicy>,
+  /// `dead_letter_handler` receives the updates that are given up according to the
+  /// `retry_policy`. Without it, the updates are dropped.
+  pub dead_letter_handler: Option<Arc<dyn DeadLetterHandler<Vec<u8>>>>,
 }
 
 impl SinkConfig {
@@ -356,6 +620,16 @@ impl SinkConfig {
     self.strategy = strategy;
     self
   }
+
+  pub fn with_retry_policy(mut self, retry_policy: impl RetryPolicy) -> Self {
+    self.retry_policy = Arc::new(retry_policy);
+    self
+  }
+
+  pub fn with_dead_letter_handler(mut self, handler: impl DeadLetterHandler<Vec<u8>>) -> Self {
+    self.dead_letter_handler = Some(Arc::new(handler));
+    self
+  }
 }
 
 impl Default for SinkConfig {
@@ -364,6 +638,8 @@ impl Default for SinkConfig {
       timeout: Duration::from_secs(DEFAULT_SYNC_TIMEOUT),
       max_merge_size: 4096,
       strategy: SinkStrategy::Asap,
+      retry_policy: Arc::new(FixedDelay::default()),
+      dead_letter_handler: None,
     }
   }
 }
@@ -384,6 +660,16 @@ impl SinkStrategy {
   }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SinkMetrics {
+  /// The number of messages that are waiting to be acknowledged by the remote.
+  pub queue_depth: usize,
+  /// The number of times the messages were not acknowledged by the remote in time.
+  pub retry_count: u64,
+  /// The number of messages that were given up.
+  pub dead_letter_count: u64,
+}
+
 pub type MsgId = u64;
 
 pub trait MsgIdCounter: Send + Sync + 'static {
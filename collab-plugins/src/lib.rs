@@ -21,7 +21,7 @@ pub mod cloud_storage;
 pub mod connect_state;
 
 if_native! {
-    pub type CollabKVDB = local_storage::rocksdb::kv_impl::KVTransactionDBRocksdbImpl;
+    pub use local_storage::collab_kv_db::CollabKVDB;
 }
 
 if_wasm! {
@@ -0,0 +1,225 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use collab_entity::CollabType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, ReadTxn, StateVector, Transact};
+
+use crate::CollabKVDB;
+use crate::local_storage::kv::doc::CollabKVAction;
+use crate::local_storage::kv::encryption::{EncryptionKeyProvider, with_encryption};
+use crate::local_storage::kv::keys::make_encryption_check_key;
+use crate::local_storage::kv::snapshot::{CollabSnapshot, SnapshotAction};
+use crate::local_storage::kv::{KVStore, KVTransactionDB, PersistenceError};
+
+const ARCHIVE_MAGIC: &[u8; 8] = b"AFCOLLAB";
+/// The version of the archive format. Bump it when the layout of the archive changes.
+pub const ARCHIVE_VERSION: u32 = 2;
+const CHECKSUM_LEN: usize = 32;
+
+/// Describes the content of a workspace archive. It's written at the beginning of the archive,
+/// so it can be read without reading the objects.
+///
+/// The archive is laid out as follows:
+///   magic (8 bytes) | version (u32) | manifest length (u64) | manifest
+///   | object length (u64) | object | checksum (32 bytes) | object length (u64) | object | ...
+///
+/// The objects are in the same order as the entries of the manifest. Each one is followed by the
+/// SHA-256 of its encoding, so the objects are written one at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+  pub version: u32,
+  pub uid: i64,
+  pub workspace_id: String,
+  /// The timestamp in seconds.
+  pub exported_at: i64,
+  pub objects: Vec<ArchiveEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+  pub object_id: String,
+  pub collab_type: Option<CollabType>,
+  pub snapshot_count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveObject {
+  object_id: String,
+  doc_state: Vec<u8>,
+  state_vector: Vec<u8>,
+  snapshots: Vec<CollabSnapshot>,
+}
+
+/// Writes every object of the workspace into the writer: its doc state, with the updates merged
+/// into it, and its snapshots. `collab_type_of` returns the [CollabType] of an object if it's
+/// known, which is recorded in the manifest.
+///
+/// The objects are decrypted with the keys of the `encryption` provider, so the archive isn't
+/// encrypted. It fails with [PersistenceError::EncryptedWorkspace] if the workspace is encrypted
+/// and no provider is given.
+pub fn export_workspace<W: Write>(
+  db: &CollabKVDB,
+  uid: i64,
+  workspace_id: &str,
+  collab_type_of: &dyn Fn(&str) -> Option<CollabType>,
+  encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  mut writer: W,
+) -> Result<ArchiveManifest, PersistenceError> {
+  let read_txn = db.read_txn();
+  let check_key = make_encryption_check_key(workspace_id.as_bytes());
+  if encryption.is_none() && read_txn.get(check_key.as_ref())?.is_some() {
+    return Err(PersistenceError::EncryptedWorkspace(
+      workspace_id.to_string(),
+    ));
+  }
+  let manifest = with_encryption!(&read_txn, workspace_id, &encryption, store => {
+    let mut object_ids = store
+      .get_all_object_ids(uid, workspace_id)?
+      .collect::<Vec<String>>();
+    object_ids.sort();
+
+    // The manifest is written before the objects, so the snapshots are counted up front and only
+    // one object is held in memory at a time
+    let entries = object_ids
+      .into_iter()
+      .map(|object_id| ArchiveEntry {
+        collab_type: collab_type_of(&object_id),
+        snapshot_count: store.get_snapshots(uid, &object_id).len(),
+        object_id,
+      })
+      .collect();
+    let manifest = ArchiveManifest {
+      version: ARCHIVE_VERSION,
+      uid,
+      workspace_id: workspace_id.to_string(),
+      exported_at: chrono::Utc::now().timestamp(),
+      objects: entries,
+    };
+    writer.write_all(ARCHIVE_MAGIC)?;
+    writer.write_all(&ARCHIVE_VERSION.to_be_bytes())?;
+    write_chunk(&mut writer, &bincode::serialize(&manifest)?)?;
+
+    for entry in &manifest.objects {
+      let doc = Doc::new();
+      store.load_doc(uid, workspace_id, &entry.object_id, &doc)?;
+      let txn = doc.transact();
+      let object = ArchiveObject {
+        object_id: entry.object_id.clone(),
+        doc_state: txn.encode_state_as_update_v1(&StateVector::default()),
+        state_vector: txn.state_vector().encode_v1(),
+        snapshots: store.get_snapshots(uid, &entry.object_id),
+      };
+      let encoded_object = bincode::serialize(&object)?;
+      write_chunk(&mut writer, &encoded_object)?;
+      writer.write_all(&Sha256::digest(&encoded_object))?;
+    }
+    manifest
+  });
+  writer.flush()?;
+  Ok(manifest)
+}
+
+/// Reads the manifest of the archive without reading its objects.
+pub fn read_archive_manifest<R: Read>(mut reader: R) -> Result<ArchiveManifest, PersistenceError> {
+  let mut magic = [0u8; 8];
+  reader.read_exact(&mut magic)?;
+  if &magic != ARCHIVE_MAGIC {
+    return Err(PersistenceError::InvalidArchive(
+      "not a collab workspace archive".to_string(),
+    ));
+  }
+  let mut version = [0u8; 4];
+  reader.read_exact(&mut version)?;
+  let version = u32::from_be_bytes(version);
+  if version != ARCHIVE_VERSION {
+    return Err(PersistenceError::InvalidArchive(format!(
+      "unsupported archive version: {}",
+      version
+    )));
+  }
+  Ok(bincode::deserialize(&read_chunk(&mut reader)?)?)
+}
+
+/// Loads the objects of the archive into the database, under the given uid and workspace id,
+/// which might be different from the ones the archive was exported with. The objects get new
+/// doc ids, and are encrypted with the keys of the `encryption` provider if one is given.
+///
+/// Every object is checked against its checksum before anything is written, and the objects are
+/// written within a single transaction. It fails if one of the objects already exists in the
+/// workspace.
+pub fn import_workspace<R: Read>(
+  db: &CollabKVDB,
+  mut reader: R,
+  uid: i64,
+  workspace_id: &str,
+  encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+) -> Result<ArchiveManifest, PersistenceError> {
+  let manifest = read_archive_manifest(&mut reader)?;
+  let mut objects = vec![];
+  for entry in &manifest.objects {
+    let encoded_object = read_chunk(&mut reader)?;
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    reader.read_exact(&mut checksum)?;
+    if Sha256::digest(&encoded_object).as_slice() != checksum {
+      return Err(PersistenceError::InvalidArchive(format!(
+        "checksum mismatch for object: {}",
+        entry.object_id
+      )));
+    }
+    let object: ArchiveObject = bincode::deserialize(&encoded_object)?;
+    if object.object_id != entry.object_id {
+      return Err(PersistenceError::InvalidArchive(format!(
+        "expect object: {}, but found: {}",
+        entry.object_id, object.object_id
+      )));
+    }
+    objects.push(object);
+  }
+
+  db.with_write_txn(|w_db_txn| {
+    with_encryption!(w_db_txn, workspace_id, &encryption, store => {
+      for object in &objects {
+        if store.is_exist(uid, workspace_id, &object.object_id) {
+          return Err(PersistenceError::DocumentAlreadyExist);
+        }
+        store.flush_doc_with(
+          uid,
+          workspace_id,
+          &object.object_id,
+          &object.doc_state,
+          &object.state_vector,
+        )?;
+        for snapshot in &object.snapshots {
+          store.insert_snapshot(uid, &object.object_id, snapshot)?;
+        }
+      }
+    });
+    Ok(())
+  })?;
+  Ok(manifest)
+}
+
+fn write_chunk<W: Write>(writer: &mut W, data: &[u8]) -> Result<(), PersistenceError> {
+  writer.write_all(&(data.len() as u64).to_be_bytes())?;
+  writer.write_all(data)?;
+  Ok(())
+}
+
+fn read_chunk<R: Read>(reader: &mut R) -> Result<Vec<u8>, PersistenceError> {
+  let mut len = [0u8; 8];
+  reader.read_exact(&mut len)?;
+  let mut data = vec![];
+  reader
+    .by_ref()
+    .take(u64::from_be_bytes(len))
+    .read_to_end(&mut data)?;
+  if data.len() as u64 != u64::from_be_bytes(len) {
+    return Err(PersistenceError::InvalidArchive(
+      "unexpected end of archive".to_string(),
+    ));
+  }
+  Ok(data)
+}
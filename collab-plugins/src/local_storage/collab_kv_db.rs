@@ -0,0 +1,307 @@
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rocksdb::TransactionDB;
+
+use crate::local_storage::kv::buffered::{BufferedEntry, BufferedKVStore};
+use crate::local_storage::kv::doc::CollabKVAction;
+use crate::local_storage::kv::encryption::{EncryptedKVStore, EncryptionKeyProvider};
+use crate::local_storage::kv::gc::{
+  GarbageCollectAction, GarbageCollectOptions, GarbageCollectReport,
+};
+use crate::local_storage::kv::stats::{StatsAction, StorageStats};
+use crate::local_storage::kv::{KVEntry, KVStore, KVTransactionDB, PersistenceError};
+use crate::local_storage::memory::kv_impl::KVTransactionDBMemoryImpl;
+use crate::local_storage::rocksdb::kv_impl::{
+  KVTransactionDBRocksdbImpl, RocksdbEntry, RocksdbKVStoreImpl, RocksdbRange,
+};
+#[cfg(feature = "sqlite")]
+use crate::local_storage::sqlite::kv_impl::KVTransactionDBSqliteImpl;
+
+/// The storage backend of a [CollabKVDB].
+#[derive(Debug, Clone)]
+pub enum KVBackend {
+  /// A rocksdb database at the given path
+  Rocksdb(PathBuf),
+  /// An in-memory database. The data is lost when the database is dropped.
+  Memory,
+  /// A SQLite database at the given path
+  #[cfg(feature = "sqlite")]
+  Sqlite(PathBuf),
+}
+
+/// The key-value database that stores the collabs. The backend is chosen when the database is
+/// opened, see [KVBackend].
+#[derive(Clone)]
+pub enum CollabKVDB {
+  Rocksdb(KVTransactionDBRocksdbImpl),
+  Memory(KVTransactionDBMemoryImpl),
+  #[cfg(feature = "sqlite")]
+  Sqlite(KVTransactionDBSqliteImpl),
+}
+
+impl CollabKVDB {
+  /// Open a rocksdb database at the given path.
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+    Ok(Self::Rocksdb(KVTransactionDBRocksdbImpl::open(path)?))
+  }
+
+  pub fn open_with_backend(backend: KVBackend) -> Result<Self, PersistenceError> {
+    match backend {
+      KVBackend::Rocksdb(path) => Self::open(path),
+      KVBackend::Memory => Ok(Self::Memory(KVTransactionDBMemoryImpl::new())),
+      #[cfg(feature = "sqlite")]
+      KVBackend::Sqlite(path) => Ok(Self::Sqlite(KVTransactionDBSqliteImpl::open(path)?)),
+    }
+  }
+
+  pub async fn is_exist(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<bool, PersistenceError> {
+    let read_txn = self.read_txn();
+    Ok(read_txn.is_exist(uid, workspace_id, object_id))
+  }
+
+  pub async fn delete_doc(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    doc_id: &str,
+  ) -> Result<(), PersistenceError> {
+    self.with_write_txn(|txn| txn.delete_doc(uid, workspace_id, doc_id))?;
+    Ok(())
+  }
+
+  /// Returns how much space the collabs of the user take, per workspace and per object.
+  pub fn get_storage_stats(&self, uid: i64) -> Result<StorageStats, PersistenceError> {
+    self.read_txn().get_storage_stats(uid)
+  }
+
+  /// Removes the data of the user that can't be reached anymore, see
+  /// [GarbageCollectAction::garbage_collect]. A dry run doesn't open a write transaction.
+  pub fn garbage_collect(
+    &self,
+    uid: i64,
+    options: &GarbageCollectOptions,
+  ) -> Result<GarbageCollectReport, PersistenceError> {
+    if options.dry_run {
+      self.read_txn().garbage_collect(uid, options)
+    } else {
+      self.with_write_txn(|txn| txn.garbage_collect(uid, options))
+    }
+  }
+
+  /// Re-encrypts the collabs of the workspace with the current key of the provider. Each collab
+  /// is re-encrypted in its own transaction, so the collabs can still be read and written while
+  /// the workspace is being re-encrypted. Returns the number of values that were re-encrypted.
+  ///
+  /// The previous keys must be available from the provider until it returns.
+  pub fn rotate_encryption_key(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    provider: Arc<dyn EncryptionKeyProvider>,
+  ) -> Result<usize, PersistenceError> {
+    let object_ids = self
+      .read_txn()
+      .get_all_object_ids(uid, workspace_id)?
+      .collect::<Vec<String>>();
+    let mut count = 0;
+    for object_id in object_ids {
+      count += self.with_write_txn(|txn| {
+        EncryptedKVStore::open(txn, workspace_id, provider.clone())?.reencrypt_doc(uid, &object_id)
+      })?;
+    }
+    self.with_write_txn(|txn| {
+      EncryptedKVStore::open(txn, workspace_id, provider.clone())?.reencrypt_check_record()
+    })?;
+    Ok(count)
+  }
+
+  /// Runs [CollabKVDB::rotate_encryption_key] on the blocking threads of the tokio runtime.
+  pub fn rotate_encryption_key_in_background(
+    self: Arc<Self>,
+    uid: i64,
+    workspace_id: String,
+    provider: Arc<dyn EncryptionKeyProvider>,
+  ) -> tokio::task::JoinHandle<Result<usize, PersistenceError>> {
+    tokio::task::spawn_blocking(move || self.rotate_encryption_key(uid, &workspace_id, provider))
+  }
+}
+
+impl From<KVTransactionDBRocksdbImpl> for CollabKVDB {
+  fn from(db: KVTransactionDBRocksdbImpl) -> Self {
+    Self::Rocksdb(db)
+  }
+}
+
+impl From<KVTransactionDBMemoryImpl> for CollabKVDB {
+  fn from(db: KVTransactionDBMemoryImpl) -> Self {
+    Self::Memory(db)
+  }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<KVTransactionDBSqliteImpl> for CollabKVDB {
+  fn from(db: KVTransactionDBSqliteImpl) -> Self {
+    Self::Sqlite(db)
+  }
+}
+
+impl KVTransactionDB for CollabKVDB {
+  type TransactionAction<'a> = CollabKVStore<'a>;
+
+  fn read_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    match self {
+      CollabKVDB::Rocksdb(db) => CollabKVStore::Rocksdb(db.read_txn()),
+      CollabKVDB::Memory(db) => CollabKVStore::Memory(db.read_txn()),
+      #[cfg(feature = "sqlite")]
+      CollabKVDB::Sqlite(db) => CollabKVStore::Sqlite(db.read_txn()),
+    }
+  }
+
+  fn write_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    match self {
+      CollabKVDB::Rocksdb(db) => CollabKVStore::Rocksdb(db.write_txn()),
+      CollabKVDB::Memory(db) => CollabKVStore::Memory(db.write_txn()),
+      #[cfg(feature = "sqlite")]
+      CollabKVDB::Sqlite(db) => CollabKVStore::Sqlite(db.write_txn()),
+    }
+  }
+
+  fn with_write_txn<'a, 'b, Output>(
+    &'b self,
+    f: impl FnOnce(&Self::TransactionAction<'a>) -> Result<Output, PersistenceError>,
+  ) -> Result<Output, PersistenceError>
+  where
+    'b: 'a,
+  {
+    let store = self.write_txn();
+    let result = f(&store)?;
+    store.commit_transaction()?;
+    Ok(result)
+  }
+
+  fn flush(&self) -> Result<(), PersistenceError> {
+    match self {
+      CollabKVDB::Rocksdb(db) => db.flush(),
+      CollabKVDB::Memory(db) => db.flush(),
+      #[cfg(feature = "sqlite")]
+      CollabKVDB::Sqlite(db) => db.flush(),
+    }
+  }
+}
+
+/// A transaction of [CollabKVDB].
+pub enum CollabKVStore<'a> {
+  Rocksdb(RocksdbKVStoreImpl<'a, TransactionDB>),
+  Memory(BufferedKVStore<'a, KVTransactionDBMemoryImpl>),
+  #[cfg(feature = "sqlite")]
+  Sqlite(BufferedKVStore<'a, KVTransactionDBSqliteImpl>),
+}
+
+/// Calls the same method on the transaction of whichever backend the [CollabKVStore] wraps.
+macro_rules! with_store {
+  ($store:expr, $inner:ident => $body:expr) => {
+    match $store {
+      CollabKVStore::Rocksdb($inner) => $body,
+      CollabKVStore::Memory($inner) => $body,
+      #[cfg(feature = "sqlite")]
+      CollabKVStore::Sqlite($inner) => $body,
+    }
+  };
+}
+
+impl CollabKVStore<'_> {
+  pub fn commit_transaction(self) -> Result<(), PersistenceError> {
+    with_store!(self, store => store.commit_transaction())
+  }
+}
+
+impl<'a> KVStore<'a> for CollabKVStore<'a> {
+  type Range = CollabKVRange<'a>;
+  type Entry = CollabKVEntry;
+  type Value = Vec<u8>;
+  type Error = PersistenceError;
+
+  fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Self::Value>, Self::Error> {
+    with_store!(self, store => store.get(key))
+  }
+
+  fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<(), Self::Error> {
+    with_store!(self, store => store.insert(key, value))
+  }
+
+  fn remove(&self, key: &[u8]) -> Result<(), Self::Error> {
+    with_store!(self, store => store.remove(key))
+  }
+
+  fn remove_range(&self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+    with_store!(self, store => store.remove_range(from, to))
+  }
+
+  fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> Result<Self::Range, Self::Error> {
+    match self {
+      CollabKVStore::Rocksdb(store) => store.range(range).map(CollabKVRange::Rocksdb),
+      CollabKVStore::Memory(store) => store.range(range).map(CollabKVRange::Buffered),
+      #[cfg(feature = "sqlite")]
+      CollabKVStore::Sqlite(store) => store.range(range).map(CollabKVRange::Buffered),
+    }
+  }
+
+  fn next_back_entry(&self, key: &[u8]) -> Result<Option<Self::Entry>, Self::Error> {
+    match self {
+      CollabKVStore::Rocksdb(store) => Ok(store.next_back_entry(key)?.map(CollabKVEntry::Rocksdb)),
+      CollabKVStore::Memory(store) => Ok(store.next_back_entry(key)?.map(CollabKVEntry::Buffered)),
+      #[cfg(feature = "sqlite")]
+      CollabKVStore::Sqlite(store) => Ok(store.next_back_entry(key)?.map(CollabKVEntry::Buffered)),
+    }
+  }
+}
+
+pub enum CollabKVRange<'a> {
+  Rocksdb(RocksdbRange<'a, TransactionDB>),
+  Buffered(std::vec::IntoIter<BufferedEntry>),
+}
+
+impl Iterator for CollabKVRange<'_> {
+  type Item = CollabKVEntry;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    match self {
+      CollabKVRange::Rocksdb(range) => range.next().map(CollabKVEntry::Rocksdb),
+      CollabKVRange::Buffered(range) => range.next().map(CollabKVEntry::Buffered),
+    }
+  }
+}
+
+pub enum CollabKVEntry {
+  Rocksdb(RocksdbEntry),
+  Buffered(BufferedEntry),
+}
+
+impl KVEntry for CollabKVEntry {
+  fn key(&self) -> &[u8] {
+    match self {
+      CollabKVEntry::Rocksdb(entry) => entry.key(),
+      CollabKVEntry::Buffered(entry) => entry.key(),
+    }
+  }
+
+  fn value(&self) -> &[u8] {
+    match self {
+      CollabKVEntry::Rocksdb(entry) => entry.value(),
+      CollabKVEntry::Buffered(entry) => entry.value(),
+    }
+  }
+}
@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+
+use crate::local_storage::kv::{KVEntry, KVStore, PersistenceError};
+
+/// The changes of a transaction, keyed by the key they apply to. [None] removes the key.
+pub type KVChanges = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+/// The committed data of a key-value backend.
+///
+/// A backend only has to read its committed data and apply a set of changes atomically.
+/// [BufferedKVStore] buffers the writes of a transaction on top of it, which gives the backend
+/// the transactions of [crate::local_storage::kv::KVTransactionDB].
+pub trait CommittedKVStore: Send + Sync {
+  fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, PersistenceError>;
+
+  /// Return the entries in [from..to), ordered by key. If `to` is [None], the range is unbounded.
+  fn range(&self, from: &[u8], to: Option<&[u8]>) -> Result<Vec<BufferedEntry>, PersistenceError>;
+
+  /// Return the last entry whose key is lower than the given key. If `inclusive` is true, the
+  /// entry of the given key is returned if it exists.
+  fn last_entry_before(
+    &self,
+    key: &[u8],
+    inclusive: bool,
+  ) -> Result<Option<BufferedEntry>, PersistenceError>;
+
+  /// Apply the changes atomically: either all of them are applied or none.
+  fn commit(&self, changes: KVChanges) -> Result<(), PersistenceError>;
+
+  /// The lock held by the write transactions of the store from their creation until they are
+  /// committed or dropped.
+  fn write_lock(&self) -> &WriteLock;
+}
+
+/// Serializes the write transactions of a [CommittedKVStore].
+///
+/// A write transaction reads the committed data and commits its changes blindly, so two write
+/// transactions that run at the same time could overwrite each other's changes, e.g. two updates
+/// pushed with the same id. Only one thread can hold the lock at a time, and the lock isn't
+/// re-entrant: a write transaction opened while another one is in progress on the same thread
+/// would wait for itself, so it fails with [PersistenceError::NestedWriteTransaction] instead.
+#[derive(Default)]
+pub struct WriteLock {
+  owner: Mutex<Option<ThreadId>>,
+  released: Condvar,
+}
+
+impl WriteLock {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Blocks until the lock is released by the other threads. Returns None if the current thread
+  /// holds the lock.
+  fn acquire(&self) -> Option<WriteLockGuard<'_>> {
+    let current = thread::current().id();
+    let mut owner = self.owner.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+      match *owner {
+        None => {
+          *owner = Some(current);
+          return Some(WriteLockGuard { lock: self });
+        },
+        Some(thread_id) if thread_id == current => return None,
+        Some(_) => {
+          owner = self
+            .released
+            .wait(owner)
+            .unwrap_or_else(PoisonError::into_inner);
+        },
+      }
+    }
+  }
+}
+
+struct WriteLockGuard<'a> {
+  lock: &'a WriteLock,
+}
+
+impl Drop for WriteLockGuard<'_> {
+  fn drop(&mut self) {
+    let mut owner = self
+      .lock
+      .owner
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    *owner = None;
+    self.lock.released.notify_one();
+  }
+}
+
+/// A transaction over a [CommittedKVStore].
+///
+/// The writes are kept in memory and applied when the transaction is committed. Dropping the
+/// transaction without committing discards them. The reads see the writes of the transaction
+/// and the data that is committed at the time of the read.
+///
+/// A write transaction holds the [WriteLock] of the store until it's committed or dropped, so the
+/// data it read isn't changed by another write transaction before it commits. It must not be kept
+/// across an await point, the task could resume on another thread.
+pub struct BufferedKVStore<'a, S> {
+  store: &'a S,
+  changes: Mutex<KVChanges>,
+  _write_guard: Option<WriteLockGuard<'a>>,
+  /// True if it's a write transaction opened while another one is in progress on the same thread.
+  /// Every operation of a nested transaction fails.
+  is_nested: bool,
+}
+
+impl<'a, S: CommittedKVStore> BufferedKVStore<'a, S> {
+  /// Create a read transaction
+  pub fn new(store: &'a S) -> Self {
+    Self {
+      store,
+      changes: Mutex::new(KVChanges::new()),
+      _write_guard: None,
+      is_nested: false,
+    }
+  }
+
+  /// Create a write transaction. It blocks until the write transactions of the other threads are
+  /// committed or dropped. If a write transaction is in progress on the current thread, every
+  /// operation of the new one fails with [PersistenceError::NestedWriteTransaction].
+  pub fn new_write(store: &'a S) -> Self {
+    let write_guard = store.write_lock().acquire();
+    Self {
+      store,
+      changes: Mutex::new(KVChanges::new()),
+      is_nested: write_guard.is_none(),
+      _write_guard: write_guard,
+    }
+  }
+
+  pub fn commit_transaction(self) -> Result<(), PersistenceError> {
+    self.check_nested()?;
+    let changes = self
+      .changes
+      .into_inner()
+      .unwrap_or_else(|err| err.into_inner());
+    if changes.is_empty() {
+      return Ok(());
+    }
+    self.store.commit(changes)
+  }
+
+  fn check_nested(&self) -> Result<(), PersistenceError> {
+    if self.is_nested {
+      return Err(PersistenceError::NestedWriteTransaction);
+    }
+    Ok(())
+  }
+
+  fn merged_range(
+    &self,
+    from: &[u8],
+    to: Option<&[u8]>,
+  ) -> Result<Vec<BufferedEntry>, PersistenceError> {
+    self.check_nested()?;
+    if to.is_some_and(|to| from >= to) {
+      return Ok(vec![]);
+    }
+
+    let mut entries = self
+      .store
+      .range(from, to)?
+      .into_iter()
+      .map(|entry| (entry.key, entry.value))
+      .collect::<BTreeMap<_, _>>();
+    let changes = self.changes.lock().unwrap();
+    let upper = to.map_or(Bound::Unbounded, |to| Bound::Excluded(to.to_vec()));
+    for (key, value) in changes.range::<Vec<u8>, _>((Bound::Included(from.to_vec()), upper)) {
+      match value {
+        Some(value) => entries.insert(key.clone(), value.clone()),
+        None => entries.remove(key),
+      };
+    }
+    Ok(
+      entries
+        .into_iter()
+        .map(|(key, value)| BufferedEntry::new(key, value))
+        .collect(),
+    )
+  }
+}
+
+impl<'a, S: CommittedKVStore> KVStore<'a> for BufferedKVStore<'a, S> {
+  type Range = std::vec::IntoIter<BufferedEntry>;
+  type Entry = BufferedEntry;
+  type Value = Vec<u8>;
+  type Error = PersistenceError;
+
+  fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Self::Value>, Self::Error> {
+    self.check_nested()?;
+    if let Some(value) = self.changes.lock().unwrap().get(key.as_ref()) {
+      return Ok(value.clone());
+    }
+    self.store.get(key.as_ref())
+  }
+
+  fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<(), Self::Error> {
+    self.check_nested()?;
+    self
+      .changes
+      .lock()
+      .unwrap()
+      .insert(key.as_ref().to_vec(), Some(value.as_ref().to_vec()));
+    Ok(())
+  }
+
+  fn remove(&self, key: &[u8]) -> Result<(), Self::Error> {
+    self.check_nested()?;
+    self.changes.lock().unwrap().insert(key.to_vec(), None);
+    Ok(())
+  }
+
+  fn remove_range(&self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+    let entries = self.merged_range(from, Some(to))?;
+    let mut changes = self.changes.lock().unwrap();
+    for entry in entries {
+      changes.insert(entry.key, None);
+    }
+    Ok(())
+  }
+
+  fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> Result<Self::Range, Self::Error> {
+    // Same as the rocksdb implementation, the lower bound is always included and the upper bound
+    // is never included.
+    let from = match range.start_bound() {
+      Bound::Included(start) | Bound::Excluded(start) => start.as_ref(),
+      Bound::Unbounded => &[],
+    };
+    let to = match range.end_bound() {
+      Bound::Included(end) | Bound::Excluded(end) => Some(end.as_ref()),
+      Bound::Unbounded => None,
+    };
+    Ok(self.merged_range(from, to)?.into_iter())
+  }
+
+  fn next_back_entry(&self, key: &[u8]) -> Result<Option<Self::Entry>, Self::Error> {
+    self.check_nested()?;
+    let changes = self.changes.lock().unwrap();
+    let mut upper = Bound::Included(key.to_vec());
+    loop {
+      let committed = match &upper {
+        Bound::Included(key) => self.store.last_entry_before(key, true)?,
+        Bound::Excluded(key) => self.store.last_entry_before(key, false)?,
+        Bound::Unbounded => unreachable!(),
+      };
+      let changed = changes
+        .range::<Vec<u8>, _>((Bound::Unbounded, upper.clone()))
+        .next_back();
+
+      match (committed, changed) {
+        // The change is the closest to the key, so it overrides the committed data
+        (committed, Some((changed_key, value)))
+          if committed
+            .as_ref()
+            .is_none_or(|committed| changed_key.as_slice() >= committed.key()) =>
+        {
+          match value {
+            Some(value) => {
+              return Ok(Some(BufferedEntry::new(changed_key.clone(), value.clone())));
+            },
+            // The key is removed in this transaction, keep looking before it
+            None => upper = Bound::Excluded(changed_key.clone()),
+          }
+        },
+        (committed, _) => return Ok(committed),
+      }
+    }
+  }
+}
+
+pub struct BufferedEntry {
+  key: Vec<u8>,
+  value: Vec<u8>,
+}
+
+impl BufferedEntry {
+  pub fn new(key: Vec<u8>, value: Vec<u8>) -> Self {
+    Self { key, value }
+  }
+}
+
+impl KVEntry for BufferedEntry {
+  fn key(&self) -> &[u8] {
+    self.key.as_ref()
+  }
+
+  fn value(&self) -> &[u8] {
+    self.value.as_ref()
+  }
+}
@@ -102,12 +102,47 @@ where
   S: KVStore<'a>,
   PersistenceError: From<<S as KVStore<'a>>::Error>,
 {
-  let snapshot = CollabSnapshot::new(data).to_vec();
+  insert_collab_snapshot(store, snapshot_id, object_id, &CollabSnapshot::new(data))
+}
+
+/// Same as [insert_snapshot_update], but the snapshot keeps its creation time.
+pub fn insert_collab_snapshot<'a, K, S>(
+  store: &S,
+  snapshot_id: SnapshotID,
+  object_id: &K,
+  snapshot: &CollabSnapshot,
+) -> Result<(), PersistenceError>
+where
+  K: AsRef<[u8]> + ?Sized + Debug,
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
   let update_key = create_update_key(snapshot_id, store, object_id, make_snapshot_update_key)?;
-  store.insert(update_key, snapshot)?;
+  store.insert(update_key, snapshot.to_vec())?;
+  Ok(())
+}
+
+/// Records the current time as the last write time of the document.
+pub fn set_doc_last_write<'a, S>(store: &S, doc_id: DocID) -> Result<(), PersistenceError>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  let now = chrono::Utc::now().timestamp_millis();
+  store.insert(make_doc_last_write_key(doc_id), now.to_be_bytes())?;
   Ok(())
 }
 
+/// Returns the last write time of the document in milliseconds, or [None] if it was not recorded.
+pub fn get_doc_last_write<'a, S>(store: &S, doc_id: DocID) -> Result<Option<i64>, PersistenceError>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  let value = store.get(make_doc_last_write_key(doc_id))?;
+  Ok(value.and_then(|value| Some(i64::from_be_bytes(value.as_ref().try_into().ok()?))))
+}
+
 pub fn insert_doc_update<'a, K, S>(
   db: &S,
   doc_id: DocID,
@@ -126,6 +161,7 @@ where
     return Err(PersistenceError::DuplicateUpdateKey);
   }
   db.insert(update_key.as_ref(), value)?;
+  set_doc_last_write(db, doc_id)?;
   Ok(update_key.to_vec())
 }
 
@@ -34,6 +34,7 @@ where
     info!("new doc:{:?}, doc state len:{}", object_id, doc_state.len());
     self.insert(doc_state_key, doc_state)?;
     self.insert(sv_key, sv)?;
+    set_doc_last_write(self, doc_id)?;
 
     Ok(())
   }
@@ -65,6 +66,7 @@ where
     // Insert new doc state and state vector
     self.insert(doc_state_key, doc_state)?;
     self.insert(sv_key, state_vector)?;
+    set_doc_last_write(self, doc_id)?;
     Ok(())
   }
 
@@ -203,6 +205,65 @@ where
     Ok(())
   }
 
+  /// Merges the updates of the document into its document state and removes the merged updates.
+  /// Returns the number of updates that were merged.
+  ///
+  /// The new document state is rebuilt from what is persisted, so it doesn't need the [Doc] that
+  /// produced the updates. Call it within a write transaction: the new document state and the
+  /// removal of the updates are committed together, so the document can be loaded whether or not
+  /// the compaction completed. Updates that are pushed after the transaction started are kept.
+  fn compact_doc(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<u32, PersistenceError> {
+    let doc_id = get_doc_id(uid, self, workspace_id, object_id).ok_or_else(|| {
+      PersistenceError::RecordNotFound(format!(
+        "doc with given object id: {:?} is not found",
+        object_id
+      ))
+    })?;
+    let doc_state_key = make_doc_state_key(doc_id);
+    let doc_state = self.get(doc_state_key.as_ref())?.ok_or_else(|| {
+      PersistenceError::InvalidData(format!("the doc state of {:?} is empty", object_id))
+    })?;
+
+    let doc = Doc::new();
+    let mut txn = doc.transact_mut();
+    txn.try_apply_update(Update::decode_v1(doc_state.as_ref())?)?;
+
+    let update_start = make_doc_update_key(doc_id, 0);
+    let update_end = make_doc_update_key(doc_id, Clock::MAX);
+    let mut last_update_key = None;
+    let mut update_count = 0;
+    for encoded_update in self.range(update_start.as_ref()..update_end.as_ref())? {
+      txn.try_apply_update(Update::decode_v1(encoded_update.value())?)?;
+      last_update_key = Some(encoded_update.key().to_vec());
+      update_count += 1;
+    }
+
+    let Some(last_update_key) = last_update_key else {
+      return Ok(0);
+    };
+    // The updates that are missing their dependencies are not part of the encoded state. Keep
+    // them as they are until the missing updates arrive.
+    if txn.store().pending_update().is_some() {
+      return Err(PersistenceError::InvalidData(format!(
+        "the updates of {:?} have missing dependencies",
+        object_id
+      )));
+    }
+
+    let doc_state = txn.encode_state_as_update_v1(&StateVector::default());
+    let sv = txn.state_vector().encode_v1();
+    self.insert(doc_state_key, doc_state)?;
+    self.insert(make_state_vector_key(doc_id), sv)?;
+    self.remove_range(update_start.as_ref(), &last_update_key)?;
+    self.remove(&last_update_key)?;
+    Ok(update_count)
+  }
+
   fn flush_doc_with(
     &self,
     uid: i64,
@@ -222,6 +283,7 @@ where
     // Insert new doc state and state vector
     self.insert(doc_state_key, doc_state)?;
     self.insert(sv_key, sv)?;
+    set_doc_last_write(self, doc_id)?;
     Ok(())
   }
 
@@ -261,6 +323,7 @@ where
         object_id.as_ref(),
       );
       let _ = self.remove(key.as_ref());
+      let _ = self.remove(make_doc_id_key_v1_record(&key).as_ref());
 
       // Delete the updates
       let start = make_doc_start_key(did);
@@ -302,6 +365,7 @@ where
     from_vec.extend_from_slice(&uid_bytes);
     from_vec.extend_from_slice(workspace_bytes);
     let from = Key(from_vec);
+    let prefix = from.clone();
 
     // Construct the `to` key by appending 0xFF to cover the full range of keys with the same prefix
     let to_vec: SmallVec<[u8; 24]> = smallvec![DOC_SPACE, DOC_SPACE_OBJECT_KEY];
@@ -309,7 +373,12 @@ where
 
     let iter = self.range(from.as_ref()..to.as_ref())?;
 
+    // The range goes past the keys of the workspace, so the keys of the other workspaces are
+    // skipped.
     Ok(iter.filter_map(move |entry| {
+      if !entry.key().starts_with(prefix.as_ref()) {
+        return None;
+      }
       extract_object_id_from_key_v1(entry.key(), uid_bytes.len(), workspace_bytes.len())
         .and_then(|object_id_bytes| String::from_utf8(object_id_bytes.to_vec()).ok())
     }))
@@ -323,9 +392,12 @@ where
     let mut workspace_ids = HashSet::new();
     // Iterate over the keys and extract workspace IDs
     for entry in iter {
-      let key_bytes = entry.key();
-      if let Some(workspace_id) = extract_uuid_from_key(key_bytes) {
-        workspace_ids.insert(Uuid::from_bytes(workspace_id).to_string());
+      // The keys without a record whose layout matches the new format are counted too
+      match doc_id_key_format(self, entry.key()) {
+        DocIdKeyFormat::V1 { workspace_id, .. } | DocIdKeyFormat::Unknown { workspace_id, .. } => {
+          workspace_ids.insert(workspace_id);
+        },
+        DocIdKeyFormat::V0 { .. } => {},
       }
     }
 
@@ -401,20 +473,33 @@ where
   S: KVStore<'a>,
   PersistenceError: From<<S as KVStore<'a>>::Error>,
 {
-  if let Some(did) = get_doc_id(uid, store, workspace_id, object_id) {
-    Ok(did)
-  } else {
-    let key = make_doc_id_key_v1(
-      &uid.to_be_bytes(),
-      workspace_id.as_ref(),
-      object_id.as_ref(),
-    );
-    let new_did = insert_doc_id_for_key(store, key)?;
-    Ok(new_did)
+  let uid_bytes = uid.to_be_bytes();
+  let key = make_doc_id_key_v1(&uid_bytes, workspace_id.as_ref(), object_id.as_ref());
+  let record_key = make_doc_id_key_v1_record(&key);
+  if let Some(did) = get_id_for_key(store, key.clone()) {
+    // The keys written before the records were added get their record here
+    if store.get(record_key.as_ref())?.is_none() {
+      store.insert(record_key, workspace_id)?;
+    }
+    return Ok(did);
   }
+
+  let old_key = make_doc_id_key_v0(&uid_bytes, object_id.as_ref());
+  if let Some(did) = get_id_for_key(store, old_key) {
+    return Ok(did);
+  }
+
+  let new_did = insert_doc_id_for_key(store, key)?;
+  store.insert(record_key, workspace_id)?;
+  Ok(new_did)
 }
 
-fn get_doc_id<'a, S>(uid: i64, store: &S, workspace_id: &str, object_id: &str) -> Option<DocID>
+pub(crate) fn get_doc_id<'a, S>(
+  uid: i64,
+  store: &S,
+  workspace_id: &str,
+  object_id: &str,
+) -> Option<DocID>
 where
   S: KVStore<'a>,
 {
@@ -452,17 +537,65 @@ where
     Some(String::from_utf8_lossy(content).to_string())
   }
 }
-fn extract_uuid_from_key(key: &[u8]) -> Option<[u8; 16]> {
-  // Start index is set to skip DOC_SPACE and DOC_SPACE_OBJECT (2 bytes)
-  let start_index = 2;
-  let end_index = start_index + 16;
-  // Ensure the key has enough length for extracting a 16-byte UUID
-  if key.len() >= end_index {
-    let mut uuid_bytes = [0u8; 16];
-    uuid_bytes.copy_from_slice(&key[start_index..end_index]);
-    Some(uuid_bytes)
-  } else {
-    None
+/// The format of a key of the object id -> [DocID] mapping.
+pub(crate) enum DocIdKeyFormat {
+  /// See [make_doc_id_key_v0].
+  V0 { object_id: String },
+  /// See [make_doc_id_key_v1].
+  V1 {
+    workspace_id: String,
+    object_id: String,
+  },
+  /// A key that was written before the keys of the new format got a record, see
+  /// [make_doc_id_key_v1_record], and that could be a key of either format. The fields are the
+  /// key read in the new format.
+  Unknown {
+    workspace_id: String,
+    object_id: String,
+  },
+}
+
+/// Returns the format of the given object id -> [DocID] mapping key. The keys of the new format
+/// are told apart by their record. The keys without a record are of the old format, unless they
+/// were written before the records were added and their layout matches the new format.
+pub(crate) fn doc_id_key_format<'a, S>(store: &S, key: &[u8]) -> DocIdKeyFormat
+where
+  S: KVStore<'a>,
+{
+  // Skip DOC_SPACE, DOC_SPACE_OBJECT (2 bytes) and the uid (8 bytes)
+  let start_index = 2 + 8;
+  let record = store
+    .get(make_doc_id_key_v1_record(key).as_ref())
+    .ok()
+    .flatten()
+    .and_then(|value| String::from_utf8(value.as_ref().to_vec()).ok());
+  if let Some(workspace_id) = record {
+    let object_id = key
+      .get(start_index + workspace_id.len()..key.len() - 1)
+      .map(|object_id| String::from_utf8_lossy(object_id).to_string())
+      .unwrap_or_default();
+    return DocIdKeyFormat::V1 {
+      workspace_id,
+      object_id,
+    };
+  }
+
+  // The workspace id is stored as a hyphenated uuid string (36 bytes) and followed by the object
+  // id, so the shorter keys or the keys without a uuid are of the old format.
+  let end_index = start_index + 36;
+  let workspace_id = key
+    .get(start_index..end_index)
+    .filter(|_| key.len() > end_index + 1)
+    .and_then(|workspace_id| std::str::from_utf8(workspace_id).ok())
+    .filter(|workspace_id| Uuid::parse_str(workspace_id).is_ok());
+  match workspace_id {
+    Some(workspace_id) => DocIdKeyFormat::Unknown {
+      workspace_id: workspace_id.to_string(),
+      object_id: String::from_utf8_lossy(&key[end_index..key.len() - 1]).to_string(),
+    },
+    None => DocIdKeyFormat::V0 {
+      object_id: String::from_utf8_lossy(oid_from_key(key)).to_string(),
+    },
   }
 }
 
@@ -477,11 +610,18 @@ where
   let iter = store.range(from.as_ref()..to.as_ref())?;
   for entry in iter {
     let old_key = entry.key();
+    if store
+      .get(make_doc_id_key_v1_record(old_key).as_ref())?
+      .is_some()
+    {
+      continue;
+    }
     let value = entry.value();
     let uid = &old_key[2..10];
     let object_id = &old_key[10..old_key.len() - 1];
 
     let new_key = make_doc_id_key_v1(uid, workspace_id.as_ref(), object_id);
+    store.insert(make_doc_id_key_v1_record(&new_key), workspace_id)?;
     store.insert(new_key, value)?;
   }
 
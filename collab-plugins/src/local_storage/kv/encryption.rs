@@ -0,0 +1,344 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+
+use crate::local_storage::kv::buffered::BufferedEntry;
+use crate::local_storage::kv::doc::get_doc_id;
+use crate::local_storage::kv::keys::*;
+use crate::local_storage::kv::snapshot::get_snapshot_id;
+use crate::local_storage::kv::{KVEntry, KVStore, PersistenceError};
+
+/// Marks a value that is encrypted by [EncryptedKVStore]. An encrypted value is laid out as
+/// follows:
+///   magic (4 bytes) | key id (u32) | nonce (12 bytes) | ciphertext
+const ENCRYPTED_VALUE_MAGIC: &[u8; 4] = b"\xffAFE";
+const NONCE_LEN: usize = 12;
+const ENCRYPTED_VALUE_HEADER_LEN: usize = ENCRYPTED_VALUE_MAGIC.len() + 4 + NONCE_LEN;
+
+/// The plaintext of the record that checks the encryption key of a workspace.
+const ENCRYPTION_CHECK_VALUE: &[u8] = b"collab-encryption-check";
+
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+/// A 256-bit AES-GCM key. The id is stored along with every value the key encrypts, so the value
+/// can still be decrypted once the key is rotated.
+#[derive(Clone)]
+pub struct EncryptionKey {
+  pub id: u32,
+  pub secret: [u8; ENCRYPTION_KEY_LEN],
+}
+
+impl EncryptionKey {
+  pub fn new(id: u32, secret: [u8; ENCRYPTION_KEY_LEN]) -> Self {
+    Self { id, secret }
+  }
+}
+
+impl Debug for EncryptionKey {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("EncryptionKey")
+      .field("id", &self.id)
+      .finish_non_exhaustive()
+  }
+}
+
+/// Provides the encryption keys of the workspaces.
+pub trait EncryptionKeyProvider: Send + Sync {
+  /// Returns the key that the new values of the workspace are encrypted with.
+  fn current_key(&self, workspace_id: &str) -> Result<EncryptionKey, PersistenceError>;
+
+  /// Returns the key with the given id. The keys that were rotated must still be returned until
+  /// [crate::CollabKVDB::rotate_encryption_key] re-encrypts the workspace.
+  fn get_key(
+    &self,
+    workspace_id: &str,
+    key_id: u32,
+  ) -> Result<Option<EncryptionKey>, PersistenceError>;
+}
+
+/// A [KVStore] that encrypts the content of the documents of a workspace: the doc states, state
+/// vectors, updates, snapshots, outbox messages and quarantined updates. The keys are not
+/// encrypted.
+///
+/// The values that are not encrypted, for example the ones written before the encryption was
+/// enabled, are read as is. They are encrypted by [EncryptedKVStore::reencrypt_doc].
+pub struct EncryptedKVStore<'s, S> {
+  store: &'s S,
+  workspace_id: String,
+  provider: Arc<dyn EncryptionKeyProvider>,
+  current_key: EncryptionKey,
+  has_check_record: AtomicBool,
+}
+
+impl<'s, S> EncryptedKVStore<'s, S> {
+  /// Wraps the store with the encryption keys of the workspace. It fails with
+  /// [PersistenceError::InvalidEncryptionKey] if the provider's keys can't decrypt the workspace.
+  pub fn open<'a>(
+    store: &'s S,
+    workspace_id: &str,
+    provider: Arc<dyn EncryptionKeyProvider>,
+  ) -> Result<Self, PersistenceError>
+  where
+    S: KVStore<'a>,
+    PersistenceError: From<<S as KVStore<'a>>::Error>,
+  {
+    let current_key = provider.current_key(workspace_id)?;
+    let encrypted_store = Self {
+      store,
+      workspace_id: workspace_id.to_string(),
+      provider,
+      current_key,
+      has_check_record: AtomicBool::new(false),
+    };
+
+    let check_key = make_encryption_check_key(workspace_id.as_bytes());
+    if let Some(value) = store.get(check_key.as_ref())? {
+      let check_value = encrypted_store.decrypt(check_key.as_ref(), value.as_ref())?;
+      if check_value != ENCRYPTION_CHECK_VALUE {
+        return Err(PersistenceError::InvalidEncryptionKey(
+          workspace_id.to_string(),
+        ));
+      }
+      encrypted_store
+        .has_check_record
+        .store(true, Ordering::SeqCst);
+    }
+    Ok(encrypted_store)
+  }
+
+  pub fn current_key_id(&self) -> u32 {
+    self.current_key.id
+  }
+
+  /// Re-encrypts the content of the document with the current key, including the values that
+  /// are not encrypted yet. Returns the number of values that were re-encrypted.
+  pub fn reencrypt_doc<'a>(&self, uid: i64, object_id: &str) -> Result<usize, PersistenceError>
+  where
+    S: KVStore<'a>,
+    PersistenceError: From<<S as KVStore<'a>>::Error>,
+  {
+    let mut ranges = vec![];
+    if let Some(doc_id) = get_doc_id(uid, self.store, &self.workspace_id, object_id) {
+      ranges.push((
+        make_doc_start_key(doc_id).to_vec(),
+        make_doc_end_key(doc_id).to_vec(),
+      ));
+    }
+    if let Some(snapshot_id) = get_snapshot_id(uid, self.store, object_id) {
+      ranges.push((
+        make_snapshot_update_key(snapshot_id, 0).to_vec(),
+        make_snapshot_update_key(snapshot_id, Clock::MAX).to_vec(),
+      ));
+    }
+    let uid = uid.to_be_bytes();
+    ranges.push((
+      make_outbox_msg_key(&uid, object_id.as_bytes(), 0).to_vec(),
+      make_outbox_msg_key(&uid, object_id.as_bytes(), OutboxMsgID::MAX).to_vec(),
+    ));
+    ranges.push((
+      make_quarantine_key(&uid, object_id.as_bytes(), 0, 0).to_vec(),
+      make_quarantine_key(&uid, object_id.as_bytes(), i64::MAX, u32::MAX).to_vec(),
+    ));
+
+    let mut count = 0;
+    for (from, to) in ranges {
+      let entries = self
+        .store
+        .range(from.as_slice()..=to.as_slice())?
+        .filter(|entry| is_doc_content_key(entry.key()) && self.should_reencrypt(entry.value()))
+        .map(|entry| (entry.key().to_vec(), entry.value().to_vec()))
+        .collect::<Vec<_>>();
+      for (key, value) in entries {
+        let plaintext = self.decrypt(&key, &value)?;
+        self.store.insert(&key, self.encrypt(&key, &plaintext)?)?;
+        count += 1;
+      }
+    }
+    Ok(count)
+  }
+
+  /// Re-encrypts the record that checks the encryption key of the workspace with the current key.
+  pub fn reencrypt_check_record<'a>(&self) -> Result<(), PersistenceError>
+  where
+    S: KVStore<'a>,
+    PersistenceError: From<<S as KVStore<'a>>::Error>,
+  {
+    self.has_check_record.store(false, Ordering::SeqCst);
+    self.insert_check_record_if_need()
+  }
+
+  fn should_reencrypt(&self, value: &[u8]) -> bool {
+    encrypted_value_key_id(value) != Some(self.current_key.id)
+  }
+
+  fn insert_check_record_if_need<'a>(&self) -> Result<(), PersistenceError>
+  where
+    S: KVStore<'a>,
+    PersistenceError: From<<S as KVStore<'a>>::Error>,
+  {
+    if self.has_check_record.load(Ordering::SeqCst) {
+      return Ok(());
+    }
+    let check_key = make_encryption_check_key(self.workspace_id.as_bytes());
+    let value = self.encrypt(check_key.as_ref(), ENCRYPTION_CHECK_VALUE)?;
+    self.store.insert(check_key, value)?;
+    self.has_check_record.store(true, Ordering::SeqCst);
+    Ok(())
+  }
+
+  fn encrypt(&self, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+    let cipher = Aes256Gcm::new(&self.current_key.secret.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    // The key is authenticated along with the value, so a value can't be moved to another key
+    let ciphertext = cipher
+      .encrypt(
+        &nonce,
+        Payload {
+          msg: plaintext,
+          aad: key,
+        },
+      )
+      .map_err(|_| PersistenceError::InvalidData("failed to encrypt the value".to_string()))?;
+
+    let mut value = Vec::with_capacity(ENCRYPTED_VALUE_HEADER_LEN + ciphertext.len());
+    value.extend_from_slice(ENCRYPTED_VALUE_MAGIC);
+    value.extend_from_slice(&self.current_key.id.to_be_bytes());
+    value.extend_from_slice(nonce.as_slice());
+    value.extend_from_slice(&ciphertext);
+    Ok(value)
+  }
+
+  fn decrypt(&self, key: &[u8], value: &[u8]) -> Result<Vec<u8>, PersistenceError> {
+    let Some(key_id) = encrypted_value_key_id(value) else {
+      return Ok(value.to_vec());
+    };
+    let encryption_key = if key_id == self.current_key.id {
+      self.current_key.clone()
+    } else {
+      self
+        .provider
+        .get_key(&self.workspace_id, key_id)?
+        .ok_or_else(|| PersistenceError::EncryptionKeyNotFound {
+          workspace_id: self.workspace_id.clone(),
+          key_id,
+        })?
+    };
+
+    let cipher = Aes256Gcm::new(&encryption_key.secret.into());
+    let nonce =
+      Nonce::from_slice(&value[ENCRYPTED_VALUE_MAGIC.len() + 4..ENCRYPTED_VALUE_HEADER_LEN]);
+    cipher
+      .decrypt(
+        nonce,
+        Payload {
+          msg: &value[ENCRYPTED_VALUE_HEADER_LEN..],
+          aad: key,
+        },
+      )
+      .map_err(|_| PersistenceError::InvalidEncryptionKey(self.workspace_id.clone()))
+  }
+
+  fn decrypt_entry<E: KVEntry>(&self, entry: E) -> Result<BufferedEntry, PersistenceError> {
+    let value = if is_doc_content_key(entry.key()) {
+      self.decrypt(entry.key(), entry.value())?
+    } else {
+      entry.value().to_vec()
+    };
+    Ok(BufferedEntry::new(entry.key().to_vec(), value))
+  }
+}
+
+impl<'s, 'a, S> KVStore<'s> for EncryptedKVStore<'s, S>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  type Range = std::vec::IntoIter<BufferedEntry>;
+  type Entry = BufferedEntry;
+  type Value = Vec<u8>;
+  type Error = PersistenceError;
+
+  fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Self::Value>, Self::Error> {
+    let key = key.as_ref();
+    match self.store.get(key)? {
+      None => Ok(None),
+      Some(value) if is_doc_content_key(key) => Ok(Some(self.decrypt(key, value.as_ref())?)),
+      Some(value) => Ok(Some(value.as_ref().to_vec())),
+    }
+  }
+
+  fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<(), Self::Error> {
+    let key = key.as_ref();
+    if is_doc_content_key(key) {
+      self.insert_check_record_if_need()?;
+      self.store.insert(key, self.encrypt(key, value.as_ref())?)?;
+    } else {
+      self.store.insert(key, value)?;
+    }
+    Ok(())
+  }
+
+  fn remove(&self, key: &[u8]) -> Result<(), Self::Error> {
+    self.store.remove(key)?;
+    Ok(())
+  }
+
+  fn remove_range(&self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+    self.store.remove_range(from, to)?;
+    Ok(())
+  }
+
+  fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> Result<Self::Range, Self::Error> {
+    // The entries are decrypted eagerly, so a value that can't be decrypted fails the range
+    // instead of being skipped.
+    let entries = self
+      .store
+      .range(range)?
+      .map(|entry| self.decrypt_entry(entry))
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries.into_iter())
+  }
+
+  fn next_back_entry(&self, key: &[u8]) -> Result<Option<Self::Entry>, Self::Error> {
+    match self.store.next_back_entry(key)? {
+      None => Ok(None),
+      Some(entry) => Ok(Some(self.decrypt_entry(entry)?)),
+    }
+  }
+}
+
+/// Returns the id of the key that encrypted the value, or [None] if the value is not encrypted.
+fn encrypted_value_key_id(value: &[u8]) -> Option<u32> {
+  if value.len() < ENCRYPTED_VALUE_HEADER_LEN || !value.starts_with(ENCRYPTED_VALUE_MAGIC) {
+    return None;
+  }
+  let key_id = &value[ENCRYPTED_VALUE_MAGIC.len()..ENCRYPTED_VALUE_MAGIC.len() + 4];
+  Some(u32::from_be_bytes(key_id.try_into().ok()?))
+}
+
+/// Binds `$store` to the transaction, wrapped in an [EncryptedKVStore] if a key provider is
+/// given, and evaluates `$body`. The enclosing function must return a [PersistenceError].
+macro_rules! with_encryption {
+  ($txn:expr, $workspace_id:expr, $provider:expr, $store:ident => $body:expr) => {
+    match $provider {
+      Some(provider) => {
+        let $store = $crate::local_storage::kv::encryption::EncryptedKVStore::open(
+          $txn,
+          $workspace_id,
+          provider.clone(),
+        )?;
+        $body
+      },
+      None => {
+        let $store = $txn;
+        $body
+      },
+    }
+  };
+}
+
+pub(crate) use with_encryption;
@@ -45,6 +45,27 @@ pub enum PersistenceError {
   #[error("Can't find the latest update key")]
   LatestUpdateKeyNotExist,
 
+  #[error("Invalid archive: {0}")]
+  InvalidArchive(String),
+
+  #[error("Invalid encryption key for workspace: {0}")]
+  InvalidEncryptionKey(String),
+
+  #[error("Can't find the encryption key {key_id} of workspace: {workspace_id}")]
+  EncryptionKeyNotFound { workspace_id: String, key_id: u32 },
+
+  #[error("The workspace is encrypted, but no encryption key provider is given: {0}")]
+  EncryptedWorkspace(String),
+
+  #[error("The database is opened in read-only mode")]
+  ReadOnly,
+
+  #[error("A write transaction is already in progress on this thread")]
+  NestedWriteTransaction,
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+
   #[error(transparent)]
   Collab(#[from] collab::error::CollabError),
 
@@ -77,3 +98,10 @@ impl From<rocksdb::Error> for PersistenceError {
     }
   }
 }
+
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+impl From<rusqlite::Error> for PersistenceError {
+  fn from(value: rusqlite::Error) -> Self {
+    PersistenceError::Internal(value.into())
+  }
+}
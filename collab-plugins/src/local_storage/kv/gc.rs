@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+
+use crate::local_storage::kv::doc::{CollabKVAction, DocIdKeyFormat, doc_id_key_format};
+use crate::local_storage::kv::keys::*;
+use crate::local_storage::kv::snapshot::{SnapshotAction, get_snapshot_id};
+use crate::local_storage::kv::*;
+
+impl<'a, T> GarbageCollectAction<'a> for T
+where
+  T: KVStore<'a> + 'a,
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+}
+
+/// Removes the data that can't be reached anymore.
+pub trait GarbageCollectAction<'a>: CollabKVAction<'a>
+where
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+  /// Removes the following data of the user:
+  ///   1. the objects of the workspaces that are not in
+  ///      [GarbageCollectOptions::existing_workspace_ids].
+  ///   2. the doc id mappings of the old key format, see [migrate_old_keys], whose document was
+  ///      migrated to the new key format or doesn't have a doc state. The keys that were
+  ///      written before the keys of the new format got a record and that might be of either
+  ///      format are kept.
+  ///   3. the snapshots of the objects that don't exist anymore.
+  ///
+  /// Nothing is removed if [GarbageCollectOptions::dry_run] is true, but the report is the same.
+  /// Call it within a write transaction, so everything is removed or nothing is.
+  ///
+  /// [migrate_old_keys]: crate::local_storage::kv::doc::migrate_old_keys
+  fn garbage_collect(
+    &self,
+    uid: i64,
+    options: &GarbageCollectOptions,
+  ) -> Result<GarbageCollectReport, PersistenceError> {
+    let uid_bytes = uid.to_be_bytes();
+    let mut doc_id_prefix = vec![DOC_SPACE, DOC_SPACE_OBJECT];
+    doc_id_prefix.extend_from_slice(&uid_bytes);
+    let mut new_format_docs = vec![];
+    let mut old_format_docs = vec![];
+    let mut live_object_ids = HashSet::new();
+    for entry in self.range(doc_id_prefix.as_slice()..=make_doc_start_key(0).as_ref())? {
+      let key = entry.key();
+      if !key.starts_with(&doc_id_prefix) || key.last() != Some(&TERMINATOR) {
+        continue;
+      }
+      let Ok(doc_id) = entry.value().try_into().map(DocID::from_be_bytes) else {
+        continue;
+      };
+      match doc_id_key_format(self, key) {
+        DocIdKeyFormat::V1 {
+          workspace_id,
+          object_id,
+        } => new_format_docs.push((workspace_id, object_id, doc_id)),
+        DocIdKeyFormat::V0 { object_id } => old_format_docs.push((key.to_vec(), object_id, doc_id)),
+        // The key is kept, because it might be of either format
+        DocIdKeyFormat::Unknown { object_id, .. } => {
+          live_object_ids.insert(object_id);
+          live_object_ids.insert(String::from_utf8_lossy(oid_from_key(key)).to_string());
+        },
+      }
+    }
+
+    let mut report = GarbageCollectReport {
+      dry_run: options.dry_run,
+      ..Default::default()
+    };
+    let mut new_format_doc_ids = HashSet::new();
+    for (workspace_id, object_id, doc_id) in new_format_docs {
+      new_format_doc_ids.insert(doc_id);
+      let is_deleted = options
+        .existing_workspace_ids
+        .as_ref()
+        .is_some_and(|workspace_ids| !workspace_ids.contains(&workspace_id));
+      if !is_deleted {
+        live_object_ids.insert(object_id);
+        continue;
+      }
+
+      report.reclaimed_bytes += doc_bytes(self, doc_id)? + snapshot_bytes(self, uid, &object_id)?;
+      if !options.dry_run {
+        self.delete_doc(uid, &workspace_id, &object_id)?;
+        self.remove(make_snapshot_id_key(&uid_bytes, object_id.as_bytes()).as_ref())?;
+      }
+      report.removed_objects.push(RemovedObject {
+        workspace_id,
+        object_id,
+      });
+    }
+
+    for (key, object_id, doc_id) in old_format_docs {
+      let is_migrated = new_format_doc_ids.contains(&doc_id);
+      let has_doc_state = self.get(make_doc_state_key(doc_id).as_ref())?.is_some();
+      if !is_migrated && has_doc_state {
+        live_object_ids.insert(object_id);
+        continue;
+      }
+
+      report.reclaimed_bytes += (key.len() + DOC_ID_LEN) as u64;
+      if !options.dry_run {
+        self.remove(&key)?;
+        // The data of a migrated document belongs to the new key
+        if !is_migrated {
+          let start = make_doc_start_key(doc_id);
+          let end = make_doc_end_key(doc_id);
+          self.remove_range(start.as_ref(), end.as_ref())?;
+        }
+      }
+      report.orphaned_doc_ids.push(object_id);
+    }
+
+    let mut snapshot_id_prefix = vec![SNAPSHOT_SPACE, SNAPSHOT_SPACE_OBJECT];
+    snapshot_id_prefix.extend_from_slice(&uid_bytes);
+    let snapshot_object_ids = self
+      .range(snapshot_id_prefix.as_slice()..[SNAPSHOT_SPACE, SNAPSHOT_SPACE_OBJECT + 1].as_slice())?
+      .filter(|entry| entry.key().starts_with(&snapshot_id_prefix))
+      .filter(|entry| !is_snapshot_update_key(entry.key()))
+      .map(|entry| {
+        let key = entry.key();
+        String::from_utf8_lossy(&key[10..key.len() - 1]).to_string()
+      })
+      .collect::<Vec<_>>();
+    let removed_object_ids = report
+      .removed_objects
+      .iter()
+      .map(|object| object.object_id.as_str())
+      .collect::<HashSet<_>>();
+    for object_id in snapshot_object_ids {
+      if live_object_ids.contains(&object_id) || removed_object_ids.contains(object_id.as_str()) {
+        continue;
+      }
+
+      report.reclaimed_bytes += snapshot_bytes(self, uid, &object_id)?;
+      if !options.dry_run {
+        self.delete_all_snapshots(uid, &object_id)?;
+        self.remove(make_snapshot_id_key(&uid_bytes, object_id.as_bytes()).as_ref())?;
+      }
+      report.orphaned_snapshots.push(object_id);
+    }
+    Ok(report)
+  }
+}
+
+fn doc_bytes<'a, S>(store: &S, doc_id: DocID) -> Result<u64, PersistenceError>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  let start = make_doc_start_key(doc_id);
+  let end = make_doc_end_key(doc_id);
+  Ok(
+    store
+      .range(start.as_ref()..end.as_ref())?
+      .map(|entry| entry.value().len() as u64)
+      .sum(),
+  )
+}
+
+fn snapshot_bytes<'a, S>(store: &S, uid: i64, object_id: &str) -> Result<u64, PersistenceError>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  let Some(snapshot_id) = get_snapshot_id(uid, store, object_id) else {
+    return Ok(0);
+  };
+  let start = make_snapshot_update_key(snapshot_id, 0);
+  let end = make_snapshot_update_key(snapshot_id, Clock::MAX);
+  Ok(
+    store
+      .range(start.as_ref()..=end.as_ref())?
+      .map(|entry| entry.value().len() as u64)
+      .sum(),
+  )
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GarbageCollectOptions {
+  /// The workspaces of the user that still exist. The objects of the other workspaces are
+  /// removed. If [None], the objects of every workspace are kept.
+  pub existing_workspace_ids: Option<HashSet<String>>,
+  /// Reports what would be removed without removing it.
+  pub dry_run: bool,
+}
+
+impl GarbageCollectOptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn existing_workspace_ids<I>(mut self, workspace_ids: I) -> Self
+  where
+    I: IntoIterator<Item = String>,
+  {
+    self.existing_workspace_ids = Some(workspace_ids.into_iter().collect());
+    self
+  }
+
+  pub fn dry_run(mut self, dry_run: bool) -> Self {
+    self.dry_run = dry_run;
+    self
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GarbageCollectReport {
+  pub dry_run: bool,
+  /// The objects of the deleted workspaces.
+  pub removed_objects: Vec<RemovedObject>,
+  /// The object ids of the removed doc id mappings of the old key format.
+  pub orphaned_doc_ids: Vec<String>,
+  /// The object ids of the removed snapshots.
+  pub orphaned_snapshots: Vec<String>,
+  /// The size of the values that are removed, or would be removed in a dry run.
+  pub reclaimed_bytes: u64,
+}
+
+impl GarbageCollectReport {
+  pub fn is_empty(&self) -> bool {
+    self.removed_objects.is_empty()
+      && self.orphaned_doc_ids.is_empty()
+      && self.orphaned_snapshots.is_empty()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedObject {
+  pub workspace_id: String,
+  pub object_id: String,
+}
@@ -0,0 +1,375 @@
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use serde::{Deserialize, Serialize};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, ReadTxn, StateVector, Transact, Update};
+
+use crate::local_storage::kv::doc::{CollabKVAction, get_doc_id};
+use crate::local_storage::kv::keys::*;
+use crate::local_storage::kv::*;
+
+impl<'a, T> IntegrityAction<'a> for T
+where
+  T: KVStore<'a> + 'a,
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+}
+
+/// Verifies that the persisted documents can be loaded, and repairs the ones that can't.
+pub trait IntegrityAction<'a>: CollabKVAction<'a>
+where
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+  /// Verifies the documents of every workspace of the user. `collab_type_of` returns the type of
+  /// an object, given its workspace id and object id, which is used to check that the document
+  /// contains the data its type requires. The check is skipped if it returns [None].
+  fn verify_all(
+    &self,
+    uid: i64,
+    collab_type_of: &dyn Fn(&str, &str) -> Option<CollabType>,
+  ) -> Result<IntegrityReport, PersistenceError> {
+    let mut workspace_ids = self.get_all_workspace_ids()?;
+    workspace_ids.sort();
+    let mut objects = vec![];
+    for workspace_id in workspace_ids {
+      let mut object_ids = self
+        .get_all_object_ids(uid, &workspace_id)?
+        .collect::<Vec<String>>();
+      object_ids.sort();
+      for object_id in object_ids {
+        let collab_type = collab_type_of(&workspace_id, &object_id);
+        objects.push(self.verify_doc(uid, &workspace_id, &object_id, collab_type.as_ref())?);
+      }
+    }
+    Ok(IntegrityReport { objects })
+  }
+
+  /// Verifies that the doc state and every update of the document can be decoded and applied,
+  /// that the stored state vector matches the doc state, and that the document contains the data
+  /// required by the given [CollabType].
+  fn verify_doc(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    object_id: &str,
+    collab_type: Option<&CollabType>,
+  ) -> Result<ObjectIntegrityReport, PersistenceError> {
+    let doc_id = get_doc_id(uid, self, workspace_id, object_id).ok_or_else(|| {
+      PersistenceError::RecordNotFound(format!(
+        "doc with given object id: {:?} is not found",
+        object_id
+      ))
+    })?;
+    let inspection = inspect_doc(self, doc_id)?;
+    let mut issues = inspection.issues;
+    if let Some(collab_type) = collab_type {
+      let doc_state = inspection
+        .doc
+        .transact()
+        .encode_state_as_update_v1(&StateVector::default());
+      let result = Collab::new_with_source(
+        CollabOrigin::Empty,
+        object_id,
+        DataSource::DocStateV1(doc_state),
+        vec![],
+        false,
+      )
+      .map_err(|err| err.to_string())
+      .and_then(|collab| {
+        collab_type
+          .validate_require_data(&collab)
+          .map_err(|err| err.to_string())
+      });
+      if let Err(err) = result {
+        issues.push(IntegrityIssue::MissingRequiredData(err));
+      }
+    }
+
+    Ok(ObjectIntegrityReport {
+      workspace_id: workspace_id.to_string(),
+      object_id: object_id.to_string(),
+      update_count: inspection.update_count,
+      issues,
+    })
+  }
+
+  /// Repairs the documents of every workspace of the user that have issues that can be repaired.
+  /// See [IntegrityAction::repair_doc].
+  fn repair_all(&self, uid: i64) -> Result<Vec<RepairReport>, PersistenceError> {
+    let report = self.verify_all(uid, &|_, _| None)?;
+    let mut repairs = vec![];
+    for object in report.objects {
+      if object.issues.iter().any(|issue| issue.is_repairable()) {
+        repairs.push(self.repair_doc(uid, &object.workspace_id, &object.object_id)?);
+      }
+    }
+    Ok(repairs)
+  }
+
+  /// Rebuilds the doc state of the document from the records that can be decoded and applied,
+  /// and removes its updates. The records that are dropped are moved to the quarantine, see
+  /// [IntegrityAction::get_quarantined_updates].
+  ///
+  /// Call it within a write transaction, so the new doc state and the quarantine are committed
+  /// together. The documents of an encrypted workspace are repaired through an
+  /// [crate::local_storage::kv::encryption::EncryptedKVStore], which also encrypts the quarantine.
+  fn repair_doc(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<RepairReport, PersistenceError> {
+    let doc_id = get_doc_id(uid, self, workspace_id, object_id).ok_or_else(|| {
+      PersistenceError::RecordNotFound(format!(
+        "doc with given object id: {:?} is not found",
+        object_id
+      ))
+    })?;
+    let inspection = inspect_doc(self, doc_id)?;
+    let quarantined_at = chrono::Utc::now().timestamp_millis();
+    let mut dropped = vec![];
+    for (seq, record) in inspection.dropped_records.into_iter().enumerate() {
+      let key = make_quarantine_key(
+        &uid.to_be_bytes(),
+        object_id.as_bytes(),
+        quarantined_at,
+        seq as u32,
+      );
+      dropped.push(DroppedRecord {
+        clock: record.clock,
+        reason: record.reason.clone(),
+        len: record.data.len(),
+      });
+      let quarantined = QuarantinedUpdate {
+        clock: record.clock,
+        reason: record.reason,
+        quarantined_at,
+        data: record.data,
+      };
+      self.insert(key, bincode::serialize(&quarantined)?)?;
+    }
+
+    let txn = inspection.doc.transact();
+    let doc_state = txn.encode_state_as_update_v1(&StateVector::default());
+    let sv = txn.state_vector().encode_v1();
+    self.flush_doc_with(uid, workspace_id, object_id, &doc_state, &sv)?;
+    Ok(RepairReport {
+      workspace_id: workspace_id.to_string(),
+      object_id: object_id.to_string(),
+      kept_update_count: inspection.update_count - inspection.dropped_update_count,
+      dropped,
+    })
+  }
+
+  /// Returns the records of the document that were dropped by [IntegrityAction::repair_doc],
+  /// oldest first.
+  fn get_quarantined_updates(
+    &self,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<Vec<QuarantinedUpdate>, PersistenceError> {
+    let start = make_quarantine_key(&uid.to_be_bytes(), object_id.as_bytes(), 0, 0);
+    let end = make_quarantine_key(&uid.to_be_bytes(), object_id.as_bytes(), i64::MAX, u32::MAX);
+    let mut updates = vec![];
+    for entry in self.range(start.as_ref()..end.as_ref())? {
+      updates.push(bincode::deserialize(entry.value())?);
+    }
+    Ok(updates)
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+  pub objects: Vec<ObjectIntegrityReport>,
+}
+
+impl IntegrityReport {
+  pub fn is_ok(&self) -> bool {
+    self.objects.iter().all(|object| object.is_ok())
+  }
+
+  /// Returns the objects that have at least one issue.
+  pub fn broken_objects(&self) -> impl Iterator<Item = &ObjectIntegrityReport> {
+    self.objects.iter().filter(|object| !object.is_ok())
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectIntegrityReport {
+  pub workspace_id: String,
+  pub object_id: String,
+  pub update_count: usize,
+  pub issues: Vec<IntegrityIssue>,
+}
+
+impl ObjectIntegrityReport {
+  pub fn is_ok(&self) -> bool {
+    self.issues.is_empty()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum IntegrityIssue {
+  MissingDocState,
+  UndecodableDocState(String),
+  UnappliableDocState(String),
+  MissingStateVector,
+  UndecodableStateVector(String),
+  /// The stored state vector doesn't match the state vector of the doc state.
+  StateVectorMismatch,
+  UndecodableUpdate {
+    clock: Clock,
+    error: String,
+  },
+  UnappliableUpdate {
+    clock: Clock,
+    error: String,
+  },
+  /// Some updates depend on updates that are not persisted, so they are not part of the document.
+  MissingDependencies,
+  /// The document doesn't contain the data required by its [CollabType].
+  MissingRequiredData(String),
+}
+
+impl IntegrityIssue {
+  /// Returns true if [IntegrityAction::repair_doc] fixes the issue.
+  pub fn is_repairable(&self) -> bool {
+    !matches!(self, IntegrityIssue::MissingRequiredData(_))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepairReport {
+  pub workspace_id: String,
+  pub object_id: String,
+  /// The number of updates that were merged into the new doc state.
+  pub kept_update_count: usize,
+  pub dropped: Vec<DroppedRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DroppedRecord {
+  /// The clock of the dropped update, or [None] for the doc state and the updates with missing
+  /// dependencies.
+  pub clock: Option<Clock>,
+  pub reason: String,
+  pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedUpdate {
+  pub clock: Option<Clock>,
+  pub reason: String,
+  /// The timestamp in milliseconds.
+  pub quarantined_at: i64,
+  pub data: Vec<u8>,
+}
+
+struct DocInspection {
+  /// The document built from the records that can be decoded and applied.
+  doc: Doc,
+  update_count: usize,
+  dropped_update_count: usize,
+  issues: Vec<IntegrityIssue>,
+  dropped_records: Vec<DroppedData>,
+}
+
+struct DroppedData {
+  clock: Option<Clock>,
+  reason: String,
+  data: Vec<u8>,
+}
+
+fn inspect_doc<'a, S>(store: &S, doc_id: DocID) -> Result<DocInspection, PersistenceError>
+where
+  S: KVStore<'a>,
+  PersistenceError: From<<S as KVStore<'a>>::Error>,
+{
+  let doc = Doc::new();
+  let mut issues = vec![];
+  let mut dropped_records = vec![];
+  let mut txn = doc.transact_mut();
+
+  match store.get(make_doc_state_key(doc_id).as_ref())? {
+    None => issues.push(IntegrityIssue::MissingDocState),
+    Some(doc_state) => {
+      let result = Update::decode_v1(doc_state.as_ref())
+        .map_err(|err| IntegrityIssue::UndecodableDocState(err.to_string()))
+        .and_then(|update| {
+          txn
+            .apply_update(update)
+            .map_err(|err| IntegrityIssue::UnappliableDocState(err.to_string()))
+        });
+      if let Err(issue) = result {
+        dropped_records.push(DroppedData {
+          clock: None,
+          reason: format!("{:?}", issue),
+          data: doc_state.as_ref().to_vec(),
+        });
+        issues.push(issue);
+      }
+    },
+  }
+
+  let doc_state_sv = txn.state_vector();
+  match store.get(make_state_vector_key(doc_id).as_ref())? {
+    None => issues.push(IntegrityIssue::MissingStateVector),
+    Some(sv) => match StateVector::decode_v1(sv.as_ref()) {
+      Ok(sv) if sv == doc_state_sv => {},
+      Ok(_) => issues.push(IntegrityIssue::StateVectorMismatch),
+      Err(err) => issues.push(IntegrityIssue::UndecodableStateVector(err.to_string())),
+    },
+  }
+
+  let update_start = make_doc_update_key(doc_id, 0);
+  let update_end = make_doc_update_key(doc_id, Clock::MAX);
+  let mut update_count = 0;
+  let mut dropped_update_count = 0;
+  for encoded_update in store.range(update_start.as_ref()..update_end.as_ref())? {
+    update_count += 1;
+    let clock = Clock::from_be_bytes(clock_from_key(encoded_update.key()).try_into().unwrap());
+    let result = Update::decode_v1(encoded_update.value())
+      .map_err(|err| IntegrityIssue::UndecodableUpdate {
+        clock,
+        error: err.to_string(),
+      })
+      .and_then(|update| {
+        txn
+          .apply_update(update)
+          .map_err(|err| IntegrityIssue::UnappliableUpdate {
+            clock,
+            error: err.to_string(),
+          })
+      });
+    if let Err(issue) = result {
+      dropped_update_count += 1;
+      dropped_records.push(DroppedData {
+        clock: Some(clock),
+        reason: format!("{:?}", issue),
+        data: encoded_update.value().to_vec(),
+      });
+      issues.push(issue);
+    }
+  }
+
+  if let Some(pending) = txn.store().pending_update() {
+    issues.push(IntegrityIssue::MissingDependencies);
+    dropped_records.push(DroppedData {
+      clock: None,
+      reason: format!("{:?}", IntegrityIssue::MissingDependencies),
+      data: pending.update.encode_v1(),
+    });
+  }
+  drop(txn);
+
+  Ok(DocInspection {
+    doc,
+    update_count,
+    dropped_update_count,
+    issues,
+    dropped_records,
+  })
+}
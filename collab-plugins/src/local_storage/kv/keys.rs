@@ -17,10 +17,23 @@ use smallvec::{SmallVec, smallvec};
 //     DOC_SPACE_OBJECT_KEY     doc_id      TERMINATOR_HI_WATERMARK (state end)
 //     DOC_SPACE_OBJECT_KEY     doc_id      DOC_STATE_VEC (state vector)
 //     DOC_SPACE_OBJECT_KEY     doc_id      DOC_UPDATE clock TERMINATOR (update)
+//     DOC_SPACE_OBJECT_KEY     doc_id      DOC_LAST_WRITE (last write time)
 //
 // SNAPSHOT_SPACE
 //     SNAPSHOT_SPACE_OBJECT        object_id       TERMINATOR
 //     SNAPSHOT_SPACE_OBJECT_KEY    snapshot_id     SNAPSHOT_UPDATE(snapshot)
+//
+// OUTBOX_SPACE
+//     OUTBOX_SPACE_MSG     uid     object_id       TERMINATOR      msg_id (outbox message)
+//
+// QUARANTINE_SPACE
+//     QUARANTINE_SPACE_UPDATE  uid     object_id   TERMINATOR  quarantined_at  seq (dropped update)
+//
+// ENCRYPTION_SPACE
+//     ENCRYPTION_SPACE_CHECK   workspace_id    TERMINATOR (encryption key check)
+//
+// DOC_KEY_SPACE
+//     DOC_KEY_SPACE_V1     uid     workspace_id    object_id   TERMINATOR (workspace id)
 
 /// Prefix byte used for all of the yrs object entries.
 pub const DOC_SPACE: u8 = 1;
@@ -45,6 +58,9 @@ pub const REMOTE_DOC_STATE_VEC: u8 = 2;
 /// Tag byte within [DOC_SPACE_OBJECT_KEY] used to identify object's update entries.
 pub const DOC_UPDATE: u8 = 2;
 
+/// Tag byte within [DOC_SPACE_OBJECT_KEY] used to identify object's last write time entry.
+pub const DOC_LAST_WRITE: u8 = 3;
+
 /// Prefix byte used for snapshot id -> [SnapshotID] mapping index key space.
 pub const SNAPSHOT_SPACE: u8 = 2;
 
@@ -57,6 +73,24 @@ pub const SNAPSHOT_UPDATE: u8 = 1;
 pub const COLLAB_SPACE: u8 = 3;
 pub const COLLAB_SPACE_OBJECT: u8 = 0;
 
+/// Prefix byte used for the messages that are sent to the remote but not acknowledged yet.
+pub const OUTBOX_SPACE: u8 = 4;
+pub const OUTBOX_SPACE_MSG: u8 = 0;
+
+/// Prefix byte used for the updates that were dropped when repairing a document.
+pub const QUARANTINE_SPACE: u8 = 5;
+pub const QUARANTINE_SPACE_UPDATE: u8 = 0;
+
+/// Prefix byte used for the records that check the encryption key of a workspace.
+pub const ENCRYPTION_SPACE: u8 = 6;
+pub const ENCRYPTION_SPACE_CHECK: u8 = 0;
+
+/// Prefix byte used for the records that mark the object id -> [DocID] mapping keys of the new
+/// format, see [make_doc_id_key_v1]. The old and the new keys can't be told apart by their
+/// layout, because an object id of the old format might start with a uuid.
+pub const DOC_KEY_SPACE: u8 = 7;
+pub const DOC_KEY_SPACE_V1: u8 = 0;
+
 pub type DocID = u64;
 pub const DOC_ID_LEN: usize = 8;
 pub const DOC_STATE_KEY_LEN: usize = DOC_ID_LEN + 4;
@@ -71,6 +105,9 @@ pub const SNAPSHOT_UPDATE_KEY_PREFIX_LEN: usize = SNAPSHOT_ID_LEN + 4;
 pub type Clock = u32;
 pub const CLOCK_LEN: usize = 4;
 
+pub type OutboxMsgID = u64;
+pub const OUTBOX_MSG_ID_LEN: usize = 8;
+
 pub fn make_doc_id_key_v1(uid: &[u8], workspace_id: &[u8], object_id: &[u8]) -> Key<20> {
   // uuid: 16 bytes
   // uid: 8 bytes
@@ -91,6 +128,14 @@ pub fn make_doc_id_key_v0(uid: &[u8], object_id: &[u8]) -> Key<20> {
   Key(v)
 }
 
+/// The record of a doc id key of the new format. Its value is the workspace id of the key.
+pub fn make_doc_id_key_v1_record(doc_id_key: &[u8]) -> Key<20> {
+  // Replaces [DOC_SPACE, DOC_SPACE_OBJECT] of the doc id key
+  let mut v: SmallVec<[u8; 20]> = smallvec![DOC_KEY_SPACE, DOC_KEY_SPACE_V1];
+  v.write_all(&doc_id_key[2..]).unwrap();
+  Key(v)
+}
+
 pub fn oid_from_key(key: &[u8]) -> &[u8] {
   // [DOC_SPACE, DOC_SPACE_OBJECT] = 2
   // uid = 8
@@ -135,6 +180,14 @@ pub fn make_remote_state_vector_key(doc_id: DocID) -> Key<DOC_STATE_KEY_LEN> {
   Key(v)
 }
 
+// [1,1,  0,0,0,0,0,0,0,0,  3]
+pub fn make_doc_last_write_key(doc_id: DocID) -> Key<DOC_STATE_KEY_LEN> {
+  let mut v: SmallVec<[u8; DOC_STATE_KEY_LEN]> = smallvec![DOC_SPACE, DOC_SPACE_OBJECT_KEY];
+  v.write_all(&doc_id.to_be_bytes()).unwrap();
+  v.push(DOC_LAST_WRITE);
+  Key(v)
+}
+
 // [1,1,  0,0,0,0,0,0,0,0,  2   0,0,0,0,  0]
 pub fn make_doc_update_key(doc_id: DocID, clock: Clock) -> Key<DOC_UPDATE_KEY_LEN> {
   let mut v: SmallVec<[u8; DOC_UPDATE_KEY_LEN]> = smallvec![DOC_SPACE, DOC_SPACE_OBJECT_KEY];
@@ -199,6 +252,63 @@ pub fn make_collab_id_key(object_id: &[u8]) -> Key<20> {
   Key(v)
 }
 
+// [4,0, uid,  object_id,  0,  0,0,0,0,0,0,0,0]
+pub fn make_outbox_msg_key(uid: &[u8], object_id: &[u8], msg_id: OutboxMsgID) -> Key<32> {
+  let mut v: SmallVec<[u8; 32]> = smallvec![OUTBOX_SPACE, OUTBOX_SPACE_MSG];
+  v.write_all(uid).unwrap();
+  v.write_all(object_id).unwrap();
+  v.push(TERMINATOR);
+  v.write_all(&msg_id.to_be_bytes()).unwrap();
+  Key(v)
+}
+
+pub fn outbox_msg_id_from_key(key: &[u8]) -> Option<OutboxMsgID> {
+  let msg_id = key.get(key.len().checked_sub(OUTBOX_MSG_ID_LEN)?..)?;
+  Some(OutboxMsgID::from_be_bytes(msg_id.try_into().ok()?))
+}
+
+// [5,0, uid,  object_id,  0,  0,0,0,0,0,0,0,0,  0,0,0,0]
+pub fn make_quarantine_key(uid: &[u8], object_id: &[u8], quarantined_at: i64, seq: u32) -> Key<40> {
+  let mut v: SmallVec<[u8; 40]> = smallvec![QUARANTINE_SPACE, QUARANTINE_SPACE_UPDATE];
+  v.write_all(uid).unwrap();
+  v.write_all(object_id).unwrap();
+  v.push(TERMINATOR);
+  v.write_all(&quarantined_at.to_be_bytes()).unwrap();
+  v.write_all(&seq.to_be_bytes()).unwrap();
+  Key(v)
+}
+
+// [6,0, workspace_id,  0]
+pub fn make_encryption_check_key(workspace_id: &[u8]) -> Key<40> {
+  let mut v: SmallVec<[u8; 40]> = smallvec![ENCRYPTION_SPACE, ENCRYPTION_SPACE_CHECK];
+  v.write_all(workspace_id).unwrap();
+  v.push(TERMINATOR);
+  Key(v)
+}
+
+/// Returns true if the value of the key is the content of a document: its doc state, state
+/// vector, updates, snapshots, the messages of its outbox or its quarantined updates.
+pub fn is_doc_content_key(key: &[u8]) -> bool {
+  match key {
+    [DOC_SPACE, DOC_SPACE_OBJECT_KEY, ..] => {
+      key.len() != DOC_STATE_KEY_LEN || key[2 + DOC_ID_LEN] != DOC_LAST_WRITE
+    },
+    [SNAPSHOT_SPACE, SNAPSHOT_SPACE_OBJECT, ..] => is_snapshot_update_key(key),
+    [OUTBOX_SPACE, OUTBOX_SPACE_MSG, ..] => true,
+    [QUARANTINE_SPACE, QUARANTINE_SPACE_UPDATE, ..] => true,
+    _ => false,
+  }
+}
+
+/// The snapshot id keys and the snapshot update keys share the same prefix. Returns true if the
+/// key is a snapshot update key.
+pub fn is_snapshot_update_key(key: &[u8]) -> bool {
+  key.len() == SNAPSHOT_UPDATE_KEY_LEN
+    && key.starts_with(&[SNAPSHOT_SPACE, SNAPSHOT_SPACE_OBJECT])
+    && key[2 + SNAPSHOT_ID_LEN] == SNAPSHOT_UPDATE
+    && key[SNAPSHOT_UPDATE_KEY_LEN - 1] == TERMINATOR
+}
+
 #[repr(transparent)]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Key<const N: usize>(pub SmallVec<[u8; N]>);
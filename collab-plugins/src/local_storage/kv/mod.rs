@@ -2,10 +2,16 @@ pub use db::*;
 pub use error::*;
 pub use range::*;
 
+pub mod buffered;
 mod db;
 pub mod doc;
+pub mod encryption;
 pub mod error;
+pub mod gc;
+pub mod integrity;
 pub mod keys;
 pub mod oid;
+pub mod outbox;
 mod range;
 pub mod snapshot;
+pub mod stats;
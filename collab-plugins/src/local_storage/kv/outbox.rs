@@ -0,0 +1,65 @@
+use crate::local_storage::kv::keys::*;
+use crate::local_storage::kv::*;
+
+impl<'a, T> OutboxAction<'a> for T
+where
+  T: KVStore<'a>,
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+}
+
+/// Keeps the messages that are sent to the remote until the remote acknowledges them, so that
+/// they can be sent again after a restart.
+pub trait OutboxAction<'a>: KVStore<'a> + Sized
+where
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+  fn insert_outbox_msg(
+    &self,
+    uid: i64,
+    object_id: &str,
+    msg_id: OutboxMsgID,
+    data: &[u8],
+  ) -> Result<(), PersistenceError> {
+    let key = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), msg_id);
+    self.insert(key, data)?;
+    Ok(())
+  }
+
+  fn remove_outbox_msg(
+    &self,
+    uid: i64,
+    object_id: &str,
+    msg_id: OutboxMsgID,
+  ) -> Result<(), PersistenceError> {
+    let key = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), msg_id);
+    self.remove(key.as_ref())?;
+    Ok(())
+  }
+
+  /// Return the messages of the given object id, ordered by their id.
+  fn get_outbox_msgs(
+    &self,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<Vec<(OutboxMsgID, Vec<u8>)>, PersistenceError> {
+    let start = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), 0);
+    let end = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), OutboxMsgID::MAX);
+    let mut msgs = vec![];
+    for entry in self.range(start.as_ref()..end.as_ref())? {
+      let msg_id = outbox_msg_id_from_key(entry.key())
+        .ok_or_else(|| PersistenceError::InvalidData("invalid outbox message key".to_string()))?;
+      msgs.push((msg_id, entry.value().to_vec()));
+    }
+    Ok(msgs)
+  }
+
+  /// Remove all the messages of the given object id.
+  fn clear_outbox(&self, uid: i64, object_id: &str) -> Result<(), PersistenceError> {
+    let start = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), 0);
+    let end = make_outbox_msg_key(&uid.to_be_bytes(), object_id.as_bytes(), OutboxMsgID::MAX);
+    self.remove_range(start.as_ref(), end.as_ref())?;
+    self.remove(end.as_ref())?;
+    Ok(())
+  }
+}
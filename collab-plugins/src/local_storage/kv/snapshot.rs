@@ -4,6 +4,10 @@ use std::panic::AssertUnwindSafe;
 
 use crate::local_storage::kv::keys::*;
 use crate::local_storage::kv::*;
+use collab::core::collab::DataSource;
+use collab::core::diff::restore_collab;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
 use collab_entity::CollabType;
 use serde::{Deserialize, Serialize};
 use yrs::updates::encoder::{Encoder, EncoderV1};
@@ -70,6 +74,20 @@ where
     insert_snapshot_update(self, snapshot_id, object_id, snapshot_data)?;
     Ok(())
   }
+  /// Inserts the snapshot as it is, keeping its creation time.
+  fn insert_snapshot<K>(
+    &self,
+    uid: i64,
+    object_id: &K,
+    snapshot: &CollabSnapshot,
+  ) -> Result<(), PersistenceError>
+  where
+    K: AsRef<[u8]> + ?Sized + Debug,
+  {
+    let snapshot_id = self.create_snapshot_id(uid, object_id.as_ref())?;
+    insert_collab_snapshot(self, snapshot_id, object_id, snapshot)
+  }
+
   /// Return list of snapshots for the given object id.
   fn get_snapshots<K: AsRef<[u8]> + ?Sized>(&self, uid: i64, object_id: &K) -> Vec<CollabSnapshot> {
     let mut snapshots = vec![];
@@ -133,6 +151,39 @@ where
     Ok(())
   }
 
+  /// Remove the snapshots of the given object id that the retention doesn't keep. Returns the
+  /// number of removed snapshots.
+  fn prune_snapshots<K: AsRef<[u8]> + ?Sized>(
+    &self,
+    uid: i64,
+    object_id: &K,
+    retention: &SnapshotRetention,
+  ) -> Result<usize, PersistenceError> {
+    let Some(snapshot_id) = get_snapshot_id(uid, self, object_id) else {
+      return Ok(0);
+    };
+    let start = make_snapshot_update_key(snapshot_id, 0);
+    let end = make_snapshot_update_key(snapshot_id, Clock::MAX);
+    let mut keys = vec![];
+    let mut created_at = vec![];
+    for encoded_snapshot in self.range(start.as_ref()..=end.as_ref())? {
+      // The snapshots that can't be decoded are left untouched
+      if let Ok(snapshot) = CollabSnapshot::try_from(encoded_snapshot.value()) {
+        keys.push(encoded_snapshot.key().to_vec());
+        created_at.push(snapshot.created_at);
+      }
+    }
+
+    let mut removed = 0;
+    for (key, keep) in keys.iter().zip(retention.keep(&created_at)) {
+      if !keep {
+        self.remove(key)?;
+        removed += 1;
+      }
+    }
+    Ok(removed)
+  }
+
   /// Create a snapshot id for the given object id.
   fn create_snapshot_id<K: AsRef<[u8]> + ?Sized>(
     &self,
@@ -184,6 +235,26 @@ pub fn try_encode_snapshot<T: ReadTxn>(
   }
 }
 
+/// Restore the [Collab] to the state of the given snapshot.
+///
+/// The restore is applied as a new update: the changes made after the snapshot are reverted
+/// instead of being dropped, so the history is kept and the restore is persisted and synced like
+/// any other change. See [restore_collab].
+pub fn restore_snapshot(
+  collab: &mut Collab,
+  snapshot: &CollabSnapshot,
+) -> Result<(), PersistenceError> {
+  let snapshot_collab = Collab::new_with_source(
+    CollabOrigin::Empty,
+    collab.object_id(),
+    DataSource::DocStateV1(snapshot.data.clone()),
+    vec![],
+    false,
+  )?;
+  restore_collab(collab, &snapshot_collab)?;
+  Ok(())
+}
+
 pub trait SnapshotPersistence: Send + Sync {
   fn create_snapshot(
     &self,
@@ -218,3 +289,90 @@ impl TryFrom<&[u8]> for CollabSnapshot {
     Ok(bincode::deserialize(value)?)
   }
 }
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Decides which snapshots of an object are kept. A snapshot is kept if any of the rules keeps it,
+/// and every snapshot is kept if none of the rules is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotRetention {
+  /// Keep the N most recent snapshots.
+  pub keep_last: Option<usize>,
+  /// Keep the most recent snapshot of each of the last N days that have a snapshot.
+  pub keep_daily: Option<usize>,
+  /// Keep the most recent snapshot of each of the last N weeks that have a snapshot. The weeks
+  /// start on Monday.
+  pub keep_weekly: Option<usize>,
+}
+
+impl SnapshotRetention {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn keep_last(mut self, keep_last: Option<usize>) -> Self {
+    self.keep_last = keep_last;
+    self
+  }
+
+  pub fn keep_daily(mut self, keep_daily: Option<usize>) -> Self {
+    self.keep_daily = keep_daily;
+    self
+  }
+
+  pub fn keep_weekly(mut self, keep_weekly: Option<usize>) -> Self {
+    self.keep_weekly = keep_weekly;
+    self
+  }
+
+  /// Takes the creation time of the snapshots, in seconds and ordered from the oldest to the
+  /// newest, and returns whether each of them is kept. The days and weeks are in UTC.
+  pub fn keep(&self, created_at: &[i64]) -> Vec<bool> {
+    if self.keep_last.is_none() && self.keep_daily.is_none() && self.keep_weekly.is_none() {
+      return vec![true; created_at.len()];
+    }
+
+    let mut keep = vec![false; created_at.len()];
+    if let Some(keep_last) = self.keep_last {
+      keep
+        .iter_mut()
+        .rev()
+        .take(keep_last)
+        .for_each(|keep| *keep = true);
+    }
+    if let Some(keep_daily) = self.keep_daily {
+      keep_latest_per_period(created_at, keep_daily, &mut keep, |created_at| {
+        created_at.div_euclid(SECONDS_PER_DAY)
+      });
+    }
+    if let Some(keep_weekly) = self.keep_weekly {
+      // 1970-01-01 is a Thursday, shifting by 3 days makes the weeks start on Monday
+      keep_latest_per_period(created_at, keep_weekly, &mut keep, |created_at| {
+        (created_at.div_euclid(SECONDS_PER_DAY) + 3).div_euclid(7)
+      });
+    }
+    keep
+  }
+}
+
+fn keep_latest_per_period(
+  created_at: &[i64],
+  number_of_periods: usize,
+  keep: &mut [bool],
+  period_of: impl Fn(i64) -> i64,
+) {
+  let mut last_period = None;
+  let mut periods = 0;
+  for (index, created_at) in created_at.iter().enumerate().rev() {
+    let period = period_of(*created_at);
+    if last_period == Some(period) {
+      continue;
+    }
+    if periods == number_of_periods {
+      break;
+    }
+    keep[index] = true;
+    last_period = Some(period);
+    periods += 1;
+  }
+}
@@ -0,0 +1,151 @@
+use crate::local_storage::kv::doc::{CollabKVAction, get_doc_id};
+use crate::local_storage::kv::keys::*;
+use crate::local_storage::kv::snapshot::get_snapshot_id;
+use crate::local_storage::kv::*;
+
+impl<'a, T> StatsAction<'a> for T
+where
+  T: KVStore<'a> + 'a,
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+}
+
+/// Reports how much space the documents take in the storage.
+pub trait StatsAction<'a>: CollabKVAction<'a>
+where
+  PersistenceError: From<<Self as KVStore<'a>>::Error>,
+{
+  /// Returns the stats of every workspace of the user. The workspaces that don't contain any
+  /// object of the user are skipped.
+  fn get_storage_stats(&self, uid: i64) -> Result<StorageStats, PersistenceError> {
+    let mut workspace_ids = self.get_all_workspace_ids()?;
+    workspace_ids.sort();
+    let mut workspaces = vec![];
+    for workspace_id in workspace_ids {
+      let workspace = self.get_workspace_stats(uid, &workspace_id)?;
+      if !workspace.objects.is_empty() {
+        workspaces.push(workspace);
+      }
+    }
+    Ok(StorageStats { workspaces })
+  }
+
+  fn get_workspace_stats(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+  ) -> Result<WorkspaceStats, PersistenceError> {
+    let mut object_ids = self
+      .get_all_object_ids(uid, workspace_id)?
+      .collect::<Vec<String>>();
+    object_ids.sort();
+    let mut objects = vec![];
+    for object_id in object_ids {
+      if let Some(object) = self.get_object_stats(uid, workspace_id, &object_id)? {
+        objects.push(object);
+      }
+    }
+    Ok(WorkspaceStats {
+      workspace_id: workspace_id.to_string(),
+      objects,
+    })
+  }
+
+  /// Returns the stats of the document, or [None] if it doesn't exist. Unlike
+  /// [CollabKVAction::number_of_updates], it also reports the size of the stored values.
+  fn get_object_stats(
+    &self,
+    uid: i64,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Option<ObjectStats>, PersistenceError> {
+    let Some(doc_id) = get_doc_id(uid, self, workspace_id, object_id) else {
+      return Ok(None);
+    };
+    let doc_state_bytes = self
+      .get(make_doc_state_key(doc_id).as_ref())?
+      .map(|value| value.as_ref().len() as u64)
+      .unwrap_or(0);
+
+    let start = make_doc_update_key(doc_id, 0);
+    let end = make_doc_update_key(doc_id, Clock::MAX);
+    let (update_count, update_bytes) = count_values(self.range(start.as_ref()..=end.as_ref())?);
+
+    let (snapshot_count, snapshot_bytes) = match get_snapshot_id(uid, self, object_id) {
+      Some(snapshot_id) => {
+        let start = make_snapshot_update_key(snapshot_id, 0);
+        let end = make_snapshot_update_key(snapshot_id, Clock::MAX);
+        count_values(self.range(start.as_ref()..=end.as_ref())?)
+      },
+      None => (0, 0),
+    };
+
+    Ok(Some(ObjectStats {
+      object_id: object_id.to_string(),
+      update_count,
+      doc_state_bytes,
+      update_bytes,
+      snapshot_count,
+      snapshot_bytes,
+      last_write_at: get_doc_last_write(self, doc_id)?,
+    }))
+  }
+}
+
+/// Returns the number of entries and the total size of their values.
+fn count_values<E: KVEntry>(entries: impl Iterator<Item = E>) -> (usize, u64) {
+  entries.fold((0, 0), |(count, bytes), entry| {
+    (count + 1, bytes + entry.value().len() as u64)
+  })
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+  pub workspaces: Vec<WorkspaceStats>,
+}
+
+impl StorageStats {
+  pub fn total_bytes(&self) -> u64 {
+    self
+      .workspaces
+      .iter()
+      .map(|workspace| workspace.total_bytes())
+      .sum()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceStats {
+  pub workspace_id: String,
+  pub objects: Vec<ObjectStats>,
+}
+
+impl WorkspaceStats {
+  pub fn total_bytes(&self) -> u64 {
+    self.objects.iter().map(|object| object.total_bytes()).sum()
+  }
+
+  pub fn update_count(&self) -> usize {
+    self.objects.iter().map(|object| object.update_count).sum()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectStats {
+  pub object_id: String,
+  /// The number of updates that are not merged into the doc state yet.
+  pub update_count: usize,
+  pub doc_state_bytes: u64,
+  pub update_bytes: u64,
+  pub snapshot_count: usize,
+  pub snapshot_bytes: u64,
+  /// The timestamp in milliseconds. [None] if the document was not written since the last write
+  /// time is recorded.
+  pub last_write_at: Option<i64>,
+}
+
+impl ObjectStats {
+  pub fn total_bytes(&self) -> u64 {
+    self.doc_state_bytes + self.update_bytes + self.snapshot_bytes
+  }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+use crate::local_storage::kv::buffered::{
+  BufferedEntry, BufferedKVStore, CommittedKVStore, KVChanges, WriteLock,
+};
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
+
+/// A [KVTransactionDB] that keeps the data in a [BTreeMap]. The data is lost once the last clone
+/// of the database is dropped, so it's meant for tests and ephemeral sessions.
+#[derive(Clone, Default)]
+pub struct KVTransactionDBMemoryImpl {
+  data: Arc<RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
+  write_lock: Arc<WriteLock>,
+}
+
+impl KVTransactionDBMemoryImpl {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl CommittedKVStore for KVTransactionDBMemoryImpl {
+  fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, PersistenceError> {
+    Ok(self.data.read().unwrap().get(key).cloned())
+  }
+
+  fn range(&self, from: &[u8], to: Option<&[u8]>) -> Result<Vec<BufferedEntry>, PersistenceError> {
+    let upper = to.map_or(Bound::Unbounded, Bound::Excluded);
+    Ok(
+      self
+        .data
+        .read()
+        .unwrap()
+        .range::<[u8], _>((Bound::Included(from), upper))
+        .map(|(key, value)| BufferedEntry::new(key.clone(), value.clone()))
+        .collect(),
+    )
+  }
+
+  fn last_entry_before(
+    &self,
+    key: &[u8],
+    inclusive: bool,
+  ) -> Result<Option<BufferedEntry>, PersistenceError> {
+    let upper = if inclusive {
+      Bound::Included(key)
+    } else {
+      Bound::Excluded(key)
+    };
+    Ok(
+      self
+        .data
+        .read()
+        .unwrap()
+        .range::<[u8], _>((Bound::Unbounded, upper))
+        .next_back()
+        .map(|(key, value)| BufferedEntry::new(key.clone(), value.clone())),
+    )
+  }
+
+  fn commit(&self, changes: KVChanges) -> Result<(), PersistenceError> {
+    let mut data = self.data.write().unwrap();
+    for (key, value) in changes {
+      match value {
+        Some(value) => data.insert(key, value),
+        None => data.remove(&key),
+      };
+    }
+    Ok(())
+  }
+
+  fn write_lock(&self) -> &WriteLock {
+    &self.write_lock
+  }
+}
+
+impl KVTransactionDB for KVTransactionDBMemoryImpl {
+  type TransactionAction<'a> = BufferedKVStore<'a, Self>;
+
+  fn read_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    BufferedKVStore::new(self)
+  }
+
+  fn write_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    BufferedKVStore::new_write(self)
+  }
+
+  fn with_write_txn<'a, 'b, Output>(
+    &'b self,
+    f: impl FnOnce(&Self::TransactionAction<'a>) -> Result<Output, PersistenceError>,
+  ) -> Result<Output, PersistenceError>
+  where
+    'b: 'a,
+  {
+    let store = BufferedKVStore::new_write(self);
+    let result = f(&store)?;
+    store.commit_transaction()?;
+    Ok(result)
+  }
+
+  fn flush(&self) -> Result<(), PersistenceError> {
+    Ok(())
+  }
+}
@@ -1,8 +1,18 @@
 pub mod kv;
+pub mod memory;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod rocksdb;
 
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+pub mod sqlite;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod archive;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod collab_kv_db;
+
 #[cfg(target_arch = "wasm32")]
 pub mod indexeddb;
 
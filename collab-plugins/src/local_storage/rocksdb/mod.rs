@@ -1,4 +1,6 @@
 pub mod kv_impl;
+pub mod read_only;
 pub mod rocksdb_plugin;
-// pub mod snapshot_plugin;
+pub mod snapshot_plugin;
 pub mod util;
+mod write_behind;
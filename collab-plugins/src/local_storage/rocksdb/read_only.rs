@@ -0,0 +1,168 @@
+use std::ops;
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::sync::Arc;
+
+use rocksdb::Direction::Forward;
+use rocksdb::{
+  DB, DBIteratorWithThreadMode, IteratorMode, Options, ReadOptions, SnapshotWithThreadMode,
+};
+
+use crate::local_storage::kv::{KVStore, PersistenceError};
+use crate::local_storage::rocksdb::kv_impl::{KVTransactionDBRocksdbImpl, RocksdbEntry};
+
+impl KVTransactionDBRocksdbImpl {
+  /// Open the database at the given path in read-only mode. It can be opened while another
+  /// process holds the database, but it only sees the data that was written before it's opened.
+  pub fn open_read_only(
+    path: impl AsRef<Path>,
+  ) -> Result<KVReadOnlyDBRocksdbImpl, PersistenceError> {
+    let db_opts = Options::default();
+    let db = DB::open_for_read_only(&db_opts, &path, false)?;
+    Ok(KVReadOnlyDBRocksdbImpl {
+      db: Arc::new(db),
+      is_secondary: false,
+    })
+  }
+
+  /// Open the database at the given path as a secondary instance. The secondary instance keeps its
+  /// own info log in `secondary_path`, and sees the data written by the primary after calling
+  /// [KVReadOnlyDBRocksdbImpl::try_catch_up_with_primary].
+  pub fn open_as_secondary(
+    path: impl AsRef<Path>,
+    secondary_path: impl AsRef<Path>,
+  ) -> Result<KVReadOnlyDBRocksdbImpl, PersistenceError> {
+    let mut db_opts = Options::default();
+    // A secondary instance must keep all the files open, otherwise the files deleted by the
+    // primary can't be read anymore.
+    db_opts.set_max_open_files(-1);
+    let db = DB::open_as_secondary(&db_opts, path.as_ref(), secondary_path.as_ref())?;
+    Ok(KVReadOnlyDBRocksdbImpl {
+      db: Arc::new(db),
+      is_secondary: true,
+    })
+  }
+}
+
+/// A rocksdb database that is opened by [KVTransactionDBRocksdbImpl::open_read_only] or
+/// [KVTransactionDBRocksdbImpl::open_as_secondary].
+///
+/// It doesn't implement [crate::local_storage::kv::KVTransactionDB], so a write transaction can't
+/// be opened. The read methods of [crate::local_storage::kv::doc::CollabKVAction] work on its
+/// [KVReadOnlyDBRocksdbImpl::read_txn], and the write methods return
+/// [PersistenceError::ReadOnly].
+#[derive(Clone)]
+pub struct KVReadOnlyDBRocksdbImpl {
+  db: Arc<DB>,
+  is_secondary: bool,
+}
+
+impl KVReadOnlyDBRocksdbImpl {
+  pub fn is_secondary(&self) -> bool {
+    self.is_secondary
+  }
+
+  /// Returns a consistent view of the data at the time it's called.
+  pub fn read_txn(&self) -> RocksdbReadOnlyKVStore<'_> {
+    RocksdbReadOnlyKVStore(self.db.snapshot())
+  }
+
+  /// Applies the changes the primary made since the database was opened or caught up. The read
+  /// transactions that are already open don't see the changes. Only a secondary instance can
+  /// catch up with the primary.
+  pub fn try_catch_up_with_primary(&self) -> Result<(), PersistenceError> {
+    if !self.is_secondary {
+      return Err(PersistenceError::Internal(anyhow::anyhow!(
+        "Only a secondary instance can catch up with the primary"
+      )));
+    }
+    self.db.try_catch_up_with_primary()?;
+    Ok(())
+  }
+}
+
+/// Implementation of [KVStore] for [KVReadOnlyDBRocksdbImpl]. This is a wrapper around a rocksdb
+/// snapshot.
+pub struct RocksdbReadOnlyKVStore<'a>(SnapshotWithThreadMode<'a, DB>);
+
+impl<'a> KVStore<'a> for RocksdbReadOnlyKVStore<'a> {
+  type Range = RocksdbReadOnlyRange<'a>;
+  type Entry = RocksdbEntry;
+  type Value = Vec<u8>;
+  type Error = PersistenceError;
+
+  fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Self::Value>, Self::Error> {
+    Ok(self.0.get(key)?)
+  }
+
+  fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, _key: K, _value: V) -> Result<(), Self::Error> {
+    Err(PersistenceError::ReadOnly)
+  }
+
+  fn remove(&self, _key: &[u8]) -> Result<(), Self::Error> {
+    Err(PersistenceError::ReadOnly)
+  }
+
+  fn remove_range(&self, _from: &[u8], _to: &[u8]) -> Result<(), Self::Error> {
+    Err(PersistenceError::ReadOnly)
+  }
+
+  fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> Result<Self::Range, Self::Error> {
+    let mut opt = ReadOptions::default();
+    let mut from: &[u8] = &[];
+    let mut to: &[u8] = &[];
+    match range.start_bound() {
+      ops::Bound::Included(start) | ops::Bound::Excluded(start) => {
+        from = start.as_ref();
+        opt.set_iterate_lower_bound(start.as_ref());
+      },
+      ops::Bound::Unbounded => {},
+    };
+    match range.end_bound() {
+      ops::Bound::Included(end) | ops::Bound::Excluded(end) => {
+        opt.set_iterate_upper_bound(end.as_ref());
+        to = end.as_ref();
+      },
+      ops::Bound::Unbounded => {},
+    };
+    let iter = self.0.iterator_opt(IteratorMode::From(from, Forward), opt);
+    Ok(RocksdbReadOnlyRange {
+      // Safe to transmute because the lifetime of the iterator is the same as the lifetime of the
+      // snapshot.
+      inner: unsafe {
+        std::mem::transmute::<DBIteratorWithThreadMode<'_, DB>, DBIteratorWithThreadMode<'_, DB>>(
+          iter,
+        )
+      },
+      to: to.to_vec(),
+    })
+  }
+
+  fn next_back_entry(&self, key: &[u8]) -> Result<Option<Self::Entry>, Self::Error> {
+    let mut raw = self.0.raw_iterator_opt(ReadOptions::default());
+    raw.seek_for_prev(key);
+    Ok(
+      raw
+        .item()
+        .map(|(key, value)| RocksdbEntry::new(key.to_vec(), value.to_vec())),
+    )
+  }
+}
+
+pub struct RocksdbReadOnlyRange<'a> {
+  inner: DBIteratorWithThreadMode<'a, DB>,
+  to: Vec<u8>,
+}
+
+impl Iterator for RocksdbReadOnlyRange<'_> {
+  type Item = RocksdbEntry;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let (key, value) = self.inner.next()?.ok()?;
+    if !self.to.is_empty() && key.as_ref() >= self.to.as_slice() {
+      None
+    } else {
+      Some(RocksdbEntry::new(key.to_vec(), value.to_vec()))
+    }
+  }
+}
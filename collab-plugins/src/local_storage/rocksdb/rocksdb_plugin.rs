@@ -1,16 +1,20 @@
 use crate::CollabKVDB;
-use crate::local_storage::CollabPersistenceConfig;
-use crate::local_storage::kv::KVTransactionDB;
 use crate::local_storage::kv::doc::CollabKVAction;
+use crate::local_storage::kv::encryption::with_encryption;
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
+use crate::local_storage::rocksdb::snapshot_plugin::{LocalSnapshotState, create_local_snapshot};
+use crate::local_storage::rocksdb::write_behind::WriteBehindBuffer;
+use crate::local_storage::{CollabPersistenceConfig, DurabilityMode};
 
 use std::ops::Deref;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::atomic::{AtomicBool, AtomicU32};
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use collab::entity::EncodedCollab;
 use collab::preclude::{Collab, CollabPlugin};
-use collab_entity::CollabType;
+use collab_entity::{CollabType, CollabValidateError};
 use tracing::{error, info, warn};
 
 use collab::core::collab_plugin::CollabPluginType;
@@ -30,11 +34,22 @@ pub struct RocksdbDiskPlugin {
   collab_type: CollabType,
   collab_db: Weak<CollabKVDB>,
   did_init: Arc<AtomicBool>,
-  update_count: Arc<AtomicU32>,
-  #[allow(dead_code)]
+  compaction: Arc<CompactionState>,
+  snapshot: LocalSnapshotState,
+  write_behind: Arc<WriteBehindBuffer>,
   config: CollabPersistenceConfig,
 }
 
+/// Tracks the updates persisted since the last compaction.
+#[derive(Default)]
+struct CompactionState {
+  update_count: AtomicU32,
+  update_size: AtomicU64,
+  is_compacting: AtomicBool,
+  is_idle_timer_running: AtomicBool,
+  last_update_at: Mutex<Option<Instant>>,
+}
+
 impl Deref for RocksdbDiskPlugin {
   type Target = Weak<CollabKVDB>;
 
@@ -52,8 +67,14 @@ impl RocksdbDiskPlugin {
     collab_db: Weak<CollabKVDB>,
     config: CollabPersistenceConfig,
   ) -> Self {
-    let update_count = Arc::new(AtomicU32::new(0));
     let did_init = Arc::new(AtomicBool::new(false));
+    let write_behind = Arc::new(WriteBehindBuffer::new(
+      uid,
+      workspace_id.clone(),
+      object_id.clone(),
+      collab_db.clone(),
+      config.encryption.clone(),
+    ));
     Self {
       workspace_id,
       object_id,
@@ -61,7 +82,9 @@ impl RocksdbDiskPlugin {
       collab_db,
       uid,
       did_init,
-      update_count,
+      compaction: Arc::new(CompactionState::default()),
+      snapshot: LocalSnapshotState::default(),
+      write_behind,
       config,
     }
   }
@@ -83,19 +106,268 @@ impl RocksdbDiskPlugin {
     )
   }
 
-  fn increase_count(&self) {
-    let _update_count = self.update_count.fetch_add(1, SeqCst);
+  /// Merges the persisted updates of the collab into its document state, and removes them.
+  /// Returns the number of updates that were merged.
+  ///
+  /// The plugin calls it in the background once one of the compaction thresholds of the
+  /// [CollabPersistenceConfig] is reached.
+  pub fn compact(&self) -> Result<u32, PersistenceError> {
+    let collab_db = self
+      .collab_db
+      .upgrade()
+      .ok_or_else(|| PersistenceError::Internal(anyhow::anyhow!("collab_db is dropped")))?;
+    collab_db.with_write_txn(|w_db_txn| {
+      with_encryption!(w_db_txn, &self.workspace_id, &self.config.encryption, store => {
+        store.compact_doc(self.uid, &self.workspace_id, &self.object_id)
+      })
+    })
+  }
+
+  /// Creates a snapshot of the collab, and removes the old snapshots according to the snapshot
+  /// retention of the [CollabPersistenceConfig].
+  ///
+  /// If [CollabPersistenceConfig::enable_snapshot] is true, the plugin calls it in the background
+  /// every [CollabPersistenceConfig::snapshot_per_update] updates.
+  pub fn create_snapshot(&self) -> Result<(), PersistenceError> {
+    let collab_db = self
+      .collab_db
+      .upgrade()
+      .ok_or_else(|| PersistenceError::Internal(anyhow::anyhow!("collab_db is dropped")))?;
+    create_local_snapshot(
+      &collab_db,
+      self.uid,
+      &self.workspace_id,
+      &self.object_id,
+      &self.config.snapshot_retention,
+      self.config.encryption.as_ref(),
+    )
+  }
+
+  /// Commits the updates that are buffered by [DurabilityMode::WriteBehind]. It does nothing if
+  /// no update is buffered.
+  pub fn flush(&self) -> Result<(), PersistenceError> {
+    for update_len in self.write_behind.flush()? {
+      self.did_persist_update(update_len);
+    }
+    Ok(())
+  }
+
+  fn flush_write_behind(&self) {
+    if let Err(err) = self.flush() {
+      error!(
+        "[Rocksdb Plugin]: {}:{} flush buffered updates failed: {}",
+        self.object_id, self.collab_type, err
+      );
+    }
+  }
+
+  /// Flushes the buffered updates after the given delay. Without a runtime, the updates are
+  /// flushed once the buffer is full or the plugin is destroyed.
+  fn start_flush_timer(&self, delay: Duration) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+      return;
+    };
+    if self.write_behind.is_timer_running.swap(true, SeqCst) {
+      return;
+    }
+
+    let plugin = self.clone();
+    runtime.spawn(async move {
+      tokio::time::sleep(delay).await;
+      // The updates buffered from now on start a new timer
+      plugin.write_behind.is_timer_running.store(false, SeqCst);
+      let _ = tokio::task::spawn_blocking(move || plugin.flush_write_behind()).await;
+    });
+  }
+
+  fn persist_update(&self, object_id: &str, update: &[u8]) {
+    if let Some(db) = self.collab_db.upgrade() {
+      //Acquire a write transaction to ensure consistency
+      let result = db.with_write_txn(|w_db_txn| {
+        with_encryption!(w_db_txn, &self.workspace_id, &self.config.encryption, store => {
+          store.push_update(self.uid, self.workspace_id.as_str(), object_id, update)?
+        });
+        #[cfg(not(feature = "verbose_log"))]
+        tracing::trace!(
+          "[Rocksdb Plugin]: Collab {} {} persisting update",
+          object_id,
+          self.collab_type
+        );
+        #[cfg(feature = "verbose_log")]
+        {
+          use yrs::updates::decoder::Decode;
+          let update = yrs::Update::decode_v1(update).unwrap();
+          tracing::trace!(
+            "[Rocksdb Plugin]: Collab {} {} persisting update: {:#?}",
+            object_id,
+            self.collab_type,
+            update
+          );
+        }
+        Ok(())
+      });
+
+      match result {
+        Ok(_) => self.did_persist_update(update.len()),
+        Err(err) => {
+          error!(
+            "[Rocksdb Plugin]: {}:{} save update failed: {:?}",
+            object_id, self.collab_type, err
+          );
+        },
+      }
+    } else {
+      tracing::warn!("[Rocksdb Plugin]: collab_db is dropped");
+    };
+  }
+
+  fn snapshot_in_background(&self) {
+    if !self.snapshot.should_create_snapshot() {
+      return;
+    }
+
+    let plugin = self.clone();
+    let create_snapshot = move || {
+      let result = plugin.create_snapshot();
+      if let Err(err) = &result {
+        warn!(
+          "[Rocksdb Plugin]: create snapshot of {}:{} failed: {}",
+          plugin.object_id, plugin.collab_type, err
+        );
+      }
+      plugin.snapshot.did_create_snapshot(&result);
+    };
+    match tokio::runtime::Handle::try_current() {
+      Ok(runtime) => {
+        runtime.spawn_blocking(create_snapshot);
+      },
+      Err(_) => create_snapshot(),
+    }
+  }
+
+  fn did_persist_update(&self, update_len: usize) {
+    if self.config.enable_snapshot
+      && self
+        .snapshot
+        .did_persist_update(self.config.snapshot_per_update)
+    {
+      self.snapshot_in_background();
+    }
+
+    self.compaction.update_count.fetch_add(1, SeqCst);
+    self
+      .compaction
+      .update_size
+      .fetch_add(update_len as u64, SeqCst);
+    if self.reach_compaction_threshold() {
+      self.compact_in_background();
+    } else if let Some(timeout) = self.config.compact_idle_timeout {
+      self.start_idle_timer(timeout);
+    }
+  }
+
+  fn reach_compaction_threshold(&self) -> bool {
+    let update_count = self.compaction.update_count.load(SeqCst);
+    let update_size = self.compaction.update_size.load(SeqCst);
+    self
+      .config
+      .compact_per_update
+      .is_some_and(|compact_per_update| update_count >= compact_per_update)
+      || self
+        .config
+        .compact_update_size
+        .is_some_and(|compact_update_size| update_size >= compact_update_size)
+  }
+
+  fn compact_in_background(&self) {
+    if self.compaction.is_compacting.swap(true, SeqCst) {
+      return;
+    }
+
+    let plugin = self.clone();
+    match tokio::runtime::Handle::try_current() {
+      Ok(runtime) => {
+        runtime.spawn_blocking(move || plugin.run_compaction());
+      },
+      // There is no runtime to run the compaction on, so it blocks the caller instead.
+      Err(_) => plugin.run_compaction(),
+    }
+  }
+
+  fn run_compaction(&self) {
+    // The counters are reset before compacting, so the updates that are pushed meanwhile count
+    // towards the next compaction. If the compaction fails, it's retried once the thresholds are
+    // reached again.
+    self.compaction.update_count.store(0, SeqCst);
+    self.compaction.update_size.store(0, SeqCst);
+    match self.compact() {
+      Ok(update_count) => {
+        info!(
+          "[Rocksdb Plugin]: compacted {} updates of {}:{}",
+          update_count, self.object_id, self.collab_type
+        );
+      },
+      Err(err) => {
+        warn!(
+          "[Rocksdb Plugin]: compact {}:{} failed: {}",
+          self.object_id, self.collab_type, err
+        );
+      },
+    }
+    self.compaction.is_compacting.store(false, SeqCst);
+
+    // The updates pushed while compacting might have reached the thresholds already
+    if self.reach_compaction_threshold() {
+      self.compact_in_background();
+    }
+  }
+
+  /// Compacts the updates once the collab hasn't received any update for the given timeout.
+  fn start_idle_timer(&self, timeout: Duration) {
+    *self.compaction.last_update_at.lock().unwrap() = Some(Instant::now());
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+      return;
+    };
+    if self.compaction.is_idle_timer_running.swap(true, SeqCst) {
+      return;
+    }
+
+    let plugin = self.clone();
+    runtime.spawn(async move {
+      loop {
+        let last_update_at = *plugin.compaction.last_update_at.lock().unwrap();
+        let deadline = last_update_at.unwrap_or_else(Instant::now) + timeout;
+        if Instant::now() >= deadline {
+          break;
+        }
+        tokio::time::sleep_until(deadline.into()).await;
+      }
+      plugin.compaction.is_idle_timer_running.store(false, SeqCst);
+      if plugin.compaction.update_count.load(SeqCst) > 0 {
+        plugin.compact_in_background();
+      }
+    });
+  }
+
+  fn validate(&self, collab: &Collab) -> Result<(), CollabValidateError> {
+    self.collab_type.validate_require_data(collab)?;
+    if self.config.validate_schema {
+      self.collab_type.validate_schema(collab)?;
+    }
+    Ok(())
   }
 
   fn write_to_disk(&self, collab: &Collab) {
     if let Some(collab_db) = self.collab_db.upgrade() {
       let rocksdb_read = collab_db.read_txn();
       if !rocksdb_read.is_exist(self.uid, &self.workspace_id, &self.object_id) {
-        match self.collab_type.validate_require_data(collab) {
+        match self.validate(collab) {
           Ok(_) => {
             let txn = collab.transact();
             if let Err(err) = collab_db.with_write_txn(|w_db_txn| {
-              w_db_txn.create_new_doc(self.uid, &self.workspace_id, &self.object_id, &txn)?;
+              with_encryption!(w_db_txn, &self.workspace_id, &self.config.encryption, store => {
+                store.create_new_doc(self.uid, &self.workspace_id, &self.object_id, &txn)?
+              });
               info!(
                 "[Rocksdb Plugin]: created new doc {}, collab_type:{}",
                 self.object_id, self.collab_type
@@ -124,6 +396,21 @@ impl CollabPlugin for RocksdbDiskPlugin {
   fn did_init(&self, collab: &Collab, _object_id: &str) {
     self.did_init.store(true, SeqCst);
     self.write_to_disk(collab);
+
+    // The updates persisted by the previous sessions count towards the next compaction
+    if let Some(collab_db) = self.collab_db.upgrade() {
+      let update_count =
+        collab_db
+          .read_txn()
+          .number_of_updates(self.uid, &self.workspace_id, &self.object_id);
+      self
+        .compaction
+        .update_count
+        .store(update_count as u32, SeqCst);
+      if self.reach_compaction_threshold() {
+        self.compact_in_background();
+      }
+    }
   }
 
   fn receive_update(&self, object_id: &str, _txn: &TransactionMut, update: &[u8]) {
@@ -131,43 +418,26 @@ impl CollabPlugin for RocksdbDiskPlugin {
     if !self.did_init.load(SeqCst) {
       return;
     }
-    if let Some(db) = self.collab_db.upgrade() {
-      self.increase_count();
-      //Acquire a write transaction to ensure consistency
-      let result = db.with_write_txn(|w_db_txn| {
-        let _ = w_db_txn.push_update(self.uid, self.workspace_id.as_str(), object_id, update)?;
-        #[cfg(not(feature = "verbose_log"))]
-        tracing::trace!(
-          "[Rocksdb Plugin]: Collab {} {} persisting update",
-          object_id,
-          self.collab_type
-        );
-        #[cfg(feature = "verbose_log")]
-        {
-          use yrs::updates::decoder::Decode;
-          let update = yrs::Update::decode_v1(update).unwrap();
-          tracing::trace!(
-            "[Rocksdb Plugin]: Collab {} {} persisting update: {:#?}",
-            object_id,
-            self.collab_type,
-            update
-          );
+    match self.config.durability {
+      DurabilityMode::PerUpdate => self.persist_update(object_id, update),
+      DurabilityMode::WriteBehind {
+        max_updates,
+        max_delay,
+      } => {
+        if self.write_behind.push(update.to_vec()) >= max_updates {
+          self.flush_write_behind();
+        } else {
+          self.start_flush_timer(max_delay);
         }
-        Ok(())
-      });
-
-      if let Err(err) = result {
-        error!(
-          "[Rocksdb Plugin]: {}:{} save update failed: {:?}",
-          object_id, self.collab_type, err
-        );
-      }
-    } else {
-      tracing::warn!("[Rocksdb Plugin]: collab_db is dropped");
-    };
+      },
+    }
   }
 
   fn plugin_type(&self) -> CollabPluginType {
     CollabPluginType::Other("RocksdbDiskPlugin".to_string())
   }
+
+  fn destroy(&self) {
+    self.flush_write_behind();
+  }
 }
@@ -1,14 +1,14 @@
-use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::{Arc, Weak};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
 
+use crate::CollabKVDB;
 use crate::local_storage::kv::doc::CollabKVAction;
-use crate::local_storage::kv::snapshot::SnapshotPersistence;
+use crate::local_storage::kv::encryption::{EncryptionKeyProvider, with_encryption};
+use crate::local_storage::kv::snapshot::{SnapshotAction, SnapshotRetention};
 use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
-use crate::CollabKVDB;
 use collab::preclude::Collab;
-use collab_entity::CollabType;
 
-use yrs::{ReadTxn, StateVector};
+use yrs::ReadTxn;
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -38,88 +38,73 @@ impl TryFrom<u8> for SnapshotState {
   }
 }
 
-#[allow(dead_code)]
-#[derive(Clone)]
-pub struct CollabSnapshot {
+/// Tracks the updates persisted since the last local snapshot of a collab, and makes sure only
+/// one snapshot is created at a time.
+#[derive(Clone, Default)]
+pub(crate) struct LocalSnapshotState {
   state: Arc<AtomicU8>,
-  snapshot_persistence: Arc<dyn SnapshotPersistence>,
+  update_count: Arc<AtomicU32>,
 }
 
-#[allow(dead_code)]
-impl CollabSnapshot {
-  pub fn new(snapshot_persistence: Arc<dyn SnapshotPersistence>) -> Self {
-    let state = Arc::new(AtomicU8::new(SnapshotState::IDLE));
-    Self {
-      snapshot_persistence,
-      state,
-    }
-  }
-
+impl LocalSnapshotState {
   #[inline]
   fn swap_state(&self, state: SnapshotState) -> SnapshotState {
     let old = self.state.swap(state as u8, Ordering::Release);
     SnapshotState::try_from(old).unwrap()
   }
 
-  pub(crate) fn should_create_snapshot(&self) -> bool {
-    let old = self.swap_state(SnapshotState::Processing);
-    old != SnapshotState::Processing
+  /// Returns true if the persisted updates reach the given number of updates per snapshot.
+  pub(crate) fn did_persist_update(&self, snapshot_per_update: u32) -> bool {
+    let update_count = self.update_count.fetch_add(1, Ordering::SeqCst) + 1;
+    update_count >= snapshot_per_update
   }
 
-  pub(crate) fn create_snapshot(
-    &self,
-    weak_collab_db: Weak<CollabKVDB>,
-    uid: i64,
-    object_id: &str,
-    collab_type: &CollabType,
-  ) {
-    let weak_snapshot_persistence = Arc::downgrade(&self.snapshot_persistence);
-    let object_id = object_id.to_string();
-    let collab_type = *collab_type;
-    let state = self.state.clone();
-
-    if let (Some(db), Some(persistence)) = (
-      weak_collab_db.upgrade(),
-      weak_snapshot_persistence.upgrade(),
-    ) {
-      tokio::spawn(async move {
-        if let Err(err) =
-          Self::try_snapshot(db, persistence, uid, object_id, collab_type, state).await
-        {
-          tracing::error!("failed to create snapshot: {}", err);
-        }
-      });
+  /// Returns false if a snapshot is already being created.
+  pub(crate) fn should_create_snapshot(&self) -> bool {
+    let old = self.swap_state(SnapshotState::Processing);
+    if old == SnapshotState::Processing {
+      return false;
     }
+    self.update_count.store(0, Ordering::SeqCst);
+    true
   }
 
-  async fn try_snapshot(
-    db: Arc<CollabKVDB>,
-    persistence: Arc<dyn SnapshotPersistence>,
-    uid: i64,
-    object_id: String,
-    collab_type: CollabType,
-    state: Arc<AtomicU8>,
-  ) -> Result<(), PersistenceError> {
-    let result: Result<(), PersistenceError> = tokio::task::spawn_blocking(move || {
-      let mut collab = Collab::new(uid, object_id.clone(), "1", vec![], false);
-      db.read_txn()
-        .load_doc_with_txn(uid, &object_id, &mut collab.transact_mut())?;
-
-      // Generate the snapshot
-      let txn = collab.transact();
-      let encoded_v1 = txn.encode_state_as_update_v1(&StateVector::default());
-      persistence.create_snapshot(uid, &object_id, &collab_type, encoded_v1)?;
-      Ok(())
-    })
-    .await
-    .unwrap();
-
+  pub(crate) fn did_create_snapshot(&self, result: &Result<(), PersistenceError>) {
     let next_state = if result.is_ok() {
       SnapshotState::Idle
     } else {
       SnapshotState::Fail
     };
-    state.store(next_state as u8, Ordering::Release);
-    result
+    self.state.store(next_state as u8, Ordering::Release);
   }
 }
+
+/// Creates a snapshot of the persisted state of the collab, and removes the snapshots that the
+/// retention doesn't keep.
+pub fn create_local_snapshot(
+  collab_db: &CollabKVDB,
+  uid: i64,
+  workspace_id: &str,
+  object_id: &str,
+  retention: &SnapshotRetention,
+  encryption: Option<&Arc<dyn EncryptionKeyProvider>>,
+) -> Result<(), PersistenceError> {
+  // The snapshot is encoded from the deleted content too, so the garbage collection is skipped
+  let mut collab = Collab::new(uid, object_id, "1", vec![], true);
+  let read_txn = collab_db.read_txn();
+  with_encryption!(&read_txn, workspace_id, encryption, store => {
+    store.load_doc_with_txn(uid, workspace_id, object_id, &mut collab.transact_mut())?
+  });
+
+  let txn = collab.transact();
+  collab_db.with_write_txn(|w_db_txn| {
+    let removed = with_encryption!(w_db_txn, workspace_id, encryption, store => {
+      store.create_snapshot(uid, object_id, &txn, txn.snapshot())?;
+      store.prune_snapshots(uid, object_id, retention)?
+    });
+    if removed > 0 {
+      tracing::trace!("Removed {} snapshots of object:{}", removed, object_id);
+    }
+    Ok(())
+  })
+}
@@ -1,19 +1,25 @@
 use crate::CollabKVDB;
-use crate::local_storage::kv::KVTransactionDB;
+use crate::local_storage::collab_kv_db::CollabKVStore;
 use crate::local_storage::kv::doc::CollabKVAction;
+use crate::local_storage::kv::encryption::{EncryptionKeyProvider, with_encryption};
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
 use anyhow::anyhow;
 use collab::core::collab::DataSource;
 use collab::core::collab_plugin::CollabPersistence;
 use collab::entity::EncodedCollab;
 use collab::error::CollabError;
 use collab::preclude::Collab;
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
 use tracing::error;
+use yrs::TransactionMut;
 
 pub struct KVDBCollabPersistenceImpl {
   pub db: Weak<CollabKVDB>,
   pub uid: i64,
   pub workspace_id: String,
+  /// Decrypts and encrypts the collab with the keys of the provider, see
+  /// [crate::local_storage::CollabPersistenceConfig::encryption].
+  encryption: Option<Arc<dyn EncryptionKeyProvider>>,
 }
 
 impl KVDBCollabPersistenceImpl {
@@ -22,9 +28,27 @@ impl KVDBCollabPersistenceImpl {
       db,
       uid,
       workspace_id,
+      encryption: None,
     }
   }
 
+  pub fn with_encryption(mut self, encryption: Option<Arc<dyn EncryptionKeyProvider>>) -> Self {
+    self.encryption = encryption;
+    self
+  }
+
+  fn load_doc_with_txn(
+    &self,
+    rocksdb_read: &CollabKVStore,
+    object_id: &str,
+    txn: &mut TransactionMut,
+  ) -> Result<(), PersistenceError> {
+    with_encryption!(rocksdb_read, &self.workspace_id, &self.encryption, store => {
+      store.load_doc_with_txn(self.uid, self.workspace_id.as_str(), object_id, txn)?
+    });
+    Ok(())
+  }
+
   pub fn into_data_source(self) -> DataSource {
     DataSource::Disk(Some(Box::new(self)))
   }
@@ -47,9 +71,7 @@ impl CollabPersistence for KVDBCollabPersistenceImpl {
 
     if rocksdb_read.is_exist(self.uid, &self.workspace_id, &object_id) {
       let mut txn = collab.transact_mut();
-      if let Err(err) =
-        rocksdb_read.load_doc_with_txn(self.uid, self.workspace_id.as_str(), &object_id, &mut txn)
-      {
+      if let Err(err) = self.load_doc_with_txn(&rocksdb_read, &object_id, &mut txn) {
         error!("🔴 load doc:{} failed: {}", object_id, err);
       }
       drop(rocksdb_read);
@@ -65,19 +87,18 @@ impl CollabPersistence for KVDBCollabPersistenceImpl {
     encoded_collab: EncodedCollab,
   ) -> Result<(), CollabError> {
     if let Some(collab_db) = self.db.upgrade() {
-      let write_txn = collab_db.write_txn();
-      write_txn
-        .flush_doc(
-          self.uid,
-          self.workspace_id.as_str(),
-          object_id,
-          encoded_collab.state_vector.to_vec(),
-          encoded_collab.doc_state.to_vec(),
-        )
-        .map_err(|err| CollabError::Internal(err.into()))?;
-
-      write_txn
-        .commit_transaction()
+      collab_db
+        .with_write_txn(|write_txn| {
+          with_encryption!(write_txn, &self.workspace_id, &self.encryption, store => {
+            store.flush_doc(
+              self.uid,
+              self.workspace_id.as_str(),
+              object_id,
+              encoded_collab.state_vector.to_vec(),
+              encoded_collab.doc_state.to_vec(),
+            )
+          })
+        })
         .map_err(|err| CollabError::Internal(err.into()))?;
       Ok(())
     } else {
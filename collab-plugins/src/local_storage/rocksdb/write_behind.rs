@@ -0,0 +1,85 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, Weak};
+
+use tracing::error;
+
+use crate::CollabKVDB;
+use crate::local_storage::kv::doc::CollabKVAction;
+use crate::local_storage::kv::encryption::{EncryptionKeyProvider, with_encryption};
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
+
+/// Buffers the updates of a collab in memory, so they can be committed in a single transaction.
+/// See [crate::local_storage::DurabilityMode::WriteBehind].
+///
+/// The buffered updates are committed when the buffer is dropped.
+pub(crate) struct WriteBehindBuffer {
+  uid: i64,
+  workspace_id: String,
+  object_id: String,
+  collab_db: Weak<CollabKVDB>,
+  encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  /// The lock is held while the updates are committed, so the updates are committed in the
+  /// order they are received.
+  pending: Mutex<Vec<Vec<u8>>>,
+  pub(crate) is_timer_running: AtomicBool,
+}
+
+impl WriteBehindBuffer {
+  pub(crate) fn new(
+    uid: i64,
+    workspace_id: String,
+    object_id: String,
+    collab_db: Weak<CollabKVDB>,
+    encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  ) -> Self {
+    Self {
+      uid,
+      workspace_id,
+      object_id,
+      collab_db,
+      encryption,
+      pending: Mutex::new(vec![]),
+      is_timer_running: AtomicBool::new(false),
+    }
+  }
+
+  /// Buffers the update and returns the number of buffered updates.
+  pub(crate) fn push(&self, update: Vec<u8>) -> usize {
+    let mut pending = self.pending.lock().unwrap();
+    pending.push(update);
+    pending.len()
+  }
+
+  /// Commits the buffered updates in a single transaction. Returns the sizes of the committed
+  /// updates. If the transaction fails, the updates are kept in the buffer.
+  pub(crate) fn flush(&self) -> Result<Vec<usize>, PersistenceError> {
+    let mut pending = self.pending.lock().unwrap();
+    if pending.is_empty() {
+      return Ok(vec![]);
+    }
+    let collab_db = self
+      .collab_db
+      .upgrade()
+      .ok_or_else(|| PersistenceError::Internal(anyhow::anyhow!("collab_db is dropped")))?;
+    collab_db.with_write_txn(|w_db_txn| {
+      with_encryption!(w_db_txn, &self.workspace_id, &self.encryption, store => {
+        for update in pending.iter() {
+          store.push_update(self.uid, &self.workspace_id, &self.object_id, update)?;
+        }
+      });
+      Ok(())
+    })?;
+    Ok(pending.drain(..).map(|update| update.len()).collect())
+  }
+}
+
+impl Drop for WriteBehindBuffer {
+  fn drop(&mut self) {
+    if let Err(err) = self.flush() {
+      error!(
+        "[Rocksdb Plugin]: flush the buffered updates of {} failed: {}",
+        self.object_id, err
+      );
+    }
+  }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::local_storage::kv::buffered::{
+  BufferedEntry, BufferedKVStore, CommittedKVStore, KVChanges, WriteLock,
+};
+use crate::local_storage::kv::{KVTransactionDB, PersistenceError};
+
+/// A [KVTransactionDB] that stores the data in a single SQLite table. It's lighter than the
+/// rocksdb implementation, for the platforms where rocksdb is too heavy.
+///
+/// The keys are stored as BLOBs, which SQLite compares with `memcmp`. So the entries are ordered
+/// the same way as in rocksdb.
+#[derive(Clone)]
+pub struct KVTransactionDBSqliteImpl {
+  conn: Arc<Mutex<Connection>>,
+  write_lock: Arc<WriteLock>,
+}
+
+impl KVTransactionDBSqliteImpl {
+  /// Open a SQLite database at the given path. The file is created if it doesn't exist.
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    Self::with_connection(conn)
+  }
+
+  /// Open a SQLite database that lives in memory.
+  pub fn open_in_memory() -> Result<Self, PersistenceError> {
+    Self::with_connection(Connection::open_in_memory()?)
+  }
+
+  fn with_connection(conn: Connection) -> Result<Self, PersistenceError> {
+    conn.execute_batch(
+      "CREATE TABLE IF NOT EXISTS collab_kv (
+        key BLOB PRIMARY KEY NOT NULL,
+        value BLOB NOT NULL
+      ) WITHOUT ROWID;",
+    )?;
+    Ok(Self {
+      conn: Arc::new(Mutex::new(conn)),
+      write_lock: Arc::new(WriteLock::new()),
+    })
+  }
+}
+
+impl CommittedKVStore for KVTransactionDBSqliteImpl {
+  fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, PersistenceError> {
+    let conn = self.conn.lock().unwrap();
+    let value = conn
+      .query_row(
+        "SELECT value FROM collab_kv WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+      )
+      .optional()?;
+    Ok(value)
+  }
+
+  fn range(&self, from: &[u8], to: Option<&[u8]>) -> Result<Vec<BufferedEntry>, PersistenceError> {
+    let conn = self.conn.lock().unwrap();
+    let to_entry = |row: &rusqlite::Row| -> rusqlite::Result<BufferedEntry> {
+      Ok(BufferedEntry::new(row.get(0)?, row.get(1)?))
+    };
+    let entries = match to {
+      Some(to) => conn
+        .prepare_cached(
+          "SELECT key, value FROM collab_kv WHERE key >= ?1 AND key < ?2 ORDER BY key ASC",
+        )?
+        .query_map(params![from, to], to_entry)?
+        .collect::<Result<Vec<_>, _>>()?,
+      None => conn
+        .prepare_cached("SELECT key, value FROM collab_kv WHERE key >= ?1 ORDER BY key ASC")?
+        .query_map(params![from], to_entry)?
+        .collect::<Result<Vec<_>, _>>()?,
+    };
+    Ok(entries)
+  }
+
+  fn last_entry_before(
+    &self,
+    key: &[u8],
+    inclusive: bool,
+  ) -> Result<Option<BufferedEntry>, PersistenceError> {
+    let sql = if inclusive {
+      "SELECT key, value FROM collab_kv WHERE key <= ?1 ORDER BY key DESC LIMIT 1"
+    } else {
+      "SELECT key, value FROM collab_kv WHERE key < ?1 ORDER BY key DESC LIMIT 1"
+    };
+    let conn = self.conn.lock().unwrap();
+    let entry = conn
+      .query_row(sql, params![key], |row| {
+        Ok(BufferedEntry::new(row.get(0)?, row.get(1)?))
+      })
+      .optional()?;
+    Ok(entry)
+  }
+
+  fn commit(&self, changes: KVChanges) -> Result<(), PersistenceError> {
+    let mut conn = self.conn.lock().unwrap();
+    let txn = conn.transaction()?;
+    {
+      let mut upsert = txn.prepare_cached(
+        "INSERT INTO collab_kv (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+      )?;
+      let mut delete = txn.prepare_cached("DELETE FROM collab_kv WHERE key = ?1")?;
+      for (key, value) in changes {
+        match value {
+          Some(value) => upsert.execute(params![key, value])?,
+          None => delete.execute(params![key])?,
+        };
+      }
+    }
+    txn.commit()?;
+    Ok(())
+  }
+
+  fn write_lock(&self) -> &WriteLock {
+    &self.write_lock
+  }
+}
+
+impl KVTransactionDB for KVTransactionDBSqliteImpl {
+  type TransactionAction<'a> = BufferedKVStore<'a, Self>;
+
+  fn read_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    BufferedKVStore::new(self)
+  }
+
+  fn write_txn<'a, 'b>(&'b self) -> Self::TransactionAction<'a>
+  where
+    'b: 'a,
+  {
+    BufferedKVStore::new_write(self)
+  }
+
+  fn with_write_txn<'a, 'b, Output>(
+    &'b self,
+    f: impl FnOnce(&Self::TransactionAction<'a>) -> Result<Output, PersistenceError>,
+  ) -> Result<Output, PersistenceError>
+  where
+    'b: 'a,
+  {
+    let store = BufferedKVStore::new_write(self);
+    let result = f(&store)?;
+    store.commit_transaction()?;
+    Ok(result)
+  }
+
+  fn flush(&self) -> Result<(), PersistenceError> {
+    let conn = self.conn.lock().unwrap();
+    conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(()))?;
+    Ok(())
+  }
+}
@@ -1,3 +1,9 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::local_storage::kv::encryption::EncryptionKeyProvider;
+use crate::local_storage::kv::snapshot::SnapshotRetention;
+
 #[derive(Clone)]
 pub struct CollabPersistenceConfig {
   /// Enable snapshot. Default is [false].
@@ -5,6 +11,58 @@ pub struct CollabPersistenceConfig {
   /// Generate a snapshot every N updates
   /// Default is 100. The value must be greater than 0.
   pub snapshot_per_update: u32,
+  /// Decides which snapshots are kept after a new snapshot is created.
+  /// Default keeps the last 5 snapshots, and one snapshot per day for 7 days and per week for
+  /// 4 weeks.
+  pub snapshot_retention: SnapshotRetention,
+  /// Compact the persisted updates of a document into its document state after N updates.
+  /// The compaction runs on the blocking pool of the current tokio runtime, or on the thread that
+  /// applies the update when there is no runtime. Default is [None], e.g. use `Some(500)` to
+  /// enable it.
+  pub compact_per_update: Option<u32>,
+  /// Compact the persisted updates once their total size exceeds N bytes.
+  /// The compaction runs like [Self::compact_per_update]. Default is [None].
+  pub compact_update_size: Option<u64>,
+  /// Compact the persisted updates once the document hasn't received any update for the given
+  /// duration. It requires a tokio runtime. Default is [None].
+  pub compact_idle_timeout: Option<Duration>,
+  /// Encrypt the doc state, updates and snapshots with the keys of the provider.
+  /// Default is [None].
+  pub encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  /// Decides when the updates are committed to the disk. Default is [DurabilityMode::PerUpdate].
+  pub durability: DurabilityMode,
+  /// Validate the collab against the schema of its type before the collab is written to the disk
+  /// for the first time, see [collab_entity::CollabType::schema]. The collab is not written if the
+  /// validation fails. Default is [false].
+  pub validate_schema: bool,
+}
+
+/// Decides when the updates of a collab are committed to the disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurabilityMode {
+  /// Every update is committed in its own transaction as soon as it's received.
+  PerUpdate,
+  /// The updates are buffered in memory, and committed in a single transaction once `max_updates`
+  /// updates are buffered, or `max_delay` after the first buffered update. The delay requires a
+  /// tokio runtime. `max_updates` must be greater than 0.
+  ///
+  /// The buffered updates are committed when the plugin is destroyed or dropped, or when
+  /// [crate::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin::flush] is called. They
+  /// are lost if the process crashes before.
+  WriteBehind {
+    max_updates: usize,
+    max_delay: Duration,
+  },
+}
+
+impl DurabilityMode {
+  /// Commits the updates every 50 updates, or 100ms after the first buffered update.
+  pub fn write_behind() -> Self {
+    Self::WriteBehind {
+      max_updates: 50,
+      max_delay: Duration::from_millis(100),
+    }
+  }
 }
 
 impl CollabPersistenceConfig {
@@ -22,13 +80,59 @@ impl CollabPersistenceConfig {
     self.snapshot_per_update = snapshot_per_update;
     self
   }
+
+  pub fn snapshot_retention(mut self, snapshot_retention: SnapshotRetention) -> Self {
+    self.snapshot_retention = snapshot_retention;
+    self
+  }
+
+  pub fn compact_per_update(mut self, compact_per_update: Option<u32>) -> Self {
+    debug_assert!(compact_per_update != Some(0));
+    self.compact_per_update = compact_per_update;
+    self
+  }
+
+  pub fn compact_update_size(mut self, compact_update_size: Option<u64>) -> Self {
+    self.compact_update_size = compact_update_size;
+    self
+  }
+
+  pub fn compact_idle_timeout(mut self, compact_idle_timeout: Option<Duration>) -> Self {
+    self.compact_idle_timeout = compact_idle_timeout;
+    self
+  }
+
+  pub fn durability(mut self, durability: DurabilityMode) -> Self {
+    self.durability = durability;
+    self
+  }
+
+  pub fn validate_schema(mut self, validate_schema: bool) -> Self {
+    self.validate_schema = validate_schema;
+    self
+  }
+
+  pub fn encryption(mut self, encryption: Option<Arc<dyn EncryptionKeyProvider>>) -> Self {
+    self.encryption = encryption;
+    self
+  }
 }
 
 impl Default for CollabPersistenceConfig {
   fn default() -> Self {
     Self {
-      enable_snapshot: true,
+      enable_snapshot: false,
       snapshot_per_update: 100,
+      snapshot_retention: SnapshotRetention::new()
+        .keep_last(Some(5))
+        .keep_daily(Some(7))
+        .keep_weekly(Some(4)),
+      compact_per_update: None,
+      compact_update_size: None,
+      compact_idle_timeout: None,
+      encryption: None,
+      durability: DurabilityMode::PerUpdate,
+      validate_schema: false,
     }
   }
 }
@@ -0,0 +1,4 @@
+mod outbox_test;
+mod sink_retry_test;
+mod sync_protocol_test;
+mod util;
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use collab_plugins::CollabKVDB;
+use collab_plugins::cloud_storage::{
+  CollabKVDBOutbox, InProcessCollabServer, SinkConfig, SinkOutbox,
+};
+use collab_plugins::local_storage::kv::KVTransactionDB;
+use collab_plugins::local_storage::kv::outbox::OutboxAction;
+use serde_json::json;
+
+use crate::cloud::util::{
+  OBJECT_ID, OfflineServer, UID, WORKSPACE_ID, open_collab, open_db, open_remote_collab,
+  push_updates, server_json, update,
+};
+use crate::wait_until;
+
+#[test]
+fn outbox_keeps_msgs_in_order_test() {
+  let (_dir, db) = open_db();
+  let outbox = CollabKVDBOutbox::<String>::new(
+    UID,
+    WORKSPACE_ID.to_string(),
+    OBJECT_ID.to_string(),
+    Arc::downgrade(&db),
+  );
+  let other_outbox = CollabKVDBOutbox::<String>::new(
+    UID,
+    WORKSPACE_ID.to_string(),
+    "other".to_string(),
+    Arc::downgrade(&db),
+  );
+  outbox.push_msg(3, &"c".to_string()).unwrap();
+  outbox.push_msg(1, &"a".to_string()).unwrap();
+  outbox.push_msg(2, &"b".to_string()).unwrap();
+  other_outbox.push_msg(1, &"other".to_string()).unwrap();
+  assert_eq!(
+    outbox.load_msgs().unwrap(),
+    vec![
+      (1, "a".to_string()),
+      (2, "b".to_string()),
+      (3, "c".to_string())
+    ]
+  );
+
+  outbox.merge_msgs(1, &"ab".to_string(), &[2]).unwrap();
+  outbox.remove_msg(3).unwrap();
+  assert_eq!(outbox.load_msgs().unwrap(), vec![(1, "ab".to_string())]);
+
+  outbox.remove_all_msgs().unwrap();
+  assert!(outbox.load_msgs().unwrap().is_empty());
+  assert_eq!(other_outbox.load_msgs().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_unacked_msgs_after_restart_test() {
+  let (_dir, db) = open_db();
+  let collab = open_collab();
+
+  // The updates can't be sent while offline, so they stay in the outbox
+  let remote_collab = open_remote_collab(
+    Arc::new(OfflineServer),
+    &collab,
+    SinkConfig::new(),
+    Some(&db),
+  );
+  let remote_collab = push_updates(remote_collab, vec![update("a", "1"), update("b", "2")]).await;
+  assert!(!outbox_msgs(&db).is_empty());
+  drop(remote_collab);
+
+  // The updates are sent once the remote is reachable again, and removed from the outbox once
+  // they are acknowledged
+  let server = Arc::new(InProcessCollabServer::new());
+  let _remote_collab = open_remote_collab(server.clone(), &collab, SinkConfig::new(), Some(&db));
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1", "b": "2" })).await;
+  wait_until(|| outbox_msgs(&db).is_empty()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_unacked_msgs_on_replay_test() {
+  let (_dir, db) = open_db();
+  let collab = open_collab();
+  let updates = vec![update("a", "1"), update("b", "2"), update("c", "3")];
+  let remote_collab = open_remote_collab(
+    Arc::new(OfflineServer),
+    &collab,
+    SinkConfig::new(),
+    Some(&db),
+  );
+  let remote_collab = push_updates(remote_collab, updates).await;
+  drop(remote_collab);
+
+  let _remote_collab = open_remote_collab(
+    Arc::new(OfflineServer),
+    &collab,
+    SinkConfig::new(),
+    Some(&db),
+  );
+  assert_eq!(outbox_msgs(&db).len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clear_keeps_unacked_msgs_in_outbox_test() {
+  let (_dir, db) = open_db();
+  let collab = open_collab();
+  let remote_collab = open_remote_collab(
+    Arc::new(OfflineServer),
+    &collab,
+    SinkConfig::new(),
+    Some(&db),
+  );
+  let remote_collab = push_updates(remote_collab, vec![update("a", "1")]).await;
+  let remote_collab = tokio::task::spawn_blocking(move || {
+    remote_collab.clear();
+    remote_collab
+  })
+  .await
+  .unwrap();
+  drop(remote_collab);
+
+  // The cleared updates are still sent once the remote is reachable again
+  let server = Arc::new(InProcessCollabServer::new());
+  let _remote_collab = open_remote_collab(server.clone(), &collab, SinkConfig::new(), Some(&db));
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1" })).await;
+  wait_until(|| outbox_msgs(&db).is_empty()).await;
+}
+
+fn outbox_msgs(db: &CollabKVDB) -> Vec<(u64, Vec<u8>)> {
+  db.read_txn().get_outbox_msgs(UID, OBJECT_ID).unwrap()
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use collab_plugins::cloud_storage::{
+  ExponentialBackoff, FixedDelay, InProcessCollabServer, RemoteCollab, RetryPolicy, SinkConfig,
+  SinkMetrics,
+};
+use collab_plugins::connect_state::{CollabConnectReachability, CollabConnectState};
+use serde_json::json;
+
+use crate::cloud::util::{
+  OBJECT_ID, OfflineServer, open_collab, open_remote_collab, push_updates, server_json, update,
+};
+use crate::wait_until;
+
+#[test]
+fn exponential_backoff_test() {
+  let policy = ExponentialBackoff::new(Duration::from_millis(100), Duration::from_millis(500))
+    .with_jitter(0.0)
+    .with_max_attempts(Some(5));
+  let delays = (1..=5)
+    .map(|attempt| policy.next_delay(attempt))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    delays,
+    vec![
+      Some(Duration::from_millis(100)),
+      Some(Duration::from_millis(200)),
+      Some(Duration::from_millis(400)),
+      Some(Duration::from_millis(500)),
+      None,
+    ]
+  );
+
+  let policy = ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(10));
+  for attempt in 1..10 {
+    let delay = policy.next_delay(attempt).unwrap();
+    assert!(delay <= Duration::from_secs(12));
+  }
+
+  let policy = FixedDelay::new(Duration::from_secs(1)).with_max_attempts(Some(2));
+  assert_eq!(policy.next_delay(1), Some(Duration::from_secs(1)));
+  assert_eq!(policy.next_delay(2), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pause_sending_while_disconnected_test() {
+  let server = Arc::new(InProcessCollabServer::new());
+  let collab = open_collab();
+  let reachability = CollabConnectReachability::new();
+  reachability.set_state(CollabConnectState::Disconnected);
+
+  let remote_collab = open_remote_collab(server.clone(), &collab, SinkConfig::new(), None);
+  remote_collab.subscribe_reachability(&reachability);
+  let remote_collab = push_updates(remote_collab, vec![update("a", "1")]).await;
+  tokio::time::sleep(Duration::from_millis(500)).await;
+  assert_eq!(server_json(&server, OBJECT_ID), json!({}));
+  assert_eq!(remote_collab.sink_metrics().await.queue_depth, 1);
+
+  // The pending updates are sent as soon as the remote is reachable
+  reachability.set_state(CollabConnectState::Connected);
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1" })).await;
+  wait_for_metrics(&remote_collab, |metrics| metrics.queue_depth == 0).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn give_up_msg_after_max_attempts_test() {
+  let collab = open_collab();
+  let dead_letters = Arc::new(Mutex::new(vec![]));
+  let cloned_dead_letters = dead_letters.clone();
+  let mut config = SinkConfig::new()
+    .with_retry_policy(FixedDelay::new(Duration::from_millis(50)).with_max_attempts(Some(2)))
+    .with_dead_letter_handler(move |_msg_id, update: Vec<u8>, attempts| {
+      cloned_dead_letters.lock().unwrap().push((update, attempts));
+    });
+  config.timeout = Duration::from_millis(100);
+
+  let remote_collab = open_remote_collab(Arc::new(OfflineServer), &collab, config, None);
+  let update = update("a", "1");
+  let remote_collab = push_updates(remote_collab, vec![update.clone()]).await;
+  wait_for_metrics(&remote_collab, |metrics| metrics.dead_letter_count == 1).await;
+  assert_eq!(
+    remote_collab.sink_metrics().await,
+    SinkMetrics {
+      queue_depth: 0,
+      retry_count: 2,
+      dead_letter_count: 1,
+    }
+  );
+  assert_eq!(dead_letters.lock().unwrap().as_slice(), &[(update, 2)]);
+}
+
+async fn wait_for_metrics(remote_collab: &RemoteCollab, condition: impl Fn(&SinkMetrics) -> bool) {
+  for _ in 0..100 {
+    if condition(&remote_collab.sink_metrics().await) {
+      return;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+  }
+  panic!("timeout waiting for the sink metrics");
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use assert_json_diff::assert_json_eq;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::lock::RwLock;
+use collab::preclude::Collab;
+use collab_entity::proto::collab::{InitSync, UpdateSync};
+use collab_entity::{CollabObject, CollabType};
+use collab_plugins::cloud_storage::{InProcessCollabServer, RemoteCollab, SinkConfig};
+use serde_json::json;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, Map, ReadTxn, StateVector, Transact, Update};
+
+use crate::cloud::util::server_json;
+use crate::wait_until;
+
+const OBJECT_ID: &str = "object";
+
+#[test]
+fn init_sync_returns_missing_updates_test() {
+  let server = InProcessCollabServer::new();
+  let client = Doc::with_client_id(1);
+  client
+    .get_or_insert_map("data")
+    .insert(&mut client.transact_mut(), "a", "1");
+  server
+    .handle_update_sync(update_sync(full_state(&client)))
+    .unwrap();
+
+  let other_client = Doc::with_client_id(2);
+  other_client
+    .get_or_insert_map("data")
+    .insert(&mut other_client.transact_mut(), "b", "2");
+  server
+    .handle_update_sync(update_sync(full_state(&other_client)))
+    .unwrap();
+
+  let server_init = server
+    .handle_init_sync(init_sync(client.transact().state_vector()))
+    .unwrap();
+
+  // Only the update of the other client is returned
+  let doc = Doc::new();
+  let map = doc.get_or_insert_map("data");
+  doc
+    .transact_mut()
+    .apply_update(Update::decode_v1(&server_init.payload).unwrap())
+    .unwrap();
+  let txn = doc.transact();
+  assert_eq!(map.len(&txn), 1);
+  assert!(map.get(&txn, "b").is_some());
+
+  let state_vector = StateVector::decode_v1(&server_init.state_vector).unwrap();
+  assert_eq!(state_vector.get(&1), 1);
+  assert_eq!(state_vector.get(&2), 1);
+}
+
+#[test]
+fn init_sync_with_up_to_date_client_test() {
+  let server = InProcessCollabServer::new();
+  let client = Doc::with_client_id(1);
+  client
+    .get_or_insert_map("data")
+    .insert(&mut client.transact_mut(), "a", "1");
+  server
+    .handle_update_sync(update_sync(full_state(&client)))
+    .unwrap();
+
+  let server_init = server
+    .handle_init_sync(init_sync(client.transact().state_vector()))
+    .unwrap();
+  let doc = Doc::new();
+  let map = doc.get_or_insert_map("data");
+  doc
+    .transact_mut()
+    .apply_update(Update::decode_v1(&server_init.payload).unwrap())
+    .unwrap();
+  assert_eq!(map.len(&doc.transact()), 0);
+}
+
+#[tokio::test]
+async fn remote_collab_sync_test() {
+  let server = Arc::new(InProcessCollabServer::new());
+
+  // The first client syncs its local data to the server
+  let (collab_a, remote_a) = open_client(&server, 1);
+  collab_a.write().await.insert("a", "1");
+  remote_a.sync(Arc::downgrade(&collab_a)).await.unwrap();
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1" })).await;
+
+  // The second client edits offline, then syncs. It receives what it's missing and sends back
+  // what the server is missing.
+  let (collab_b, remote_b) = open_client(&server, 2);
+  collab_b.write().await.insert("b", "2");
+  let remote_update = remote_b.sync(Arc::downgrade(&collab_b)).await.unwrap();
+  assert_json_eq!(
+    collab_b.read().await.to_json_value(),
+    json!({ "a": "1", "b": "2" })
+  );
+  let remote_collab = Collab::new_with_source(
+    CollabOrigin::Empty,
+    OBJECT_ID,
+    DataSource::DocStateV1(remote_update),
+    vec![],
+    false,
+  )
+  .unwrap();
+  assert_json_eq!(remote_collab.to_json_value(), json!({ "a": "1" }));
+
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1", "b": "2" })).await;
+
+  // The update sent by the second client is broadcast to the first one
+  wait_until(|| {
+    collab_a
+      .try_read()
+      .is_ok_and(|collab| collab.to_json_value() == json!({ "a": "1", "b": "2" }))
+  })
+  .await;
+}
+
+#[tokio::test]
+async fn up_to_date_client_does_not_send_back_updates_test() {
+  let server = Arc::new(InProcessCollabServer::new());
+  let (collab, remote_collab) = open_client(&server, 1);
+  collab.write().await.insert("a", "1");
+  remote_collab.sync(Arc::downgrade(&collab)).await.unwrap();
+  wait_until(|| server_json(&server, OBJECT_ID) == json!({ "a": "1" })).await;
+
+  // The server already has all the local changes, so there is nothing to send back.
+  let remote_collab = RemoteCollab::new(
+    remote_collab_object(1),
+    server.clone(),
+    SinkConfig::new(),
+    Arc::downgrade(&collab),
+  );
+  remote_collab.sync(Arc::downgrade(&collab)).await.unwrap();
+  assert_eq!(remote_collab.sink_metrics().await.queue_depth, 0);
+}
+
+fn open_client(
+  server: &Arc<InProcessCollabServer>,
+  uid: i64,
+) -> (Arc<RwLock<Collab>>, RemoteCollab) {
+  let object = remote_collab_object(uid);
+  let collab = Arc::new(RwLock::from(Collab::new(
+    uid,
+    OBJECT_ID,
+    uid.to_string(),
+    vec![],
+    false,
+  )));
+  let remote_collab = RemoteCollab::new(
+    object,
+    server.clone(),
+    SinkConfig::new(),
+    Arc::downgrade(&collab),
+  );
+  (collab, remote_collab)
+}
+
+fn remote_collab_object(uid: i64) -> CollabObject {
+  CollabObject::new(
+    uid,
+    OBJECT_ID.to_string(),
+    CollabType::Unknown,
+    "workspace".to_string(),
+    uid.to_string(),
+  )
+}
+
+fn init_sync(state_vector: StateVector) -> InitSync {
+  InitSync {
+    object_id: OBJECT_ID.to_string(),
+    payload: state_vector.encode_v1(),
+    ..Default::default()
+  }
+}
+
+fn update_sync(update: Vec<u8>) -> UpdateSync {
+  UpdateSync {
+    object_id: OBJECT_ID.to_string(),
+    payload: update,
+    ..Default::default()
+  }
+}
+
+fn full_state(doc: &Doc) -> Vec<u8> {
+  doc
+    .transact()
+    .encode_state_as_update_v1(&StateVector::default())
+}
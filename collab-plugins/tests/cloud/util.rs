@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::lock::RwLock;
+use collab::preclude::Collab;
+use collab_entity::{CollabObject, CollabType};
+use collab_plugins::CollabKVDB;
+use collab_plugins::cloud_storage::{
+  InProcessCollabServer, RemoteCollab, RemoteCollabSnapshot, RemoteCollabState,
+  RemoteCollabStorage, RemoteUpdateReceiver, SinkConfig,
+};
+use serde_json::Value;
+use tempfile::TempDir;
+use yrs::{Doc, Map, ReadTxn, StateVector, Transact};
+
+pub const UID: i64 = 1;
+pub const WORKSPACE_ID: &str = "workspace";
+pub const OBJECT_ID: &str = "object";
+
+/// A remote that is never reachable.
+pub struct OfflineServer;
+
+#[async_trait]
+impl RemoteCollabStorage for OfflineServer {
+  fn is_enable(&self) -> bool {
+    false
+  }
+
+  async fn get_doc_state(&self, _object: &CollabObject) -> Result<DataSource, anyhow::Error> {
+    Err(anyhow!("offline"))
+  }
+
+  async fn get_snapshots(&self, _object_id: &str, _limit: usize) -> Vec<RemoteCollabSnapshot> {
+    vec![]
+  }
+
+  async fn get_collab_state(
+    &self,
+    _object_id: &str,
+  ) -> Result<Option<RemoteCollabState>, anyhow::Error> {
+    Err(anyhow!("offline"))
+  }
+
+  async fn create_snapshot(
+    &self,
+    _object: &CollabObject,
+    _snapshot: Vec<u8>,
+  ) -> Result<i64, anyhow::Error> {
+    Err(anyhow!("offline"))
+  }
+
+  async fn send_update(
+    &self,
+    _object: &CollabObject,
+    _id: u64,
+    _update: Vec<u8>,
+  ) -> Result<(), anyhow::Error> {
+    Err(anyhow!("offline"))
+  }
+
+  async fn send_init_sync(
+    &self,
+    _object: &CollabObject,
+    _id: u64,
+    _init_update: Vec<u8>,
+  ) -> Result<(), anyhow::Error> {
+    Err(anyhow!("offline"))
+  }
+
+  fn subscribe_remote_updates(&self, _object: &CollabObject) -> Option<RemoteUpdateReceiver> {
+    None
+  }
+}
+
+pub fn open_db() -> (TempDir, Arc<CollabKVDB>) {
+  let dir = TempDir::new().unwrap();
+  let db = Arc::new(CollabKVDB::open(dir.path()).unwrap());
+  (dir, db)
+}
+
+/// Opens the local collab of the [OBJECT_ID] object.
+pub fn open_collab() -> Arc<RwLock<Collab>> {
+  Arc::new(RwLock::from(Collab::new(
+    UID,
+    OBJECT_ID,
+    "1",
+    vec![],
+    false,
+  )))
+}
+
+/// Opens the [RemoteCollab] of the [OBJECT_ID] object. The messages that are not acknowledged by
+/// the remote are kept in the outbox of the given db, if any.
+pub fn open_remote_collab(
+  storage: Arc<dyn RemoteCollabStorage>,
+  collab: &Arc<RwLock<Collab>>,
+  config: SinkConfig,
+  outbox_db: Option<&Arc<CollabKVDB>>,
+) -> RemoteCollab {
+  let object = CollabObject::new(
+    UID,
+    OBJECT_ID.to_string(),
+    CollabType::Unknown,
+    WORKSPACE_ID.to_string(),
+    "1".to_string(),
+  );
+  match outbox_db {
+    Some(db) => RemoteCollab::new_with_outbox(
+      object,
+      storage,
+      config,
+      Arc::downgrade(collab),
+      Arc::downgrade(db),
+      None,
+    ),
+    None => RemoteCollab::new(object, storage, config, Arc::downgrade(collab)),
+  }
+}
+
+pub fn server_json(server: &InProcessCollabServer, object_id: &str) -> Value {
+  let Some(doc_state) = server.doc_state(object_id) else {
+    return Value::Null;
+  };
+  Collab::new_with_source(
+    CollabOrigin::Empty,
+    object_id,
+    DataSource::DocStateV1(doc_state),
+    vec![],
+    false,
+  )
+  .unwrap()
+  .to_json_value()
+}
+
+/// [RemoteCollab::push_update] blocks the current thread, so it runs outside of the runtime.
+pub async fn push_updates(remote_collab: RemoteCollab, updates: Vec<Vec<u8>>) -> RemoteCollab {
+  tokio::task::spawn_blocking(move || {
+    for update in updates {
+      remote_collab.push_update(&update).unwrap();
+    }
+    remote_collab
+  })
+  .await
+  .unwrap()
+}
+
+pub fn update(key: &str, value: &str) -> Vec<u8> {
+  let doc = Doc::new();
+  doc
+    .get_or_insert_map("data")
+    .insert(&mut doc.transact_mut(), key, value);
+  doc
+    .transact()
+    .encode_state_as_update_v1(&StateVector::default())
+}
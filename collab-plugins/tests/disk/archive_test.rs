@@ -0,0 +1,85 @@
+use collab_entity::CollabType;
+use collab_plugins::local_storage::archive::{
+  ARCHIVE_VERSION, export_workspace, import_workspace, read_archive_manifest,
+};
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::snapshot::{CollabSnapshot, SnapshotAction};
+use collab_plugins::local_storage::kv::{KVTransactionDB, PersistenceError};
+use uuid::Uuid;
+
+use crate::disk::util::{create_doc, load_text, rocks_db};
+
+#[test]
+fn export_import_workspace_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, 1, &workspace_id, "1", &["hello"]);
+  create_doc(&db, 1, &workspace_id, "2", &["world"]);
+  let snapshot = CollabSnapshot {
+    data: vec![1, 2, 3],
+    created_at: 100,
+  };
+  db.with_write_txn(|store| store.insert_snapshot(1, "1", &snapshot))
+    .unwrap();
+
+  let mut archive = vec![];
+  let collab_type_of = |object_id: &str| (object_id == "1").then_some(CollabType::Document);
+  export_workspace(&db, 1, &workspace_id, &collab_type_of, None, &mut archive).unwrap();
+
+  let manifest = read_archive_manifest(archive.as_slice()).unwrap();
+  assert_eq!(manifest.version, ARCHIVE_VERSION);
+  assert_eq!(manifest.workspace_id, workspace_id);
+  assert_eq!(manifest.objects.len(), 2);
+  assert_eq!(manifest.objects[0].collab_type, Some(CollabType::Document));
+  assert_eq!(manifest.objects[0].snapshot_count, 1);
+  assert_eq!(manifest.objects[1].collab_type, None);
+
+  // Import under another user and workspace
+  let (_other_path, other_db) = rocks_db();
+  let other_workspace_id = Uuid::new_v4().to_string();
+  import_workspace(&other_db, archive.as_slice(), 2, &other_workspace_id, None).unwrap();
+  assert_eq!(
+    load_text(&other_db.read_txn(), 2, &other_workspace_id, "1"),
+    "hello"
+  );
+  assert_eq!(
+    load_text(&other_db.read_txn(), 2, &other_workspace_id, "2"),
+    "world"
+  );
+  let snapshots = other_db.read_txn().get_snapshots(2, "1");
+  assert_eq!(snapshots.len(), 1);
+  assert_eq!(snapshots[0].data, vec![1, 2, 3]);
+  assert_eq!(snapshots[0].created_at, 100);
+
+  // The objects already exist
+  let result = import_workspace(&other_db, archive.as_slice(), 2, &other_workspace_id, None);
+  assert!(matches!(
+    result,
+    Err(PersistenceError::DocumentAlreadyExist)
+  ));
+}
+
+#[test]
+fn import_corrupted_archive_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, 1, &workspace_id, "1", &["hello"]);
+  let mut archive = vec![];
+  export_workspace(&db, 1, &workspace_id, &|_| None, None, &mut archive).unwrap();
+
+  let last = archive.len() - 1;
+  archive[last] ^= 0xff;
+  let (_other_path, other_db) = rocks_db();
+  let result = import_workspace(&other_db, archive.as_slice(), 1, &workspace_id, None);
+  assert!(matches!(result, Err(PersistenceError::InvalidArchive(_))));
+  assert!(!other_db.read_txn().is_exist(1, &workspace_id, "1"));
+
+  let result = import_workspace(
+    &other_db,
+    &archive[..archive.len() / 2],
+    1,
+    &workspace_id,
+    None,
+  );
+  assert!(result.is_err());
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use assert_json_diff::assert_json_eq;
+use collab_plugins::local_storage::CollabPersistenceConfig;
+
+use crate::disk::script::CollabPersistenceTest;
+use crate::wait_until;
+
+const DOC_ID: &str = "1";
+
+#[tokio::test]
+async fn compact_merges_updates_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(None)
+    .compact_update_size(None);
+  let (mut collab, plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..20 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 20);
+
+  assert_eq!(plugin.compact().unwrap(), 20);
+  assert_eq!(test.number_of_updates(DOC_ID), 0);
+
+  // The updates pushed after the compaction are kept
+  collab.insert("20", "20");
+  assert_eq!(test.number_of_updates(DOC_ID), 1);
+
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[tokio::test]
+async fn compaction_is_disabled_by_default_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, CollabPersistenceConfig::new());
+  for i in 0..600 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 600);
+}
+
+#[tokio::test]
+async fn compact_after_update_count_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(Some(10))
+    .compact_update_size(None);
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..25 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+
+  wait_until(|| test.number_of_updates(DOC_ID) < 10).await;
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[tokio::test]
+async fn compact_after_update_size_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(None)
+    .compact_update_size(Some(1024));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  collab.insert("text", "a".repeat(2048));
+
+  wait_until(|| test.number_of_updates(DOC_ID) == 0).await;
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[tokio::test]
+async fn compact_after_idle_timeout_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(None)
+    .compact_update_size(None)
+    .compact_idle_timeout(Some(Duration::from_millis(200)));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..5 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 5);
+
+  wait_until(|| test.number_of_updates(DOC_ID) == 0).await;
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[tokio::test]
+async fn compact_updates_of_previous_session_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(None)
+    .compact_update_size(None);
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..10 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  let expected = collab.to_json_value();
+  drop(collab);
+
+  // Opening the collab again with a lower threshold compacts the existing updates
+  let config = CollabPersistenceConfig::new()
+    .compact_per_update(Some(5))
+    .compact_update_size(None);
+  let (collab, _plugin) = test.open_collab(DOC_ID, config);
+  wait_until(|| test.number_of_updates(DOC_ID) == 0).await;
+  assert_json_eq!(collab.to_json_value(), expected);
+}
@@ -0,0 +1,285 @@
+use std::sync::{Arc, Mutex};
+
+use assert_json_diff::assert_json_eq;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::CollabPersistenceConfig;
+use collab_plugins::local_storage::archive::{export_workspace, import_workspace};
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::encryption::{
+  EncryptedKVStore, EncryptionKey, EncryptionKeyProvider,
+};
+use collab_plugins::local_storage::kv::integrity::IntegrityAction;
+use collab_plugins::local_storage::kv::keys::{
+  DOC_SPACE, OUTBOX_SPACE, QUARANTINE_SPACE, SNAPSHOT_SPACE, is_doc_content_key,
+};
+use collab_plugins::local_storage::kv::{KVEntry, KVStore, KVTransactionDB, PersistenceError};
+use yrs::{Doc, ReadTxn, StateVector, Text, Transact};
+
+use crate::disk::script::CollabPersistenceTest;
+use crate::disk::util::rocks_db;
+
+const DOC_ID: &str = "1";
+
+#[tokio::test]
+async fn encrypt_collab_test() {
+  let provider = TestKeyProvider::new(1);
+  let test = CollabPersistenceTest::new(encrypted_config(&provider));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, encrypted_config(&provider));
+  collab.insert("title", "top secret");
+  let expected = collab.to_json_value();
+  drop(collab);
+
+  assert!(!raw_doc_values(&test.db).is_empty());
+  for value in raw_doc_values(&test.db) {
+    assert!(!contains(&value, b"top secret"));
+  }
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+
+  // The workspace can't be opened with another key
+  let read_txn = test.db.read_txn();
+  let other_provider = TestKeyProvider::with_key(EncryptionKey::new(1, [2; 32]));
+  let result = EncryptedKVStore::open(&read_txn, &test.workspace_id, other_provider);
+  assert!(matches!(
+    result.err(),
+    Some(PersistenceError::InvalidEncryptionKey(_))
+  ));
+}
+
+#[tokio::test]
+async fn rotate_encryption_key_test() {
+  let provider = TestKeyProvider::new(1);
+  let test = CollabPersistenceTest::new(encrypted_config(&provider));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, encrypted_config(&provider));
+  for i in 0..10 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  let expected = collab.to_json_value();
+  drop(collab);
+
+  provider.rotate(2);
+  let count = test
+    .db
+    .clone()
+    .rotate_encryption_key_in_background(test.uid, test.workspace_id.clone(), provider.clone())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(count, raw_doc_values(&test.db).len());
+
+  // The previous key is not needed anymore
+  provider.remove_previous_keys();
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[test]
+fn encrypt_quarantined_updates_test() {
+  let provider = TestKeyProvider::new(1);
+  let test = CollabPersistenceTest::new(encrypted_config(&provider));
+  let (collab, _plugin) = test.open_collab(DOC_ID, encrypted_config(&provider));
+  drop(collab);
+
+  // The update can't be integrated into the doc, so the repair moves it to the quarantine
+  let update = update_with_missing_dependency("top secret");
+  let repair = test
+    .db
+    .with_write_txn(|txn| {
+      let store = EncryptedKVStore::open(txn, &test.workspace_id, provider.clone())?;
+      store.push_update(test.uid, &test.workspace_id, DOC_ID, &update)?;
+      store.repair_doc(test.uid, &test.workspace_id, DOC_ID)
+    })
+    .unwrap();
+  assert_eq!(repair.dropped.len(), 1);
+
+  assert_eq!(raw_values(&test.db, QUARANTINE_SPACE).len(), 1);
+  for value in raw_doc_values(&test.db) {
+    assert!(!contains(&value, b"top secret"));
+  }
+  let read_txn = test.db.read_txn();
+  let quarantined = EncryptedKVStore::open(&read_txn, &test.workspace_id, provider)
+    .unwrap()
+    .get_quarantined_updates(test.uid, DOC_ID)
+    .unwrap();
+  assert!(contains(&quarantined[0].data, b"top secret"));
+}
+
+#[cfg(feature = "postgres_plugin")]
+#[test]
+fn encrypt_outbox_msgs_test() {
+  use collab_plugins::cloud_storage::{CollabKVDBOutbox, SinkOutbox};
+
+  let provider = TestKeyProvider::new(1);
+  let test = CollabPersistenceTest::new(encrypted_config(&provider));
+  let outbox = CollabKVDBOutbox::<String>::new(
+    test.uid,
+    test.workspace_id.clone(),
+    DOC_ID.to_string(),
+    Arc::downgrade(&test.db),
+  )
+  .with_encryption(Some(provider));
+  outbox.push_msg(1, &"top secret".to_string()).unwrap();
+
+  assert_eq!(raw_values(&test.db, OUTBOX_SPACE).len(), 1);
+  for value in raw_doc_values(&test.db) {
+    assert!(!contains(&value, b"top secret"));
+  }
+  assert_eq!(
+    outbox.load_msgs().unwrap(),
+    vec![(1, "top secret".to_string())]
+  );
+}
+
+#[tokio::test]
+async fn export_import_encrypted_workspace_test() {
+  let provider = TestKeyProvider::new(1);
+  let test = CollabPersistenceTest::new(encrypted_config(&provider));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, encrypted_config(&provider));
+  collab.insert("title", "top secret");
+  let expected = collab.to_json_value();
+  drop(collab);
+
+  // The encrypted objects can't be exported without the keys
+  let result = export_workspace(
+    &test.db,
+    test.uid,
+    &test.workspace_id,
+    &|_| None,
+    None,
+    vec![],
+  );
+  assert!(matches!(
+    result,
+    Err(PersistenceError::EncryptedWorkspace(_))
+  ));
+
+  let mut archive = vec![];
+  export_workspace(
+    &test.db,
+    test.uid,
+    &test.workspace_id,
+    &|_| None,
+    Some(provider.clone()),
+    &mut archive,
+  )
+  .unwrap();
+
+  // The imported objects are encrypted again
+  let (_path, other_db) = rocks_db();
+  import_workspace(
+    &other_db,
+    archive.as_slice(),
+    test.uid,
+    &test.workspace_id,
+    Some(provider.clone()),
+  )
+  .unwrap();
+  assert!(!raw_doc_values(&other_db).is_empty());
+  for value in raw_doc_values(&other_db) {
+    assert!(!contains(&value, b"top secret"));
+  }
+  let read_txn = other_db.read_txn();
+  let store = EncryptedKVStore::open(&read_txn, &test.workspace_id, provider).unwrap();
+  let doc = Doc::new();
+  store
+    .load_doc(test.uid, &test.workspace_id, DOC_ID, &doc)
+    .unwrap();
+  let doc_state = doc
+    .transact()
+    .encode_state_as_update_v1(&StateVector::default());
+  let collab = Collab::new_with_source(
+    CollabOrigin::Empty,
+    DOC_ID,
+    DataSource::DocStateV1(doc_state),
+    vec![],
+    false,
+  )
+  .unwrap();
+  assert_json_eq!(collab.to_json_value(), expected);
+}
+
+struct TestKeyProvider {
+  /// The last key is the current key
+  keys: Mutex<Vec<EncryptionKey>>,
+}
+
+impl TestKeyProvider {
+  fn new(id: u32) -> Arc<Self> {
+    Self::with_key(EncryptionKey::new(id, [id as u8; 32]))
+  }
+
+  fn with_key(key: EncryptionKey) -> Arc<Self> {
+    Arc::new(Self {
+      keys: Mutex::new(vec![key]),
+    })
+  }
+
+  fn rotate(&self, id: u32) {
+    self
+      .keys
+      .lock()
+      .unwrap()
+      .push(EncryptionKey::new(id, [id as u8; 32]));
+  }
+
+  fn remove_previous_keys(&self) {
+    let mut keys = self.keys.lock().unwrap();
+    let current_key = keys.pop().unwrap();
+    *keys = vec![current_key];
+  }
+}
+
+impl EncryptionKeyProvider for TestKeyProvider {
+  fn current_key(&self, _workspace_id: &str) -> Result<EncryptionKey, PersistenceError> {
+    Ok(self.keys.lock().unwrap().last().cloned().unwrap())
+  }
+
+  fn get_key(
+    &self,
+    _workspace_id: &str,
+    key_id: u32,
+  ) -> Result<Option<EncryptionKey>, PersistenceError> {
+    let keys = self.keys.lock().unwrap();
+    Ok(keys.iter().find(|key| key.id == key_id).cloned())
+  }
+}
+
+fn encrypted_config(provider: &Arc<TestKeyProvider>) -> CollabPersistenceConfig {
+  CollabPersistenceConfig::new().encryption(Some(provider.clone()))
+}
+
+/// Returns an update that inserts the text after a change the update doesn't contain.
+fn update_with_missing_dependency(text: &str) -> Vec<u8> {
+  let doc = Doc::with_client_id(42);
+  let content = doc.get_or_insert_text("text");
+  content.insert(&mut doc.transact_mut(), 0, "a");
+  let state_vector = doc.transact().state_vector();
+  content.insert(&mut doc.transact_mut(), 1, text);
+  doc.transact().encode_state_as_update_v1(&state_vector)
+}
+
+/// Returns the values of the doc states, state vectors, updates, snapshots, outbox messages and
+/// quarantined updates as they are stored.
+fn raw_doc_values(db: &CollabKVDB) -> Vec<Vec<u8>> {
+  [DOC_SPACE, SNAPSHOT_SPACE, OUTBOX_SPACE, QUARANTINE_SPACE]
+    .into_iter()
+    .flat_map(|space| raw_values(db, space))
+    .collect()
+}
+
+/// Returns the values of the given key space that hold the content of a document.
+fn raw_values(db: &CollabKVDB, space: u8) -> Vec<Vec<u8>> {
+  let from = [space];
+  let to = [space + 1];
+  db.read_txn()
+    .range(from.as_slice()..to.as_slice())
+    .unwrap()
+    .filter(|entry| is_doc_content_key(entry.key()))
+    .map(|entry| entry.value().to_vec())
+    .collect()
+}
+
+fn contains(value: &[u8], pattern: &[u8]) -> bool {
+  value.windows(pattern.len()).any(|window| window == pattern)
+}
@@ -41,11 +41,8 @@ async fn flush_test() {
     &doc_id,
     CollabType::Unknown,
   );
-  let data_source = KVDBCollabPersistenceImpl {
-    db: Arc::downgrade(&test.db),
-    uid: 1,
-    workspace_id: test.workspace_id.clone(),
-  };
+  let data_source =
+    KVDBCollabPersistenceImpl::new(Arc::downgrade(&test.db), 1, test.workspace_id.clone());
 
   let mut collab = CollabBuilder::new(1, &doc_id, data_source.into())
     .with_device_id("1")
@@ -0,0 +1,83 @@
+use collab_entity::CollabType;
+use collab_plugins::local_storage::kv::KVTransactionDB;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::integrity::{IntegrityAction, IntegrityIssue};
+use uuid::Uuid;
+
+use crate::disk::util::{create_doc, load_text, push_text, rocks_db};
+
+const UID: i64 = 1;
+
+#[test]
+fn verify_healthy_docs_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  for object_id in ["1", "2"] {
+    create_doc(&db, UID, &workspace_id, object_id, &["hello"]);
+  }
+
+  let report = db.read_txn().verify_all(UID, &|_, _| None).unwrap();
+  assert!(report.is_ok());
+  assert_eq!(report.objects.len(), 2);
+  assert_eq!(report.objects[0].workspace_id, workspace_id);
+  assert_eq!(report.objects[0].update_count, 1);
+}
+
+#[test]
+fn repair_corrupted_update_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  let doc = create_doc(&db, UID, &workspace_id, "1", &["hello"]);
+  db.with_write_txn(|store| store.push_update(UID, &workspace_id, "1", &[255, 255, 255]))
+    .unwrap();
+  push_text(&db, UID, &workspace_id, "1", &doc, " world");
+
+  let report = db.read_txn().verify_all(UID, &|_, _| None).unwrap();
+  let broken = report.broken_objects().collect::<Vec<_>>();
+  assert_eq!(broken.len(), 1);
+  // The clocks are derived from the key that precedes the first update, so they aren't known
+  // upfront
+  let clock = match broken[0].issues.as_slice() {
+    [IntegrityIssue::UndecodableUpdate { clock, .. }] => *clock,
+    issues => panic!("unexpected issues: {:?}", issues),
+  };
+
+  let repairs = db.with_write_txn(|store| store.repair_all(UID)).unwrap();
+  assert_eq!(repairs.len(), 1);
+  assert_eq!(repairs[0].kept_update_count, 2);
+  assert_eq!(repairs[0].dropped.len(), 1);
+  assert_eq!(repairs[0].dropped[0].clock, Some(clock));
+
+  // The dropped update is kept in the quarantine
+  let quarantined = db.read_txn().get_quarantined_updates(UID, "1").unwrap();
+  assert_eq!(quarantined.len(), 1);
+  assert_eq!(quarantined[0].data, vec![255, 255, 255]);
+
+  // The doc state is rebuilt from the other updates
+  assert!(db.read_txn().verify_all(UID, &|_, _| None).unwrap().is_ok());
+  assert_eq!(db.read_txn().number_of_updates(UID, &workspace_id, "1"), 0);
+  assert_eq!(
+    load_text(&db.read_txn(), UID, &workspace_id, "1"),
+    "hello world"
+  );
+}
+
+#[test]
+fn verify_required_data_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, UID, &workspace_id, "1", &[]);
+
+  let report = db
+    .read_txn()
+    .verify_all(UID, &|_, _| Some(CollabType::Document))
+    .unwrap();
+  assert!(matches!(
+    report.objects[0].issues.as_slice(),
+    [IntegrityIssue::MissingRequiredData(_)]
+  ));
+
+  // The missing data can't be repaired
+  let repairs = db.with_write_txn(|store| store.repair_all(UID)).unwrap();
+  assert!(repairs.is_empty());
+}
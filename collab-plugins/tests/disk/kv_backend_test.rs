@@ -0,0 +1,307 @@
+use std::sync::Arc;
+
+use assert_json_diff::assert_json_eq;
+use collab::preclude::CollabBuilder;
+use collab_entity::CollabType;
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::collab_kv_db::KVBackend;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::snapshot::SnapshotAction;
+use collab_plugins::local_storage::kv::{KVEntry, KVStore, KVTransactionDB, PersistenceError};
+use collab_plugins::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin;
+use collab_plugins::local_storage::rocksdb::util::KVDBCollabPersistenceImpl;
+use tempfile::TempDir;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact};
+
+use crate::disk::util::{create_doc, load_text};
+
+const UID: i64 = 1;
+const WORKSPACE_ID: &str = "w1";
+
+struct Backend {
+  db: CollabKVDB,
+  _dir: Option<TempDir>,
+}
+
+/// Every backend of [CollabKVDB] runs the same tests
+fn backends() -> Vec<Backend> {
+  let rocksdb_dir = TempDir::new().unwrap();
+  let mut backends = vec![Backend {
+    db: CollabKVDB::open_with_backend(KVBackend::Rocksdb(rocksdb_dir.path().to_path_buf()))
+      .unwrap(),
+    _dir: Some(rocksdb_dir),
+  }];
+  backends.extend(buffered_backends());
+  backends
+}
+
+/// The backends whose transactions are buffered in memory until they are committed
+fn buffered_backends() -> Vec<Backend> {
+  #[allow(unused_mut)]
+  let mut backends = vec![Backend {
+    db: CollabKVDB::open_with_backend(KVBackend::Memory).unwrap(),
+    _dir: None,
+  }];
+  #[cfg(feature = "sqlite")]
+  {
+    let sqlite_dir = TempDir::new().unwrap();
+    backends.push(Backend {
+      db: CollabKVDB::open_with_backend(KVBackend::Sqlite(sqlite_dir.path().join("collab.db")))
+        .unwrap(),
+      _dir: Some(sqlite_dir),
+    });
+  }
+  backends
+}
+
+#[test]
+fn kv_store_test() {
+  for Backend { db, _dir } in backends() {
+    db.with_write_txn(|txn| {
+      txn.insert([0, 0, 1], [1])?;
+      txn.insert([0, 0, 2], [2])?;
+      txn.insert([0, 0, 3], [3])?;
+      txn.insert([0, 1, 0], [4])?;
+      // The writes of a transaction are visible to the transaction itself
+      assert_eq!(txn.get([0, 0, 2])?, Some(vec![2]));
+      Ok(())
+    })
+    .unwrap();
+
+    let txn = db.read_txn();
+    let values = txn
+      .range([0, 0, 1]..[0, 0, 3])
+      .unwrap()
+      .map(|entry| entry.value().to_vec())
+      .collect::<Vec<_>>();
+    assert_eq!(values, vec![vec![1], vec![2]]);
+    let entry = txn.next_back_entry(&[0, 0, 255]).unwrap().unwrap();
+    assert_eq!(entry.key(), &[0, 0, 3]);
+    let entry = txn.next_back_entry(&[0, 0, 3]).unwrap().unwrap();
+    assert_eq!(entry.key(), &[0, 0, 3]);
+    assert!(txn.next_back_entry(&[0, 0, 0]).unwrap().is_none());
+    drop(txn);
+
+    db.with_write_txn(|txn| {
+      txn.remove_range(&[0, 0, 1], &[0, 0, 3])?;
+      txn.remove(&[0, 1, 0])?;
+      // The removed keys are skipped
+      let entry = txn.next_back_entry(&[0, 1, 0])?.unwrap();
+      assert_eq!(entry.key(), &[0, 0, 3]);
+      Ok(())
+    })
+    .unwrap();
+
+    let txn = db.read_txn();
+    let keys = txn
+      .range([0, 0, 0]..[1, 0, 0])
+      .unwrap()
+      .map(|entry| entry.key().to_vec())
+      .collect::<Vec<_>>();
+    assert_eq!(keys, vec![vec![0, 0, 3]]);
+  }
+}
+
+#[test]
+fn uncommitted_transaction_test() {
+  for Backend { db, _dir } in backends() {
+    let txn = db.write_txn();
+    txn.insert([1, 2, 3], [1]).unwrap();
+    drop(txn);
+    assert_eq!(db.read_txn().get([1, 2, 3]).unwrap(), None);
+
+    let result = db.with_write_txn(|txn| {
+      txn.insert([1, 2, 3], [1])?;
+      Err::<(), _>(PersistenceError::UnexpectedEmptyUpdates)
+    });
+    assert!(result.is_err());
+    assert_eq!(db.read_txn().get([1, 2, 3]).unwrap(), None);
+
+    let txn = db.write_txn();
+    txn.insert([1, 2, 3], [1]).unwrap();
+    txn.commit_transaction().unwrap();
+    assert_eq!(db.read_txn().get([1, 2, 3]).unwrap(), Some(vec![1]));
+  }
+}
+
+#[test]
+fn nested_write_transaction_test() {
+  for Backend { db, _dir } in buffered_backends() {
+    let txn = db.write_txn();
+    txn.insert([1, 2, 3], [1]).unwrap();
+    let result = db.with_write_txn(|nested_txn| nested_txn.insert([1, 2, 4], [1]));
+    assert!(matches!(
+      result,
+      Err(PersistenceError::NestedWriteTransaction)
+    ));
+    txn.commit_transaction().unwrap();
+
+    // The lock is released with the outer transaction
+    db.with_write_txn(|txn| txn.insert([1, 2, 4], [1])).unwrap();
+    assert_eq!(db.read_txn().get([1, 2, 3]).unwrap(), Some(vec![1]));
+    assert_eq!(db.read_txn().get([1, 2, 4]).unwrap(), Some(vec![1]));
+  }
+}
+
+#[test]
+fn collab_kv_action_test() {
+  for Backend { db, _dir } in backends() {
+    let doc = create_doc(&db, UID, WORKSPACE_ID, "doc_1", &["hello", " ", "world"]);
+    assert!(db.read_txn().is_exist(UID, WORKSPACE_ID, "doc_1"));
+    assert_eq!(
+      db.read_txn().number_of_updates(UID, WORKSPACE_ID, "doc_1"),
+      3
+    );
+    assert_eq!(
+      load_text(&db.read_txn(), UID, WORKSPACE_ID, "doc_1"),
+      "hello world"
+    );
+
+    // Flushing replaces the updates with the doc state
+    let doc_state = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    let sv = doc.transact().state_vector();
+    db.with_write_txn(|txn| txn.flush_doc(UID, WORKSPACE_ID, "doc_1", sv.encode_v1(), doc_state))
+      .unwrap();
+    assert_eq!(
+      db.read_txn().number_of_updates(UID, WORKSPACE_ID, "doc_1"),
+      0
+    );
+    assert_eq!(
+      load_text(&db.read_txn(), UID, WORKSPACE_ID, "doc_1"),
+      "hello world"
+    );
+
+    db.with_write_txn(|txn| txn.create_new_doc(UID, WORKSPACE_ID, "doc_2", &doc.transact()))
+      .unwrap();
+    let mut object_ids = db
+      .read_txn()
+      .get_all_object_ids(UID, WORKSPACE_ID)
+      .unwrap()
+      .collect::<Vec<_>>();
+    object_ids.sort();
+    assert_eq!(object_ids, vec!["doc_1", "doc_2"]);
+
+    db.with_write_txn(|txn| txn.delete_doc(UID, WORKSPACE_ID, "doc_1"))
+      .unwrap();
+    assert!(!db.read_txn().is_exist(UID, WORKSPACE_ID, "doc_1"));
+    assert!(db.read_txn().is_exist(UID, WORKSPACE_ID, "doc_2"));
+  }
+}
+
+#[test]
+fn compact_doc_test() {
+  for Backend { db, _dir } in backends() {
+    let contents = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+    create_doc(&db, UID, WORKSPACE_ID, "doc", &contents);
+
+    let count = db
+      .with_write_txn(|txn| txn.compact_doc(UID, WORKSPACE_ID, "doc"))
+      .unwrap();
+    assert_eq!(count, 10);
+    assert_eq!(db.read_txn().number_of_updates(UID, WORKSPACE_ID, "doc"), 0);
+    assert_eq!(
+      load_text(&db.read_txn(), UID, WORKSPACE_ID, "doc"),
+      "0123456789"
+    );
+  }
+}
+
+#[test]
+fn concurrent_push_update_test() {
+  for Backend { db, _dir } in buffered_backends() {
+    let doc = Doc::new();
+    let text = doc.get_or_insert_text("text");
+    db.with_write_txn(|txn| txn.create_new_doc(UID, WORKSPACE_ID, "doc", &doc.transact()))
+      .unwrap();
+    let updates = (0..80)
+      .map(|i| {
+        let before = doc.transact().state_vector();
+        text.push(&mut doc.transact_mut(), &i.to_string());
+        doc.transact().encode_state_as_update_v1(&before)
+      })
+      .collect::<Vec<_>>();
+
+    // Every update gets its own id even if the transactions run at the same time
+    std::thread::scope(|scope| {
+      for chunk in updates.chunks(10) {
+        let db = &db;
+        scope.spawn(move || {
+          for update in chunk {
+            db.with_write_txn(|txn| txn.push_update(UID, WORKSPACE_ID, "doc", update))
+              .unwrap();
+          }
+        });
+      }
+    });
+    assert_eq!(
+      db.read_txn().number_of_updates(UID, WORKSPACE_ID, "doc"),
+      80
+    );
+    assert_eq!(
+      load_text(&db.read_txn(), UID, WORKSPACE_ID, "doc"),
+      text.get_string(&doc.transact())
+    );
+  }
+}
+
+#[test]
+fn snapshot_action_test() {
+  for Backend { db, _dir } in backends() {
+    db.with_write_txn(|txn| {
+      txn.create_snapshot_with_data(UID, "doc", vec![1, 2, 3])?;
+      txn.create_snapshot_with_data(UID, "doc", vec![4, 5, 6])
+    })
+    .unwrap();
+
+    let txn = db.read_txn();
+    let snapshots = txn.get_snapshots(UID, "doc");
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[0].data, vec![1, 2, 3]);
+    assert_eq!(
+      txn.get_last_snapshot(UID, "doc").unwrap().data,
+      vec![4, 5, 6]
+    );
+    drop(txn);
+
+    db.with_write_txn(|txn| txn.delete_all_snapshots(UID, "doc"))
+      .unwrap();
+    assert!(db.read_txn().get_snapshots(UID, "doc").is_empty());
+  }
+}
+
+#[tokio::test]
+async fn disk_plugin_with_backend_test() {
+  for Backend { db, _dir } in backends() {
+    let db = Arc::new(db);
+    let data_source =
+      || KVDBCollabPersistenceImpl::new(Arc::downgrade(&db), UID, WORKSPACE_ID.to_string());
+    let plugin = RocksdbDiskPlugin::new(
+      UID,
+      WORKSPACE_ID.to_string(),
+      "collab".to_string(),
+      CollabType::Unknown,
+      Arc::downgrade(&db),
+    );
+    let mut collab = CollabBuilder::new(UID, "collab", data_source().into())
+      .with_device_id("1")
+      .with_plugin(plugin)
+      .build()
+      .unwrap();
+    collab.initialize();
+    for i in 0..10 {
+      collab.insert(&i.to_string(), i.to_string());
+    }
+    let expected = collab.to_json_value();
+    drop(collab);
+
+    let mut collab = CollabBuilder::new(UID, "collab", data_source().into())
+      .with_device_id("1")
+      .build()
+      .unwrap();
+    collab.initialize();
+    assert_json_eq!(collab.to_json_value(), expected);
+  }
+}
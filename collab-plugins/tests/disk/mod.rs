@@ -1,7 +1,17 @@
+mod archive_test;
+mod compaction_test;
 mod delete_test;
+mod encryption_test;
 mod insert_test;
+mod integrity_test;
+mod kv_backend_test;
 mod range_test;
+mod read_only_test;
 mod restore_test;
+mod schema_test;
 mod script;
+mod snapshot_test;
+mod stats_test;
 mod undo_test;
 mod util;
+mod write_behind_test;
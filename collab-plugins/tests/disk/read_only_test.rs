@@ -0,0 +1,64 @@
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::{KVTransactionDB, PersistenceError};
+use collab_plugins::local_storage::rocksdb::kv_impl::KVTransactionDBRocksdbImpl;
+use tempfile::TempDir;
+
+use crate::disk::util::{create_doc, load_text, rocks_db};
+
+const UID: i64 = 1;
+const WORKSPACE_ID: &str = "w1";
+
+#[test]
+fn open_read_only_test() {
+  let (path, db) = rocks_db();
+  create_doc(&db, UID, WORKSPACE_ID, "1", &["hello", " world"]);
+
+  // The primary still holds the database
+  let read_only = KVTransactionDBRocksdbImpl::open_read_only(&path).unwrap();
+  assert!(!read_only.is_secondary());
+  let txn = read_only.read_txn();
+  assert_eq!(load_text(&txn, UID, WORKSPACE_ID, "1"), "hello world");
+  assert_eq!(
+    txn
+      .get_all_object_ids(UID, WORKSPACE_ID)
+      .unwrap()
+      .collect::<Vec<_>>(),
+    vec!["1".to_string()]
+  );
+  assert_eq!(
+    txn.get_all_updates(UID, WORKSPACE_ID, "1").unwrap().len(),
+    2
+  );
+
+  let result = txn.push_update(UID, WORKSPACE_ID, "1", &[1, 2, 3]);
+  assert!(matches!(result, Err(PersistenceError::ReadOnly)));
+  assert!(read_only.try_catch_up_with_primary().is_err());
+}
+
+#[test]
+fn open_as_secondary_test() {
+  let (path, db) = rocks_db();
+  create_doc(&db, UID, WORKSPACE_ID, "1", &["hello"]);
+
+  let secondary_dir = TempDir::new().unwrap();
+  let secondary =
+    KVTransactionDBRocksdbImpl::open_as_secondary(&path, secondary_dir.path()).unwrap();
+  assert!(secondary.is_secondary());
+  assert_eq!(
+    load_text(&secondary.read_txn(), UID, WORKSPACE_ID, "1"),
+    "hello"
+  );
+
+  // The changes of the primary are visible after catching up
+  create_doc(&db, UID, WORKSPACE_ID, "2", &["world"]);
+  assert!(!secondary.read_txn().is_exist(UID, WORKSPACE_ID, "2"));
+  secondary.try_catch_up_with_primary().unwrap();
+  assert_eq!(
+    load_text(&secondary.read_txn(), UID, WORKSPACE_ID, "2"),
+    "world"
+  );
+
+  let result = secondary.read_txn().delete_doc(UID, WORKSPACE_ID, "1");
+  assert!(matches!(result, Err(PersistenceError::ReadOnly)));
+  assert!(db.read_txn().is_exist(UID, WORKSPACE_ID, "1"));
+}
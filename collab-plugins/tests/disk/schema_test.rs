@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::core::type_hint::TypeHint;
+use collab::preclude::{Collab, CollabBuilder, ReadTxn, StateVector};
+use collab_entity::CollabType;
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::CollabPersistenceConfig;
+use collab_plugins::local_storage::kv::KVTransactionDB;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin;
+use serde_json::json;
+
+use crate::disk::script::CollabPersistenceTest;
+
+const DOC_ID: &str = "1";
+
+#[tokio::test]
+async fn validate_schema_before_create_doc_test() {
+  // The views of a folder must be a map, but the required data is present
+  let doc_state = folder_doc_state(
+    json!({"folder": {"meta": {"current_workspace": "w1"}, "views": ["v1"]}}),
+    TypeHint::array(TypeHint::Any),
+  );
+
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let _collab = open_folder(&test, doc_state.clone(), true);
+  assert!(!is_exist(&test));
+
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let _collab = open_folder(&test, doc_state, false);
+  assert!(is_exist(&test));
+}
+
+#[tokio::test]
+async fn validate_schema_valid_folder_test() {
+  let doc_state = folder_doc_state(
+    json!({"folder": {"meta": {"current_workspace": "w1"}, "views": {"v1": {"id": "v1"}}}}),
+    TypeHint::map_of(TypeHint::map()),
+  );
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let _collab = open_folder(&test, doc_state, true);
+  assert!(is_exist(&test));
+}
+
+/// Encodes a folder whose views are stored with the given hint.
+fn folder_doc_state(value: serde_json::Value, views: TypeHint) -> Vec<u8> {
+  let hint = TypeHint::map().field(
+    "folder",
+    TypeHint::map()
+      .field("meta", TypeHint::map())
+      .field("views", views),
+  );
+  let collab = Collab::from_json(CollabOrigin::Empty, DOC_ID, &value, &hint).unwrap();
+  collab
+    .transact()
+    .encode_state_as_update_v1(&StateVector::default())
+}
+
+fn open_folder(test: &CollabPersistenceTest, doc_state: Vec<u8>, validate_schema: bool) -> Collab {
+  let config = CollabPersistenceConfig::new().validate_schema(validate_schema);
+  let plugin = RocksdbDiskPlugin::new_with_config(
+    test.uid,
+    test.workspace_id.clone(),
+    DOC_ID.to_string(),
+    CollabType::Folder,
+    Arc::downgrade(&test.db),
+    config,
+  );
+  let mut collab = CollabBuilder::new(test.uid, DOC_ID, DataSource::DocStateV1(doc_state))
+    .with_device_id("1")
+    .with_plugin(plugin)
+    .build()
+    .unwrap();
+  collab.initialize();
+  collab
+}
+
+fn is_exist(test: &CollabPersistenceTest) -> bool {
+  let db: &CollabKVDB = &test.db;
+  db.read_txn().is_exist(test.uid, &test.workspace_id, DOC_ID)
+}
@@ -10,6 +10,7 @@ use collab_plugins::CollabKVDB;
 use collab_plugins::local_storage::CollabPersistenceConfig;
 use collab_plugins::local_storage::kv::KVTransactionDB;
 use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::encryption::EncryptionKeyProvider;
 use collab_plugins::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin;
 use collab_plugins::local_storage::rocksdb::util::KVDBCollabPersistenceImpl;
 use tempfile::TempDir;
@@ -23,7 +24,6 @@ pub struct CollabPersistenceTest {
   cleaner: Cleaner,
   #[allow(dead_code)]
   pub db: Arc<CollabKVDB>,
-  #[allow(dead_code)]
   config: CollabPersistenceConfig,
 }
 
@@ -54,11 +54,7 @@ impl CollabPersistenceTest {
       &id,
       CollabType::Unknown,
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&self.db),
-      uid: self.uid,
-      workspace_id: self.workspace_id.clone(),
-    };
+    let data_source = self.data_source(None);
     let mut collab = CollabBuilder::new(1, id.clone(), data_source.into())
       .with_device_id("1")
       .with_plugin(disk_plugin)
@@ -76,11 +72,7 @@ impl CollabPersistenceTest {
       &id,
       CollabType::Unknown,
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&self.db),
-      uid: self.uid,
-      workspace_id: self.workspace_id.clone(),
-    };
+    let data_source = self.data_source(None);
     let mut collab = CollabBuilder::new(1, id.clone(), data_source.into())
       .with_device_id("1")
       .with_plugin(disk_plugin)
@@ -144,11 +136,7 @@ impl CollabPersistenceTest {
       &doc_id,
       CollabType::Unknown,
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&self.db),
-      uid: self.uid,
-      workspace_id: self.workspace_id.clone(),
-    };
+    let data_source = self.data_source(None);
     let mut collab = CollabBuilder::new(1, &doc_id, data_source.into())
       .with_device_id("1")
       .with_plugin(disk_plugin)
@@ -190,11 +178,7 @@ impl CollabPersistenceTest {
       id,
       CollabType::Document,
     );
-    let data_source = KVDBCollabPersistenceImpl {
-      db: Arc::downgrade(&self.db),
-      uid: self.uid,
-      workspace_id: self.workspace_id.clone(),
-    };
+    let data_source = self.data_source(None);
     let mut collab = CollabBuilder::new(1, id, data_source.into())
       .with_device_id("1")
       .with_plugin(disk_plugin)
@@ -205,6 +189,62 @@ impl CollabPersistenceTest {
     assert_json_diff::assert_json_eq!(json, expected);
   }
 
+  /// Opens the collab with a disk plugin that uses the given config. The plugin is returned so
+  /// that the tests can flush, compact or snapshot the collab directly.
+  pub fn open_collab(
+    &self,
+    id: &str,
+    config: CollabPersistenceConfig,
+  ) -> (Collab, RocksdbDiskPlugin) {
+    let data_source = self.data_source(config.encryption.clone());
+    let plugin = RocksdbDiskPlugin::new_with_config(
+      self.uid,
+      self.workspace_id.clone(),
+      id.to_string(),
+      CollabType::Unknown,
+      Arc::downgrade(&self.db),
+      config,
+    );
+    let mut collab = CollabBuilder::new(self.uid, id, data_source.into())
+      .with_device_id("1")
+      .with_plugin(plugin.clone())
+      .build()
+      .unwrap();
+    collab.initialize();
+    (collab, plugin)
+  }
+
+  /// Loads the collab from the disk without any plugin, using the encryption of the config the
+  /// test was created with.
+  pub fn reopen_collab(&self, id: &str) -> Collab {
+    let data_source = self.data_source(self.config.encryption.clone());
+    let mut collab = CollabBuilder::new(self.uid, id, data_source.into())
+      .with_device_id("1")
+      .build()
+      .unwrap();
+    collab.initialize();
+    collab
+  }
+
+  pub fn number_of_updates(&self, id: &str) -> usize {
+    self
+      .db
+      .read_txn()
+      .number_of_updates(self.uid, &self.workspace_id, id)
+  }
+
+  fn data_source(
+    &self,
+    encryption: Option<Arc<dyn EncryptionKeyProvider>>,
+  ) -> KVDBCollabPersistenceImpl {
+    KVDBCollabPersistenceImpl::new(
+      Arc::downgrade(&self.db),
+      self.uid,
+      self.workspace_id.clone(),
+    )
+    .with_encryption(encryption)
+  }
+
   pub async fn undo(&mut self, id: &str) {
     self
       .collab_by_id
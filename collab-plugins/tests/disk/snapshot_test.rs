@@ -0,0 +1,173 @@
+use assert_json_diff::assert_json_eq;
+use collab::core::collab::DataSource;
+use collab::preclude::{Collab, CollabBuilder};
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::CollabPersistenceConfig;
+use collab_plugins::local_storage::kv::KVTransactionDB;
+use collab_plugins::local_storage::kv::snapshot::{
+  CollabSnapshot, SnapshotAction, SnapshotRetention, restore_snapshot,
+};
+use serde_json::json;
+
+use crate::disk::script::CollabPersistenceTest;
+
+const DOC_ID: &str = "1";
+const DAY: i64 = 24 * 60 * 60;
+
+#[test]
+fn create_snapshot_per_update_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .enable_snapshot(true)
+    .snapshot_per_update(5)
+    .snapshot_retention(SnapshotRetention::new());
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..12 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+
+  let snapshots = snapshots(&test);
+  assert_eq!(snapshots.len(), 2);
+  // The last snapshot has the first 10 updates
+  let restored = collab_from_snapshot(snapshots.last().unwrap());
+  assert_eq!(restored.to_json_value().as_object().unwrap().len(), 10);
+}
+
+#[test]
+fn disable_snapshot_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .enable_snapshot(false)
+    .snapshot_per_update(1);
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..5 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert!(snapshots(&test).is_empty());
+}
+
+#[test]
+fn prune_snapshots_with_retention_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new()
+    .enable_snapshot(true)
+    .snapshot_per_update(1)
+    .snapshot_retention(SnapshotRetention::new().keep_last(Some(2)));
+  let (mut collab, _plugin) = test.open_collab(DOC_ID, config);
+  for i in 0..6 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+
+  let snapshots = snapshots(&test);
+  assert_eq!(snapshots.len(), 2);
+  assert_json_eq!(
+    collab_from_snapshot(&snapshots[1]).to_json_value(),
+    collab.to_json_value()
+  );
+}
+
+#[test]
+fn snapshot_retention_test() {
+  let monday = 4 * DAY;
+  let created_at = [
+    monday - 7 * DAY,
+    monday - 7 * DAY + 60,
+    monday,
+    monday + 60,
+    monday + DAY,
+    monday + 2 * DAY,
+    monday + 2 * DAY + 60,
+  ];
+
+  let retention = SnapshotRetention::new();
+  assert_eq!(retention.keep(&created_at), vec![true; 7]);
+
+  let retention = SnapshotRetention::new().keep_last(Some(2));
+  assert_eq!(
+    retention.keep(&created_at),
+    vec![false, false, false, false, false, true, true]
+  );
+
+  let retention = SnapshotRetention::new().keep_daily(Some(3));
+  assert_eq!(
+    retention.keep(&created_at),
+    vec![false, false, false, true, true, false, true]
+  );
+
+  let retention = SnapshotRetention::new().keep_weekly(Some(2));
+  assert_eq!(
+    retention.keep(&created_at),
+    vec![false, true, false, false, false, false, true]
+  );
+
+  let retention = SnapshotRetention::new()
+    .keep_last(Some(1))
+    .keep_daily(Some(2))
+    .keep_weekly(Some(2));
+  assert_eq!(
+    retention.keep(&created_at),
+    vec![false, true, false, false, true, false, true]
+  );
+}
+
+#[test]
+fn restore_snapshot_as_new_update_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new().enable_snapshot(false);
+  let (mut collab, plugin) = test.open_collab(DOC_ID, config);
+  collab.insert("1", "a");
+  collab.insert("2", "b");
+  plugin.create_snapshot().unwrap();
+  let snapshot = snapshots(&test).pop().unwrap();
+
+  collab.insert("1", "c");
+  collab.remove("2");
+  collab.insert("3", "d");
+  let update_count = test.number_of_updates(DOC_ID);
+
+  restore_snapshot(&mut collab, &snapshot).unwrap();
+  let expected = json!({ "1": "a", "2": "b" });
+  assert_json_eq!(collab.to_json_value(), expected);
+
+  // The restore is persisted as a new update on top of the previous ones
+  assert_eq!(test.number_of_updates(DOC_ID), update_count + 1);
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[test]
+fn restore_snapshot_keeps_later_history_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let config = CollabPersistenceConfig::new().enable_snapshot(false);
+  let (mut collab, plugin) = test.open_collab(DOC_ID, config);
+  collab.insert("1", "a");
+  plugin.create_snapshot().unwrap();
+  collab.insert("1", "b");
+  plugin.create_snapshot().unwrap();
+  let snapshots = snapshots(&test);
+  assert_eq!(snapshots.len(), 2);
+
+  // Roll back to the first snapshot, then forward to the second one
+  restore_snapshot(&mut collab, &snapshots[0]).unwrap();
+  assert_json_eq!(collab.to_json_value(), json!({ "1": "a" }));
+  restore_snapshot(&mut collab, &snapshots[1]).unwrap();
+  assert_json_eq!(collab.to_json_value(), json!({ "1": "b" }));
+
+  drop(collab);
+  assert_json_eq!(
+    test.reopen_collab(DOC_ID).to_json_value(),
+    json!({ "1": "b" })
+  );
+}
+
+fn collab_from_snapshot(snapshot: &CollabSnapshot) -> Collab {
+  CollabBuilder::new(1, DOC_ID, DataSource::DocStateV1(snapshot.data.clone()))
+    .with_device_id("1")
+    .build()
+    .unwrap()
+}
+
+fn snapshots(test: &CollabPersistenceTest) -> Vec<CollabSnapshot> {
+  let db: &CollabKVDB = &test.db;
+  db.read_txn().get_snapshots(test.uid, DOC_ID)
+}
@@ -0,0 +1,151 @@
+use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::gc::{GarbageCollectOptions, RemovedObject};
+use collab_plugins::local_storage::kv::keys::{
+  make_doc_id_key_v0, make_doc_id_key_v1, make_doc_state_key,
+};
+use collab_plugins::local_storage::kv::snapshot::{CollabSnapshot, SnapshotAction};
+use collab_plugins::local_storage::kv::{KVStore, KVTransactionDB};
+use uuid::Uuid;
+use yrs::{Doc, GetString, Transact};
+
+use crate::disk::util::{create_doc, rocks_db};
+
+const UID: i64 = 1;
+
+#[test]
+fn storage_stats_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, UID, &workspace_id, "1", &["hello", " world"]);
+  create_doc(&db, UID, &workspace_id, "2", &[]);
+  insert_snapshot(&db, "2");
+
+  let stats = db.get_storage_stats(UID).unwrap();
+  assert_eq!(stats.workspaces.len(), 1);
+  let workspace = &stats.workspaces[0];
+  assert_eq!(workspace.workspace_id, workspace_id);
+  assert_eq!(workspace.update_count(), 2);
+  assert_eq!(workspace.objects.len(), 2);
+
+  let object = &workspace.objects[0];
+  assert_eq!(object.object_id, "1");
+  assert_eq!(object.update_count, 2);
+  assert!(object.doc_state_bytes > 0);
+  assert!(object.update_bytes > 0);
+  assert_eq!(object.snapshot_count, 0);
+  assert!(object.last_write_at.is_some());
+
+  let object = &workspace.objects[1];
+  assert_eq!(object.update_count, 0);
+  assert_eq!(object.snapshot_count, 1);
+  assert_eq!(object.snapshot_bytes, 3 + 16);
+  assert_eq!(stats.total_bytes(), workspace.total_bytes());
+
+  // Compacting merges the updates into the doc state
+  db.with_write_txn(|store| store.compact_doc(UID, &workspace_id, "1"))
+    .unwrap();
+  let stats = db.get_storage_stats(UID).unwrap();
+  assert_eq!(stats.workspaces[0].objects[0].update_count, 0);
+  assert_eq!(stats.workspaces[0].objects[0].update_bytes, 0);
+}
+
+#[test]
+fn garbage_collect_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  let deleted_workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, UID, &workspace_id, "1", &["hello"]);
+  create_doc(&db, UID, &deleted_workspace_id, "2", &["world"]);
+  insert_snapshot(&db, "2");
+  insert_snapshot(&db, "3");
+
+  // The doc id mapping of the old key format is kept by migrate_old_keys
+  let new_key = make_doc_id_key_v1(&UID.to_be_bytes(), workspace_id.as_bytes(), b"1");
+  let doc_id = db.read_txn().get(new_key.as_ref()).unwrap().unwrap();
+  let old_key = make_doc_id_key_v0(&UID.to_be_bytes(), b"1");
+  db.with_write_txn(|store| store.insert(old_key.as_ref(), &doc_id))
+    .unwrap();
+
+  let options = GarbageCollectOptions::new()
+    .existing_workspace_ids(vec![workspace_id.clone()])
+    .dry_run(true);
+  let dry_run_report = db.garbage_collect(UID, &options).unwrap();
+  assert_eq!(
+    dry_run_report.removed_objects,
+    vec![RemovedObject {
+      workspace_id: deleted_workspace_id.clone(),
+      object_id: "2".to_string(),
+    }]
+  );
+  assert_eq!(dry_run_report.orphaned_doc_ids, vec!["1".to_string()]);
+  assert_eq!(dry_run_report.orphaned_snapshots, vec!["3".to_string()]);
+  assert!(dry_run_report.reclaimed_bytes > 0);
+  assert!(db.read_txn().get(old_key.as_ref()).unwrap().is_some());
+  assert!(db.read_txn().is_exist(UID, &deleted_workspace_id, "2"));
+
+  let report = db
+    .garbage_collect(UID, &options.clone().dry_run(false))
+    .unwrap();
+  assert_eq!(report.removed_objects, dry_run_report.removed_objects);
+  assert_eq!(report.orphaned_doc_ids, dry_run_report.orphaned_doc_ids);
+  assert_eq!(report.orphaned_snapshots, dry_run_report.orphaned_snapshots);
+  assert_eq!(report.reclaimed_bytes, dry_run_report.reclaimed_bytes);
+  assert!(db.read_txn().get(old_key.as_ref()).unwrap().is_none());
+  assert!(!db.read_txn().is_exist(UID, &deleted_workspace_id, "2"));
+  assert!(db.read_txn().get_snapshots(UID, "3").is_empty());
+
+  // The live document is untouched
+  let doc = Doc::new();
+  let text = doc.get_or_insert_text("text");
+  db.read_txn()
+    .load_doc(UID, &workspace_id, "1", &doc)
+    .unwrap();
+  assert_eq!(text.get_string(&doc.transact()), "hello");
+  assert!(db.garbage_collect(UID, &options).unwrap().is_empty());
+}
+
+#[test]
+fn garbage_collect_keeps_old_key_with_uuid_prefix_test() {
+  let (_path, db) = rocks_db();
+  let workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, UID, &workspace_id, "1", &["hello"]);
+
+  // An object id of the old key format that starts with a uuid has the layout of the new format
+  let object_id = format!("{}-document", Uuid::new_v4());
+  let doc_id = 100_u64;
+  let old_key = make_doc_id_key_v0(&UID.to_be_bytes(), object_id.as_bytes());
+  db.with_write_txn(|store| {
+    store.insert(old_key.as_ref(), doc_id.to_be_bytes())?;
+    store.insert(make_doc_state_key(doc_id).as_ref(), [0, 0])
+  })
+  .unwrap();
+
+  let options = GarbageCollectOptions::new().existing_workspace_ids(vec![workspace_id.clone()]);
+  let report = db.garbage_collect(UID, &options).unwrap();
+  assert!(report.is_empty());
+  assert!(db.read_txn().get(old_key.as_ref()).unwrap().is_some());
+  assert!(db.read_txn().is_exist(UID, &workspace_id, &object_id));
+
+  // The keys of the new format are still told apart by their record
+  let deleted_workspace_id = Uuid::new_v4().to_string();
+  create_doc(&db, UID, &deleted_workspace_id, "2", &["world"]);
+  let report = db.garbage_collect(UID, &options).unwrap();
+  assert_eq!(
+    report.removed_objects,
+    vec![RemovedObject {
+      workspace_id: deleted_workspace_id,
+      object_id: "2".to_string(),
+    }]
+  );
+  assert!(db.read_txn().get(old_key.as_ref()).unwrap().is_some());
+}
+
+fn insert_snapshot(db: &CollabKVDB, object_id: &str) {
+  let snapshot = CollabSnapshot {
+    data: vec![1, 2, 3],
+    created_at: 100,
+  };
+  db.with_write_txn(|store| store.insert_snapshot(UID, object_id, &snapshot))
+    .unwrap();
+}
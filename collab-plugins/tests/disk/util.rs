@@ -1,7 +1,10 @@
 use std::path::PathBuf;
 
 use collab_plugins::CollabKVDB;
+use collab_plugins::local_storage::kv::doc::CollabKVAction;
+use collab_plugins::local_storage::kv::{KVTransactionDB, PersistenceError};
 use tempfile::TempDir;
+use yrs::{Doc, GetString, Text, Transact};
 
 pub fn rocks_db() -> (PathBuf, CollabKVDB) {
   let tempdir = TempDir::new().unwrap();
@@ -9,3 +12,51 @@ pub fn rocks_db() -> (PathBuf, CollabKVDB) {
   let cloned_path = path.clone();
   (path, CollabKVDB::open(cloned_path).unwrap())
 }
+
+/// Creates the doc of the object, and pushes one update for each of the contents, appended to the
+/// "text" of the doc. Returns the doc so that more updates can be pushed with [push_text].
+pub fn create_doc(
+  db: &CollabKVDB,
+  uid: i64,
+  workspace_id: &str,
+  object_id: &str,
+  contents: &[&str],
+) -> Doc {
+  let doc = Doc::new();
+  db.with_write_txn(|store| store.create_new_doc(uid, workspace_id, object_id, &doc.transact()))
+    .unwrap();
+  for content in contents {
+    push_text(db, uid, workspace_id, object_id, &doc, content);
+  }
+  doc
+}
+
+/// Appends the content to the "text" of the doc, and pushes the update.
+pub fn push_text(
+  db: &CollabKVDB,
+  uid: i64,
+  workspace_id: &str,
+  object_id: &str,
+  doc: &Doc,
+  content: &str,
+) {
+  let text = doc.get_or_insert_text("text");
+  let mut txn = doc.transact_mut();
+  let len = text.len(&txn);
+  text.insert(&mut txn, len, content);
+  let update = txn.encode_update_v1();
+  db.with_write_txn(|store| store.push_update(uid, workspace_id, object_id, &update))
+    .unwrap();
+}
+
+/// Loads the doc of the object from the store, and returns its "text".
+pub fn load_text<'a, S>(store: &S, uid: i64, workspace_id: &str, object_id: &str) -> String
+where
+  S: CollabKVAction<'a>,
+  PersistenceError: From<S::Error>,
+{
+  let doc = Doc::new();
+  let text = doc.get_or_insert_text("text");
+  store.load_doc(uid, workspace_id, object_id, &doc).unwrap();
+  text.get_string(&doc.transact())
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use assert_json_diff::assert_json_eq;
+use collab::preclude::Collab;
+use collab_plugins::local_storage::rocksdb::rocksdb_plugin::RocksdbDiskPlugin;
+use collab_plugins::local_storage::{CollabPersistenceConfig, DurabilityMode};
+
+use crate::disk::script::CollabPersistenceTest;
+use crate::wait_until;
+
+const DOC_ID: &str = "1";
+
+#[tokio::test]
+async fn write_behind_flush_after_update_count_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let (mut collab, plugin) = open_collab(&test, 10, Duration::from_secs(3600));
+  for i in 0..25 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 20);
+
+  plugin.flush().unwrap();
+  assert_eq!(test.number_of_updates(DOC_ID), 25);
+
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[tokio::test]
+async fn write_behind_flush_after_delay_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let (mut collab, _plugin) = open_collab(&test, 100, Duration::from_millis(200));
+  for i in 0..5 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 0);
+
+  wait_until(|| test.number_of_updates(DOC_ID) == 5).await;
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+#[test]
+fn write_behind_flush_on_destroy_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let (mut collab, _plugin) = open_collab(&test, 100, Duration::from_secs(3600));
+  for i in 0..5 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  assert_eq!(test.number_of_updates(DOC_ID), 0);
+
+  collab.remove_all_plugins();
+  assert_eq!(test.number_of_updates(DOC_ID), 5);
+  assert_json_eq!(
+    test.reopen_collab(DOC_ID).to_json_value(),
+    collab.to_json_value()
+  );
+}
+
+#[test]
+fn write_behind_flush_on_drop_test() {
+  let test = CollabPersistenceTest::new(CollabPersistenceConfig::new());
+  let (mut collab, plugin) = open_collab(&test, 100, Duration::from_secs(3600));
+  for i in 0..5 {
+    collab.insert(&i.to_string(), i.to_string());
+  }
+  let expected = collab.to_json_value();
+  drop(collab);
+  assert_eq!(test.number_of_updates(DOC_ID), 0);
+
+  // The updates are flushed once the last reference to the plugin is dropped
+  drop(plugin);
+  assert_eq!(test.number_of_updates(DOC_ID), 5);
+  assert_json_eq!(test.reopen_collab(DOC_ID).to_json_value(), expected);
+}
+
+fn open_collab(
+  test: &CollabPersistenceTest,
+  max_updates: usize,
+  max_delay: Duration,
+) -> (Collab, RocksdbDiskPlugin) {
+  let config = CollabPersistenceConfig::new().durability(DurabilityMode::WriteBehind {
+    max_updates,
+    max_delay,
+  });
+  test.open_collab(DOC_ID, config)
+}
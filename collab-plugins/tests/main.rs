@@ -1,3 +1,6 @@
+#[cfg(all(feature = "postgres_plugin", not(target_arch = "wasm32")))]
+mod cloud;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod disk;
 
@@ -26,3 +29,15 @@ pub fn setup_log() {
     subscriber.try_init().unwrap();
   });
 }
+
+/// Polls the condition every 100ms, and panics if it's still false after 10 seconds.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn wait_until(condition: impl Fn() -> bool) {
+  for _ in 0..100 {
+    if condition() {
+      return;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  }
+  panic!("timeout waiting for the condition");
+}
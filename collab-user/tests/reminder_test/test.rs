@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use collab_entity::CollabType;
 use collab_entity::reminder::{ObjectType, Reminder};
 
 use crate::util::UserAwarenessTest;
@@ -169,3 +170,11 @@ fn delete_reminder_test() {
     })
   )
 }
+
+#[test]
+fn created_user_awareness_matches_schema_test() {
+  let test = UserAwarenessTest::new(1);
+  CollabType::UserAwareness
+    .validate_schema(&test.user_awareness)
+    .unwrap();
+}
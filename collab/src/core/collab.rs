@@ -707,7 +707,7 @@ impl CollabBuilder {
   }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Path(Vec<String>);
 
 impl IntoIterator for Path {
@@ -0,0 +1,473 @@
+use std::collections::BTreeSet;
+
+use yrs::types::{AsPrelim, ToJson};
+use yrs::updates::encoder::{Encoder, EncoderV1};
+use yrs::{
+  Any, Array, ArrayRef, GetString, Map, MapRef, Out, ReadTxn, StateVector, Text, TextRef,
+  TransactionMut,
+};
+
+use crate::core::collab::{Collab, DataSource, Path};
+use crate::core::origin::CollabOrigin;
+use crate::entity::EncodedCollab;
+use crate::error::CollabError;
+
+/// A change between two states of the data of a [Collab]. The `path` is the path of the map, array
+/// or text that changed, starting from [Collab::data].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollabChange {
+  MapInserted {
+    path: Path,
+    key: String,
+    value: Any,
+  },
+  MapUpdated {
+    path: Path,
+    key: String,
+    old_value: Any,
+    new_value: Any,
+  },
+  MapRemoved {
+    path: Path,
+    key: String,
+    old_value: Any,
+  },
+  /// The index is the index in the array after the previous changes of the array are applied.
+  ArrayInserted {
+    path: Path,
+    index: u32,
+    values: Vec<Any>,
+  },
+  /// The index is the index in the array after the previous changes of the array are applied.
+  ArrayDeleted {
+    path: Path,
+    index: u32,
+    len: u32,
+  },
+  /// The formatting of the text is not compared.
+  TextChanged {
+    path: Path,
+    delta: Vec<TextDelta>,
+  },
+}
+
+impl CollabChange {
+  pub fn path(&self) -> &Path {
+    match self {
+      CollabChange::MapInserted { path, .. }
+      | CollabChange::MapUpdated { path, .. }
+      | CollabChange::MapRemoved { path, .. }
+      | CollabChange::ArrayInserted { path, .. }
+      | CollabChange::ArrayDeleted { path, .. }
+      | CollabChange::TextChanged { path, .. } => path,
+    }
+  }
+}
+
+/// The lengths are in UTF-16 code units, like the indexes of the texts of a [Collab].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextDelta {
+  Retain(u32),
+  Insert(String),
+  Delete(u32),
+}
+
+/// Returns the changes that turn the data of `old` into the data of `new`. The keys of a map are
+/// compared in alphabetical order.
+pub fn diff_collab(old: &Collab, new: &Collab) -> Vec<CollabChange> {
+  let old_txn = old.transact();
+  let new_txn = new.transact();
+  let mut differ = Differ {
+    old_txn: &old_txn,
+    new_txn: &new_txn,
+    changes: vec![],
+  };
+  differ.diff_map(&Path::from(Vec::<String>::new()), &old.data, &new.data);
+  differ.changes
+}
+
+/// Same as [diff_collab], but the states are encoded.
+pub fn diff_encoded_collab(
+  old: &EncodedCollab,
+  new: &EncodedCollab,
+) -> Result<Vec<CollabChange>, CollabError> {
+  let old = decode_collab(old.clone().into())?;
+  let new = decode_collab(new.clone().into())?;
+  Ok(diff_collab(&old, &new))
+}
+
+/// Returns the changes of the collab since the given state vector.
+///
+/// A state vector doesn't record the deletions, so the state at the state vector is rebuilt from
+/// the items inserted before it that are not deleted now. The content inserted before the state
+/// vector and deleted after it is not reported, and a map value replaced after it is reported as
+/// inserted. Use [diff_encoded_collab] with the encoded state of the collab at that time to get
+/// these changes. The collab must be created with `skip_gc`.
+pub fn diff_since(
+  collab: &Collab,
+  state_vector: &StateVector,
+) -> Result<Vec<CollabChange>, CollabError> {
+  let doc_state = {
+    let txn = collab.transact();
+    let mut snapshot = txn.snapshot();
+    snapshot.state_map = state_vector.clone();
+    let mut encoder = EncoderV1::new();
+    txn
+      .encode_state_from_snapshot(&snapshot, &mut encoder)
+      .map_err(|err| CollabError::YrsEncodeStateError(format!("{:?}", err)))?;
+    encoder.to_vec()
+  };
+  let old = decode_collab(DataSource::DocStateV1(doc_state))?;
+  Ok(diff_collab(&old, collab))
+}
+
+/// Applies the changes returned by `diff_collab(collab, new)` in a single transaction, so the data
+/// of the collab becomes the same as the data of `new`. The inserted maps, arrays and texts are
+/// copied from `new`.
+pub fn apply_diff(
+  collab: &mut Collab,
+  new: &Collab,
+  changes: &[CollabChange],
+) -> Result<(), CollabError> {
+  let data = collab.data.clone();
+  let new_txn = new.transact();
+  collab.context.with_txn(|txn| {
+    for change in changes {
+      apply_change(txn, &data, &new_txn, &new.data, change)?;
+    }
+    Ok(())
+  })?
+}
+
+/// Changes the data of the collab to the data of `target`. The changes are applied as a new
+/// update, so the history of the collab is kept and the update is synced and persisted like the
+/// other changes.
+pub fn restore_collab(collab: &mut Collab, target: &Collab) -> Result<(), CollabError> {
+  let changes = diff_collab(collab, target);
+  if changes.is_empty() {
+    return Ok(());
+  }
+  apply_diff(collab, target, &changes)
+}
+
+/// Returns the value at the path, starting from the given map. The segments of the path are the
+/// keys of the maps and the indexes of the arrays.
+pub(crate) fn get_out<T: ReadTxn>(txn: &T, root: &MapRef, path: &[String]) -> Option<Out> {
+  let mut current = Out::YMap(root.clone());
+  for segment in path {
+    current = match current {
+      Out::YMap(map) => map.get(txn, segment)?,
+      Out::YArray(array) => array.get(txn, segment.parse().ok()?)?,
+      _ => return None,
+    };
+  }
+  Some(current)
+}
+
+fn apply_change<T: ReadTxn>(
+  txn: &mut TransactionMut,
+  data: &MapRef,
+  new_txn: &T,
+  new_data: &MapRef,
+  change: &CollabChange,
+) -> Result<(), CollabError> {
+  let path = change.path();
+  let not_found = || CollabError::NoRequiredData(format!("can't find the value at {:?}", path));
+  let target = get_out(&*txn, data, path).ok_or_else(not_found)?;
+  match (change, target) {
+    (
+      CollabChange::MapInserted { key, .. } | CollabChange::MapUpdated { key, .. },
+      Out::YMap(map),
+    ) => {
+      let mut new_path = path.to_vec();
+      new_path.push(key.clone());
+      let value = get_out(new_txn, new_data, &new_path).ok_or_else(not_found)?;
+      map.insert(txn, key.as_str(), value.as_prelim(new_txn));
+    },
+    (CollabChange::MapRemoved { key, .. }, Out::YMap(map)) => {
+      map.remove(txn, key);
+    },
+    (CollabChange::ArrayInserted { index, values, .. }, Out::YArray(array)) => {
+      let Some(Out::YArray(new_array)) = get_out(new_txn, new_data, path) else {
+        return Err(not_found());
+      };
+      for i in *index..*index + values.len() as u32 {
+        let value = new_array.get(new_txn, i).ok_or_else(not_found)?;
+        array.insert(txn, i, value.as_prelim(new_txn));
+      }
+    },
+    (CollabChange::ArrayDeleted { index, len, .. }, Out::YArray(array)) => {
+      array.remove_range(txn, *index, *len);
+    },
+    (CollabChange::TextChanged { delta, .. }, Out::YText(text)) => {
+      apply_text_delta(txn, &text, delta);
+    },
+    _ => {
+      return Err(CollabError::NoRequiredData(format!(
+        "the value at {:?} doesn't match the change",
+        path
+      )));
+    },
+  }
+  Ok(())
+}
+
+pub(crate) fn decode_collab(data_source: DataSource) -> Result<Collab, CollabError> {
+  Collab::new_with_source(CollabOrigin::Empty, "", data_source, vec![], false)
+}
+
+struct Differ<'a, O, N> {
+  old_txn: &'a O,
+  new_txn: &'a N,
+  changes: Vec<CollabChange>,
+}
+
+impl<O: ReadTxn, N: ReadTxn> Differ<'_, O, N> {
+  fn diff_map(&mut self, path: &Path, old: &MapRef, new: &MapRef) {
+    let keys = old
+      .keys(self.old_txn)
+      .chain(new.keys(self.new_txn))
+      .map(|key| key.to_string())
+      .collect::<BTreeSet<String>>();
+    for key in keys {
+      match (old.get(self.old_txn, &key), new.get(self.new_txn, &key)) {
+        (Some(old_value), None) => self.changes.push(CollabChange::MapRemoved {
+          path: path.clone(),
+          key,
+          old_value: old_value.to_json(self.old_txn),
+        }),
+        (None, Some(new_value)) => self.changes.push(CollabChange::MapInserted {
+          path: path.clone(),
+          key,
+          value: new_value.to_json(self.new_txn),
+        }),
+        (Some(old_value), Some(new_value)) => {
+          if !self.diff_shared(&child_path(path, &key), &old_value, &new_value) {
+            let old_value = old_value.to_json(self.old_txn);
+            let new_value = new_value.to_json(self.new_txn);
+            if old_value != new_value {
+              self.changes.push(CollabChange::MapUpdated {
+                path: path.clone(),
+                key,
+                old_value,
+                new_value,
+              });
+            }
+          }
+        },
+        (None, None) => {},
+      }
+    }
+  }
+
+  /// Compares the content of the values if they are both maps, arrays or texts. Returns false
+  /// otherwise.
+  fn diff_shared(&mut self, path: &Path, old: &Out, new: &Out) -> bool {
+    match (old, new) {
+      (Out::YMap(old), Out::YMap(new)) => self.diff_map(path, old, new),
+      (Out::YArray(old), Out::YArray(new)) => self.diff_array(path, old, new),
+      (Out::YText(old), Out::YText(new)) => self.diff_text(path, old, new),
+      _ => return false,
+    }
+    true
+  }
+
+  fn diff_array(&mut self, path: &Path, old: &ArrayRef, new: &ArrayRef) {
+    let old_items = old.iter(self.old_txn).collect::<Vec<_>>();
+    let new_items = new.iter(self.new_txn).collect::<Vec<_>>();
+    let old_values = old_items
+      .iter()
+      .map(|item| item.to_json(self.old_txn))
+      .collect::<Vec<_>>();
+    let new_values = new_items
+      .iter()
+      .map(|item| item.to_json(self.new_txn))
+      .collect::<Vec<_>>();
+
+    let mut index = 0;
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_end, new_end) in common_subsequence(&old_values, &new_values)
+      .into_iter()
+      .chain([(old_values.len(), new_values.len())])
+    {
+      // The items between the common items are replaced. The maps, arrays and texts that are
+      // replaced by the same type are compared, the other items are deleted and inserted.
+      while old_index < old_end && new_index < new_end {
+        if !self.diff_shared(
+          &child_path(path, &index.to_string()),
+          &old_items[old_index],
+          &new_items[new_index],
+        ) {
+          break;
+        }
+        index += 1;
+        old_index += 1;
+        new_index += 1;
+      }
+      if old_index < old_end {
+        self.changes.push(CollabChange::ArrayDeleted {
+          path: path.clone(),
+          index,
+          len: (old_end - old_index) as u32,
+        });
+      }
+      if new_index < new_end {
+        self.changes.push(CollabChange::ArrayInserted {
+          path: path.clone(),
+          index,
+          values: new_values[new_index..new_end].to_vec(),
+        });
+        index += (new_end - new_index) as u32;
+      }
+      // Skip the common item
+      index += 1;
+      old_index = old_end + 1;
+      new_index = new_end + 1;
+    }
+  }
+
+  fn diff_text(&mut self, path: &Path, old: &TextRef, new: &TextRef) {
+    let old_text = old.get_string(self.old_txn);
+    let new_text = new.get_string(self.new_txn);
+    if old_text == new_text {
+      return;
+    }
+    self.changes.push(CollabChange::TextChanged {
+      path: path.clone(),
+      delta: text_delta(&old_text, &new_text),
+    });
+  }
+}
+
+/// Returns the delta that turns the old text into the new text. The common prefix and suffix are
+/// retained, and the rest is replaced.
+pub(crate) fn text_delta(old_text: &str, new_text: &str) -> Vec<TextDelta> {
+  let old_chars = old_text.chars().collect::<Vec<char>>();
+  let new_chars = new_text.chars().collect::<Vec<char>>();
+  let prefix = old_chars
+    .iter()
+    .zip(&new_chars)
+    .take_while(|(old, new)| old == new)
+    .count();
+  let suffix = old_chars[prefix..]
+    .iter()
+    .rev()
+    .zip(new_chars[prefix..].iter().rev())
+    .take_while(|(old, new)| old == new)
+    .count();
+
+  let mut delta = vec![];
+  if prefix > 0 {
+    delta.push(TextDelta::Retain(utf16_len(&old_chars[..prefix])));
+  }
+  let deleted = &old_chars[prefix..old_chars.len() - suffix];
+  if !deleted.is_empty() {
+    delta.push(TextDelta::Delete(utf16_len(deleted)));
+  }
+  let inserted = &new_chars[prefix..new_chars.len() - suffix];
+  if !inserted.is_empty() {
+    delta.push(TextDelta::Insert(inserted.iter().collect()));
+  }
+  delta
+}
+
+pub(crate) fn apply_text_delta(txn: &mut TransactionMut, text: &TextRef, delta: &[TextDelta]) {
+  let mut index = 0;
+  for delta in delta {
+    match delta {
+      TextDelta::Retain(len) => index += len,
+      TextDelta::Delete(len) => text.remove_range(txn, index, *len),
+      TextDelta::Insert(chunk) => {
+        text.insert(txn, index, chunk);
+        index += chunk.encode_utf16().count() as u32;
+      },
+    }
+  }
+}
+
+fn child_path(path: &Path, key: &str) -> Path {
+  let mut path = path.clone();
+  path.push(key.to_string());
+  path
+}
+
+fn utf16_len(chars: &[char]) -> u32 {
+  chars.iter().map(|c| c.len_utf16() as u32).sum()
+}
+
+/// Returns the indexes of the longest common subsequence of the two lists, in ascending order.
+fn common_subsequence(old: &[Any], new: &[Any]) -> Vec<(usize, usize)> {
+  // The common prefix and suffix don't need to be compared with the other items
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let old_middle = &old[prefix..old.len() - suffix];
+  let new_middle = &new[prefix..new.len() - suffix];
+
+  let mut indexes = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+  middle_subsequence(old_middle, new_middle, (prefix, prefix), &mut indexes);
+  indexes.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+  indexes
+}
+
+/// Appends the indexes of the longest common subsequence of the two lists with Hirschberg's
+/// algorithm, which only keeps one row of the lengths table, so the memory is linear in the
+/// length of the lists. `offsets` are added to the indexes.
+fn middle_subsequence(
+  old: &[Any],
+  new: &[Any],
+  offsets: (usize, usize),
+  indexes: &mut Vec<(usize, usize)>,
+) {
+  if old.is_empty() || new.is_empty() {
+    return;
+  }
+  if old.len() == 1 {
+    if let Some(j) = new.iter().position(|value| value == &old[0]) {
+      indexes.push((offsets.0, offsets.1 + j));
+    }
+    return;
+  }
+
+  // Split the old list in two halves, and the new list where the sum of the subsequences of the
+  // halves is the longest
+  let middle = old.len() / 2;
+  let forward = subsequence_lengths(old[..middle].iter(), new.iter());
+  let backward = subsequence_lengths(old[middle..].iter().rev(), new.iter().rev());
+  let split = (0..=new.len())
+    .max_by_key(|&j| forward[j] + backward[new.len() - j])
+    .unwrap_or_default();
+  middle_subsequence(&old[..middle], &new[..split], offsets, indexes);
+  middle_subsequence(
+    &old[middle..],
+    &new[split..],
+    (offsets.0 + middle, offsets.1 + split),
+    indexes,
+  );
+}
+
+/// Returns the lengths of the longest common subsequences of `old` and every prefix of `new`.
+fn subsequence_lengths<'a, I>(old: impl Iterator<Item = &'a Any>, new: I) -> Vec<usize>
+where
+  I: ExactSizeIterator<Item = &'a Any> + Clone,
+{
+  let mut lengths = vec![0; new.len() + 1];
+  for old_value in old {
+    // The length of the previous row at j - 1
+    let mut diagonal = 0;
+    for (j, new_value) in new.clone().enumerate() {
+      let above = lengths[j + 1];
+      lengths[j + 1] = if old_value == new_value {
+        diagonal + 1
+      } else {
+        above.max(lengths[j])
+      };
+      diagonal = above;
+    }
+  }
+  lengths
+}
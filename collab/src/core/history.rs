@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::{Encode, Encoder, EncoderV1};
+use yrs::{ReadTxn, Snapshot};
+
+use crate::core::collab::{Collab, DataSource};
+use crate::core::diff::restore_collab;
+use crate::core::origin::CollabOrigin;
+use crate::error::CollabError;
+
+/// A list of [CollabHistorySnapshot]s of a [Collab], ordered by the time they were captured.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CollabHistory {
+  snapshots: Vec<CollabHistorySnapshot>,
+}
+
+impl CollabHistory {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Captures the current state of the collab and appends it to the history.
+  pub fn capture(&mut self, collab: &Collab) -> Result<&CollabHistorySnapshot, CollabError> {
+    self.snapshots.push(CollabHistorySnapshot::new(collab)?);
+    Ok(self.snapshots.last().unwrap())
+  }
+
+  pub fn snapshots(&self) -> &[CollabHistorySnapshot] {
+    &self.snapshots
+  }
+
+  /// Removes the snapshots captured before the given timestamp.
+  pub fn remove_before(&mut self, timestamp: i64) {
+    self
+      .snapshots
+      .retain(|snapshot| snapshot.created_at >= timestamp);
+  }
+}
+
+/// The state of a [Collab] at some point in time. It only keeps the state vector and the deleted
+/// items of the document, so the collab must be created with `skip_gc` to read the content of a
+/// snapshot, see [crate::core::collab::CollabBuilder::with_skip_gc].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollabHistorySnapshot {
+  /// The timestamp in milliseconds
+  pub created_at: i64,
+  /// The origin of the collab that captured the snapshot
+  pub origin: CollabOrigin,
+  /// The encoded [Snapshot]
+  snapshot: Vec<u8>,
+}
+
+impl CollabHistorySnapshot {
+  pub fn new(collab: &Collab) -> Result<Self, CollabError> {
+    if !collab.doc().skip_gc() {
+      return Err(CollabError::NoRequiredData(
+        "the collab must be created with skip_gc to capture a snapshot".to_string(),
+      ));
+    }
+    Ok(Self {
+      created_at: chrono::Utc::now().timestamp_millis(),
+      origin: collab.origin().clone(),
+      snapshot: collab.transact().snapshot().encode_v1(),
+    })
+  }
+
+  /// Returns a new collab with the data the given collab had at this snapshot. The returned collab
+  /// is detached from the given collab: it has no plugins, and its changes are not synced or
+  /// persisted.
+  pub fn materialize(&self, collab: &Collab) -> Result<Collab, CollabError> {
+    let snapshot = Snapshot::decode_v1(&self.snapshot)?;
+    let doc_state = {
+      let txn = collab.transact();
+      let mut encoder = EncoderV1::new();
+      txn
+        .encode_state_from_snapshot(&snapshot, &mut encoder)
+        .map_err(|err| CollabError::YrsEncodeStateError(format!("{:?}", err)))?;
+      encoder.to_vec()
+    };
+    Collab::new_with_source(
+      CollabOrigin::Empty,
+      collab.object_id(),
+      DataSource::DocStateV1(doc_state),
+      vec![],
+      false,
+    )
+  }
+
+  pub fn to_json_value(&self, collab: &Collab) -> Result<JsonValue, CollabError> {
+    Ok(self.materialize(collab)?.to_json_value())
+  }
+
+  /// Restores the data of the collab to this snapshot. The history of the collab is kept: the
+  /// restore is applied as a new update, so it's synced and persisted like the other changes.
+  pub fn restore(&self, collab: &mut Collab) -> Result<(), CollabError> {
+    let old = self.materialize(collab)?;
+    restore_collab(collab, &old)
+  }
+}
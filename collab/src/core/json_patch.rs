@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use yrs::block::Prelim;
+use yrs::types::{AsPrelim, ToJson};
+use yrs::{Array, GetString, Map, MapRef, Out, ReadTxn, StateVector, TransactionMut};
+
+use crate::core::collab::{Collab, DataSource, Path};
+use crate::core::diff::{apply_text_delta, decode_collab, get_out, text_delta};
+use crate::core::value::Entity;
+use crate::error::CollabError;
+
+/// An operation of a JSON Patch, see [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902).
+/// The paths are JSON Pointers relative to [Collab::data].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+  Add { path: String, value: JsonValue },
+  Remove { path: String },
+  Replace { path: String, value: JsonValue },
+  Move { from: String, path: String },
+  Copy { from: String, path: String },
+  Test { path: String, value: JsonValue },
+}
+
+/// Parses a JSON Pointer, see [RFC 6901](https://datatracker.ietf.org/doc/html/rfc6901). The
+/// empty pointer refers to the whole document.
+pub fn parse_json_pointer(pointer: &str) -> Result<Path, CollabError> {
+  if pointer.is_empty() {
+    return Ok(Path::from(Vec::<String>::new()));
+  }
+  let tokens = pointer.strip_prefix('/').ok_or_else(|| {
+    CollabError::InvalidJsonPatch(format!("json pointer must start with '/': {}", pointer))
+  })?;
+  Ok(Path::from(
+    tokens
+      .split('/')
+      .map(|token| token.replace("~1", "/").replace("~0", "~"))
+      .collect::<Vec<String>>(),
+  ))
+}
+
+impl Collab {
+  /// Returns the value the JSON Pointer refers to, or [None] if it doesn't exist.
+  pub fn get_with_json_pointer(&self, pointer: &str) -> Result<Option<JsonValue>, CollabError> {
+    let path = parse_json_pointer(pointer)?;
+    let txn = self.transact();
+    match get_out(&txn, &self.data, &path) {
+      Some(out) => Ok(Some(serde_json::to_value(out.to_json(&txn))?)),
+      None => Ok(None),
+    }
+  }
+
+  /// Applies the JSON Patch in a single transaction. If any operation fails, none of them is
+  /// applied.
+  ///
+  /// The objects and arrays of the added values are inserted as maps and arrays. Replacing a text
+  /// with a string only changes the characters that differ, and moving an item within the same
+  /// array moves it instead of copying it, so the concurrent changes of the item are kept.
+  pub fn apply_json_patch(&mut self, patch: &[PatchOperation]) -> Result<(), CollabError> {
+    // A yrs transaction can't be rolled back, so the patch is applied to a copy of the collab
+    // before it's applied to the collab.
+    let doc_state = self
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    let mut copy = decode_collab(DataSource::DocStateV1(doc_state))?;
+    apply_patch(&mut copy, patch)?;
+    apply_patch(self, patch)
+  }
+}
+
+fn apply_patch(collab: &mut Collab, patch: &[PatchOperation]) -> Result<(), CollabError> {
+  let data = collab.data.clone();
+  collab.context.with_txn(|txn| {
+    for operation in patch {
+      apply_to_collab(txn, &data, operation)?;
+    }
+    Ok(())
+  })?
+}
+
+fn apply_to_collab(
+  txn: &mut TransactionMut,
+  data: &MapRef,
+  operation: &PatchOperation,
+) -> Result<(), CollabError> {
+  match operation {
+    PatchOperation::Add { path, value } => {
+      insert_value(
+        txn,
+        data,
+        &parse_json_pointer(path)?,
+        Entity::from(value.clone()),
+      )?;
+    },
+    PatchOperation::Remove { path } => {
+      remove_value(txn, data, &parse_json_pointer(path)?)?;
+    },
+    PatchOperation::Replace { path, value } => {
+      let path = parse_json_pointer(path)?;
+      if let (Some(Out::YText(text)), JsonValue::String(new_text)) =
+        (get_out(&*txn, data, &path), value)
+      {
+        let delta = text_delta(&text.get_string(txn), new_text);
+        apply_text_delta(txn, &text, &delta);
+      } else {
+        remove_value(txn, data, &path)?;
+        insert_value(txn, data, &path, Entity::from(value.clone()))?;
+      }
+    },
+    PatchOperation::Move { from, path } => {
+      let from = parse_json_pointer(from)?;
+      let path = parse_json_pointer(path)?;
+      if from == path {
+        return Ok(());
+      }
+      if path.starts_with(&from[..]) {
+        return Err(invalid_patch("can't move a value into itself", &path));
+      }
+      let (from_parent, from_token) = split_path(&from)?;
+      let (parent, token) = split_path(&path)?;
+      if from_parent == parent {
+        if let Some(Out::YArray(array)) = get_out(&*txn, data, from_parent) {
+          let len = array.len(txn) as usize;
+          let source = array_index(from_token, len).map_err(|_| invalid_index(&from))?;
+          // The index of the target is the index after the item is removed
+          let target = insert_index(token, len - 1).map_err(|_| invalid_index(&path))?;
+          let target = if target > source { target + 1 } else { target };
+          array.move_to(txn, source as u32, target as u32);
+          return Ok(());
+        }
+      }
+      let value = get_out(&*txn, data, &from)
+        .ok_or_else(|| invalid_patch("value not found", &from))?
+        .as_prelim(&*txn);
+      remove_value(txn, data, &from)?;
+      insert_value(txn, data, &path, value)?;
+    },
+    PatchOperation::Copy { from, path } => {
+      let from = parse_json_pointer(from)?;
+      let value = get_out(&*txn, data, &from)
+        .ok_or_else(|| invalid_patch("value not found", &from))?
+        .as_prelim(&*txn);
+      insert_value(txn, data, &parse_json_pointer(path)?, value)?;
+    },
+    PatchOperation::Test { path, value } => {
+      let path = parse_json_pointer(path)?;
+      let out =
+        get_out(&*txn, data, &path).ok_or_else(|| invalid_patch("value not found", &path))?;
+      if serde_json::to_value(out.to_json(&*txn))? != *value {
+        return Err(invalid_patch("test failed", &path));
+      }
+    },
+  }
+  Ok(())
+}
+
+fn insert_value<V: Prelim>(
+  txn: &mut TransactionMut,
+  data: &MapRef,
+  path: &[String],
+  value: V,
+) -> Result<(), CollabError> {
+  let (parent, token) = split_path(path)?;
+  match get_out(&*txn, data, parent) {
+    Some(Out::YMap(map)) => {
+      map.insert(txn, token, value);
+    },
+    Some(Out::YArray(array)) => {
+      let index = insert_index(token, array.len(txn) as usize).map_err(|_| invalid_index(path))?;
+      array.insert(txn, index as u32, value);
+    },
+    _ => return Err(invalid_patch("parent is not a map or an array", path)),
+  }
+  Ok(())
+}
+
+fn remove_value(
+  txn: &mut TransactionMut,
+  data: &MapRef,
+  path: &[String],
+) -> Result<(), CollabError> {
+  let (parent, token) = split_path(path)?;
+  match get_out(&*txn, data, parent) {
+    Some(Out::YMap(map)) => {
+      map
+        .remove(txn, token)
+        .ok_or_else(|| invalid_patch("value not found", path))?;
+    },
+    Some(Out::YArray(array)) => {
+      let index = array_index(token, array.len(txn) as usize).map_err(|_| invalid_index(path))?;
+      array.remove(txn, index as u32);
+    },
+    _ => return Err(invalid_patch("parent is not a map or an array", path)),
+  }
+  Ok(())
+}
+
+fn split_path(path: &[String]) -> Result<(&[String], &str), CollabError> {
+  let (last, parent) = path
+    .split_last()
+    .ok_or_else(|| invalid_patch("can't change the root", path))?;
+  Ok((parent, last))
+}
+
+/// Returns the index of an existing item of an array with the given length.
+fn array_index(token: &str, len: usize) -> Result<usize, ()> {
+  // Leading zeros are not allowed
+  if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+    return Err(());
+  }
+  match token.parse::<usize>() {
+    Ok(index) if index < len => Ok(index),
+    _ => Err(()),
+  }
+}
+
+/// Same as [array_index], but the index can be the length of the array, and `-` refers to the end
+/// of the array.
+fn insert_index(token: &str, len: usize) -> Result<usize, ()> {
+  if token == "-" {
+    return Ok(len);
+  }
+  array_index(token, len + 1)
+}
+
+fn invalid_index(path: &[String]) -> CollabError {
+  invalid_patch("invalid array index", path)
+}
+
+fn invalid_patch(reason: &str, path: &[String]) -> CollabError {
+  CollabError::InvalidJsonPatch(format!("{}: /{}", reason, path.join("/")))
+}
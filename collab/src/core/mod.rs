@@ -3,7 +3,12 @@ pub mod collab;
 pub mod collab_plugin;
 mod collab_search;
 pub mod collab_state;
+pub mod diff;
 pub mod fill;
+pub mod history;
+pub mod json_patch;
 pub mod origin;
+pub mod schema;
 pub mod transaction;
+pub mod type_hint;
 pub mod value;
@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+use yrs::types::ToJson;
+use yrs::{Any, Array, Map, Out, ReadTxn};
+
+use crate::core::collab::{Collab, Path};
+use crate::core::diff::decode_collab;
+use crate::entity::EncodedCollab;
+use crate::error::CollabError;
+
+/// The kind of a value stored in a [Collab].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+  Map,
+  Array,
+  Text,
+  /// A value that is not a shared type, see [Any].
+  Any,
+  /// The other shared types, like the xml types and the sub documents.
+  Other,
+}
+
+impl ValueKind {
+  pub fn of(value: &Out) -> Self {
+    match value {
+      Out::YMap(_) => ValueKind::Map,
+      Out::YArray(_) => ValueKind::Array,
+      Out::YText(_) => ValueKind::Text,
+      Out::Any(_) => ValueKind::Any,
+      _ => ValueKind::Other,
+    }
+  }
+}
+
+impl Display for ValueKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ValueKind::Map => f.write_str("map"),
+      ValueKind::Array => f.write_str("array"),
+      ValueKind::Text => f.write_str("text"),
+      ValueKind::Any => f.write_str("any"),
+      ValueKind::Other => f.write_str("other"),
+    }
+  }
+}
+
+/// Describes the expected shape of the data of a [Collab]: the kind of each value, the required
+/// keys of the maps, the schemas of their values and of the array items, and custom checks.
+///
+/// The fields, values and items also apply to the maps and arrays stored as [Any].
+///
+/// ```
+/// use collab::core::schema::Schema;
+///
+/// let schema = Schema::map().required(
+///   "database",
+///   Schema::map()
+///     .required("id", Schema::string())
+///     .optional("fields", Schema::map().values(Schema::map())),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+  kind: Option<ValueKind>,
+  fields: BTreeMap<String, Field>,
+  values: Option<Box<Schema>>,
+  items: Option<Box<Schema>>,
+  checks: Vec<Check>,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+  schema: Schema,
+  required: bool,
+}
+
+#[derive(Clone)]
+struct Check {
+  description: String,
+  predicate: Arc<dyn Fn(&Any) -> bool + Send + Sync>,
+}
+
+impl Debug for Check {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Check").field(&self.description).finish()
+  }
+}
+
+impl Schema {
+  /// A schema that accepts any value.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn of_kind(kind: ValueKind) -> Self {
+    Self {
+      kind: Some(kind),
+      ..Default::default()
+    }
+  }
+
+  pub fn map() -> Self {
+    Self::of_kind(ValueKind::Map)
+  }
+
+  pub fn array() -> Self {
+    Self::of_kind(ValueKind::Array)
+  }
+
+  pub fn text() -> Self {
+    Self::of_kind(ValueKind::Text)
+  }
+
+  pub fn any() -> Self {
+    Self::of_kind(ValueKind::Any)
+  }
+
+  /// A string stored as [Any].
+  pub fn string() -> Self {
+    Self::any().check("must be a string", |value| matches!(value, Any::String(_)))
+  }
+
+  /// The key must exist in the map, and its value must match the schema.
+  pub fn required(mut self, key: &str, schema: Schema) -> Self {
+    self.fields.insert(
+      key.to_string(),
+      Field {
+        schema,
+        required: true,
+      },
+    );
+    self
+  }
+
+  /// If the key exists in the map, its value must match the schema.
+  pub fn optional(mut self, key: &str, schema: Schema) -> Self {
+    self.fields.insert(
+      key.to_string(),
+      Field {
+        schema,
+        required: false,
+      },
+    );
+    self
+  }
+
+  /// The values of the keys that are not added with [Schema::required] or [Schema::optional] must
+  /// match the schema, like the rows of a map of rows by id.
+  pub fn values(mut self, schema: Schema) -> Self {
+    self.values = Some(Box::new(schema));
+    self
+  }
+
+  /// The items of the array must match the schema.
+  pub fn items(mut self, schema: Schema) -> Self {
+    self.items = Some(Box::new(schema));
+    self
+  }
+
+  /// The value must pass the predicate. The predicate receives the JSON-like representation of
+  /// the value, and is only called if the value has the expected kind.
+  pub fn check<F>(mut self, description: &str, predicate: F) -> Self
+  where
+    F: Fn(&Any) -> bool + Send + Sync + 'static,
+  {
+    self.checks.push(Check {
+      description: description.to_string(),
+      predicate: Arc::new(predicate),
+    });
+    self
+  }
+
+  /// Validates [Collab::data] and returns every violation. The data is valid if there's none.
+  pub fn validate(&self, collab: &Collab) -> Vec<SchemaViolation> {
+    let txn = collab.transact();
+    let mut violations = vec![];
+    self.validate_value(
+      &txn,
+      &Path::from(Vec::<String>::new()),
+      &Out::YMap(collab.data.clone()),
+      &mut violations,
+    );
+    violations
+  }
+
+  /// Same as [Schema::validate], for the data of an encoded collab.
+  pub fn validate_encoded_collab(
+    &self,
+    encoded_collab: &EncodedCollab,
+  ) -> Result<Vec<SchemaViolation>, CollabError> {
+    let collab = decode_collab(encoded_collab.clone().into())?;
+    Ok(self.validate(&collab))
+  }
+
+  fn validate_value<T: ReadTxn>(
+    &self,
+    txn: &T,
+    path: &Path,
+    value: &Out,
+    violations: &mut Vec<SchemaViolation>,
+  ) {
+    let kind = ValueKind::of(value);
+    if let Some(expected) = self.kind {
+      if expected != kind {
+        violations.push(SchemaViolation {
+          path: path.clone(),
+          reason: ViolationReason::UnexpectedKind {
+            expected,
+            actual: kind,
+          },
+        });
+        return;
+      }
+    }
+
+    if let Some(entries) = map_entries(txn, value) {
+      for (key, field) in &self.fields {
+        match entries.get(key) {
+          Some(value) => field
+            .schema
+            .validate_value(txn, &child(path, key), value, violations),
+          None if field.required => violations.push(SchemaViolation {
+            path: child(path, key),
+            reason: ViolationReason::Missing,
+          }),
+          None => {},
+        }
+      }
+      if let Some(schema) = &self.values {
+        for (key, value) in &entries {
+          if !self.fields.contains_key(key) {
+            schema.validate_value(txn, &child(path, key), value, violations);
+          }
+        }
+      }
+    }
+
+    if let Some(schema) = &self.items {
+      for (index, item) in array_items(txn, value).into_iter().flatten().enumerate() {
+        schema.validate_value(txn, &child(path, &index.to_string()), &item, violations);
+      }
+    }
+
+    if !self.checks.is_empty() {
+      let json = value.to_json(txn);
+      for check in &self.checks {
+        if !(check.predicate)(&json) {
+          violations.push(SchemaViolation {
+            path: path.clone(),
+            reason: ViolationReason::Check(check.description.clone()),
+          });
+        }
+      }
+    }
+  }
+}
+
+/// A value of a [Collab] that doesn't match its [Schema].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+  pub path: Path,
+  pub reason: ViolationReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationReason {
+  /// A required key is missing
+  Missing,
+  UnexpectedKind {
+    expected: ValueKind,
+    actual: ValueKind,
+  },
+  /// The value didn't pass the check with the given description
+  Check(String),
+}
+
+impl Display for SchemaViolation {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "/{}: ", self.path.join("/"))?;
+    match &self.reason {
+      ViolationReason::Missing => f.write_str("missing"),
+      ViolationReason::UnexpectedKind { expected, actual } => {
+        write!(f, "expected {}, found {}", expected, actual)
+      },
+      ViolationReason::Check(description) => f.write_str(description),
+    }
+  }
+}
+
+fn map_entries<T: ReadTxn>(txn: &T, value: &Out) -> Option<BTreeMap<String, Out>> {
+  match value {
+    Out::YMap(map) => Some(
+      map
+        .iter(txn)
+        .map(|(key, value)| (key.to_string(), value))
+        .collect(),
+    ),
+    Out::Any(Any::Map(map)) => Some(
+      map
+        .iter()
+        .map(|(key, value)| (key.clone(), Out::Any(value.clone())))
+        .collect(),
+    ),
+    _ => None,
+  }
+}
+
+fn array_items<T: ReadTxn>(txn: &T, value: &Out) -> Option<Vec<Out>> {
+  match value {
+    Out::YArray(array) => Some(array.iter(txn).collect()),
+    Out::Any(Any::Array(items)) => Some(items.iter().cloned().map(Out::Any).collect()),
+    _ => None,
+  }
+}
+
+fn child(path: &Path, segment: &str) -> Path {
+  let mut segments = path.to_vec();
+  segments.push(segment.to_string());
+  Path::from(segments)
+}
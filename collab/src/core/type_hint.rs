@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use yrs::block::Prelim;
+use yrs::types::{Attrs, TypeRef};
+use yrs::{
+  Any, Array, ArrayPrelim, ArrayRef, Map, MapPrelim, MapRef, Text, TextPrelim, TextRef,
+  TransactionMut,
+};
+
+use crate::core::collab::Collab;
+use crate::core::fill::FillError;
+use crate::core::origin::CollabOrigin;
+use crate::error::CollabError;
+
+/// Describes how a JSON value is stored in a [Collab], see [Collab::from_json]. It can be written
+/// as JSON too, for example `{"type": "map", "fields": {"name": {"type": "text"}}}`.
+///
+/// A `null` is stored as [Any::Null] whatever the hint is.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TypeHint {
+  /// The value is stored as an [Any]. The objects and arrays are not shared types.
+  #[default]
+  Any,
+  /// The object is stored as a [MapRef]. The values of the keys that are not in `fields` use the
+  /// `other` hint.
+  Map {
+    #[serde(default)]
+    fields: BTreeMap<String, TypeHint>,
+    #[serde(default)]
+    other: Box<TypeHint>,
+  },
+  /// The array is stored as an [ArrayRef], and its items use the `item` hint.
+  Array {
+    #[serde(default)]
+    item: Box<TypeHint>,
+  },
+  /// The value is stored as a [TextRef]. It's either a string, or a list of deltas like
+  /// `[{"insert": "hello", "attributes": {"bold": true}}]`.
+  Text,
+}
+
+impl TypeHint {
+  /// A map whose values are stored as [Any], unless they're added with [TypeHint::field].
+  pub fn map() -> Self {
+    Self::map_of(TypeHint::Any)
+  }
+
+  /// A map whose values use the same hint, like a map of rows by id.
+  pub fn map_of(other: TypeHint) -> Self {
+    TypeHint::Map {
+      fields: BTreeMap::new(),
+      other: Box::new(other),
+    }
+  }
+
+  pub fn array(item: TypeHint) -> Self {
+    TypeHint::Array {
+      item: Box::new(item),
+    }
+  }
+
+  /// Sets the hint of a key of the map. Does nothing if the hint is not a map.
+  pub fn field(mut self, key: &str, hint: TypeHint) -> Self {
+    if let TypeHint::Map { fields, .. } = &mut self {
+      fields.insert(key.to_string(), hint);
+    }
+    self
+  }
+
+  /// Inserts the entries of the JSON object into the map. The existing values of the keys are
+  /// replaced, and the other keys are kept.
+  pub fn fill_map(
+    &self,
+    txn: &mut TransactionMut,
+    map_ref: &MapRef,
+    value: &JsonValue,
+  ) -> Result<(), FillError> {
+    let (TypeHint::Map { fields, other }, JsonValue::Object(object)) = (self, value) else {
+      return Err(FillError::InvalidData(TypeRef::Map, value.to_string()));
+    };
+    for (key, value) in object {
+      let hint = fields.get(key).unwrap_or(other);
+      hint.insert(txn, Parent::Map(map_ref, key), value)?;
+    }
+    Ok(())
+  }
+
+  /// Appends the items of the JSON array to the array.
+  pub fn fill_array(
+    &self,
+    txn: &mut TransactionMut,
+    array_ref: &ArrayRef,
+    value: &JsonValue,
+  ) -> Result<(), FillError> {
+    let (TypeHint::Array { item }, JsonValue::Array(values)) = (self, value) else {
+      return Err(FillError::InvalidData(TypeRef::Array, value.to_string()));
+    };
+    for value in values {
+      item.insert(txn, Parent::Array(array_ref), value)?;
+    }
+    Ok(())
+  }
+
+  /// Appends the string or the deltas to the text.
+  pub fn fill_text(
+    &self,
+    txn: &mut TransactionMut,
+    text_ref: &TextRef,
+    value: &JsonValue,
+  ) -> Result<(), FillError> {
+    let invalid_data = || FillError::InvalidData(TypeRef::Text, value.to_string());
+    if !matches!(self, TypeHint::Text) {
+      return Err(invalid_data());
+    }
+    let deltas = match value {
+      JsonValue::String(text) => {
+        text_ref.push(txn, text);
+        return Ok(());
+      },
+      JsonValue::Array(deltas) => deltas,
+      _ => return Err(invalid_data()),
+    };
+    for delta in deltas {
+      let chunk = delta
+        .get("insert")
+        .and_then(|insert| insert.as_str())
+        .ok_or_else(invalid_data)?;
+      let index = text_ref.len(txn);
+      match delta.get("attributes") {
+        Some(JsonValue::Object(attributes)) => {
+          let attributes = attributes
+            .iter()
+            .map(|(key, value)| Ok((Arc::from(key.as_str()), json_to_any(value)?)))
+            .collect::<Result<Attrs, FillError>>()?;
+          text_ref.insert_with_attributes(txn, index, chunk, attributes);
+        },
+        None | Some(JsonValue::Null) => text_ref.insert(txn, index, chunk),
+        Some(_) => return Err(invalid_data()),
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns the error [TypeHint::fill_map] would return for the JSON value, without changing
+  /// anything.
+  pub fn validate_map(&self, value: &JsonValue) -> Result<(), FillError> {
+    let (TypeHint::Map { fields, other }, JsonValue::Object(object)) = (self, value) else {
+      return Err(FillError::InvalidData(TypeRef::Map, value.to_string()));
+    };
+    for (key, value) in object {
+      fields.get(key).unwrap_or(other).validate(value)?;
+    }
+    Ok(())
+  }
+
+  /// Same as [TypeHint::validate_map], for [TypeHint::fill_array].
+  fn validate_array(&self, value: &JsonValue) -> Result<(), FillError> {
+    let (TypeHint::Array { item }, JsonValue::Array(values)) = (self, value) else {
+      return Err(FillError::InvalidData(TypeRef::Array, value.to_string()));
+    };
+    values.iter().try_for_each(|value| item.validate(value))
+  }
+
+  /// Same as [TypeHint::validate_map], for [TypeHint::fill_text].
+  fn validate_text(&self, value: &JsonValue) -> Result<(), FillError> {
+    let invalid_data = || FillError::InvalidData(TypeRef::Text, value.to_string());
+    let deltas = match (self, value) {
+      (TypeHint::Text, JsonValue::String(_)) => return Ok(()),
+      (TypeHint::Text, JsonValue::Array(deltas)) => deltas,
+      _ => return Err(invalid_data()),
+    };
+    for delta in deltas {
+      if !delta.get("insert").is_some_and(JsonValue::is_string) {
+        return Err(invalid_data());
+      }
+      match delta.get("attributes") {
+        Some(JsonValue::Object(attributes)) => {
+          attributes
+            .values()
+            .try_for_each(|value| json_to_any(value).map(|_| ()))?;
+        },
+        None | Some(JsonValue::Null) => {},
+        Some(_) => return Err(invalid_data()),
+      }
+    }
+    Ok(())
+  }
+
+  /// Same as [TypeHint::validate_map], for [TypeHint::insert].
+  fn validate(&self, value: &JsonValue) -> Result<(), FillError> {
+    if value.is_null() {
+      return Ok(());
+    }
+    match self {
+      TypeHint::Any => json_to_any(value).map(|_| ()),
+      TypeHint::Map { .. } => self.validate_map(value),
+      TypeHint::Array { .. } => self.validate_array(value),
+      TypeHint::Text => self.validate_text(value),
+    }
+  }
+
+  /// Inserts the value into the parent, then fills the inserted shared type.
+  fn insert(
+    &self,
+    txn: &mut TransactionMut,
+    parent: Parent,
+    value: &JsonValue,
+  ) -> Result<(), FillError> {
+    if value.is_null() {
+      parent.insert(txn, Any::Null);
+      return Ok(());
+    }
+    match self {
+      TypeHint::Any => {
+        parent.insert(txn, json_to_any(value)?);
+      },
+      TypeHint::Map { .. } => {
+        let map_ref: MapRef = parent.insert(txn, MapPrelim::default());
+        self.fill_map(txn, &map_ref, value)?;
+      },
+      TypeHint::Array { .. } => {
+        let array_ref: ArrayRef = parent.insert(txn, ArrayPrelim::default());
+        self.fill_array(txn, &array_ref, value)?;
+      },
+      TypeHint::Text => {
+        let text_ref: TextRef = parent.insert(txn, TextPrelim::new(""));
+        self.fill_text(txn, &text_ref, value)?;
+      },
+    }
+    Ok(())
+  }
+}
+
+impl Collab {
+  /// Creates a collab whose data is the given JSON object, see [TypeHint]. It's the inverse of
+  /// [Collab::to_json_value].
+  pub fn from_json(
+    origin: CollabOrigin,
+    object_id: &str,
+    value: &JsonValue,
+    hint: &TypeHint,
+  ) -> Result<Self, CollabError> {
+    let mut collab = Collab::new_with_origin(origin, object_id, vec![], false);
+    collab.fill_with_json(value, hint)?;
+    Ok(collab)
+  }
+
+  /// Inserts the entries of the JSON object into [Collab::data] in a single transaction, see
+  /// [TypeHint::fill_map]. The value is checked against the hint before the transaction, so
+  /// nothing is inserted if it doesn't match.
+  pub fn fill_with_json(&mut self, value: &JsonValue, hint: &TypeHint) -> Result<(), CollabError> {
+    hint.validate_map(value)?;
+    let data = self.data.clone();
+    self
+      .context
+      .with_txn(|txn| hint.fill_map(txn, &data, value))??;
+    Ok(())
+  }
+}
+
+/// The map or array a value is inserted into.
+enum Parent<'a> {
+  Map(&'a MapRef, &'a str),
+  Array(&'a ArrayRef),
+}
+
+impl Parent<'_> {
+  fn insert<P: Prelim>(&self, txn: &mut TransactionMut, prelim: P) -> P::Return {
+    match self {
+      Parent::Map(map_ref, key) => map_ref.insert(txn, *key, prelim),
+      Parent::Array(array_ref) => array_ref.push_back(txn, prelim),
+    }
+  }
+}
+
+fn json_to_any(value: &JsonValue) -> Result<Any, FillError> {
+  serde_json::from_value(value.clone())
+    .map_err(|_| FillError::InvalidData(TypeRef::Undefined, value.to_string()))
+}
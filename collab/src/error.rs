@@ -36,6 +36,12 @@ pub enum CollabError {
   #[error("Failed to apply update: {0}")]
   UpdateFailed(#[from] yrs::error::UpdateError),
 
+  #[error("Invalid json patch: {0}")]
+  InvalidJsonPatch(String),
+
+  #[error(transparent)]
+  Fill(#[from] crate::core::fill::FillError),
+
   #[error("Internal failure: {0}")]
   Internal(#[from] anyhow::Error),
 }
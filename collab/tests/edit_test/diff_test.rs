@@ -0,0 +1,165 @@
+use collab::core::collab::{Collab, Path};
+use collab::core::diff::{
+  CollabChange, TextDelta, apply_diff, diff_collab, diff_encoded_collab, diff_since,
+};
+use collab::error::CollabError;
+use collab::preclude::{Any, Array, ArrayPrelim, Map, MapPrelim, MapRef, ReadTxn, TextPrelim};
+
+#[tokio::test]
+async fn diff_map_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  collab.insert("title", "hello");
+  collab.insert("removed", true);
+  {
+    let mut txn = collab.context.transact_mut();
+    let meta = collab.data.insert(&mut txn, "meta", MapPrelim::default());
+    meta.insert(&mut txn, "level", 1);
+  }
+  let old = collab
+    .encode_collab_v1(|_| Ok::<_, CollabError>(()))
+    .unwrap();
+
+  collab.insert("title", "world");
+  collab.insert("icon", "🚀");
+  collab.remove("removed");
+  {
+    let mut txn = collab.context.transact_mut();
+    let meta: MapRef = collab.data.get(&txn, "meta").unwrap().cast().unwrap();
+    meta.insert(&mut txn, "level", 2);
+  }
+  let new = collab
+    .encode_collab_v1(|_| Ok::<_, CollabError>(()))
+    .unwrap();
+
+  let changes = diff_encoded_collab(&old, &new).unwrap();
+  assert_eq!(
+    changes,
+    vec![
+      CollabChange::MapInserted {
+        path: root(),
+        key: "icon".to_string(),
+        value: Any::from("🚀"),
+      },
+      CollabChange::MapUpdated {
+        path: Path::from(["meta"]),
+        key: "level".to_string(),
+        old_value: Any::from(1),
+        new_value: Any::from(2),
+      },
+      CollabChange::MapRemoved {
+        path: root(),
+        key: "removed".to_string(),
+        old_value: Any::from(true),
+      },
+      CollabChange::MapUpdated {
+        path: root(),
+        key: "title".to_string(),
+        old_value: Any::from("hello"),
+        new_value: Any::from("world"),
+      },
+    ]
+  );
+  assert!(diff_encoded_collab(&new, &new).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn diff_array_and_text_test() {
+  let mut old = Collab::new(1, "1", "1", vec![], false);
+  {
+    let mut txn = old.context.transact_mut();
+    let array = old.data.insert(&mut txn, "array", ArrayPrelim::default());
+    array.insert_range(&mut txn, 0, ["a", "b", "c", "d"]);
+    old
+      .data
+      .insert(&mut txn, "text", TextPrelim::new("hello world"));
+  }
+  let mut new = Collab::new(1, "1", "1", vec![], false);
+  {
+    let mut txn = new.context.transact_mut();
+    let array = new.data.insert(&mut txn, "array", ArrayPrelim::default());
+    array.insert_range(&mut txn, 0, ["a", "c", "x", "y", "d"]);
+    new
+      .data
+      .insert(&mut txn, "text", TextPrelim::new("hello brave world"));
+  }
+
+  let changes = diff_collab(&old, &new);
+  assert_eq!(
+    changes,
+    vec![
+      CollabChange::ArrayDeleted {
+        path: Path::from(["array"]),
+        index: 1,
+        len: 1,
+      },
+      CollabChange::ArrayInserted {
+        path: Path::from(["array"]),
+        index: 2,
+        values: vec![Any::from("x"), Any::from("y")],
+      },
+      CollabChange::TextChanged {
+        path: Path::from(["text"]),
+        delta: vec![
+          TextDelta::Retain(6),
+          TextDelta::Insert("brave ".to_string())
+        ],
+      },
+    ]
+  );
+}
+
+#[tokio::test]
+async fn diff_large_array_test() {
+  let array_collab = |values: Vec<i64>| {
+    let mut collab = Collab::new(1, "1", "1", vec![], false);
+    {
+      let mut txn = collab.context.transact_mut();
+      let array = collab
+        .data
+        .insert(&mut txn, "array", ArrayPrelim::default());
+      array.insert_range(&mut txn, 0, values);
+    }
+    collab
+  };
+  // The arrays differ at both ends, so the common items are only found by the subsequence
+  let old_values = (0..2000).collect::<Vec<i64>>();
+  let mut new_values = old_values.clone();
+  new_values[0] = -1;
+  new_values.remove(1000);
+  new_values.insert(1500, -2);
+  new_values[1999] = -3;
+  let mut old = array_collab(old_values);
+  let new = array_collab(new_values);
+
+  let changes = diff_collab(&old, &new);
+  assert_eq!(changes.len(), 6);
+  apply_diff(&mut old, &new, &changes).unwrap();
+  assert_eq!(old.to_json_value(), new.to_json_value());
+}
+
+#[tokio::test]
+async fn diff_since_state_vector_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], true);
+  collab.insert("title", "hello");
+  let state_vector = collab.transact().state_vector();
+
+  collab.insert("icon", "🚀");
+  let changes = diff_since(&collab, &state_vector).unwrap();
+  assert_eq!(
+    changes,
+    vec![CollabChange::MapInserted {
+      path: root(),
+      key: "icon".to_string(),
+      value: Any::from("🚀"),
+    }]
+  );
+  assert!(
+    diff_since(&collab, &collab.transact().state_vector())
+      .unwrap()
+      .is_empty()
+  );
+}
+
+fn root() -> Path {
+  Path::from(Vec::<String>::new())
+}
@@ -0,0 +1,70 @@
+use collab::core::collab::Collab;
+use collab::core::history::CollabHistory;
+use collab::preclude::{Map, MapPrelim, MapRef, Out, ReadTxn, Text, TextPrelim, TextRef};
+use serde_json::json;
+
+#[tokio::test]
+async fn materialize_history_snapshot_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], true);
+  let mut history = CollabHistory::new();
+  collab.insert("title", "v1");
+  history.capture(&collab).unwrap();
+  collab.insert("title", "v2");
+  collab.insert("icon", "🚀");
+  history.capture(&collab).unwrap();
+  collab.remove("icon");
+
+  let snapshots = history.snapshots();
+  assert_eq!(snapshots.len(), 2);
+  assert!(snapshots[0].created_at <= snapshots[1].created_at);
+  assert_eq!(snapshots[0].origin, *collab.origin());
+  assert_eq!(
+    snapshots[0].to_json_value(&collab).unwrap(),
+    json!({"title": "v1"})
+  );
+  assert_eq!(
+    snapshots[1].to_json_value(&collab).unwrap(),
+    json!({"title": "v2", "icon": "🚀"})
+  );
+  assert_eq!(collab.to_json_value(), json!({"title": "v2"}));
+}
+
+#[tokio::test]
+async fn restore_history_snapshot_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], true);
+  {
+    let mut txn = collab.context.transact_mut();
+    let meta = collab.data.insert(&mut txn, "meta", MapPrelim::default());
+    meta.insert(&mut txn, "level", 1);
+    collab
+      .data
+      .insert(&mut txn, "text", TextPrelim::new("hello world"));
+  }
+  let mut history = CollabHistory::new();
+  history.capture(&collab).unwrap();
+  let expected = collab.to_json_value();
+
+  {
+    let mut txn = collab.context.transact_mut();
+    let meta: MapRef = collab.data.get(&txn, "meta").unwrap().cast().unwrap();
+    meta.insert(&mut txn, "level", 2);
+    let text: TextRef = collab.data.get(&txn, "text").unwrap().cast().unwrap();
+    text.insert(&mut txn, 6, "brave ");
+    collab.data.insert(&mut txn, "title", "draft");
+  }
+  let state_vector = collab.transact().state_vector();
+
+  history.snapshots()[0].restore(&mut collab).unwrap();
+  assert_eq!(collab.to_json_value(), expected);
+  // The restore is a new update, and the maps and texts are kept
+  assert_ne!(collab.transact().state_vector(), state_vector);
+  let txn = collab.transact();
+  assert!(matches!(collab.data.get(&txn, "meta"), Some(Out::YMap(_))));
+  assert!(matches!(collab.data.get(&txn, "text"), Some(Out::YText(_))));
+}
+
+#[tokio::test]
+async fn capture_without_skip_gc_test() {
+  let collab = Collab::new(1, "1", "1", vec![], false);
+  assert!(CollabHistory::new().capture(&collab).is_err());
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use collab::core::collab::{Collab, Path};
+use collab::core::json_patch::{PatchOperation, parse_json_pointer};
+use collab::error::CollabError;
+use collab::preclude::{Any, Map, Out, ReadTxn, TextPrelim};
+use serde_json::json;
+
+#[tokio::test]
+async fn apply_json_patch_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  let patch: Vec<PatchOperation> = serde_json::from_value(json!([
+    {"op": "add", "path": "/title", "value": "hello"},
+    {"op": "add", "path": "/rows", "value": [{"id": "1"}, {"id": "2"}]},
+    {"op": "add", "path": "/rows/-", "value": {"id": "3"}},
+    {"op": "replace", "path": "/rows/0/id", "value": "0"},
+    {"op": "copy", "from": "/title", "path": "/name"},
+    {"op": "remove", "path": "/title"},
+    {"op": "test", "path": "/rows/2/id", "value": "3"},
+  ]))
+  .unwrap();
+  collab.apply_json_patch(&patch).unwrap();
+  assert_eq!(
+    collab.to_json_value(),
+    json!({
+      "name": "hello",
+      "rows": [{"id": "0"}, {"id": "2"}, {"id": "3"}],
+    })
+  );
+
+  // The objects and arrays are inserted as maps and arrays
+  let txn = collab.transact();
+  assert!(matches!(
+    collab.data.get(&txn, "rows"),
+    Some(Out::YArray(_))
+  ));
+  drop(txn);
+
+  assert_eq!(
+    collab.get_with_json_pointer("/rows/1/id").unwrap(),
+    Some(json!("2"))
+  );
+  assert_eq!(collab.get_with_json_pointer("/rows/3").unwrap(), None);
+  assert!(collab.get_with_json_pointer("rows").is_err());
+}
+
+#[tokio::test]
+async fn move_with_json_patch_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  collab
+    .apply_json_patch(&[PatchOperation::Add {
+      path: "/rows".to_string(),
+      value: json!(["a", "b", "c", "d"]),
+    }])
+    .unwrap();
+
+  collab
+    .apply_json_patch(&[
+      PatchOperation::Move {
+        from: "/rows/0".to_string(),
+        path: "/rows/2".to_string(),
+      },
+      PatchOperation::Move {
+        from: "/rows/3".to_string(),
+        path: "/last".to_string(),
+      },
+    ])
+    .unwrap();
+  assert_eq!(
+    collab.to_json_value(),
+    json!({"rows": ["b", "c", "a"], "last": "d"})
+  );
+}
+
+#[tokio::test]
+async fn replace_text_with_json_patch_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  {
+    let mut txn = collab.context.transact_mut();
+    collab
+      .data
+      .insert(&mut txn, "text", TextPrelim::new("hello world"));
+  }
+  collab
+    .apply_json_patch(&[PatchOperation::Replace {
+      path: "/text".to_string(),
+      value: json!("hello brave world"),
+    }])
+    .unwrap();
+  assert_eq!(collab.to_json_value(), json!({"text": "hello brave world"}));
+  let txn = collab.transact();
+  assert!(matches!(collab.data.get(&txn, "text"), Some(Out::YText(_))));
+}
+
+#[tokio::test]
+async fn reject_json_patch_atomically_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  collab.insert("title", "hello");
+  let state_vector = collab.transact().state_vector();
+
+  let result = collab.apply_json_patch(&[
+    PatchOperation::Replace {
+      path: "/title".to_string(),
+      value: json!("world"),
+    },
+    PatchOperation::Test {
+      path: "/title".to_string(),
+      value: json!("hello"),
+    },
+  ]);
+  assert!(matches!(result, Err(CollabError::InvalidJsonPatch(_))));
+
+  let result = collab.apply_json_patch(&[
+    PatchOperation::Add {
+      path: "/icon".to_string(),
+      value: json!("🚀"),
+    },
+    PatchOperation::Remove {
+      path: "/missing".to_string(),
+    },
+  ]);
+  assert!(matches!(result, Err(CollabError::InvalidJsonPatch(_))));
+
+  assert_eq!(collab.to_json_value(), json!({"title": "hello"}));
+  assert_eq!(collab.transact().state_vector(), state_vector);
+}
+
+#[tokio::test]
+async fn reject_json_patch_inside_any_value_test() {
+  let mut collab = Collab::new(1, "1", "1", vec![], false);
+  {
+    let mut txn = collab.context.transact_mut();
+    let meta = HashMap::from([("name".to_string(), Any::from("hello"))]);
+    collab.data.insert(&mut txn, "meta", Any::from(meta));
+  }
+  let state_vector = collab.transact().state_vector();
+
+  // The map is a single value, so the paths inside it can't be changed
+  let result = collab.apply_json_patch(&[
+    PatchOperation::Add {
+      path: "/title".to_string(),
+      value: json!("world"),
+    },
+    PatchOperation::Replace {
+      path: "/meta/name".to_string(),
+      value: json!("world"),
+    },
+  ]);
+  assert!(matches!(result, Err(CollabError::InvalidJsonPatch(_))));
+
+  assert_eq!(collab.to_json_value(), json!({"meta": {"name": "hello"}}));
+  assert_eq!(collab.transact().state_vector(), state_vector);
+}
+
+#[test]
+fn parse_json_pointer_test() {
+  assert_eq!(
+    parse_json_pointer("/a~1b/c~0d/0").unwrap(),
+    Path::from(["a/b", "c~d", "0"])
+  );
+  assert_eq!(
+    parse_json_pointer("").unwrap(),
+    Path::from(Vec::<String>::new())
+  );
+  assert!(parse_json_pointer("a").is_err());
+}
@@ -1,5 +1,10 @@
 mod awareness_test;
+mod diff_test;
+mod history_test;
 mod insert_test;
+mod json_patch_test;
 mod observer_test;
 mod restore_test;
+mod schema_test;
 mod state_vec_test;
+mod type_hint_test;
@@ -0,0 +1,163 @@
+use collab::core::collab::{Collab, Path};
+use collab::core::origin::CollabOrigin;
+use collab::core::schema::{Schema, SchemaViolation, ValueKind, ViolationReason};
+use collab::core::type_hint::TypeHint;
+use collab::preclude::Any;
+use serde_json::json;
+
+fn database_schema() -> Schema {
+  Schema::map().required(
+    "database",
+    Schema::map()
+      .required("id", Schema::string())
+      .required("title", Schema::text())
+      .optional(
+        "fields",
+        Schema::map().values(Schema::map().required("name", Schema::string())),
+      )
+      .optional(
+        "rows",
+        Schema::array().items(Schema::any().check("must be a number", |value| {
+          matches!(value, Any::Number(_) | Any::BigInt(_))
+        })),
+      ),
+  )
+}
+
+fn database_hint() -> TypeHint {
+  TypeHint::map().field(
+    "database",
+    TypeHint::map()
+      .field("title", TypeHint::Text)
+      .field("fields", TypeHint::map_of(TypeHint::map()))
+      .field("rows", TypeHint::array(TypeHint::Any)),
+  )
+}
+
+#[tokio::test]
+async fn validate_valid_collab_test() {
+  let value = json!({
+    "database": {
+      "id": "d1",
+      "title": "hello",
+      "fields": {"f1": {"name": "a"}, "f2": {"name": "b", "width": 100}},
+      "rows": [1, 2],
+    },
+    "other": [1],
+  });
+  let collab = Collab::from_json(CollabOrigin::Empty, "1", &value, &database_hint()).unwrap();
+  assert!(database_schema().validate(&collab).is_empty());
+
+  let encoded_collab = collab
+    .encode_collab_v1(|_| Ok::<_, anyhow::Error>(()))
+    .unwrap();
+  assert!(
+    database_schema()
+      .validate_encoded_collab(&encoded_collab)
+      .unwrap()
+      .is_empty()
+  );
+}
+
+#[tokio::test]
+async fn validate_reports_every_violation_test() {
+  let value = json!({
+    "database": {
+      "title": "hello",
+      "fields": {"f1": {"name": 1}, "f2": [], "f3": {}},
+      "rows": [1, "2"],
+    },
+  });
+  // The title is stored as a string instead of a text, and f2 as an array
+  let hint = TypeHint::map().field(
+    "database",
+    TypeHint::map()
+      .field(
+        "fields",
+        TypeHint::map()
+          .field("f1", TypeHint::map())
+          .field("f3", TypeHint::map()),
+      )
+      .field("rows", TypeHint::array(TypeHint::Any)),
+  );
+  let collab = Collab::from_json(CollabOrigin::Empty, "1", &value, &hint).unwrap();
+  let violations = database_schema().validate(&collab);
+  assert_eq!(
+    violations,
+    vec![
+      violation(
+        ["database", "fields", "f1", "name"],
+        ViolationReason::Check("must be a string".to_string())
+      ),
+      violation(
+        ["database", "fields", "f2"],
+        ViolationReason::UnexpectedKind {
+          expected: ValueKind::Map,
+          actual: ValueKind::Any,
+        }
+      ),
+      violation(
+        ["database", "fields", "f3", "name"],
+        ViolationReason::Missing
+      ),
+      violation(["database", "id"], ViolationReason::Missing),
+      violation(
+        ["database", "rows", "1"],
+        ViolationReason::Check("must be a number".to_string())
+      ),
+      violation(
+        ["database", "title"],
+        ViolationReason::UnexpectedKind {
+          expected: ValueKind::Text,
+          actual: ValueKind::Any,
+        }
+      ),
+    ]
+  );
+  assert_eq!(violations[3].to_string(), "/database/id: missing");
+}
+
+#[tokio::test]
+async fn validate_values_stored_as_any_test() {
+  // The nested objects are not shared types, but they are validated the same way
+  let value = json!({"database": {"id": "d1", "title": "a", "fields": {"f1": {}}}});
+  let hint = TypeHint::map().field(
+    "database",
+    TypeHint::map()
+      .field("title", TypeHint::Text)
+      .field("fields", TypeHint::Any),
+  );
+  let collab = Collab::from_json(CollabOrigin::Empty, "1", &value, &hint).unwrap();
+  assert_eq!(
+    database_schema().validate(&collab),
+    vec![violation(
+      ["database", "fields"],
+      ViolationReason::UnexpectedKind {
+        expected: ValueKind::Map,
+        actual: ValueKind::Any,
+      }
+    ),]
+  );
+
+  let schema = Schema::map().required(
+    "database",
+    Schema::new().optional(
+      "fields",
+      Schema::any().values(Schema::any().required("name", Schema::string())),
+    ),
+  );
+  assert_eq!(
+    schema.validate(&collab),
+    vec![violation(
+      ["database", "fields", "f1", "name"],
+      ViolationReason::Missing
+    )]
+  );
+}
+
+fn violation<const N: usize>(path: [&'static str; N], reason: ViolationReason) -> SchemaViolation {
+  SchemaViolation {
+    path: Path::from(path),
+    reason,
+  }
+}
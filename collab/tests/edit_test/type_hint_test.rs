@@ -0,0 +1,114 @@
+use collab::core::collab::Collab;
+use collab::core::origin::CollabOrigin;
+use collab::core::type_hint::TypeHint;
+use collab::error::CollabError;
+use collab::preclude::{GetString, Map, Out, ReadTxn, TextRef};
+use serde_json::json;
+
+fn document_hint() -> TypeHint {
+  TypeHint::map()
+    .field("title", TypeHint::Text)
+    .field("meta", TypeHint::map())
+    .field("rows", TypeHint::map_of(TypeHint::map()))
+    .field("tags", TypeHint::array(TypeHint::Any))
+}
+
+#[tokio::test]
+async fn from_json_round_trip_test() {
+  let value = json!({
+    "title": "hello world",
+    "meta": {"created_at": 1, "deleted": false},
+    "rows": {"1": {"name": "a"}, "2": {"name": "b"}},
+    "tags": ["a", {"b": [1, 2]}],
+    "other": {"x": null},
+  });
+  let collab = Collab::from_json(CollabOrigin::Empty, "1", &value, &document_hint()).unwrap();
+  assert_eq!(collab.to_json_value(), value);
+
+  let txn = collab.transact();
+  assert!(matches!(
+    collab.data.get(&txn, "title"),
+    Some(Out::YText(_))
+  ));
+  assert!(matches!(collab.data.get(&txn, "meta"), Some(Out::YMap(_))));
+  assert!(matches!(
+    collab.data.get(&txn, "tags"),
+    Some(Out::YArray(_))
+  ));
+  // The values of the keys without a hint are not shared types
+  assert!(matches!(collab.data.get(&txn, "other"), Some(Out::Any(_))));
+  let Some(Out::YMap(rows)) = collab.data.get(&txn, "rows") else {
+    panic!("rows is not a map");
+  };
+  assert!(matches!(rows.get(&txn, "1"), Some(Out::YMap(_))));
+}
+
+#[tokio::test]
+async fn from_json_text_deltas_test() {
+  let value = json!({
+    "title": [
+      {"insert": "hello", "attributes": {"bold": true}},
+      {"insert": " world"},
+    ],
+  });
+  let collab = Collab::from_json(CollabOrigin::Empty, "1", &value, &document_hint()).unwrap();
+  let txn = collab.transact();
+  let text: TextRef = collab.data.get(&txn, "title").unwrap().cast().unwrap();
+  assert_eq!(text.get_string(&txn), "hello world");
+}
+
+#[tokio::test]
+async fn fill_with_json_keeps_other_keys_test() {
+  let hint = document_hint();
+  let mut collab =
+    Collab::from_json(CollabOrigin::Empty, "1", &json!({"title": "a"}), &hint).unwrap();
+  collab
+    .fill_with_json(&json!({"tags": ["b"]}), &hint)
+    .unwrap();
+  assert_eq!(collab.to_json_value(), json!({"title": "a", "tags": ["b"]}));
+}
+
+#[tokio::test]
+async fn from_json_type_mismatch_test() {
+  let hint = document_hint();
+  for value in [
+    json!({"meta": [1, 2]}),
+    json!({"tags": {"a": 1}}),
+    json!({"title": 1}),
+    json!({"title": [{"retain": 1}]}),
+  ] {
+    let result = Collab::from_json(CollabOrigin::Empty, "1", &value, &hint);
+    assert!(matches!(result, Err(CollabError::Fill(_))), "{}", value);
+  }
+  let result = Collab::from_json(CollabOrigin::Empty, "1", &json!([1]), &hint);
+  assert!(matches!(result, Err(CollabError::Fill(_))));
+}
+
+#[tokio::test]
+async fn fill_with_invalid_json_changes_nothing_test() {
+  let hint = document_hint();
+  let mut collab =
+    Collab::from_json(CollabOrigin::Empty, "1", &json!({"title": "a"}), &hint).unwrap();
+  let state_vector = collab.transact().state_vector();
+
+  // The keys are filled in order, so "meta" would be inserted before "title" is rejected
+  let result = collab.fill_with_json(&json!({"meta": {"level": 1}, "title": 1}), &hint);
+  assert!(matches!(result, Err(CollabError::Fill(_))));
+  assert_eq!(collab.to_json_value(), json!({"title": "a"}));
+  assert_eq!(collab.transact().state_vector(), state_vector);
+}
+
+#[tokio::test]
+async fn type_hint_from_json_schema_test() {
+  let hint: TypeHint = serde_json::from_value(json!({
+    "type": "map",
+    "fields": {
+      "title": {"type": "text"},
+      "meta": {"type": "map"},
+      "rows": {"type": "map", "other": {"type": "map"}},
+      "tags": {"type": "array"},
+    },
+  }))
+  .unwrap();
+  assert_eq!(hint, document_hint());
+}